@@ -12,7 +12,7 @@ fn main() {
     println!("Root: {:?}", root);
 
     // Add an item to the Merkle tree.
-    merkle_tree.insert(&"Gandalf the Grey");
+    merkle_tree.insert(&"Gandalf the Grey").unwrap();
 
     // Get the new root hash of the Merkle tree.
     let new_root = merkle_tree.root();
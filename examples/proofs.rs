@@ -1,4 +1,4 @@
-use merkle_tree::MerkleTree;
+use merkle_tree::{LeafHash, MerkleTree};
 
 fn main() {
     // Create a new Merkle tree from a list of items.
@@ -24,7 +24,7 @@ fn main() {
     let corrupt_tree = MerkleTree::build(&corrupt_items).unwrap();
 
     // Generate a proof of inclusion for the corrupt item.
-    let corrupt_hash = MerkleTree::hash(corrupt_items[4].as_bytes());
+    let corrupt_hash = LeafHash::of(corrupt_items[4]);
 
     let corrupt_proof = corrupt_tree.proof_of_inclusion(&corrupt_hash).unwrap();
 
@@ -35,7 +35,7 @@ fn main() {
     let good_tree = MerkleTree::build(&items).unwrap();
 
     // Generate a proof of inclusion for the good item.
-    let good_hash = MerkleTree::hash(items[4].as_bytes());
+    let good_hash = LeafHash::of(items[4]);
 
     let good_proof = good_tree.proof_of_inclusion(&good_hash).unwrap();
 
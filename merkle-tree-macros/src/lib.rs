@@ -0,0 +1,242 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{
+    parenthesized, parse_macro_input, parse_quote, Data, DeriveInput, Fields, Ident, Index,
+    LitByteStr, LitStr, Token,
+};
+
+type Hash = [u8; 32];
+
+/// A single item of an `include_merkle!` invocation.
+enum Item {
+    /// A string or byte string literal, hashed as is.
+    Literal(Vec<u8>),
+    /// A file, relative to the crate's `CARGO_MANIFEST_DIR`, hashed by contents.
+    File(LitStr),
+}
+
+impl Parse for Item {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(LitStr) {
+            let literal: LitStr = input.parse()?;
+            return Ok(Item::Literal(literal.value().into_bytes()));
+        }
+
+        if input.peek(LitByteStr) {
+            let literal: LitByteStr = input.parse()?;
+            return Ok(Item::Literal(literal.value()));
+        }
+
+        let ident: Ident = input.parse()?;
+        if ident != "file" {
+            return Err(syn::Error::new(
+                ident.span(),
+                "expected a string literal, a byte string literal or `file(\"path\")`",
+            ));
+        }
+
+        let content;
+        parenthesized!(content in input);
+        Ok(Item::File(content.parse()?))
+    }
+}
+
+struct Items(Punctuated<Item, Token![,]>);
+
+impl Parse for Items {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Ok(Items(Punctuated::parse_terminated(input)?))
+    }
+}
+
+/// Builds a Merkle tree at compile time and embeds it as an `IncludedTree`.
+///
+/// Each item is either a literal, hashed as is, or `file("path")`, hashed by
+/// the contents of the file at `path` relative to the crate's manifest directory.
+/// The root is computed the same way `MerkleTree::build` computes it.
+///
+/// # Examples
+/// ```ignore
+/// use merkle_tree::{include_merkle, IncludedTree};
+///
+/// const VERSES: IncludedTree = include_merkle!["In a hole in the ground", "there lived a hobbit."];
+/// ```
+#[proc_macro]
+pub fn include_merkle(input: TokenStream) -> TokenStream {
+    let items = parse_macro_input!(input as Items).0;
+
+    if items.is_empty() {
+        return syn::Error::new(Span::call_site(), "include_merkle! needs at least one item")
+            .to_compile_error()
+            .into();
+    }
+
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+
+    let mut leaves = Vec::with_capacity(items.len());
+    let mut tracked_files = Vec::new();
+
+    for item in items {
+        let bytes = match item {
+            Item::Literal(bytes) => bytes,
+            Item::File(path) => {
+                let full_path = std::path::Path::new(&manifest_dir).join(path.value());
+                match std::fs::read(&full_path) {
+                    Ok(bytes) => {
+                        tracked_files.push(full_path.to_string_lossy().into_owned());
+                        bytes
+                    }
+                    Err(error) => {
+                        return syn::Error::new(
+                            path.span(),
+                            format!("could not read {}: {}", full_path.display(), error),
+                        )
+                        .to_compile_error()
+                        .into();
+                    }
+                }
+            }
+        };
+        leaves.push(hash(&bytes));
+    }
+
+    let root = merkle_root(leaves.clone());
+
+    let leaves = leaves.iter().map(|leaf| quote! { [#(#leaf),*] });
+
+    // Referencing the files through `include_bytes!` makes cargo rebuild when they change.
+    quote! {
+        {
+            #(const _: &[u8] = include_bytes!(#tracked_files);)*
+            ::merkle_tree::IncludedTree {
+                root: [#(#root),*],
+                leaves: &[#(#leaves),*],
+            }
+        }
+    }
+    .into()
+}
+
+/// Derives `MerkleLeaf` for a struct by encoding its fields in declaration order.
+///
+/// Every field must implement `MerkleLeaf` itself.
+///
+/// # Examples
+/// ```ignore
+/// use merkle_tree::{MerkleLeaf, MerkleTree};
+///
+/// #[derive(MerkleLeaf)]
+/// struct Hobbit {
+///     name: String,
+///     age: u16,
+/// }
+/// ```
+#[proc_macro_derive(MerkleLeaf)]
+pub fn derive_merkle_leaf(input: TokenStream) -> TokenStream {
+    let mut input = parse_macro_input!(input as DeriveInput);
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return syn::Error::new(
+                input.ident.span(),
+                "MerkleLeaf can only be derived for structs",
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+
+    let accessors: Vec<proc_macro2::TokenStream> = match fields {
+        Fields::Named(fields) => fields
+            .named
+            .iter()
+            .map(|field| {
+                let ident = &field.ident;
+                quote! { #ident }
+            })
+            .collect(),
+        Fields::Unnamed(fields) => (0..fields.unnamed.len())
+            .map(|index| {
+                let index = Index::from(index);
+                quote! { #index }
+            })
+            .collect(),
+        Fields::Unit => Vec::new(),
+    };
+
+    for param in input.generics.type_params_mut() {
+        param.bounds.push(parse_quote!(::merkle_tree::MerkleLeaf));
+    }
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics ::merkle_tree::MerkleLeaf for #name #type_generics #where_clause {
+            fn encode_leaf(&self, out: &mut ::std::vec::Vec<u8>) {
+                #(::merkle_tree::MerkleLeaf::encode_leaf(&self.#accessors, out);)*
+            }
+        }
+    }
+    .into()
+}
+
+fn hash(bytes: &[u8]) -> Hash {
+    hmac_sha256::Hash::hash(bytes)
+}
+
+/// Mirrors `MerkleTree::merkle_parent`: children are sorted before hashing.
+fn merkle_parent(left: Hash, right: Hash) -> Hash {
+    let mut children = [left, right];
+    children.sort();
+    hash(children.as_flattened())
+}
+
+/// Mirrors `MerkleTree::construct_levels`: odd levels duplicate their last hash.
+fn merkle_root(mut level: Vec<Hash>) -> Hash {
+    while level.len() > 1 {
+        if level.len() % 2 == 1 {
+            level.extend(level.last().cloned());
+        }
+
+        level = level
+            .chunks_exact(2)
+            .map(|pair| merkle_parent(pair[0], pair[1]))
+            .collect();
+    }
+
+    level[0]
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_merkle_root_single_leaf_is_the_leaf() {
+        let leaf = hash("In a hole in the ground".as_bytes());
+
+        assert_eq!(merkle_root(vec![leaf]), leaf);
+    }
+
+    #[test]
+    fn test_merkle_root_duplicates_last_leaf() {
+        let leaves = vec![
+            hash("One Ring to rule them all,".as_bytes()),
+            hash("One Ring to find them,".as_bytes()),
+            hash("One Ring to bring them all".as_bytes()),
+        ];
+
+        let expected = merkle_parent(
+            merkle_parent(leaves[0], leaves[1]),
+            merkle_parent(leaves[2], leaves[2]),
+        );
+
+        assert_eq!(merkle_root(leaves), expected);
+    }
+}
@@ -0,0 +1,376 @@
+use std::ops::Range;
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::{Proof, ProofError};
+
+/// A summary of leaves carried by every node of an `AggregateTree`: a
+/// monoid whose `combine` is associative and has `identity` as neutral.
+pub trait Aggregate: Clone + PartialEq {
+    /// The summary of no leaves, which pads odd levels.
+    fn identity() -> Self;
+
+    fn combine(&self, other: &Self) -> Self;
+
+    /// The encoding the node hashes commit to.
+    fn to_bytes(&self) -> Vec<u8>;
+}
+
+/// A count or a total, e.g. of bytes. Saturates rather than overflowing.
+impl Aggregate for u64 {
+    fn identity() -> Self {
+        0
+    }
+
+    fn combine(&self, other: &Self) -> Self {
+        self.saturating_add(*other)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.to_be_bytes().to_vec()
+    }
+}
+
+/// Two summaries side by side, e.g. a count and a byte total.
+impl<A: Aggregate, B: Aggregate> Aggregate for (A, B) {
+    fn identity() -> Self {
+        (A::identity(), B::identity())
+    }
+
+    fn combine(&self, other: &Self) -> Self {
+        (self.0.combine(&other.0), self.1.combine(&other.1))
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let first = self.0.to_bytes();
+        let mut bytes = (first.len() as u64).to_be_bytes().to_vec();
+        bytes.extend_from_slice(&first);
+        bytes.extend_from_slice(&self.1.to_bytes());
+        bytes
+    }
+}
+
+/// The range of values, e.g. of timestamps, `None` for no leaves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MinMax(pub Option<(u64, u64)>);
+
+impl MinMax {
+    pub fn of(value: u64) -> Self {
+        Self(Some((value, value)))
+    }
+}
+
+impl Aggregate for MinMax {
+    fn identity() -> Self {
+        Self(None)
+    }
+
+    fn combine(&self, other: &Self) -> Self {
+        match (self.0, other.0) {
+            (Some((min, max)), Some((other_min, other_max))) => {
+                Self(Some((min.min(other_min), max.max(other_max))))
+            }
+            (range, None) | (None, range) => Self(range),
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        match self.0 {
+            None => vec![0],
+            Some((min, max)) => [[1].as_slice(), &min.to_be_bytes(), &max.to_be_bytes()].concat(),
+        }
+    }
+}
+
+/// A node of an `AggregateTree`: a hash and the summary of the leaves below it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AggregateNode<A> {
+    pub hash: Hash,
+    pub aggregate: A,
+}
+
+impl<A: Aggregate> AggregateNode<A> {
+    /// Pads odd levels. Duplicating the last node, as `MerkleTree` does,
+    /// would summarize its leaves twice.
+    fn empty() -> Self {
+        Self {
+            hash: MerkleTree::hash(b"aggregate:empty"),
+            aggregate: A::identity(),
+        }
+    }
+
+    /// The node of a leaf, committing to its hash and summary.
+    pub fn leaf(leaf: &Hash, aggregate: A) -> Self {
+        let mut bytes = vec![0];
+        bytes.extend_from_slice(leaf);
+        bytes.extend_from_slice(&aggregate.to_bytes());
+
+        Self {
+            hash: MerkleTree::hash(&bytes),
+            aggregate,
+        }
+    }
+
+    /// The parent commits to both children hashes and summaries, in order.
+    fn parent(left: &Self, right: &Self) -> Self {
+        let mut bytes = vec![1];
+        for child in [left, right] {
+            let aggregate = child.aggregate.to_bytes();
+            bytes.extend_from_slice(&child.hash);
+            bytes.extend_from_slice(&(aggregate.len() as u64).to_be_bytes());
+            bytes.extend_from_slice(&aggregate);
+        }
+
+        Self {
+            hash: MerkleTree::hash(&bytes),
+            aggregate: left.aggregate.combine(&right.aggregate),
+        }
+    }
+}
+
+/// The proof of inclusion of a leaf and its summary in an `AggregateTree`.
+/// The siblings carry their summaries, which are checked on the way up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AggregateProof<A> {
+    pub leaf_index: usize,
+    pub leaf_count: usize,
+    pub siblings: Vec<AggregateNode<A>>,
+}
+
+impl<A: Aggregate> AggregateProof<A> {
+    /// Checks that the leaf with its summary is at its index under `root`,
+    /// whose summary must be the combination of every leaf's.
+    pub fn verify(&self, leaf: &Hash, aggregate: A, root: &AggregateNode<A>) -> Result<(), Error> {
+        if self.leaf_index >= self.leaf_count {
+            return Err(Error::IndexOutOfRange {
+                index: self.leaf_index,
+                leaf_count: self.leaf_count,
+            });
+        }
+        let expected = Proof::expected_length(self.leaf_count);
+        if self.siblings.len() != expected {
+            return Err(Error::InvalidProof(ProofError::LengthMismatch {
+                expected,
+                actual: self.siblings.len(),
+            }));
+        }
+
+        let mut node = AggregateNode::leaf(leaf, aggregate);
+        let mut index = self.leaf_index;
+        for sibling in &self.siblings {
+            node = if index.is_multiple_of(2) {
+                AggregateNode::parent(&node, sibling)
+            } else {
+                AggregateNode::parent(sibling, &node)
+            };
+            index /= 2;
+        }
+
+        if node != *root {
+            return Err(Error::RootMismatch);
+        }
+        Ok(())
+    }
+}
+
+/// A tree whose nodes carry a user-defined summary of the leaves below
+/// them next to their hash, answering both whether a leaf is included and
+/// what a subtree summarizes, e.g. its number of events and time span.
+///
+/// Odd levels are padded with an empty node rather than the last node.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{AggregateTree, MerkleTree, MinMax};
+///
+/// let events = [("Weathertop", 3018), ("Rivendell", 3019), ("Moria", 3019)];
+/// let leaves = events
+///     .iter()
+///     .map(|(place, year)| (MerkleTree::hash(place.as_bytes()), MinMax::of(*year)))
+///     .collect();
+///
+/// let tree = AggregateTree::build(leaves).unwrap();
+/// assert_eq!(tree.root().aggregate, MinMax(Some((3018, 3019))));
+///
+/// let proof = tree.proof(2).unwrap();
+/// assert!(proof.verify(&MerkleTree::hash(b"Moria"), MinMax::of(3019), &tree.root()).is_ok());
+/// ```
+#[derive(Debug, Clone)]
+pub struct AggregateTree<A> {
+    levels: Vec<Vec<AggregateNode<A>>>,
+}
+
+impl<A: Aggregate> AggregateTree<A> {
+    /// Builds the tree over leaf hashes and their summaries.
+    pub fn build(leaves: Vec<(Hash, A)>) -> Result<Self, Error> {
+        if leaves.is_empty() {
+            return Err(Error::EmptyTree);
+        }
+
+        let leaves = leaves
+            .into_iter()
+            .map(|(leaf, aggregate)| AggregateNode::leaf(&leaf, aggregate))
+            .collect();
+        let mut levels: Vec<Vec<AggregateNode<A>>> = vec![leaves];
+
+        while levels.last().unwrap().len() > 1 {
+            let level = levels.last().unwrap();
+            let parents = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => AggregateNode::parent(left, right),
+                    [last] => AggregateNode::parent(last, &AggregateNode::empty()),
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(parents);
+        }
+
+        Ok(Self { levels })
+    }
+
+    pub fn root(&self) -> AggregateNode<A> {
+        self.levels.last().unwrap()[0].clone()
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// The node at `index` of `level`, the leaves being level 0.
+    pub fn node(&self, level: usize, index: usize) -> Option<&AggregateNode<A>> {
+        self.levels.get(level)?.get(index)
+    }
+
+    /// Combines the summaries of the leaves in `range`, from the fewest nodes covering it.
+    pub fn aggregate(&self, range: Range<usize>) -> Result<A, Error> {
+        if range.start > range.end || range.end > self.leaf_count() {
+            return Err(Error::InvalidInput(format!(
+                "the range {:?} is not within {} leaves",
+                range,
+                self.leaf_count()
+            )));
+        }
+
+        let (mut left, mut right) = (A::identity(), A::identity());
+        let (mut start, mut end) = (range.start, range.end);
+        for level in &self.levels {
+            if start >= end {
+                break;
+            }
+            if !start.is_multiple_of(2) {
+                left = left.combine(&level[start].aggregate);
+                start += 1;
+            }
+            if !end.is_multiple_of(2) {
+                end -= 1;
+                right = level[end].aggregate.combine(&right);
+            }
+            start /= 2;
+            end /= 2;
+        }
+
+        Ok(left.combine(&right))
+    }
+
+    pub fn proof(&self, index: usize) -> Result<AggregateProof<A>, Error> {
+        if index >= self.leaf_count() {
+            return Err(Error::IndexOutOfRange {
+                index,
+                leaf_count: self.leaf_count(),
+            });
+        }
+
+        let siblings = self.levels[..self.levels.len() - 1]
+            .iter()
+            .enumerate()
+            .map(|(level, nodes)| {
+                nodes
+                    .get((index >> level) ^ 1)
+                    .cloned()
+                    .unwrap_or_else(AggregateNode::empty)
+            })
+            .collect();
+
+        Ok(AggregateProof {
+            leaf_index: index,
+            leaf_count: self.leaf_count(),
+            siblings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /// The number of entries and bytes of a log segment.
+    fn tree(count: usize) -> AggregateTree<(u64, u64)> {
+        let leaves = (0..count)
+            .map(|entry| {
+                let entry = format!("log entry {}", entry);
+                (MerkleTree::hash(entry.as_bytes()), (1, entry.len() as u64))
+            })
+            .collect();
+        AggregateTree::build(leaves).unwrap()
+    }
+
+    #[test]
+    fn test_ranges_are_summarized() {
+        for count in [1, 2, 5, 16, 23] {
+            let tree = tree(count);
+            for start in 0..=count {
+                for end in start..=count {
+                    let expected = (start..end)
+                        .map(|entry| (1, format!("log entry {}", entry).len() as u64))
+                        .fold(<(u64, u64)>::identity(), |total, leaf| total.combine(&leaf));
+                    assert_eq!(tree.aggregate(start..end), Ok(expected));
+                }
+            }
+            assert_eq!(tree.root().aggregate, tree.aggregate(0..count).unwrap());
+        }
+
+        assert!(tree(3).aggregate(2..4).is_err());
+    }
+
+    #[test]
+    fn test_proofs_commit_to_summaries() {
+        for count in [1, 2, 5, 16, 23] {
+            let tree = tree(count);
+            let root = tree.root();
+            for index in 0..count {
+                let entry = format!("log entry {}", index);
+                let leaf = MerkleTree::hash(entry.as_bytes());
+                let proof = tree.proof(index).unwrap();
+
+                assert_eq!(proof.verify(&leaf, (1, entry.len() as u64), &root), Ok(()));
+                assert!(proof.verify(&leaf, (2, entry.len() as u64), &root).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_forged_summaries_are_rejected() {
+        let tree = tree(6);
+        let leaf = MerkleTree::hash(b"log entry 4");
+        let mut proof = tree.proof(4).unwrap();
+
+        let mut forged_root = tree.root();
+        forged_root.aggregate.0 -= 1;
+        assert!(proof.verify(&leaf, (1, 11), &forged_root).is_err());
+
+        proof.siblings[0].aggregate.1 += 100;
+        assert!(proof.verify(&leaf, (1, 11), &tree.root()).is_err());
+        proof.siblings.pop();
+        assert!(matches!(
+            proof.verify(&leaf, (1, 11), &tree.root()),
+            Err(Error::InvalidProof(_))
+        ));
+        assert!(tree
+            .proof(4)
+            .unwrap()
+            .verify(&leaf, (1, 11), &tree.root())
+            .is_ok());
+    }
+}
@@ -0,0 +1,427 @@
+use std::collections::HashSet;
+use std::io::BufRead;
+
+use serde_json::{json, Map, Value};
+
+use crate::error::Error;
+use crate::keccak::keccak256;
+use crate::leaf::MerkleLeaf;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::profile::{Profile, ProfileTree};
+
+/// How an `(address, amount)` record is turned into a leaf, and the profile
+/// of the tree over the leaves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordEncoding {
+    /// Solidity's `keccak256(abi.encodePacked(address, uint256))`: the 20
+    /// address bytes followed by the amount as a 32 bytes big-endian integer,
+    /// in a tree of `Profile::OpenZeppelin` which `MerkleProof.verify` checks.
+    AbiPacked,
+    /// OpenZeppelin's `StandardMerkleTree` of `["address", "uint256"]`: the
+    /// `abi.encode` of the record, both values padded to 32 bytes, with the
+    /// leaves and the tree of `Profile::OpenZeppelin`.
+    Abi,
+    /// The `MerkleLeaf` encoding of the address string followed by the amount,
+    /// in a tree of `Profile::LegacySorted`.
+    Canonical,
+}
+
+impl RecordEncoding {
+    /// The profile of the tree over the leaves.
+    pub fn profile(&self) -> Profile {
+        match self {
+            RecordEncoding::AbiPacked | RecordEncoding::Abi => Profile::OpenZeppelin,
+            RecordEncoding::Canonical => Profile::LegacySorted,
+        }
+    }
+
+    fn encode(&self, record: &AirdropRecord) -> Result<Vec<u8>, Error> {
+        match self {
+            RecordEncoding::AbiPacked | RecordEncoding::Abi => {
+                let address = record
+                    .address
+                    .strip_prefix("0x")
+                    .and_then(|address| hex::decode(address).ok())
+                    .filter(|address| address.len() == 20)
+                    .ok_or_else(|| {
+                        Error::InvalidInput(format!("invalid address {}", record.address))
+                    })?;
+
+                let mut bytes = match self {
+                    RecordEncoding::Abi => vec![0; 12],
+                    _ => Vec::new(),
+                };
+                bytes.extend_from_slice(&address);
+                bytes.extend_from_slice(&[0; 16]);
+                bytes.extend_from_slice(&record.amount.to_be_bytes());
+                Ok(bytes)
+            }
+            RecordEncoding::Canonical => {
+                let mut bytes = record.address.leaf_bytes();
+                record.amount.encode_leaf(&mut bytes);
+                Ok(bytes)
+            }
+        }
+    }
+
+    fn leaf(&self, record: &AirdropRecord) -> Result<Hash, Error> {
+        let bytes = self.encode(record)?;
+        Ok(match self {
+            RecordEncoding::AbiPacked => keccak256(&bytes),
+            RecordEncoding::Abi => Profile::OpenZeppelin.hash_leaf(&bytes),
+            RecordEncoding::Canonical => MerkleTree::hash(&bytes),
+        })
+    }
+}
+
+/// A recipient of the airdrop and the amount it can claim.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AirdropRecord {
+    pub address: String,
+    pub amount: u128,
+}
+
+/// A committed allowlist: the tree over the records and the proof of each recipient.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{Airdrop, RecordEncoding};
+///
+/// let csv = "address,amount
+/// 0x1111111111111111111111111111111111111111,100
+/// 0x2222222222222222222222222222222222222222,250
+/// ";
+///
+/// let airdrop = Airdrop::from_csv(csv.as_bytes(), RecordEncoding::AbiPacked).unwrap();
+///
+/// let claim = airdrop.claim("0x2222222222222222222222222222222222222222").unwrap();
+/// assert!(airdrop.verify(&claim));
+///
+/// let exported = airdrop.to_json();
+/// ```
+pub struct Airdrop {
+    records: Vec<AirdropRecord>,
+    leaves: Vec<Hash>,
+    encoding: RecordEncoding,
+    tree: ProfileTree,
+}
+
+/// Everything a recipient needs to claim its amount.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Claim {
+    pub address: String,
+    pub amount: u128,
+    pub leaf: Hash,
+    pub proof: Vec<Hash>,
+}
+
+impl Airdrop {
+    /// Builds the allowlist from the records.
+    /// Fails if there are no records, an address appears twice or a record can't be encoded.
+    pub fn from_records<I>(records: I, encoding: RecordEncoding) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = AirdropRecord>,
+    {
+        let records: Vec<AirdropRecord> = records.into_iter().collect();
+
+        let mut addresses = HashSet::new();
+        for record in &records {
+            if !addresses.insert(record.address.to_lowercase()) {
+                return Err(Error::InvalidInput(format!(
+                    "duplicate address {}",
+                    record.address
+                )));
+            }
+        }
+
+        let leaves = records
+            .iter()
+            .map(|record| encoding.leaf(record))
+            .collect::<Result<Vec<Hash>, Error>>()?;
+
+        let tree = MerkleTree::builder()
+            .hashes_only()
+            .build_profile_from_hashes(encoding.profile(), leaves.clone())?;
+
+        Ok(Self {
+            records,
+            leaves,
+            encoding,
+            tree,
+        })
+    }
+
+    /// Builds the allowlist from `address,amount` lines.
+    /// A first line with the `address,amount` header is skipped, as are blank lines.
+    pub fn from_csv<R: BufRead>(reader: R, encoding: RecordEncoding) -> Result<Self, Error> {
+        let mut records = Vec::new();
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line.map_err(|error| Error::InvalidInput(error.to_string()))?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if number == 0 && Self::is_header(line) {
+                continue;
+            }
+
+            match Self::parse_line(line) {
+                Some(record) => records.push(record),
+                None => {
+                    return Err(Error::InvalidInput(format!(
+                        "invalid record on line {}: {}",
+                        number + 1,
+                        line
+                    )))
+                }
+            }
+        }
+
+        Self::from_records(records, encoding)
+    }
+
+    fn is_header(line: &str) -> bool {
+        line.split_once(',').is_some_and(|(address, amount)| {
+            address.trim().eq_ignore_ascii_case("address")
+                && amount.trim().eq_ignore_ascii_case("amount")
+        })
+    }
+
+    fn parse_line(line: &str) -> Option<AirdropRecord> {
+        let (address, amount) = line.split_once(',')?;
+        let amount = amount.trim().parse().ok()?;
+
+        Some(AirdropRecord {
+            address: address.trim().to_string(),
+            amount,
+        })
+    }
+
+    pub fn root(&self) -> Hash {
+        self.tree.root()
+    }
+
+    pub fn tree(&self) -> &ProfileTree {
+        &self.tree
+    }
+
+    pub fn encoding(&self) -> RecordEncoding {
+        self.encoding
+    }
+
+    /// Returns the claim of the given address, if it is part of the airdrop.
+    pub fn claim(&self, address: &str) -> Option<Claim> {
+        let index = self
+            .records
+            .iter()
+            .position(|record| record.address.eq_ignore_ascii_case(address))?;
+
+        self.claim_at(index)
+    }
+
+    fn claim_at(&self, index: usize) -> Option<Claim> {
+        let record = self.records.get(index)?;
+
+        Some(Claim {
+            address: record.address.clone(),
+            amount: record.amount,
+            leaf: self.leaves[index],
+            proof: self.tree.proof(index).ok()?.siblings,
+        })
+    }
+
+    /// Checks a claim the way an on-chain verifier does, hashing the sorted
+    /// pair of each node and sibling up to the root.
+    pub fn verify(&self, claim: &Claim) -> bool {
+        let profile = self.encoding.profile();
+        let root = claim.proof.iter().fold(claim.leaf, |node, sibling| {
+            profile.hash_node(&node, sibling)
+        });
+
+        root == self.root()
+    }
+
+    /// Exports the root and the claim of every recipient, keyed by address.
+    /// Hashes are 0x-prefixed hex strings and amounts decimal strings.
+    pub fn to_json(&self) -> Value {
+        let mut claims = Map::new();
+
+        for index in 0..self.records.len() {
+            let claim = self.claim_at(index).expect("Every record has a claim.");
+            claims.insert(
+                claim.address,
+                json!({
+                    "amount": claim.amount.to_string(),
+                    "leaf": to_hex(&claim.leaf),
+                    "proof": claim.proof.iter().map(to_hex).collect::<Vec<String>>(),
+                }),
+            );
+        }
+
+        let encoding = match self.encoding {
+            RecordEncoding::AbiPacked => "abi-packed",
+            RecordEncoding::Abi => "abi",
+            RecordEncoding::Canonical => "canonical",
+        };
+
+        json!({
+            "root": to_hex(&self.root()),
+            "encoding": encoding,
+            "claims": claims,
+        })
+    }
+}
+
+fn to_hex(hash: &Hash) -> String {
+    format!("0x{}", hex::encode(hash))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn records() -> Vec<AirdropRecord> {
+        vec![
+            AirdropRecord {
+                address: "0x1111111111111111111111111111111111111111".to_string(),
+                amount: 100,
+            },
+            AirdropRecord {
+                address: "0x2222222222222222222222222222222222222222".to_string(),
+                amount: 250,
+            },
+            AirdropRecord {
+                address: "0x3333333333333333333333333333333333333333".to_string(),
+                amount: 1,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_abi_packed_encoding() {
+        let record = &records()[0];
+
+        let bytes = RecordEncoding::AbiPacked.encode(record).unwrap();
+
+        assert_eq!(bytes.len(), 52);
+        assert_eq!(&bytes[..20], &[0x11; 20]);
+        assert_eq!(bytes[51], 100);
+    }
+
+    #[test]
+    fn test_every_claim_validates() {
+        let airdrop = Airdrop::from_records(records(), RecordEncoding::Canonical).unwrap();
+
+        for record in records() {
+            let claim = airdrop.claim(&record.address).unwrap();
+            assert_eq!(claim.amount, record.amount);
+            assert!(airdrop.verify(&claim));
+        }
+
+        assert!(airdrop
+            .claim("0x4444444444444444444444444444444444444444")
+            .is_none());
+    }
+
+    #[test]
+    fn test_from_csv_matches_from_records() {
+        let csv = "address,amount\n\
+            0x1111111111111111111111111111111111111111,100\n\
+            \n\
+            0x2222222222222222222222222222222222222222, 250\n\
+            0x3333333333333333333333333333333333333333,1\n";
+
+        let from_csv = Airdrop::from_csv(csv.as_bytes(), RecordEncoding::AbiPacked).unwrap();
+        let from_records = Airdrop::from_records(records(), RecordEncoding::AbiPacked).unwrap();
+
+        assert_eq!(from_csv.root(), from_records.root());
+    }
+
+    #[test]
+    fn test_invalid_records_are_rejected() {
+        let csv = "0x1111111111111111111111111111111111111111,100\nnot a record\n";
+        assert!(Airdrop::from_csv(csv.as_bytes(), RecordEncoding::Canonical).is_err());
+
+        // Only the header may be skipped, not a malformed first recipient.
+        let csv = "0x1111111111111111111111111111111111111111;100\n\
+            0x2222222222222222222222222222222222222222,250\n";
+        assert!(Airdrop::from_csv(csv.as_bytes(), RecordEncoding::Canonical).is_err());
+
+        let mut duplicated = records();
+        duplicated.push(records()[0].clone());
+        assert!(Airdrop::from_records(duplicated, RecordEncoding::Canonical).is_err());
+
+        let short_address = vec![AirdropRecord {
+            address: "0x1234".to_string(),
+            amount: 1,
+        }];
+        assert!(Airdrop::from_records(short_address, RecordEncoding::AbiPacked).is_err());
+    }
+
+    #[test]
+    fn test_json_export() {
+        let airdrop = Airdrop::from_records(records(), RecordEncoding::AbiPacked).unwrap();
+
+        let exported = airdrop.to_json();
+
+        assert_eq!(exported["root"], to_hex(&airdrop.root()));
+        assert_eq!(exported["encoding"], "abi-packed");
+        let claim = &exported["claims"]["0x2222222222222222222222222222222222222222"];
+        assert_eq!(claim["amount"], "250");
+        let proof = airdrop
+            .claim("0x2222222222222222222222222222222222222222")
+            .unwrap()
+            .proof;
+        assert_eq!(claim["proof"].as_array().unwrap().len(), proof.len());
+    }
+
+    #[test]
+    fn test_openzeppelin_standard_tree_root() {
+        // The example of the README of @openzeppelin/merkle-tree.
+        let records = vec![
+            AirdropRecord {
+                address: "0x1111111111111111111111111111111111111111".to_string(),
+                amount: 5_000_000_000_000_000_000,
+            },
+            AirdropRecord {
+                address: "0x2222222222222222222222222222222222222222".to_string(),
+                amount: 2_500_000_000_000_000_000,
+            },
+        ];
+
+        let airdrop = Airdrop::from_records(records.clone(), RecordEncoding::Abi).unwrap();
+
+        assert_eq!(
+            to_hex(&airdrop.root()),
+            "0xd4dee0beab2d53f2cc83e567171bd2820e49898130a22622b10ead383e90bd77"
+        );
+        for record in records {
+            assert!(airdrop.verify(&airdrop.claim(&record.address).unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_abi_packed_claims_verify_with_keccak() {
+        let airdrop = Airdrop::from_records(records(), RecordEncoding::AbiPacked).unwrap();
+
+        for record in records() {
+            let claim = airdrop.claim(&record.address).unwrap();
+            let packed = RecordEncoding::AbiPacked.encode(&record).unwrap();
+            assert_eq!(claim.leaf, keccak256(&packed));
+
+            // MerkleProof.verify of OpenZeppelin: Keccak-256 of each sorted pair.
+            let root = claim.proof.iter().fold(claim.leaf, |node, sibling| {
+                let (first, second) = if node <= *sibling {
+                    (node, *sibling)
+                } else {
+                    (*sibling, node)
+                };
+                keccak256(&[first, second].concat())
+            });
+            assert_eq!(root, airdrop.root());
+        }
+    }
+}
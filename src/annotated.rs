@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+
+use crate::error::Error;
+use crate::leaf::MerkleLeaf;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// Small key-value data attached to a leaf, such as timestamps, tags or external IDs.
+pub type LeafMetadata = BTreeMap<String, String>;
+
+/// Whether the metadata of the leaves is part of the root.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MetadataMode {
+    /// The metadata is stored and returned with proofs, the leaves are the items alone.
+    #[default]
+    Detached,
+    /// Every leaf commits to its item and to its metadata.
+    Committed,
+}
+
+impl MetadataMode {
+    /// The leaf of an item with the given metadata.
+    pub fn leaf_hash(&self, item: &[u8], metadata: &LeafMetadata) -> Hash {
+        self.leaf_of_item_hash(MerkleTree::hash(item), metadata)
+    }
+
+    fn leaf_of_item_hash(&self, item: Hash, metadata: &LeafMetadata) -> Hash {
+        match self {
+            MetadataMode::Detached => item,
+            MetadataMode::Committed => {
+                let mut bytes = item.to_vec();
+                bytes.extend_from_slice(&metadata_digest(metadata));
+                MerkleTree::hash(&bytes)
+            }
+        }
+    }
+}
+
+/// The hash of the `MerkleLeaf` encoding of the entries, sorted by key.
+fn metadata_digest(metadata: &LeafMetadata) -> Hash {
+    let mut bytes = (metadata.len() as u64).leaf_bytes();
+    for (key, value) in metadata {
+        key.encode_leaf(&mut bytes);
+        value.encode_leaf(&mut bytes);
+    }
+    MerkleTree::hash(&bytes)
+}
+
+/// A proof of inclusion with the metadata of its leaf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnotatedProof {
+    pub proof: Proof,
+    pub metadata: LeafMetadata,
+    pub mode: MetadataMode,
+}
+
+impl AnnotatedProof {
+    /// Checks the item is in the tree. With `MetadataMode::Committed` the
+    /// metadata is checked too, otherwise it is only informative.
+    pub fn verify(&self, item: &[u8], root: &Hash) -> Result<(), Error> {
+        self.proof
+            .verify(&self.mode.leaf_hash(item, &self.metadata), root)
+    }
+}
+
+/// A tree that stores metadata with its leaves, so it can't drift from the tree.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{AnnotatedTree, LeafMetadata, MetadataMode};
+///
+/// let metadata = LeafMetadata::from([("forged".to_string(), "S.A. 1600".to_string())]);
+///
+/// let tree = AnnotatedTree::build(
+///     &[("the One Ring", metadata.clone()), ("Narya", LeafMetadata::new())],
+///     MetadataMode::Committed,
+/// )
+/// .unwrap();
+///
+/// let proof = tree.prove(0).unwrap();
+/// assert_eq!(proof.metadata, metadata);
+/// assert!(proof.verify(b"the One Ring", &tree.root()).is_ok());
+/// ```
+pub struct AnnotatedTree {
+    items: Vec<Hash>,
+    metadata: Vec<LeafMetadata>,
+    mode: MetadataMode,
+    tree: MerkleTree,
+}
+
+impl AnnotatedTree {
+    /// Builds the tree from items and their metadata.
+    pub fn build<T: AsRef<[u8]>>(
+        items: &[(T, LeafMetadata)],
+        mode: MetadataMode,
+    ) -> Result<Self, Error> {
+        let metadata: Vec<LeafMetadata> =
+            items.iter().map(|(_, metadata)| metadata.clone()).collect();
+        let leaves = items
+            .iter()
+            .map(|(item, metadata)| mode.leaf_hash(item.as_ref(), metadata))
+            .collect();
+
+        Ok(Self {
+            items: items
+                .iter()
+                .map(|(item, _)| MerkleTree::hash(item.as_ref()))
+                .collect(),
+            metadata,
+            mode,
+            tree: MerkleTree::from_leaves(leaves).ok_or(Error::EmptyTree)?,
+        })
+    }
+
+    /// Inserts an item with its metadata and returns its index.
+    pub fn insert<T: AsRef<[u8]>>(
+        &mut self,
+        item: &T,
+        metadata: LeafMetadata,
+    ) -> Result<usize, Error> {
+        self.tree.try_insert_leaf(
+            self.mode.leaf_hash(item.as_ref(), &metadata),
+            item.as_ref().len(),
+        )?;
+        self.items.push(MerkleTree::hash(item.as_ref()));
+        self.metadata.push(metadata);
+
+        Ok(self.items.len() - 1)
+    }
+
+    pub fn root(&self) -> Hash {
+        self.tree.root().expect("A tree has a root.")
+    }
+
+    pub fn tree(&self) -> &MerkleTree {
+        &self.tree
+    }
+
+    pub fn mode(&self) -> MetadataMode {
+        self.mode
+    }
+
+    /// The metadata of the leaf at `index`.
+    pub fn metadata(&self, index: usize) -> Option<&LeafMetadata> {
+        self.metadata.get(index)
+    }
+
+    /// Replaces the metadata of the leaf at `index`.
+    /// With `MetadataMode::Committed` the root changes accordingly.
+    pub fn set_metadata(&mut self, index: usize, metadata: LeafMetadata) -> Result<(), Error> {
+        if index >= self.items.len() {
+            return Err(Error::IndexOutOfRange {
+                index,
+                leaf_count: self.items.len(),
+            });
+        }
+
+        self.metadata[index] = metadata;
+
+        if self.mode == MetadataMode::Committed {
+            let leaves = self
+                .items
+                .iter()
+                .zip(&self.metadata)
+                .map(|(item, metadata)| self.mode.leaf_of_item_hash(*item, metadata))
+                .collect();
+            self.tree = MerkleTree::from_leaves(leaves).expect("The tree has leaves.");
+        }
+
+        Ok(())
+    }
+
+    /// Returns the proof of inclusion of the leaf at `index` with its metadata.
+    pub fn prove(&self, index: usize) -> Result<AnnotatedProof, Error> {
+        Ok(AnnotatedProof {
+            proof: self.tree.proof(index)?,
+            metadata: self.metadata[index].clone(),
+            mode: self.mode,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn tagged(tag: &str) -> LeafMetadata {
+        LeafMetadata::from([
+            ("tag".to_string(), tag.to_string()),
+            ("id".to_string(), format!("ext-{}", tag)),
+        ])
+    }
+
+    fn items() -> Vec<(&'static str, LeafMetadata)> {
+        vec![
+            ("Vilya", tagged("air")),
+            ("Nenya", tagged("water")),
+            ("Narya", tagged("fire")),
+        ]
+    }
+
+    #[test]
+    fn test_detached_metadata_does_not_change_the_root() {
+        let tree = AnnotatedTree::build(&items(), MetadataMode::Detached).unwrap();
+
+        assert_eq!(
+            tree.root(),
+            MerkleTree::build(&["Vilya", "Nenya", "Narya"])
+                .unwrap()
+                .root()
+                .unwrap()
+        );
+
+        let mut proof = tree.prove(1).unwrap();
+        assert_eq!(proof.metadata, tagged("water"));
+        proof.metadata = tagged("earth");
+        assert_eq!(proof.verify(b"Nenya", &tree.root()), Ok(()));
+    }
+
+    #[test]
+    fn test_committed_metadata_is_verified() {
+        let mut tree = AnnotatedTree::build(&items(), MetadataMode::Committed).unwrap();
+        let index = tree.insert(&"the One Ring", tagged("shadow")).unwrap();
+
+        let proof = tree.prove(index).unwrap();
+        assert_eq!(proof.verify(b"the One Ring", &tree.root()), Ok(()));
+
+        let mut forged = proof.clone();
+        forged.metadata = tagged("light");
+        assert!(forged.verify(b"the One Ring", &tree.root()).is_err());
+    }
+
+    #[test]
+    fn test_set_metadata_updates_committed_roots() {
+        let mut tree = AnnotatedTree::build(&items(), MetadataMode::Committed).unwrap();
+        let before = tree.root();
+
+        tree.set_metadata(2, tagged("ember")).unwrap();
+
+        assert_ne!(tree.root(), before);
+        assert_eq!(tree.metadata(2), Some(&tagged("ember")));
+        assert_eq!(
+            tree.prove(2).unwrap().verify(b"Narya", &tree.root()),
+            Ok(())
+        );
+        assert!(tree.set_metadata(3, LeafMetadata::new()).is_err());
+    }
+}
@@ -0,0 +1,285 @@
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::{Proof, ProofError};
+
+/// A proof that appending a given batch of leaves to a tree transforms its
+/// root into another, checked by a party holding only the old root.
+///
+/// It is the proof of inclusion of the last leaf of the old tree. Its left
+/// siblings are complete subtrees, the same in the tree after the append,
+/// so with the appended leaves they are enough to compute the new root.
+/// Unlike a `ConsistencyProof`, it binds the appended content.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::MerkleTree;
+///
+/// let mut tree = MerkleTree::build(&["Elros", "Vardamir", "Tar-Amandil"]).unwrap();
+/// let old_root = tree.root().unwrap();
+///
+/// let proof = tree.append_proof();
+/// tree.insert(&"Tar-Elendil").unwrap();
+/// tree.insert(&"Tar-Meneldur").unwrap();
+///
+/// let appended = [MerkleTree::hash(b"Tar-Elendil"), MerkleTree::hash(b"Tar-Meneldur")];
+/// assert!(proof.verify(&old_root, &appended, &tree.root().unwrap()).is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppendProof {
+    pub old_size: usize,
+    /// The last leaf of the old tree.
+    pub last_leaf: Hash,
+    /// The proof of inclusion of the last leaf in the old tree.
+    pub siblings: Vec<Hash>,
+}
+
+impl AppendProof {
+    /// Checks the proof against the old root and returns the root of the
+    /// tree after appending `leaves`.
+    pub fn new_root(&self, old_root: &Hash, leaves: &[Hash]) -> Result<Hash, Error> {
+        if self.old_size == 0 {
+            return Err(Error::EmptyTree);
+        }
+
+        Proof {
+            leaf_index: self.old_size - 1,
+            leaf_count: self.old_size,
+            siblings: self.siblings.clone(),
+        }
+        .verify(&self.last_leaf, old_root)?;
+
+        let new_size = self.new_size(leaves)?;
+        Ok(self.node(Proof::expected_length(new_size), 0, leaves))
+    }
+
+    /// Checks that appending `leaves` to the tree of `old_root` leads to `new_root`.
+    pub fn verify(&self, old_root: &Hash, leaves: &[Hash], new_root: &Hash) -> Result<(), Error> {
+        if self.new_root(old_root, leaves)? != *new_root {
+            return Err(Error::RootMismatch);
+        }
+        Ok(())
+    }
+
+    /// Returns the append proof of the tree after appending `leaves`, so a
+    /// party tailing a growing tree keeps only the last one.
+    ///
+    /// Fails if the proof does not have the shape of a proof in a tree of
+    /// `old_size` leaves.
+    pub fn extended(&self, leaves: &[Hash]) -> Result<AppendProof, Error> {
+        let expected = Proof::expected_length(self.old_size);
+        if self.siblings.len() != expected {
+            return Err(Error::InvalidProof(ProofError::LengthMismatch {
+                expected,
+                actual: self.siblings.len(),
+            }));
+        }
+        let new_size = self.new_size(leaves)?;
+        let last = new_size - 1;
+
+        let siblings = (0..Proof::expected_length(new_size))
+            .map(|level| {
+                let position = last >> level;
+                if position.is_multiple_of(2) {
+                    // The last node of a level without sibling is paired with itself.
+                    self.node(level, position, leaves)
+                } else if position << level < self.old_size {
+                    // A complete subtree of old leaves before the last old leaf,
+                    // a left sibling on its path too.
+                    self.siblings[level]
+                } else {
+                    self.node(level, position - 1, leaves)
+                }
+            })
+            .collect();
+
+        Ok(AppendProof {
+            old_size: new_size,
+            last_leaf: leaves.last().copied().unwrap_or(self.last_leaf),
+            siblings,
+        })
+    }
+
+    fn new_size(&self, leaves: &[Hash]) -> Result<usize, Error> {
+        if self.old_size == 0 {
+            return Err(Error::EmptyTree);
+        }
+        self.old_size
+            .checked_add(leaves.len())
+            .ok_or_else(|| Error::InvalidInput("the tree would overflow".to_string()))
+    }
+
+    /// Computes a node of the new tree whose leaves include the last old
+    /// leaf or only appended leaves. The other nodes are never needed.
+    fn node(&self, level: usize, index: usize, leaves: &[Hash]) -> Hash {
+        let last = self.old_size - 1;
+        let new_size = self.old_size + leaves.len();
+        let first = index << level;
+
+        if first > last {
+            let end = ((index + 1) << level).min(new_size);
+            return subtree_root(&leaves[first - self.old_size..end - self.old_size], level);
+        }
+        if level == 0 {
+            return self.last_leaf;
+        }
+
+        let (left, right) = (2 * index, 2 * index + 1);
+        let left_hash = if last >> (level - 1) == left {
+            self.node(level - 1, left, leaves)
+        } else {
+            // The path goes through the right child, the left one is a
+            // complete subtree of the old tree.
+            self.siblings[level - 1]
+        };
+        let right_hash = if right < MerkleTree::level_width(new_size, level - 1) {
+            self.node(level - 1, right, leaves)
+        } else {
+            left_hash
+        };
+
+        MerkleTree::merkle_parent(&[left_hash, right_hash])
+    }
+}
+
+/// The root of the subtree at `level` over `leaves`, starting at an aligned index.
+fn subtree_root(leaves: &[Hash], level: usize) -> Hash {
+    let mut nodes = leaves.to_vec();
+    for _ in 0..level {
+        if nodes.len() % 2 == 1 {
+            nodes.extend(nodes.last().cloned());
+        }
+        nodes = nodes
+            .chunks_exact(2)
+            .map(MerkleTree::merkle_parent)
+            .collect();
+    }
+    nodes[0]
+}
+
+impl MerkleTree {
+    /// Returns the proof that appending leaves to this tree leads to a given
+    /// root, checked against the roots without the leaf count commitment.
+    pub fn append_proof(&self) -> AppendProof {
+        let last = self.leaf_count() - 1;
+
+        AppendProof {
+            old_size: self.leaf_count(),
+            last_leaf: self.node(0, last).expect("The tree has leaves."),
+            siblings: self
+                .proof(last)
+                .expect("The last leaf is in the tree.")
+                .siblings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn entries(count: usize) -> Vec<Hash> {
+        (0..count)
+            .map(|entry| MerkleTree::hash(format!("king of Númenor {}", entry).as_bytes()))
+            .collect()
+    }
+
+    fn root(leaves: &[Hash]) -> Hash {
+        MerkleTree::builder()
+            .build_from_hashes(leaves.to_vec())
+            .unwrap()
+            .root()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_every_append_is_proven() {
+        let leaves = entries(40);
+
+        for old_size in 1..=20 {
+            let old = MerkleTree::builder()
+                .build_from_hashes(leaves[..old_size].to_vec())
+                .unwrap();
+            let proof = old.append_proof();
+
+            for new_size in old_size..=40 {
+                assert_eq!(
+                    proof.new_root(&root(&leaves[..old_size]), &leaves[old_size..new_size]),
+                    Ok(root(&leaves[..new_size])),
+                    "{} -> {}",
+                    old_size,
+                    new_size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_appended_content_is_bound() {
+        let leaves = entries(12);
+        let old = MerkleTree::builder()
+            .build_from_hashes(leaves[..7].to_vec())
+            .unwrap();
+        let proof = old.append_proof();
+        let new_root = root(&leaves);
+
+        assert_eq!(
+            proof.verify(&old.root().unwrap(), &leaves[7..], &new_root),
+            Ok(())
+        );
+
+        let mut other = leaves[7..].to_vec();
+        other[2] = MerkleTree::hash(b"Ar-Pharazon");
+        assert_eq!(
+            proof.verify(&old.root().unwrap(), &other, &new_root),
+            Err(Error::RootMismatch)
+        );
+        assert!(proof
+            .verify(&root(&leaves[..6]), &leaves[7..], &new_root)
+            .is_err());
+    }
+
+    #[test]
+    fn test_extended_proofs_match_the_new_tree() {
+        let leaves = entries(40);
+
+        for old_size in 1..=20 {
+            let old = MerkleTree::builder()
+                .build_from_hashes(leaves[..old_size].to_vec())
+                .unwrap();
+
+            for new_size in old_size..=40 {
+                let new = MerkleTree::builder()
+                    .build_from_hashes(leaves[..new_size].to_vec())
+                    .unwrap();
+                assert_eq!(
+                    old.append_proof().extended(&leaves[old_size..new_size]),
+                    Ok(new.append_proof()),
+                    "{} -> {}",
+                    old_size,
+                    new_size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_malformed_proofs_are_errors() {
+        let leaves = entries(9);
+        let mut proof = MerkleTree::builder()
+            .build_from_hashes(leaves[..5].to_vec())
+            .unwrap()
+            .append_proof();
+        proof.siblings.pop();
+
+        assert!(proof.extended(&leaves[5..]).is_err());
+        assert!(proof.new_root(&root(&leaves[..5]), &leaves[5..]).is_err());
+
+        let empty = AppendProof {
+            old_size: 0,
+            last_leaf: leaves[0],
+            siblings: Vec::new(),
+        };
+        assert_eq!(empty.extended(&[]), Err(Error::EmptyTree));
+    }
+}
@@ -0,0 +1,342 @@
+use std::io::{Read, Write};
+
+use crate::audit_log::{Checkpoint, MerkleLogWriter};
+use crate::consistency::ConsistencyProof;
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+const HEADER: &str = "merkle-archive 1";
+
+/// A self-contained record of a log for long-term retention: its leaves,
+/// every published checkpoint and the consistency proofs between
+/// consecutive checkpoints, so past roots stay verifiable offline.
+///
+/// The bundle is written in one pass as text lines and ends with a footer
+/// holding the SHA-256 of everything before it:
+///
+/// ```text
+/// merkle-archive 1
+/// leaves <n>
+/// <hex leaf>                                   n lines
+/// checkpoints <m>
+/// <size> <hex root>                            m lines
+/// consistency <m - 1>
+/// <old size> <new size> <hex leaf> <hex siblings...>
+/// footer <hex SHA-256 of the previous lines>
+/// ```
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{ArchiveBundle, MerkleLogWriter};
+///
+/// let mut writer = MerkleLogWriter::new(Vec::new(), Vec::new(), 2);
+/// for line in ["Rohan answers", "the host rides", "the Pelennor is won"] {
+///     writer.append(line).unwrap();
+/// }
+/// writer.checkpoint().unwrap();
+///
+/// let mut bytes = Vec::new();
+/// writer.archive().unwrap().write_to(&mut bytes).unwrap();
+///
+/// let bundle = ArchiveBundle::read(bytes.as_slice()).unwrap();
+/// let checkpoint = bundle.checkpoints()[0];
+/// let proof = bundle.prove(1, checkpoint.size).unwrap();
+/// assert!(checkpoint.verify_line("the host rides", &proof).is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveBundle {
+    leaves: Vec<Hash>,
+    checkpoints: Vec<Checkpoint>,
+    consistency: Vec<ConsistencyProof>,
+}
+
+impl ArchiveBundle {
+    /// Bundles the leaves of a log with its checkpoints, oldest first,
+    /// failing if a checkpoint is not a root of the leaves.
+    pub fn new(leaves: Vec<Hash>, checkpoints: Vec<Checkpoint>) -> Result<Self, Error> {
+        let consistency = checkpoints
+            .windows(2)
+            .map(|pair| {
+                MerkleTree::from_leaves(leaves[..pair[1].size.min(leaves.len())].to_vec())
+                    .ok_or(Error::EmptyTree)?
+                    .consistency_proof(pair[0].size)
+            })
+            .collect::<Result<_, _>>()?;
+
+        let bundle = Self {
+            leaves,
+            checkpoints,
+            consistency,
+        };
+        bundle.verify()?;
+        Ok(bundle)
+    }
+
+    pub fn leaves(&self) -> &[Hash] {
+        &self.leaves
+    }
+
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    /// The proofs that every checkpoint extends the one before it.
+    pub fn consistency_proofs(&self) -> &[ConsistencyProof] {
+        &self.consistency
+    }
+
+    /// Checks every checkpoint against the leaves and every consistency
+    /// proof against the checkpoints it links.
+    pub fn verify(&self) -> Result<(), Error> {
+        for checkpoint in &self.checkpoints {
+            let root = self.tree_at(checkpoint.size)?.root();
+            if root != Some(checkpoint.root) {
+                return Err(Error::RootMismatch);
+            }
+        }
+
+        if self.consistency.len() != self.checkpoints.len().saturating_sub(1) {
+            return Err(Error::InvalidInput(format!(
+                "{} checkpoints need {} consistency proofs, not {}",
+                self.checkpoints.len(),
+                self.checkpoints.len().saturating_sub(1),
+                self.consistency.len()
+            )));
+        }
+        for (pair, proof) in self.checkpoints.windows(2).zip(&self.consistency) {
+            if proof.old_size != pair[0].size || proof.new_size != pair[1].size {
+                return Err(Error::InvalidInput(format!(
+                    "the proof is from {} to {} leaves, not from {} to {}",
+                    proof.old_size, proof.new_size, pair[0].size, pair[1].size
+                )));
+            }
+            proof.verify(&pair[0].root, &pair[1].root)?;
+        }
+
+        Ok(())
+    }
+
+    /// Proves the leaf at `index` was in the log when it had `size` leaves.
+    pub fn prove(&self, index: usize, size: usize) -> Result<Proof, Error> {
+        self.tree_at(size)?.proof(index)
+    }
+
+    fn tree_at(&self, size: usize) -> Result<MerkleTree, Error> {
+        if size > self.leaves.len() {
+            return Err(Error::InvalidInput(format!(
+                "the archive has {} leaves, not {}",
+                self.leaves.len(),
+                size
+            )));
+        }
+
+        MerkleTree::from_leaves(self.leaves[..size].to_vec()).ok_or(Error::EmptyTree)
+    }
+
+    /// Writes the bundle and its integrity footer.
+    pub fn write_to<W: Write>(&self, mut out: W) -> Result<(), Error> {
+        let mut body = format!("{}\nleaves {}\n", HEADER, self.leaves.len());
+        for leaf in &self.leaves {
+            body.push_str(&format!("{}\n", hex::encode(leaf)));
+        }
+        body.push_str(&format!("checkpoints {}\n", self.checkpoints.len()));
+        for checkpoint in &self.checkpoints {
+            body.push_str(&format!("{}\n", checkpoint.to_line()));
+        }
+        body.push_str(&format!("consistency {}\n", self.consistency.len()));
+        for proof in &self.consistency {
+            let mut line = format!(
+                "{} {} {}",
+                proof.old_size,
+                proof.new_size,
+                hex::encode(proof.leaf)
+            );
+            for sibling in &proof.siblings {
+                line.push_str(&format!(" {}", hex::encode(sibling)));
+            }
+            body.push_str(&format!("{}\n", line));
+        }
+
+        let footer = format!(
+            "footer {}\n",
+            hex::encode(MerkleTree::hash(body.as_bytes()))
+        );
+        let io_error = |error: std::io::Error| Error::InvalidInput(error.to_string());
+        out.write_all(body.as_bytes()).map_err(io_error)?;
+        out.write_all(footer.as_bytes()).map_err(io_error)?;
+        out.flush().map_err(io_error)
+    }
+
+    /// Reads a bundle written by `write_to`, checking its footer, then
+    /// every checkpoint and consistency proof.
+    pub fn read<R: Read>(mut input: R) -> Result<Self, Error> {
+        let mut text = String::new();
+        input
+            .read_to_string(&mut text)
+            .map_err(|error| Error::InvalidInput(error.to_string()))?;
+        let invalid = |reason: &str| Error::InvalidInput(format!("invalid archive: {}", reason));
+
+        let footer_start = text
+            .trim_end_matches('\n')
+            .rfind('\n')
+            .map(|position| position + 1)
+            .ok_or_else(|| invalid("no footer"))?;
+        let (body, footer) = text.split_at(footer_start);
+        let digest = footer
+            .trim_end()
+            .strip_prefix("footer ")
+            .ok_or_else(|| invalid("no footer"))?;
+        if digest != hex::encode(MerkleTree::hash(body.as_bytes())) {
+            return Err(invalid("the footer does not match the content"));
+        }
+
+        let mut lines = body.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(invalid("unknown header"));
+        }
+        let mut section = |name: &str| -> Result<Vec<&str>, Error> {
+            let count: usize = lines
+                .next()
+                .and_then(|line| line.strip_prefix(name))
+                .and_then(|count| count.trim().parse().ok())
+                .ok_or_else(|| invalid(name))?;
+            (0..count)
+                .map(|_| lines.next().ok_or_else(|| invalid(name)))
+                .collect()
+        };
+
+        let leaves = section("leaves")?
+            .into_iter()
+            .map(|line| parse_hash(line).ok_or_else(|| invalid("leaves")))
+            .collect::<Result<_, _>>()?;
+        let checkpoints = section("checkpoints")?
+            .into_iter()
+            .map(Checkpoint::parse_line)
+            .collect::<Result<_, _>>()?;
+        let consistency = section("consistency")?
+            .into_iter()
+            .map(|line| parse_consistency(line).ok_or_else(|| invalid("consistency")))
+            .collect::<Result<_, _>>()?;
+
+        let bundle = Self {
+            leaves,
+            checkpoints,
+            consistency,
+        };
+        bundle.verify()?;
+        Ok(bundle)
+    }
+}
+
+fn parse_hash(text: &str) -> Option<Hash> {
+    hex::decode(text).ok()?.try_into().ok()
+}
+
+fn parse_consistency(line: &str) -> Option<ConsistencyProof> {
+    let mut fields = line.split(' ');
+    Some(ConsistencyProof {
+        old_size: fields.next()?.parse().ok()?,
+        new_size: fields.next()?.parse().ok()?,
+        leaf: parse_hash(fields.next()?)?,
+        siblings: fields.map(parse_hash).collect::<Option<_>>()?,
+    })
+}
+
+impl<L: Write, S: Write> MerkleLogWriter<L, S> {
+    /// Bundles the lines and checkpoints of the log for archival.
+    pub fn archive(&self) -> Result<ArchiveBundle, Error> {
+        ArchiveBundle::new(self.leaves().to_vec(), self.checkpoints().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn archive() -> Vec<u8> {
+        let mut writer = MerkleLogWriter::new(Vec::new(), Vec::new(), 4);
+        for line in 0..14 {
+            writer
+                .append(&format!("ledger of Minas Tirith, entry {}", line))
+                .unwrap();
+        }
+
+        let mut bytes = Vec::new();
+        writer.archive().unwrap().write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_archives_are_read_back_and_verified() {
+        let bundle = ArchiveBundle::read(archive().as_slice()).unwrap();
+
+        assert_eq!(bundle.leaves().len(), 14);
+        assert_eq!(
+            bundle
+                .checkpoints()
+                .iter()
+                .map(|c| c.size)
+                .collect::<Vec<_>>(),
+            vec![4, 8, 12]
+        );
+        assert_eq!(bundle.consistency_proofs().len(), 2);
+
+        for checkpoint in bundle.checkpoints() {
+            let proof = bundle.prove(3, checkpoint.size).unwrap();
+            assert_eq!(
+                checkpoint.verify_line("ledger of Minas Tirith, entry 3", &proof),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn test_altered_archives_are_rejected() {
+        let bytes = archive();
+        let text = String::from_utf8(bytes.clone()).unwrap();
+
+        let mut flipped = bytes.clone();
+        flipped[40] ^= 1;
+        assert!(ArchiveBundle::read(flipped.as_slice()).is_err());
+
+        let truncated = text.lines().take(10).collect::<Vec<_>>().join("\n");
+        assert!(ArchiveBundle::read(truncated.as_bytes()).is_err());
+
+        // A rewritten leaf with a recomputed footer is caught by the checkpoints.
+        let body: String = text
+            .lines()
+            .filter(|line| !line.starts_with("footer"))
+            .enumerate()
+            .map(|(index, line)| match index {
+                3 => format!("{}\n", hex::encode(MerkleTree::hash(b"forged"))),
+                _ => format!("{}\n", line),
+            })
+            .collect();
+        let forged = format!(
+            "{}footer {}\n",
+            body,
+            hex::encode(MerkleTree::hash(body.as_bytes()))
+        );
+        assert_eq!(
+            ArchiveBundle::read(forged.as_bytes()),
+            Err(Error::RootMismatch)
+        );
+    }
+
+    #[test]
+    fn test_checkpoints_must_match_the_leaves() {
+        let leaves: Vec<Hash> = (0..5u8).map(|leaf| MerkleTree::hash(&[leaf])).collect();
+        let checkpoint = Checkpoint {
+            size: 3,
+            root: MerkleTree::hash(b"not a root"),
+        };
+
+        assert_eq!(
+            ArchiveBundle::new(leaves.clone(), vec![checkpoint]),
+            Err(Error::RootMismatch)
+        );
+        assert!(ArchiveBundle::new(leaves, vec![]).is_ok());
+    }
+}
@@ -0,0 +1,201 @@
+use bumpalo::Bump;
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// A tree whose nodes live in a bump arena, for short-lived trees built per
+/// request.
+///
+/// Every level is stored in one slice allocated from the arena, instead of
+/// one `Vec` per level, and the memory is released all at once when the
+/// arena is reset or dropped. The roots and proofs are those of `MerkleTree`.
+///
+/// # Examples
+/// ```
+/// use bumpalo::Bump;
+/// use merkle_tree::{ArenaTree, MerkleTree};
+///
+/// let arena = Bump::new();
+/// let tree = ArenaTree::build(&arena, &["Fornost", "Annúminas", "Amon Sûl"]).unwrap();
+///
+/// let proof = tree.proof(2).unwrap();
+/// assert!(proof.verify(&MerkleTree::hash("Amon Sûl".as_bytes()), &tree.root()).is_ok());
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ArenaTree<'a> {
+    /// The nodes of every level, from the leaves up to the root.
+    nodes: &'a [Hash],
+    /// The offset in `nodes` of every level.
+    offsets: &'a [usize],
+    leaf_count: usize,
+}
+
+impl<'a> ArenaTree<'a> {
+    /// Builds the tree from items representable as bytes.
+    pub fn build<T: AsRef<[u8]>>(arena: &'a Bump, items: &[T]) -> Result<Self, Error> {
+        Self::build_from_hashes(
+            arena,
+            items.len(),
+            items.iter().map(|item| MerkleTree::hash(item.as_ref())),
+        )
+    }
+
+    /// Builds the tree from `leaf_count` already hashed leaves.
+    pub fn build_from_hashes<I>(
+        arena: &'a Bump,
+        leaf_count: usize,
+        leaves: I,
+    ) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = Hash>,
+    {
+        if leaf_count == 0 {
+            return Err(Error::EmptyTree);
+        }
+
+        let levels = Proof::expected_length(leaf_count) + 1;
+        let offsets = arena.alloc_slice_fill_default::<usize>(levels);
+        for level in 1..levels {
+            offsets[level] = offsets[level - 1] + MerkleTree::level_width(leaf_count, level - 1);
+        }
+
+        let total = offsets[levels - 1] + 1;
+        let nodes = arena.alloc_slice_fill_copy(total, [0u8; 32]);
+
+        let mut written = 0;
+        for (node, leaf) in nodes[..leaf_count].iter_mut().zip(leaves) {
+            *node = leaf;
+            written += 1;
+        }
+        if written != leaf_count {
+            return Err(Error::InvalidInput(format!(
+                "expected {} leaves, received {}",
+                leaf_count, written
+            )));
+        }
+
+        for level in 1..levels {
+            let (below, above) = nodes.split_at_mut(offsets[level]);
+            let children = &below[offsets[level - 1]..];
+            for (index, parent) in above[..MerkleTree::level_width(leaf_count, level)]
+                .iter_mut()
+                .enumerate()
+            {
+                let left = children[2 * index];
+                let right = children.get(2 * index + 1).copied().unwrap_or(left);
+                *parent = MerkleTree::merkle_parent(&[left, right]);
+            }
+        }
+
+        Ok(Self {
+            nodes,
+            offsets,
+            leaf_count,
+        })
+    }
+
+    pub fn root(&self) -> Hash {
+        self.nodes[self.nodes.len() - 1]
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    /// The number of levels above the leaves.
+    pub fn height(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// The node at `index` of `level`, the leaves being level 0.
+    pub fn node(&self, level: usize, index: usize) -> Option<Hash> {
+        if index >= MerkleTree::level_width(self.leaf_count, level) {
+            return None;
+        }
+        Some(self.nodes[self.offsets[level] + index])
+    }
+
+    /// Returns the proof of inclusion of the leaf at `index`.
+    pub fn proof(&self, index: usize) -> Result<Proof, Error> {
+        let mut siblings = vec![[0; 32]; self.height()];
+        self.proof_into(index, &mut siblings)?;
+
+        Ok(Proof {
+            leaf_index: index,
+            leaf_count: self.leaf_count,
+            siblings,
+        })
+    }
+
+    /// Writes the siblings of the proof of the leaf at `index` at the start
+    /// of `buffer` and returns their number, as `MerkleTree::proof_into`.
+    pub fn proof_into(&self, index: usize, buffer: &mut [Hash]) -> Result<usize, Error> {
+        if index >= self.leaf_count {
+            return Err(Error::IndexOutOfRange {
+                index,
+                leaf_count: self.leaf_count,
+            });
+        }
+
+        let height = self.height();
+        if buffer.len() < height {
+            return Err(Error::InvalidInput(format!(
+                "the proof has {} hashes, the buffer holds {}",
+                height,
+                buffer.len()
+            )));
+        }
+
+        for (level, sibling) in buffer[..height].iter_mut().enumerate() {
+            let position = index >> level;
+            *sibling = self
+                .node(level, position ^ 1)
+                .or_else(|| self.node(level, position))
+                .expect("Every level has the node on the path.");
+        }
+
+        Ok(height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_arena_trees_match_merkle_trees() {
+        let mut arena = Bump::new();
+
+        for count in [1, 2, 3, 7, 8, 100] {
+            let items: Vec<String> = (0..count).map(|item| format!("request {}", item)).collect();
+            let expected = MerkleTree::build(&items).unwrap();
+
+            let tree = ArenaTree::build(&arena, &items).unwrap();
+            assert_eq!(Some(tree.root()), expected.root());
+            assert_eq!(tree.height(), expected.height());
+            for index in 0..count {
+                assert_eq!(tree.proof(index), expected.proof(index));
+            }
+            assert_eq!(tree.node(0, count), None);
+
+            arena.reset();
+        }
+    }
+
+    #[test]
+    fn test_invalid_inputs_are_rejected() {
+        let arena = Bump::new();
+
+        assert_eq!(
+            ArenaTree::build::<&str>(&arena, &[]).err(),
+            Some(Error::EmptyTree)
+        );
+        assert!(ArenaTree::build_from_hashes(&arena, 3, [[0; 32]; 2]).is_err());
+        assert!(ArenaTree::build(&arena, &["Bree"])
+            .unwrap()
+            .proof(1)
+            .is_err());
+    }
+}
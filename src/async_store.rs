@@ -0,0 +1,248 @@
+use std::future::Future;
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+use crate::store::{batched_nodes, missing_node, MemoryNodeStore, NodeId, NodeStore};
+
+/// The asynchronous counterpart of `NodeStore`, for backends that await
+/// network I/O such as an object store or a remote key-value service.
+pub trait AsyncNodeStore {
+    /// Returns the node of `tree` at `id`, `None` if the store does not have it.
+    fn get(&self, tree: &str, id: NodeId) -> impl Future<Output = Result<Option<Hash>, Error>>;
+
+    /// Returns the nodes of `tree` at `ids`, in order. Backends with batch
+    /// reads should override it, the default awaits one `get` per node.
+    fn get_batch(
+        &self,
+        tree: &str,
+        ids: &[NodeId],
+    ) -> impl Future<Output = Result<Vec<Option<Hash>>, Error>> {
+        async move {
+            let mut nodes = Vec::with_capacity(ids.len());
+            for id in ids {
+                nodes.push(self.get(tree, *id).await?);
+            }
+            Ok(nodes)
+        }
+    }
+
+    /// Writes nodes of `tree`, replacing the nodes at the same positions.
+    fn put(
+        &mut self,
+        tree: &str,
+        nodes: &[(NodeId, Hash)],
+    ) -> impl Future<Output = Result<(), Error>>;
+
+    /// Returns the number of leaves of `tree`, 0 if the store does not have it.
+    fn leaf_count(&self, tree: &str) -> impl Future<Output = Result<usize, Error>>;
+
+    /// Records the number of leaves of `tree`.
+    fn set_leaf_count(
+        &mut self,
+        tree: &str,
+        leaf_count: usize,
+    ) -> impl Future<Output = Result<(), Error>>;
+
+    /// Removes every node of `tree`.
+    fn remove_tree(&mut self, tree: &str) -> impl Future<Output = Result<(), Error>>;
+}
+
+impl AsyncNodeStore for MemoryNodeStore {
+    async fn get(&self, tree: &str, id: NodeId) -> Result<Option<Hash>, Error> {
+        NodeStore::get(self, tree, id)
+    }
+
+    async fn get_batch(&self, tree: &str, ids: &[NodeId]) -> Result<Vec<Option<Hash>>, Error> {
+        NodeStore::get_batch(self, tree, ids)
+    }
+
+    async fn put(&mut self, tree: &str, nodes: &[(NodeId, Hash)]) -> Result<(), Error> {
+        NodeStore::put(self, tree, nodes)
+    }
+
+    async fn leaf_count(&self, tree: &str) -> Result<usize, Error> {
+        NodeStore::leaf_count(self, tree)
+    }
+
+    async fn set_leaf_count(&mut self, tree: &str, leaf_count: usize) -> Result<(), Error> {
+        NodeStore::set_leaf_count(self, tree, leaf_count)
+    }
+
+    async fn remove_tree(&mut self, tree: &str) -> Result<(), Error> {
+        NodeStore::remove_tree(self, tree)
+    }
+}
+
+/// Reads nodes the store must have, in one batch.
+async fn stored_nodes<S: AsyncNodeStore>(
+    store: &S,
+    tree: &str,
+    ids: &[NodeId],
+) -> Result<Vec<Hash>, Error> {
+    let nodes = store.get_batch(tree, ids).await?;
+    batched_nodes(tree, ids, nodes)
+}
+
+impl MerkleTree {
+    /// The asynchronous counterpart of `store`.
+    pub async fn store_async<S: AsyncNodeStore>(
+        &self,
+        store: &mut S,
+        tree: &str,
+    ) -> Result<(), Error> {
+        store.remove_tree(tree).await?;
+        store.put(tree, &self.nodes_from(0)).await?;
+        store.set_leaf_count(tree, self.leaf_count()).await
+    }
+
+    /// The asynchronous counterpart of `load`, reading the leaves in one batch.
+    pub async fn load_async<S: AsyncNodeStore>(store: &S, tree: &str) -> Result<Self, Error> {
+        let ids: Vec<NodeId> = (0..store.leaf_count(tree).await?)
+            .map(|index| NodeId::new(0, index))
+            .collect();
+        let leaves = stored_nodes(store, tree, &ids).await?;
+
+        MerkleTree::from_leaves(leaves).ok_or(Error::EmptyTree)
+    }
+
+    /// The asynchronous counterpart of `stored_root`.
+    pub async fn stored_root_async<S: AsyncNodeStore>(
+        store: &S,
+        tree: &str,
+    ) -> Result<Hash, Error> {
+        let leaf_count = store.leaf_count(tree).await?;
+        if leaf_count == 0 {
+            return Err(Error::EmptyTree);
+        }
+        let id = NodeId::new(Proof::expected_length(leaf_count), 0);
+        store
+            .get(tree, id)
+            .await?
+            .ok_or_else(|| missing_node(tree, id))
+    }
+
+    /// The asynchronous counterpart of `stored_proof`, fetching the nodes
+    /// of the path in one batch.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::executor::block_on;
+    /// use merkle_tree::{MemoryNodeStore, MerkleTree};
+    ///
+    /// let tree = MerkleTree::build(&["Thranduil", "Legolas", "Tauriel"]).unwrap();
+    /// let mut store = MemoryNodeStore::new();
+    ///
+    /// block_on(tree.store_async(&mut store, "mirkwood")).unwrap();
+    /// let proof = block_on(MerkleTree::stored_proof_async(&store, "mirkwood", 1)).unwrap();
+    ///
+    /// assert_eq!(proof, tree.proof(1).unwrap());
+    /// ```
+    pub async fn stored_proof_async<S: AsyncNodeStore>(
+        store: &S,
+        tree: &str,
+        index: usize,
+    ) -> Result<Proof, Error> {
+        let leaf_count = store.leaf_count(tree).await?;
+        if index >= leaf_count {
+            return Err(Error::IndexOutOfRange { index, leaf_count });
+        }
+
+        let ids = Self::sibling_ids(leaf_count, index);
+        let siblings = stored_nodes(store, tree, &ids).await?;
+
+        Ok(Proof {
+            leaf_index: index,
+            leaf_count,
+            siblings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::cell::Cell;
+
+    use futures::executor::block_on;
+
+    use super::*;
+
+    /// A remote store counting its round trips.
+    #[derive(Default)]
+    struct RemoteStore {
+        nodes: MemoryNodeStore,
+        round_trips: Cell<usize>,
+    }
+
+    impl AsyncNodeStore for RemoteStore {
+        async fn get(&self, tree: &str, id: NodeId) -> Result<Option<Hash>, Error> {
+            self.round_trips.set(self.round_trips.get() + 1);
+            NodeStore::get(&self.nodes, tree, id)
+        }
+
+        async fn get_batch(&self, tree: &str, ids: &[NodeId]) -> Result<Vec<Option<Hash>>, Error> {
+            self.round_trips.set(self.round_trips.get() + 1);
+            ids.iter()
+                .map(|id| NodeStore::get(&self.nodes, tree, *id))
+                .collect()
+        }
+
+        async fn put(&mut self, tree: &str, nodes: &[(NodeId, Hash)]) -> Result<(), Error> {
+            NodeStore::put(&mut self.nodes, tree, nodes)
+        }
+
+        async fn leaf_count(&self, tree: &str) -> Result<usize, Error> {
+            NodeStore::leaf_count(&self.nodes, tree)
+        }
+
+        async fn set_leaf_count(&mut self, tree: &str, leaf_count: usize) -> Result<(), Error> {
+            NodeStore::set_leaf_count(&mut self.nodes, tree, leaf_count)
+        }
+
+        async fn remove_tree(&mut self, tree: &str) -> Result<(), Error> {
+            NodeStore::remove_tree(&mut self.nodes, tree)
+        }
+    }
+
+    #[test]
+    fn test_async_stores_serve_the_same_proofs() {
+        let items: Vec<String> = (0..21).map(|item| format!("elven gate {}", item)).collect();
+        let tree = MerkleTree::build(&items).unwrap();
+        let mut store = RemoteStore::default();
+
+        block_on(tree.store_async(&mut store, "moria")).unwrap();
+
+        assert_eq!(
+            block_on(MerkleTree::stored_root_async(&store, "moria")),
+            Ok(tree.root().unwrap())
+        );
+        for index in 0..21 {
+            store.round_trips.set(0);
+            assert_eq!(
+                block_on(MerkleTree::stored_proof_async(&store, "moria", index)),
+                tree.proof(index)
+            );
+            assert_eq!(store.round_trips.get(), 1);
+        }
+        assert_eq!(
+            block_on(MerkleTree::load_async(&store, "moria"))
+                .unwrap()
+                .root(),
+            tree.root()
+        );
+    }
+
+    #[test]
+    fn test_missing_nodes_are_errors() {
+        let mut store = MemoryNodeStore::new();
+        block_on(AsyncNodeStore::set_leaf_count(&mut store, "moria", 4)).unwrap();
+
+        assert_eq!(
+            block_on(MerkleTree::stored_root_async(&store, "balin")),
+            Err(Error::EmptyTree)
+        );
+        assert!(block_on(MerkleTree::stored_proof_async(&store, "moria", 2)).is_err());
+        assert!(block_on(MerkleTree::stored_proof_async(&store, "moria", 4)).is_err());
+    }
+}
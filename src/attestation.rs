@@ -0,0 +1,264 @@
+use serde_json::{json, Value};
+
+use crate::error::Error;
+use crate::jcs::canonicalize_json;
+use crate::leaf::MerkleLeaf;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+use crate::signature::{SignatureVerifier, Signer};
+
+const DOCUMENT_TYPE: &str = "merkle-tree/build-attestation/v1";
+
+/// A build output: its path and the SHA-256 digest of its content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subject {
+    pub path: String,
+    pub digest: Hash,
+}
+
+impl Subject {
+    /// The leaf of the subject, the `MerkleLeaf` encoding of its path followed by its digest.
+    pub fn leaf_hash(&self) -> Hash {
+        let mut bytes = self.path.leaf_bytes();
+        self.digest.encode_leaf(&mut bytes);
+        MerkleTree::hash(&bytes)
+    }
+}
+
+/// An attestation of the outputs of a build: a tree root over the manifest
+/// of their paths and digests, exported as a JSON document for provenance tooling.
+///
+/// The document holds the root, the manifest and the hashing profile needed
+/// to recompute the root. It is serialized with the JSON Canonicalization
+/// Scheme, so a detached signature over its bytes can be checked by anyone
+/// who re-serializes it.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{BuildAttestation, MerkleTree, Subject};
+///
+/// let attestation = BuildAttestation::new(vec![
+///     Subject { path: "bin/anduril".to_string(), digest: MerkleTree::hash(b"reforged") },
+///     Subject { path: "lib/narsil.so".to_string(), digest: MerkleTree::hash(b"broken") },
+/// ])
+/// .unwrap();
+///
+/// let document = attestation.document();
+/// let imported = BuildAttestation::from_document(&document).unwrap();
+///
+/// assert_eq!(imported.root(), attestation.root());
+/// ```
+pub struct BuildAttestation {
+    subjects: Vec<Subject>,
+    tree: MerkleTree,
+}
+
+impl BuildAttestation {
+    /// Creates the attestation of the given outputs, sorted by path.
+    /// Fails if there are no outputs or if a path appears twice.
+    pub fn new(mut subjects: Vec<Subject>) -> Result<Self, Error> {
+        subjects.sort_by(|a, b| a.path.cmp(&b.path));
+
+        if let Some(pair) = subjects
+            .windows(2)
+            .find(|pair| pair[0].path == pair[1].path)
+        {
+            return Err(Error::InvalidInput(format!(
+                "the path {} appears twice",
+                pair[0].path
+            )));
+        }
+
+        let tree = MerkleTree::from_leaves(subjects.iter().map(Subject::leaf_hash).collect())
+            .ok_or(Error::EmptyTree)?;
+
+        Ok(Self { subjects, tree })
+    }
+
+    pub fn root(&self) -> Hash {
+        self.tree.root().expect("An attestation has a root.")
+    }
+
+    /// The outputs of the build, sorted by path.
+    pub fn subjects(&self) -> &[Subject] {
+        &self.subjects
+    }
+
+    /// Proves the output at `path` is part of the attestation.
+    pub fn prove(&self, path: &str) -> Result<Proof, Error> {
+        let index = self
+            .subjects
+            .binary_search_by(|subject| subject.path.as_str().cmp(path))
+            .map_err(|_| {
+                Error::InvalidInput(format!("the path {} is not in the attestation", path))
+            })?;
+
+        self.tree.proof(index)
+    }
+
+    /// The attestation as a JSON value.
+    pub fn to_json(&self) -> Value {
+        let subjects: Vec<Value> = self
+            .subjects
+            .iter()
+            .map(|subject| {
+                json!({
+                    "path": subject.path,
+                    "digest": { "sha256": hex::encode(subject.digest) },
+                })
+            })
+            .collect();
+
+        json!({
+            "type": DOCUMENT_TYPE,
+            "root": hex::encode(self.root()),
+            "profile": {
+                "leaf": "sha256(merkle-leaf(path) || digest)",
+                "node": "sha256(sorted pair)",
+                "odd_level": "duplicate last node",
+            },
+            "subjects": subjects,
+        })
+    }
+
+    /// The canonical JSON document, the bytes a detached signature covers.
+    pub fn document(&self) -> String {
+        canonicalize_json(&self.to_json())
+    }
+
+    /// Signs the document, returning the detached signature.
+    pub fn sign<S: Signer>(&self, signer: &S) -> Vec<u8> {
+        signer.sign(self.document().as_bytes())
+    }
+
+    /// Reads an attestation document, recomputing its root from the manifest.
+    /// Fails if the document is malformed or its root does not match.
+    pub fn from_document(document: &str) -> Result<Self, Error> {
+        let value: Value = serde_json::from_str(document)
+            .map_err(|error| Error::InvalidInput(error.to_string()))?;
+
+        if value["type"] != DOCUMENT_TYPE {
+            return Err(Error::InvalidInput(format!(
+                "the document is not a {}",
+                DOCUMENT_TYPE
+            )));
+        }
+
+        let subjects = value["subjects"]
+            .as_array()
+            .ok_or_else(|| Error::InvalidInput("the document has no subjects".to_string()))?
+            .iter()
+            .map(|subject| {
+                Some(Subject {
+                    path: subject["path"].as_str()?.to_string(),
+                    digest: parse_hash(&subject["digest"]["sha256"])?,
+                })
+            })
+            .collect::<Option<Vec<Subject>>>()
+            .ok_or_else(|| {
+                Error::InvalidInput("the document has an invalid subject".to_string())
+            })?;
+
+        let root = parse_hash(&value["root"])
+            .ok_or_else(|| Error::InvalidInput("the document has an invalid root".to_string()))?;
+
+        let attestation = Self::new(subjects)?;
+        if attestation.root() != root {
+            return Err(Error::RootMismatch);
+        }
+
+        Ok(attestation)
+    }
+
+    /// Checks a detached signature over the document, then reads it.
+    pub fn verify_document<V: SignatureVerifier>(
+        document: &str,
+        signature: &[u8],
+        verifier: &V,
+    ) -> Result<Self, Error> {
+        if !verifier.verify(document.as_bytes(), signature) {
+            return Err(Error::InvalidSignature);
+        }
+
+        Self::from_document(document)
+    }
+}
+
+fn parse_hash(value: &Value) -> Option<Hash> {
+    hex::decode(value.as_str()?).ok()?.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    struct HmacKey(&'static [u8]);
+
+    impl Signer for HmacKey {
+        fn sign(&self, message: &[u8]) -> Vec<u8> {
+            hmac_sha256::HMAC::mac(message, self.0).to_vec()
+        }
+    }
+
+    impl SignatureVerifier for HmacKey {
+        fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+            hmac_sha256::HMAC::mac(message, self.0) == signature
+        }
+    }
+
+    fn attestation() -> BuildAttestation {
+        let subjects = ["target/release/erebor", "target/release/moria", "README.md"]
+            .iter()
+            .map(|path| Subject {
+                path: path.to_string(),
+                digest: MerkleTree::hash(path.as_bytes()),
+            })
+            .collect();
+
+        BuildAttestation::new(subjects).unwrap()
+    }
+
+    #[test]
+    fn test_signed_documents_round_trip() {
+        let attestation = attestation();
+        let document = attestation.document();
+        let signature = attestation.sign(&HmacKey(b"Durin"));
+
+        let imported =
+            BuildAttestation::verify_document(&document, &signature, &HmacKey(b"Durin")).unwrap();
+
+        assert_eq!(imported.root(), attestation.root());
+        assert_eq!(imported.subjects(), attestation.subjects());
+        assert_eq!(
+            BuildAttestation::verify_document(&document, &signature, &HmacKey(b"Smaug")).err(),
+            Some(Error::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_subjects_are_proven_against_the_root() {
+        let attestation = attestation();
+
+        for subject in attestation.subjects() {
+            let proof = attestation.prove(&subject.path).unwrap();
+            assert_eq!(
+                proof.verify(&subject.leaf_hash(), &attestation.root()),
+                Ok(())
+            );
+        }
+        assert!(attestation.prove("target/release/dale").is_err());
+    }
+
+    #[test]
+    fn test_tampered_documents_are_rejected() {
+        let document = attestation().document();
+
+        let tampered = document.replace("moria", "khazad-dum");
+        assert_eq!(
+            BuildAttestation::from_document(&tampered).err(),
+            Some(Error::RootMismatch)
+        );
+        assert!(BuildAttestation::from_document("{\"type\":\"sbom\"}").is_err());
+    }
+}
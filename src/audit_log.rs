@@ -0,0 +1,441 @@
+use std::io::{BufRead, Write};
+
+use crate::append::AppendProof;
+use crate::consistency::ConsistencyProof;
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// A published state of the log: its number of lines and the root over them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub size: usize,
+    pub root: Hash,
+}
+
+impl Checkpoint {
+    /// Formats the checkpoint as a sidecar line, `<size> <hex root>`.
+    pub fn to_line(&self) -> String {
+        format!("{} {}", self.size, hex::encode(self.root))
+    }
+
+    /// Parses a sidecar line written by `to_line`.
+    pub fn parse_line(line: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidInput(format!("invalid checkpoint {}", line));
+
+        let (size, root) = line.trim().split_once(' ').ok_or_else(invalid)?;
+
+        Ok(Self {
+            size: size.parse().map_err(|_| invalid())?,
+            root: hex::decode(root)
+                .ok()
+                .and_then(|root| root.try_into().ok())
+                .ok_or_else(invalid)?,
+        })
+    }
+
+    /// Reads every checkpoint of a sidecar file.
+    pub fn read_all<R: BufRead>(reader: R) -> Result<Vec<Self>, Error> {
+        reader
+            .lines()
+            .map(|line| line.map_err(|error| Error::InvalidInput(error.to_string())))
+            .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .map(|line| Self::parse_line(&line?))
+            .collect()
+    }
+
+    /// The leaf of the line at `index`: the hash of the index, as 8 bytes
+    /// big-endian, followed by the line.
+    ///
+    /// The tree hashes sorted pairs, which don't authenticate positions, so
+    /// the leaves do: a line moved to another index has another leaf.
+    pub fn leaf_hash(index: usize, line: &str) -> Hash {
+        MerkleTree::hash(&[&(index as u64).to_be_bytes(), line.as_bytes()].concat())
+    }
+
+    /// Checks that `line` is the line at `proof.leaf_index` of the log at this checkpoint.
+    pub fn verify_line(&self, line: &str, proof: &Proof) -> Result<(), Error> {
+        if proof.leaf_count != self.size {
+            return Err(Error::InvalidInput(format!(
+                "the proof is for a log of {} lines, the checkpoint has {}",
+                proof.leaf_count, self.size
+            )));
+        }
+
+        proof.verify(&Self::leaf_hash(proof.leaf_index, line), &self.root)
+    }
+}
+
+/// Appends lines to a log and commits to them in a growing tree, a
+/// tamper-evidence layer for audit logs.
+///
+/// Every `checkpoint_interval` lines the size and root of the log are written
+/// to a sidecar. Anyone holding a checkpoint can later be shown that a line
+/// was in the log at that point, and any rewrite or reordering of that
+/// history changes the roots already published.
+///
+/// The writer keeps the nodes of the complete subtrees as lines are
+/// appended, so checkpoints and proofs at any size cost a logarithmic number
+/// of hashes rather than a rebuild of the tree.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::MerkleLogWriter;
+///
+/// let mut writer = MerkleLogWriter::new(Vec::new(), Vec::new(), 2);
+///
+/// writer.append("gate opened by Gandalf").unwrap();
+/// writer.append("Balrog sighted on the bridge").unwrap();
+/// writer.append("gate closed").unwrap();
+///
+/// let checkpoint = writer.checkpoints()[0];
+/// let proof = writer.prove(1, checkpoint.size).unwrap();
+///
+/// assert!(checkpoint.verify_line("Balrog sighted on the bridge", &proof).is_ok());
+/// ```
+pub struct MerkleLogWriter<L: Write, S: Write> {
+    log: L,
+    sidecar: S,
+    /// The nodes of the complete subtrees, by level, the leaves first.
+    nodes: Vec<Vec<Hash>>,
+    checkpoint_interval: usize,
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl<L: Write, S: Write> MerkleLogWriter<L, S> {
+    /// Creates a writer for an empty log. A `checkpoint_interval` of 0
+    /// only writes checkpoints when `checkpoint` is called.
+    pub fn new(log: L, sidecar: S, checkpoint_interval: usize) -> Self {
+        Self {
+            log,
+            sidecar,
+            nodes: vec![Vec::new()],
+            checkpoint_interval,
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Continues a log whose lines are read from `existing`, so that new
+    /// checkpoints and proofs cover the earlier lines too.
+    pub fn resume<R: BufRead>(
+        existing: R,
+        log: L,
+        sidecar: S,
+        checkpoint_interval: usize,
+    ) -> Result<Self, Error> {
+        let mut writer = Self::new(log, sidecar, checkpoint_interval);
+
+        for line in existing.lines() {
+            let line = line.map_err(|error| Error::InvalidInput(error.to_string()))?;
+            writer.push(&line);
+        }
+
+        Ok(writer)
+    }
+
+    /// Writes a line to the log and returns its index.
+    /// Lines can't contain line breaks, which would split them when read back.
+    pub fn append(&mut self, line: &str) -> Result<usize, Error> {
+        if line.contains(['\n', '\r']) {
+            return Err(Error::InvalidInput(
+                "a log line can't contain line breaks".to_string(),
+            ));
+        }
+
+        writeln!(self.log, "{}", line).map_err(|error| Error::InvalidInput(error.to_string()))?;
+        self.push(line);
+
+        if self.checkpoint_interval > 0 && self.len().is_multiple_of(self.checkpoint_interval) {
+            self.checkpoint()?;
+        }
+
+        Ok(self.len() - 1)
+    }
+
+    /// Adds the leaf of a line and the subtrees it completes.
+    fn push(&mut self, line: &str) {
+        let index = self.len();
+        self.nodes[0].push(Checkpoint::leaf_hash(index, line));
+
+        let mut level = 0;
+        while (index + 1).is_multiple_of(2 << level) {
+            let children = &self.nodes[level];
+            let parent = MerkleTree::merkle_parent(&children[children.len() - 2..]);
+            if self.nodes.len() == level + 1 {
+                self.nodes.push(Vec::new());
+            }
+            self.nodes[level + 1].push(parent);
+            level += 1;
+        }
+    }
+
+    /// Writes the current size and root to the sidecar.
+    pub fn checkpoint(&mut self) -> Result<Checkpoint, Error> {
+        let checkpoint = Checkpoint {
+            size: self.len(),
+            root: self.root_at(self.len())?,
+        };
+
+        let io_error = |error: std::io::Error| Error::InvalidInput(error.to_string());
+        self.log.flush().map_err(io_error)?;
+        writeln!(self.sidecar, "{}", checkpoint.to_line()).map_err(io_error)?;
+        self.sidecar.flush().map_err(io_error)?;
+
+        self.checkpoints.push(checkpoint);
+        Ok(checkpoint)
+    }
+
+    /// The checkpoints written since the writer was created.
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    /// The leaves of the lines, in order.
+    pub(crate) fn leaves(&self) -> &[Hash] {
+        &self.nodes[0]
+    }
+
+    /// The number of lines in the log.
+    pub fn len(&self) -> usize {
+        self.nodes[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes[0].is_empty()
+    }
+
+    /// Proves the line at `index` was in the log when it had `size` lines.
+    pub fn prove(&self, index: usize, size: usize) -> Result<Proof, Error> {
+        self.check_size(size)?;
+        if index >= size {
+            return Err(Error::IndexOutOfRange {
+                index,
+                leaf_count: size,
+            });
+        }
+
+        let siblings = (0..Proof::expected_length(size))
+            .map(|level| {
+                let position = index >> level;
+                match position ^ 1 < MerkleTree::level_width(size, level) {
+                    true => self.node_at(level, position ^ 1, size),
+                    false => self.node_at(level, position, size),
+                }
+            })
+            .collect();
+
+        Ok(Proof {
+            leaf_index: index,
+            leaf_count: size,
+            siblings,
+        })
+    }
+
+    /// Proves the log at `new_size` lines extends the log at `old_size` lines.
+    pub fn prove_consistency(
+        &self,
+        old_size: usize,
+        new_size: usize,
+    ) -> Result<ConsistencyProof, Error> {
+        self.check_size(new_size)?;
+        if old_size == 0 || old_size > new_size {
+            return Err(Error::InvalidInput(format!(
+                "a log of {} lines has no prefix of {}",
+                new_size, old_size
+            )));
+        }
+
+        Ok(ConsistencyProof {
+            old_size,
+            new_size,
+            leaf: self.nodes[0][old_size - 1],
+            siblings: self.prove(old_size - 1, new_size)?.siblings,
+        })
+    }
+
+    /// The append proof of the log at `size` lines, the state a `LogFollower`
+    /// starts from.
+    pub fn append_proof(&self, size: usize) -> Result<AppendProof, Error> {
+        self.check_size(size)?;
+        if size == 0 {
+            return Err(Error::EmptyTree);
+        }
+
+        Ok(AppendProof {
+            old_size: size,
+            last_leaf: self.nodes[0][size - 1],
+            siblings: self.prove(size - 1, size)?.siblings,
+        })
+    }
+
+    fn check_size(&self, size: usize) -> Result<(), Error> {
+        if size > self.len() {
+            return Err(Error::InvalidInput(format!(
+                "the log has {} lines, not {}",
+                self.len(),
+                size
+            )));
+        }
+        Ok(())
+    }
+
+    fn root_at(&self, size: usize) -> Result<Hash, Error> {
+        if size == 0 {
+            return Err(Error::EmptyTree);
+        }
+        Ok(self.node_at(Proof::expected_length(size), 0, size))
+    }
+
+    /// The node at `level` and `index` of the tree over the first `size`
+    /// lines. Only the nodes on the right edge of that tree are hashed,
+    /// the others are complete subtrees already kept.
+    fn node_at(&self, level: usize, index: usize, size: usize) -> Hash {
+        if level == 0 || (index + 1) << level <= size {
+            return self.nodes[level][index];
+        }
+
+        let left = self.node_at(level - 1, 2 * index, size);
+        let right = match 2 * index + 1 < MerkleTree::level_width(size, level - 1) {
+            true => self.node_at(level - 1, 2 * index + 1, size),
+            false => left,
+        };
+        MerkleTree::merkle_parent(&[left, right])
+    }
+
+    /// Returns the log and the sidecar.
+    pub fn into_inner(self) -> (L, S) {
+        (self.log, self.sidecar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn lines(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|line| format!("watch report {}: all quiet at the Black Gate", line))
+            .collect()
+    }
+
+    fn root(lines: &[String]) -> Hash {
+        let leaves = lines
+            .iter()
+            .enumerate()
+            .map(|(index, line)| Checkpoint::leaf_hash(index, line))
+            .collect();
+        MerkleTree::from_leaves(leaves).unwrap().root().unwrap()
+    }
+
+    #[test]
+    fn test_checkpoints_are_written_to_the_sidecar() {
+        let mut writer = MerkleLogWriter::new(Vec::new(), Vec::new(), 3);
+        for line in lines(7) {
+            writer.append(&line).unwrap();
+        }
+
+        let (log, sidecar) = writer.into_inner();
+        let checkpoints = Checkpoint::read_all(sidecar.as_slice()).unwrap();
+
+        assert_eq!(log.iter().filter(|byte| **byte == b'\n').count(), 7);
+        assert_eq!(
+            checkpoints
+                .iter()
+                .map(|checkpoint| checkpoint.size)
+                .collect::<Vec<_>>(),
+            vec![3, 6]
+        );
+        assert_eq!(checkpoints[1].root, root(&lines(6)));
+    }
+
+    #[test]
+    fn test_historical_lines_are_proven_against_old_checkpoints() {
+        let lines = lines(10);
+        let mut writer = MerkleLogWriter::new(Vec::new(), Vec::new(), 4);
+        for line in &lines {
+            writer.append(line).unwrap();
+        }
+        let last = writer.checkpoint().unwrap();
+
+        let first = writer.checkpoints()[0];
+        let proof = writer.prove(2, first.size).unwrap();
+        assert_eq!(first.verify_line(&lines[2], &proof), Ok(()));
+        assert!(first.verify_line("all quiet, really", &proof).is_err());
+        assert!(last.verify_line(&lines[2], &proof).is_err());
+
+        let proof = writer.prove(9, last.size).unwrap();
+        assert_eq!(last.verify_line(&lines[9], &proof), Ok(()));
+        assert!(writer.prove(9, first.size).is_err());
+    }
+
+    #[test]
+    fn test_proofs_match_the_rebuilt_tree() {
+        let lines = lines(37);
+        let mut writer = MerkleLogWriter::new(Vec::new(), Vec::new(), 0);
+        for line in &lines {
+            writer.append(line).unwrap();
+        }
+
+        for size in 1..=lines.len() {
+            let leaves = writer.leaves()[..size].to_vec();
+            let tree = MerkleTree::from_leaves(leaves).unwrap();
+            assert_eq!(writer.root_at(size), Ok(tree.root().unwrap()));
+            for index in [0, size / 2, size - 1] {
+                assert_eq!(writer.prove(index, size), tree.proof(index));
+            }
+            assert_eq!(
+                writer.prove_consistency(size, lines.len()),
+                MerkleTree::from_leaves(writer.leaves().to_vec())
+                    .unwrap()
+                    .consistency_proof(size)
+            );
+        }
+    }
+
+    #[test]
+    fn test_lines_are_bound_to_their_position() {
+        let lines = lines(4);
+        let mut writer = MerkleLogWriter::new(Vec::new(), Vec::new(), 0);
+        for line in &lines {
+            writer.append(line).unwrap();
+        }
+        let checkpoint = writer.checkpoint().unwrap();
+
+        let mut proof = writer.prove(2, 4).unwrap();
+        proof.leaf_index = 3;
+        assert!(checkpoint.verify_line(&lines[2], &proof).is_err());
+
+        let mut swapped = lines.clone();
+        swapped.swap(0, 1);
+        assert_ne!(root(&swapped), checkpoint.root);
+    }
+
+    #[test]
+    fn test_resume_covers_earlier_lines() {
+        let lines = lines(5);
+        let mut writer = MerkleLogWriter::new(Vec::new(), Vec::new(), 0);
+        for line in &lines[..3] {
+            writer.append(line).unwrap();
+        }
+        let (log, _) = writer.into_inner();
+
+        let mut resumed =
+            MerkleLogWriter::resume(log.as_slice(), Vec::new(), Vec::new(), 0).unwrap();
+        for line in &lines[3..] {
+            resumed.append(line).unwrap();
+        }
+
+        let checkpoint = resumed.checkpoint().unwrap();
+        assert_eq!(checkpoint.root, root(&lines));
+    }
+
+    #[test]
+    fn test_invalid_input_is_rejected() {
+        let mut writer = MerkleLogWriter::new(Vec::new(), Vec::new(), 0);
+
+        assert!(writer.append("two\nlines").is_err());
+        assert!(writer.checkpoint().is_err());
+        assert!(Checkpoint::parse_line("three deadbeef").is_err());
+    }
+}
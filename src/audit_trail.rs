@@ -0,0 +1,236 @@
+use crate::error::Error;
+use crate::hashing::MerkleHasher;
+use crate::merkle_tree::{GenericMerkleTree, Hash, MerkleTree};
+
+/// A change recorded in an `AuditTrail`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeMutation {
+    /// The trail was enabled on the tree as it was.
+    Start,
+    /// A leaf was appended.
+    Insert { leaf: Hash },
+    /// The leaves were replaced, by an update or a removal.
+    Rebuild,
+}
+
+/// An entry of an `AuditTrail`, chained to the one before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub sequence: u64,
+    /// The hash of the previous entry, zero for the first one.
+    pub previous: Hash,
+    pub mutation: TreeMutation,
+    /// The root of the tree after the mutation.
+    pub root: Hash,
+    pub leaf_count: usize,
+}
+
+impl AuditEntry {
+    /// The hash the next entry links to, over every field of this one.
+    pub fn hash(&self) -> Hash {
+        let mut bytes = Vec::with_capacity(115);
+        bytes.extend_from_slice(&self.previous);
+        bytes.extend_from_slice(&self.sequence.to_be_bytes());
+        match self.mutation {
+            TreeMutation::Start => bytes.push(0),
+            TreeMutation::Insert { leaf } => {
+                bytes.push(1);
+                bytes.extend_from_slice(&leaf);
+            }
+            TreeMutation::Rebuild => bytes.push(2),
+        }
+        bytes.extend_from_slice(&self.root);
+        bytes.extend_from_slice(&(self.leaf_count as u64).to_be_bytes());
+        MerkleTree::hash(&bytes)
+    }
+}
+
+/// A hash chain of the mutations of a tree, each entry committing to the
+/// previous one and to the root it led to, so the history of the tree is
+/// tamper-evident: publishing the head commits to every entry before it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditTrail {
+    entries: Vec<AuditEntry>,
+}
+
+impl AuditTrail {
+    fn start<H: MerkleHasher>(tree: &GenericMerkleTree<H>) -> Self {
+        let mut trail = Self {
+            entries: Vec::new(),
+        };
+        trail.record(tree, TreeMutation::Start);
+        trail
+    }
+
+    fn record<H: MerkleHasher>(&mut self, tree: &GenericMerkleTree<H>, mutation: TreeMutation) {
+        self.entries.push(AuditEntry {
+            sequence: self.entries.len() as u64,
+            previous: self.entries.last().map_or([0; 32], AuditEntry::hash),
+            mutation,
+            root: tree.root().expect("A tree has a root."),
+            leaf_count: tree.leaf_count(),
+        });
+    }
+
+    pub fn entries(&self) -> &[AuditEntry] {
+        &self.entries
+    }
+
+    /// The hash of the last entry.
+    pub fn head(&self) -> Hash {
+        self.entries.last().expect("A trail has an entry.").hash()
+    }
+
+    /// Checks that every entry links to the one before it and returns the head.
+    pub fn verify(&self) -> Result<Hash, Error> {
+        Self::verify_entries(&self.entries)
+    }
+
+    /// Checks that `entries` form a chain from a first entry and returns its head.
+    pub fn verify_entries(entries: &[AuditEntry]) -> Result<Hash, Error> {
+        let mut previous = [0; 32];
+
+        for (sequence, entry) in entries.iter().enumerate() {
+            if entry.sequence != sequence as u64 || entry.previous != previous {
+                return Err(Error::InvalidInput(format!(
+                    "the audit entry {} does not follow the entry before it",
+                    sequence
+                )));
+            }
+            previous = entry.hash();
+        }
+
+        match entries.is_empty() {
+            true => Err(Error::InvalidInput(
+                "an audit trail needs an entry".to_string(),
+            )),
+            false => Ok(previous),
+        }
+    }
+
+    /// Checks the chain ends at a head published earlier.
+    pub fn verify_head(entries: &[AuditEntry], head: &Hash) -> Result<(), Error> {
+        if Self::verify_entries(entries)? != *head {
+            return Err(Error::RootMismatch);
+        }
+        Ok(())
+    }
+}
+
+impl<H: MerkleHasher> GenericMerkleTree<H> {
+    /// Starts recording every mutation of the tree in a hash chain.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::{AuditTrail, MerkleTree};
+    ///
+    /// let mut tree = MerkleTree::build(&["Thorin", "Balin"]).unwrap();
+    /// tree.enable_audit_trail();
+    /// tree.insert(&"Dwalin").unwrap();
+    ///
+    /// let head = tree.audit_head().unwrap();
+    /// let entries = tree.audit_trail().unwrap().entries();
+    ///
+    /// assert_eq!(entries.len(), 2);
+    /// assert!(AuditTrail::verify_head(entries, &head).is_ok());
+    /// ```
+    pub fn enable_audit_trail(&mut self) {
+        self.audit = Some(AuditTrail::start(self));
+    }
+
+    pub fn disable_audit_trail(&mut self) {
+        self.audit = None;
+    }
+
+    pub fn audit_trail(&self) -> Option<&AuditTrail> {
+        self.audit.as_ref()
+    }
+
+    /// The hash of the last mutation recorded, `None` if no trail is kept.
+    pub fn audit_head(&self) -> Option<Hash> {
+        self.audit.as_ref().map(AuditTrail::head)
+    }
+
+    pub(crate) fn record_mutation(&mut self, mutation: TreeMutation) {
+        if let Some(mut trail) = self.audit.take() {
+            trail.record(self, mutation);
+            self.audit = Some(trail);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_every_mutation_is_chained() {
+        let mut tree = MerkleTree::build(&["Gandalf", "Saruman"]).unwrap();
+        tree.enable_audit_trail();
+
+        tree.insert(&"Radagast").unwrap();
+        let mut leaves = tree.leaves().to_vec();
+        leaves[1] = MerkleTree::hash(b"Saruman of many colours");
+        tree.replace_leaves(leaves).unwrap();
+        tree.insert(&"Alatar").unwrap();
+
+        let trail = tree.audit_trail().unwrap();
+        let kinds: Vec<_> = trail.entries().iter().map(|entry| entry.mutation).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TreeMutation::Start,
+                TreeMutation::Insert {
+                    leaf: MerkleTree::hash(b"Radagast")
+                },
+                TreeMutation::Rebuild,
+                TreeMutation::Insert {
+                    leaf: MerkleTree::hash(b"Alatar")
+                },
+            ]
+        );
+        assert_eq!(trail.verify(), Ok(tree.audit_head().unwrap()));
+        assert_eq!(trail.entries()[3].root, tree.root().unwrap());
+        assert_eq!(trail.entries()[3].leaf_count, 4);
+    }
+
+    #[test]
+    fn test_rewritten_history_breaks_the_chain() {
+        let mut tree = MerkleTree::build(&["Elrond"]).unwrap();
+        tree.enable_audit_trail();
+        for name in ["Elladan", "Elrohir", "Arwen"] {
+            tree.insert(&name).unwrap();
+        }
+        let head = tree.audit_head().unwrap();
+        let entries = tree.audit_trail().unwrap().entries().to_vec();
+
+        let mut rewritten = entries.clone();
+        rewritten[2].mutation = TreeMutation::Insert {
+            leaf: MerkleTree::hash(b"Celebrian"),
+        };
+        assert!(AuditTrail::verify_entries(&rewritten).is_err());
+
+        let mut removed = entries.clone();
+        removed.remove(1);
+        assert!(AuditTrail::verify_entries(&removed).is_err());
+
+        assert_eq!(
+            AuditTrail::verify_head(&entries[..3], &head),
+            Err(Error::RootMismatch)
+        );
+        assert_eq!(AuditTrail::verify_head(&entries, &head), Ok(()));
+    }
+
+    #[test]
+    fn test_trails_are_optional() {
+        let mut tree = MerkleTree::build(&["Cirdan"]).unwrap();
+        assert_eq!(tree.audit_head(), None);
+
+        tree.enable_audit_trail();
+        assert!(tree.audit_head().is_some());
+        tree.disable_audit_trail();
+        tree.insert(&"Gil-galad").unwrap();
+        assert!(tree.audit_trail().is_none());
+    }
+}
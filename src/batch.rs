@@ -0,0 +1,135 @@
+use rayon::prelude::*;
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+impl MerkleTree {
+    /// Creates a tree from a rayon parallel iterator of items, hashing them
+    /// in parallel as they flow out of the pipeline instead of collecting
+    /// them first. Items keep the order of the iterator when it is indexed.
+    /// The creation will fail if the iterator is empty.
+    ///
+    /// The tree is the one `build` creates from the same items in the same
+    /// order, so its root does not depend on the number of threads.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::MerkleTree;
+    /// use rayon::prelude::*;
+    ///
+    /// let rings = (0..9).into_par_iter().map(|ring| format!("ring {}", ring));
+    /// let tree = MerkleTree::from_par_iter(rings).unwrap();
+    ///
+    /// let items: Vec<String> = (0..9).map(|ring| format!("ring {}", ring)).collect();
+    /// assert_eq!(tree.root(), MerkleTree::build(&items).unwrap().root());
+    /// ```
+    pub fn from_par_iter<I>(items: I) -> Option<Self>
+    where
+        I: IntoParallelIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let leaves: Vec<Hash> = items
+            .into_par_iter()
+            .map(|item| Self::hash(item.as_ref()))
+            .collect();
+
+        Self::from_leaves(leaves)
+    }
+}
+
+impl Proof {
+    /// Verifies many proofs of leaves against the same root in parallel, for
+    /// servers validating large submissions of claims in bulk.
+    ///
+    /// Returns the result of each `(leaf, proof)` pair, in the input order,
+    /// so one bad claim does not reject the others.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::{MerkleTree, Proof};
+    ///
+    /// let tree = MerkleTree::build(&["Gimli", "Legolas", "Aragorn"]).unwrap();
+    /// let claims = vec![
+    ///     (MerkleTree::hash(b"Gimli"), tree.proof(0).unwrap()),
+    ///     (MerkleTree::hash(b"Boromir"), tree.proof(1).unwrap()),
+    /// ];
+    ///
+    /// let results = Proof::verify_batch(&tree.root().unwrap(), &claims);
+    ///
+    /// assert!(results[0].is_ok());
+    /// assert!(results[1].is_err());
+    /// ```
+    pub fn verify_batch(root: &Hash, items: &[(Hash, Proof)]) -> Vec<Result<(), Error>> {
+        items
+            .par_iter()
+            .map(|(leaf, proof)| proof.verify(leaf, root))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn test_batch_matches_sequential_verification() {
+        let claims: Vec<String> = (0..3000)
+            .map(|claim| format!("claim {} of the Shire", claim))
+            .collect();
+        let tree = MerkleTree::build(&claims).unwrap();
+        let root = tree.root().unwrap();
+
+        let mut items: Vec<_> = claims
+            .iter()
+            .enumerate()
+            .map(|(index, claim)| {
+                (
+                    MerkleTree::hash(claim.as_bytes()),
+                    tree.proof(index).unwrap(),
+                )
+            })
+            .collect();
+        items[17].0 = MerkleTree::hash(b"forged claim");
+        items[2024].1.siblings.pop();
+
+        let results = Proof::verify_batch(&root, &items);
+
+        assert_eq!(results.len(), items.len());
+        for (index, ((leaf, proof), result)) in items.iter().zip(&results).enumerate() {
+            assert_eq!(*result, proof.verify(leaf, &root), "claim {}", index);
+        }
+        assert_eq!(results.iter().filter(|result| result.is_err()).count(), 2);
+    }
+
+    #[test]
+    fn test_parallel_iterators_build_the_same_tree() {
+        let verses: Vec<String> = (0..1000)
+            .map(|verse| format!("verse {} of the Lay of Leithian", verse))
+            .collect();
+
+        let tree = MerkleTree::from_par_iter(verses.par_iter()).unwrap();
+
+        assert_eq!(tree.root(), MerkleTree::build(&verses).unwrap().root());
+        assert_eq!(tree.leaf_count(), verses.len());
+        assert!(MerkleTree::from_par_iter(Vec::<String>::new()).is_none());
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn test_parallel_builds_match_serial_builds(
+            items in prop::collection::vec(prop::collection::vec(any::<u8>(), 0..8), 1..300),
+        ) {
+            let tree = MerkleTree::from_par_iter(items.par_iter()).unwrap();
+            let serial = MerkleTree::build(&items).unwrap();
+
+            prop_assert_eq!(tree.root(), serial.root());
+            prop_assert_eq!(tree.leaves(), serial.leaves());
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::ops::Range;
+
+use crate::config::MutationGuard;
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::sync::coalesce;
+
+impl MerkleTree {
+    /// Locates the leaves of this tree that disagree with a trusted root of a
+    /// tree of the same size, asking the trusted side for node hashes.
+    ///
+    /// `fetch(level, index)` returns the trusted hash of a node. Starting from
+    /// the root, only the children of disagreeing nodes are requested, and
+    /// every answer is checked against its already trusted parent, so a
+    /// wrong answer fails with `Error::RootMismatch` instead of misleading
+    /// the search. Each corrupted leaf costs about two requests per level.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::MerkleTree;
+    ///
+    /// let trusted = MerkleTree::build(&["Thorin", "Balin", "Dwalin", "Fíli", "Kíli"]).unwrap();
+    /// let local = MerkleTree::build(&["Thorin", "Balin", "Dwalin", "Fili", "Kili"]).unwrap();
+    ///
+    /// let corrupted = local
+    ///     .bisect(&trusted.root().unwrap(), |level, index| Ok(trusted.node(level, index).unwrap()))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(corrupted, vec![3..5]);
+    /// ```
+    pub fn bisect<F>(&self, trusted_root: &Hash, mut fetch: F) -> Result<Vec<Range<usize>>, Error>
+    where
+        F: FnMut(usize, usize) -> Result<Hash, Error>,
+    {
+        if self.root() == Some(*trusted_root) {
+            return Ok(Vec::new());
+        }
+
+        let top = match self.mutation_guard() {
+            MutationGuard::CommitLeafCount => {
+                let top = fetch(self.height(), 0)?;
+                if Self::commit_leaf_count(&top, self.leaf_count()) != *trusted_root {
+                    return Err(Error::RootMismatch);
+                }
+                top
+            }
+            _ => *trusted_root,
+        };
+
+        let mut corrupted = Vec::new();
+        let mut pending = vec![(self.height(), 0, top)];
+
+        while let Some((level, index, trusted)) = pending.pop() {
+            if self.node(level, index) == Some(trusted) {
+                continue;
+            }
+            if level == 0 {
+                corrupted.push(index);
+                continue;
+            }
+
+            let left = fetch(level - 1, 2 * index)?;
+            let right = if 2 * index + 1 < Self::level_width(self.leaf_count(), level - 1) {
+                Some(fetch(level - 1, 2 * index + 1)?)
+            } else {
+                None
+            };
+
+            if Self::merkle_parent(&[left, right.unwrap_or(left)]) != trusted {
+                return Err(Error::RootMismatch);
+            }
+
+            if let Some(right) = right {
+                pending.push((level - 1, 2 * index + 1, right));
+            }
+            pending.push((level - 1, 2 * index, left));
+        }
+
+        corrupted.sort_unstable();
+        Ok(coalesce(&corrupted))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn blocks(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|block| format!("block {} of the Red Book", block))
+            .collect()
+    }
+
+    #[test]
+    fn test_corrupted_ranges_are_located() {
+        let trusted = MerkleTree::build(&blocks(100)).unwrap();
+        let mut corrupted = blocks(100);
+        for block in [7, 40, 41, 42, 99] {
+            corrupted[block] = "bit rot".to_string();
+        }
+        let local = MerkleTree::build(&corrupted).unwrap();
+
+        let mut queries = 0;
+        let ranges = local
+            .bisect(&trusted.root().unwrap(), |level, index| {
+                queries += 1;
+                Ok(trusted.node(level, index).unwrap())
+            })
+            .unwrap();
+
+        assert_eq!(ranges, vec![7..8, 40..43, 99..100]);
+        assert!(queries <= 5 * 2 * trusted.height());
+    }
+
+    #[test]
+    fn test_matching_trees_need_no_queries() {
+        let tree = MerkleTree::build(&blocks(10)).unwrap();
+
+        let ranges = tree
+            .bisect(&tree.root().unwrap(), |_, _| panic!("no query is needed"))
+            .unwrap();
+
+        assert!(ranges.is_empty());
+    }
+
+    #[test]
+    fn test_wrong_answers_are_detected() {
+        let trusted = MerkleTree::build(&blocks(16)).unwrap();
+        let mut corrupted = blocks(16);
+        corrupted[5] = "bit rot".to_string();
+        let local = MerkleTree::build(&corrupted).unwrap();
+
+        // A trusted side that lies about the nodes at level 2.
+        let result = local.bisect(&trusted.root().unwrap(), |level, index| {
+            if level == 2 {
+                Ok(local.node(level, index).unwrap())
+            } else {
+                Ok(trusted.node(level, index).unwrap())
+            }
+        });
+
+        assert_eq!(result, Err(Error::RootMismatch));
+    }
+
+    #[test]
+    fn test_bisect_with_leaf_count_commitment() {
+        let builder = MerkleTree::builder().mutation_guard(MutationGuard::CommitLeafCount);
+        let trusted = builder.build(&blocks(9)).unwrap();
+        let mut corrupted = blocks(9);
+        corrupted[8] = "bit rot".to_string();
+        let local = builder.build(&corrupted).unwrap();
+
+        let ranges = local
+            .bisect(&trusted.root().unwrap(), |level, index| {
+                Ok(trusted.node(level, index).unwrap())
+            })
+            .unwrap();
+
+        assert_eq!(ranges, vec![8..9]);
+    }
+}
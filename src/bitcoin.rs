@@ -0,0 +1,502 @@
+use crate::error::Error;
+use crate::merkle_tree::Hash;
+
+/// A block can't hold more transactions than this, which bounds untrusted input.
+const MAX_TRANSACTIONS: u32 = 4_000_000 / 60;
+
+/// Bitcoin's double SHA-256.
+pub fn sha256d(bytes: &[u8]) -> Hash {
+    hmac_sha256::Hash::hash(&hmac_sha256::Hash::hash(bytes))
+}
+
+/// Bitcoin hashes children in order, without sorting them.
+fn merkle_parent(left: &Hash, right: &Hash) -> Hash {
+    let mut bytes = [0; 64];
+    bytes[..32].copy_from_slice(left);
+    bytes[32..].copy_from_slice(right);
+    sha256d(&bytes)
+}
+
+/// Parses a txid or block hash as displayed by explorers and RPCs,
+/// which show the bytes of the hash reversed.
+pub fn hash_from_display_hex(display: &str) -> Result<Hash, Error> {
+    let mut hash: Hash = hex::decode(display)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| Error::InvalidInput(format!("invalid hash {}", display)))?;
+    hash.reverse();
+    Ok(hash)
+}
+
+/// Formats a hash in the reversed byte order explorers and RPCs display.
+pub fn hash_to_display_hex(hash: &Hash) -> String {
+    let mut reversed = *hash;
+    reversed.reverse();
+    hex::encode(reversed)
+}
+
+/// Verifies an Electrum style merkle branch: the siblings of the transaction
+/// from the bottom up, the side of each one given by the bits of its position.
+///
+/// The branch alone does not prove a transaction. SPV clients must also
+/// check the transaction count of the block, from a trusted source, and
+/// that the branch has one hash per level of a tree of that many leaves. An
+/// inner node is the double SHA-256 of 64 bytes, so a 64 byte transaction
+/// can be confused with one: a shorter branch proves an inner node as a
+/// transaction, and a longer one half of a 64 byte transaction.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{hash_from_display_hex, verify_merkle_branch};
+///
+/// // Block 100000.
+/// let root = hash_from_display_hex("f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766").unwrap();
+/// let txid = hash_from_display_hex("6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4").unwrap();
+/// let branch = vec![
+///     hash_from_display_hex("e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d").unwrap(),
+///     hash_from_display_hex("ccdafb73d8dcd0173d5d5c3c9a0770d0b3953db889dab99ef05b1907518cb815").unwrap(),
+/// ];
+///
+/// assert!(verify_merkle_branch(&txid, &branch, 2, &root));
+/// ```
+pub fn verify_merkle_branch(txid: &Hash, branch: &[Hash], position: u32, root: &Hash) -> bool {
+    if branch.len() < 32 && position >> branch.len() != 0 {
+        return false;
+    }
+
+    let computed = branch
+        .iter()
+        .enumerate()
+        .fold(*txid, |current, (level, sibling)| {
+            if (position >> level) & 1 == 1 {
+                merkle_parent(sibling, &current)
+            } else {
+                merkle_parent(&current, sibling)
+            }
+        });
+
+    computed == *root
+}
+
+/// The partial merkle tree of a BIP 37 `merkleblock` message: the hashes
+/// and flag bits of a depth-first traversal that proves which transactions
+/// of a block matched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialMerkleTree {
+    pub total_transactions: u32,
+    pub hashes: Vec<Hash>,
+    pub flags: Vec<bool>,
+}
+
+impl PartialMerkleTree {
+    /// Builds the partial tree of a block's transactions proving the matched ones.
+    pub fn build(txids: &[Hash], matches: &[bool]) -> Result<Self, Error> {
+        if txids.is_empty() || txids.len() != matches.len() {
+            return Err(Error::InvalidInput(
+                "every transaction needs a match flag".to_string(),
+            ));
+        }
+
+        let mut tree = Self {
+            total_transactions: txids.len() as u32,
+            hashes: Vec::new(),
+            flags: Vec::new(),
+        };
+
+        let height = tree.height();
+        tree.traverse_and_build(height, 0, txids, matches);
+
+        Ok(tree)
+    }
+
+    fn width(&self, height: usize) -> usize {
+        (self.total_transactions as usize + (1 << height) - 1) >> height
+    }
+
+    fn height(&self) -> usize {
+        let mut height = 0;
+        while self.width(height) > 1 {
+            height += 1;
+        }
+        height
+    }
+
+    fn node_hash(&self, height: usize, position: usize, txids: &[Hash]) -> Hash {
+        if height == 0 {
+            return txids[position];
+        }
+
+        let left = self.node_hash(height - 1, position * 2, txids);
+        let right = if position * 2 + 1 < self.width(height - 1) {
+            self.node_hash(height - 1, position * 2 + 1, txids)
+        } else {
+            left
+        };
+
+        merkle_parent(&left, &right)
+    }
+
+    fn traverse_and_build(
+        &mut self,
+        height: usize,
+        position: usize,
+        txids: &[Hash],
+        matches: &[bool],
+    ) {
+        let first = position << height;
+        let last = ((position + 1) << height).min(txids.len());
+        let parent_of_match = matches[first..last].iter().any(|matched| *matched);
+
+        self.flags.push(parent_of_match);
+
+        if height == 0 || !parent_of_match {
+            let hash = self.node_hash(height, position, txids);
+            self.hashes.push(hash);
+        } else {
+            self.traverse_and_build(height - 1, position * 2, txids, matches);
+            if position * 2 + 1 < self.width(height - 1) {
+                self.traverse_and_build(height - 1, position * 2 + 1, txids, matches);
+            }
+        }
+    }
+
+    /// Recomputes the merkle root and returns it with the matched `(position, txid)` pairs.
+    ///
+    /// Malformed trees are rejected, including the ones that duplicate a
+    /// hash to mutate the root (CVE-2012-2459) or leave hashes or flags unused.
+    pub fn extract_matches(&self) -> Result<(Hash, Vec<(u32, Hash)>), Error> {
+        let malformed =
+            |reason: &str| Error::InvalidInput(format!("invalid partial merkle tree: {}", reason));
+
+        if self.total_transactions == 0 || self.total_transactions > MAX_TRANSACTIONS {
+            return Err(malformed("invalid transaction count"));
+        }
+        if self.hashes.len() > self.total_transactions as usize {
+            return Err(malformed("more hashes than transactions"));
+        }
+        if self.flags.len() < self.hashes.len() {
+            return Err(malformed("fewer flags than hashes"));
+        }
+
+        let mut traversal = Traversal {
+            tree: self,
+            flags_used: 0,
+            hashes_used: 0,
+            matches: Vec::new(),
+        };
+
+        let root = traversal
+            .extract(self.height(), 0)
+            .ok_or_else(|| malformed("bad traversal"))?;
+
+        if traversal.flags_used.div_ceil(8) != self.flags.len().div_ceil(8) {
+            return Err(malformed("unused flags"));
+        }
+        if traversal.hashes_used != self.hashes.len() {
+            return Err(malformed("unused hashes"));
+        }
+
+        Ok((root, traversal.matches))
+    }
+}
+
+struct Traversal<'a> {
+    tree: &'a PartialMerkleTree,
+    flags_used: usize,
+    hashes_used: usize,
+    matches: Vec<(u32, Hash)>,
+}
+
+impl Traversal<'_> {
+    fn extract(&mut self, height: usize, position: usize) -> Option<Hash> {
+        let parent_of_match = *self.tree.flags.get(self.flags_used)?;
+        self.flags_used += 1;
+
+        if height == 0 || !parent_of_match {
+            let hash = *self.tree.hashes.get(self.hashes_used)?;
+            self.hashes_used += 1;
+            if height == 0 && parent_of_match {
+                self.matches.push((position as u32, hash));
+            }
+            return Some(hash);
+        }
+
+        let left = self.extract(height - 1, position * 2)?;
+        let right = if position * 2 + 1 < self.tree.width(height - 1) {
+            let right = self.extract(height - 1, position * 2 + 1)?;
+            if right == left {
+                return None;
+            }
+            right
+        } else {
+            left
+        };
+
+        Some(merkle_parent(&left, &right))
+    }
+}
+
+/// A BIP 37 `merkleblock` message: a block header and a partial merkle tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleBlock {
+    pub header: [u8; 80],
+    pub tree: PartialMerkleTree,
+}
+
+impl MerkleBlock {
+    /// Parses the payload of a `merkleblock` message.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader { bytes, offset: 0 };
+
+        let header: [u8; 80] = reader.take(80)?.try_into().unwrap();
+        let total_transactions = u32::from_le_bytes(reader.take(4)?.try_into().unwrap());
+
+        let hash_count = reader.compact_size()?;
+        if hash_count > MAX_TRANSACTIONS as u64 {
+            return Err(Error::InvalidInput("too many hashes".to_string()));
+        }
+        let hashes = (0..hash_count)
+            .map(|_| reader.take(32).map(|hash| hash.try_into().unwrap()))
+            .collect::<Result<Vec<Hash>, Error>>()?;
+
+        let flag_bytes_count = reader.compact_size()?;
+        if flag_bytes_count > MAX_TRANSACTIONS as u64 {
+            return Err(Error::InvalidInput("too many flags".to_string()));
+        }
+        let flag_bytes = reader.take(flag_bytes_count as usize)?;
+        let flags = (0..flag_bytes.len() * 8)
+            .map(|bit| flag_bytes[bit / 8] & (1 << (bit % 8)) != 0)
+            .collect();
+
+        if reader.offset != bytes.len() {
+            return Err(Error::InvalidInput("trailing bytes".to_string()));
+        }
+
+        Ok(Self {
+            header,
+            tree: PartialMerkleTree {
+                total_transactions,
+                hashes,
+                flags,
+            },
+        })
+    }
+
+    /// Serializes the message payload.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.to_vec();
+        bytes.extend_from_slice(&self.tree.total_transactions.to_le_bytes());
+
+        write_compact_size(self.tree.hashes.len() as u64, &mut bytes);
+        for hash in &self.tree.hashes {
+            bytes.extend_from_slice(hash);
+        }
+
+        let mut flag_bytes = vec![0u8; self.tree.flags.len().div_ceil(8)];
+        for (bit, flag) in self.tree.flags.iter().enumerate() {
+            if *flag {
+                flag_bytes[bit / 8] |= 1 << (bit % 8);
+            }
+        }
+        write_compact_size(flag_bytes.len() as u64, &mut bytes);
+        bytes.extend_from_slice(&flag_bytes);
+
+        bytes
+    }
+
+    /// The merkle root committed by the header, in internal byte order.
+    pub fn merkle_root(&self) -> Hash {
+        self.header[36..68].try_into().unwrap()
+    }
+
+    /// The hash of the header, i.e. the block hash, in internal byte order.
+    pub fn block_hash(&self) -> Hash {
+        sha256d(&self.header)
+    }
+
+    /// Checks that the transaction is proven to be in the block and returns its position.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::{hash_from_display_hex, MerkleBlock};
+    ///
+    /// # let txids: Vec<_> = [
+    /// #     "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+    /// #     "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+    /// #     "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+    /// #     "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+    /// # ].iter().map(|txid| hash_from_display_hex(txid).unwrap()).collect();
+    /// # let tree = merkle_tree::PartialMerkleTree::build(&txids, &[false, false, true, false]).unwrap();
+    /// # let mut header = [0u8; 80];
+    /// # header[36..68].copy_from_slice(&tree.extract_matches().unwrap().0);
+    /// # let payload = MerkleBlock { header, tree }.to_bytes();
+    /// let merkle_block = MerkleBlock::parse(&payload).unwrap();
+    /// let txid = hash_from_display_hex("6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4").unwrap();
+    ///
+    /// assert_eq!(merkle_block.verify_transaction(&txid).unwrap(), 2);
+    /// ```
+    pub fn verify_transaction(&self, txid: &Hash) -> Result<u32, Error> {
+        let (root, matches) = self.tree.extract_matches()?;
+
+        if root != self.merkle_root() {
+            return Err(Error::RootMismatch);
+        }
+
+        matches
+            .iter()
+            .find(|(_, matched)| matched == txid)
+            .map(|(position, _)| *position)
+            .ok_or_else(|| {
+                Error::InvalidInput("the transaction is not proven by the block".to_string())
+            })
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        let end = self
+            .offset
+            .checked_add(length)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| Error::InvalidInput("unexpected end of message".to_string()))?;
+
+        let taken = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(taken)
+    }
+
+    fn compact_size(&mut self) -> Result<u64, Error> {
+        let prefix = self.take(1)?[0];
+
+        Ok(match prefix {
+            0xfd => u16::from_le_bytes(self.take(2)?.try_into().unwrap()) as u64,
+            0xfe => u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as u64,
+            0xff => u64::from_le_bytes(self.take(8)?.try_into().unwrap()),
+            value => value as u64,
+        })
+    }
+}
+
+fn write_compact_size(value: u64, out: &mut Vec<u8>) {
+    if value < 0xfd {
+        out.push(value as u8);
+    } else if value <= u16::MAX as u64 {
+        out.push(0xfd);
+        out.extend_from_slice(&(value as u16).to_le_bytes());
+    } else if value <= u32::MAX as u64 {
+        out.push(0xfe);
+        out.extend_from_slice(&(value as u32).to_le_bytes());
+    } else {
+        out.push(0xff);
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /// The transactions of block 100000.
+    fn txids() -> Vec<Hash> {
+        [
+            "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+            "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+            "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+            "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+        ]
+        .iter()
+        .map(|txid| hash_from_display_hex(txid).unwrap())
+        .collect()
+    }
+
+    fn block_root() -> Hash {
+        hash_from_display_hex("f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766")
+            .unwrap()
+    }
+
+    fn merkle_block(matches: &[bool]) -> MerkleBlock {
+        let mut header = [0u8; 80];
+        header[36..68].copy_from_slice(&block_root());
+
+        MerkleBlock {
+            header,
+            tree: PartialMerkleTree::build(&txids(), matches).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_display_hex_is_reversed() {
+        let txid = txids()[0];
+
+        assert_eq!(txid[0], 0x87);
+        assert_eq!(
+            hash_to_display_hex(&txid),
+            "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87"
+        );
+    }
+
+    #[test]
+    fn test_partial_tree_matches_block_root() {
+        let tree = PartialMerkleTree::build(&txids(), &[false, false, true, false]).unwrap();
+
+        let (root, matches) = tree.extract_matches().unwrap();
+
+        assert_eq!(root, block_root());
+        assert_eq!(matches, vec![(2, txids()[2])]);
+        assert_eq!(tree.flags, vec![true, false, true, true, false]);
+    }
+
+    #[test]
+    fn test_merkle_block_round_trip_and_verification() {
+        let merkle_block = merkle_block(&[true, false, false, true]);
+
+        let parsed = MerkleBlock::parse(&merkle_block.to_bytes()).unwrap();
+
+        assert_eq!(parsed.verify_transaction(&txids()[0]), Ok(0));
+        assert_eq!(parsed.verify_transaction(&txids()[3]), Ok(3));
+        assert!(parsed.verify_transaction(&txids()[1]).is_err());
+    }
+
+    #[test]
+    fn test_wrong_header_root_is_rejected() {
+        let mut merkle_block = merkle_block(&[false, true, false, false]);
+        merkle_block.header[36] ^= 1;
+
+        assert_eq!(
+            merkle_block.verify_transaction(&txids()[1]),
+            Err(Error::RootMismatch)
+        );
+    }
+
+    #[test]
+    fn test_malformed_trees_are_rejected() {
+        let mut duplicated =
+            PartialMerkleTree::build(&txids(), &[false, false, true, false]).unwrap();
+        duplicated.hashes[2] = duplicated.hashes[1];
+        assert!(duplicated.extract_matches().is_err());
+
+        let mut extra_hash =
+            PartialMerkleTree::build(&txids(), &[false, false, true, false]).unwrap();
+        extra_hash.hashes.push(txids()[0]);
+        assert!(extra_hash.extract_matches().is_err());
+
+        let bytes = merkle_block(&[true, false, false, false]).to_bytes();
+        assert!(MerkleBlock::parse(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_verify_merkle_branch() {
+        let txids = txids();
+        let branch = vec![txids[3], merkle_parent(&txids[0], &txids[1])];
+
+        assert!(verify_merkle_branch(&txids[2], &branch, 2, &block_root()));
+        assert!(!verify_merkle_branch(&txids[2], &branch, 3, &block_root()));
+        assert!(!verify_merkle_branch(&txids[2], &branch, 6, &block_root()));
+    }
+}
@@ -0,0 +1,161 @@
+use crate::hashing::MerkleHasher;
+use crate::merkle_tree::{GenericMerkleTree, Hash};
+
+/// A Bloom filter over hashes, answering most negative membership queries
+/// without looking at the hashes themselves.
+///
+/// The hashes are already uniform, so the positions of a hash are derived
+/// from its own bytes instead of hashing it again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    hashes: u32,
+    capacity: usize,
+    bits_per_item: usize,
+}
+
+impl BloomFilter {
+    /// Creates a filter for `capacity` hashes with `bits_per_item` bits each.
+    /// 10 bits per item give about 1% of false positives at capacity.
+    pub fn new(capacity: usize, bits_per_item: usize) -> Self {
+        let bits_per_item = bits_per_item.max(1);
+        let length = (capacity.max(1) * bits_per_item).div_ceil(64);
+
+        Self {
+            bits: vec![0; length],
+            hashes: ((bits_per_item as f64 * std::f64::consts::LN_2).round() as u32).max(1),
+            capacity: capacity.max(1),
+            bits_per_item,
+        }
+    }
+
+    /// The number of hashes the filter was sized for.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn insert(&mut self, hash: &Hash) {
+        for position in self.positions(hash).collect::<Vec<_>>() {
+            self.bits[position / 64] |= 1 << (position % 64);
+        }
+    }
+
+    /// Returns `false` if the hash was never inserted, `true` if it
+    /// probably was.
+    pub fn may_contain(&self, hash: &Hash) -> bool {
+        self.positions(hash)
+            .all(|position| self.bits[position / 64] & (1 << (position % 64)) != 0)
+    }
+
+    fn positions(&self, hash: &Hash) -> impl Iterator<Item = usize> {
+        let first = u64::from_le_bytes(hash[..8].try_into().unwrap());
+        let step = u64::from_le_bytes(hash[8..16].try_into().unwrap()) | 1;
+        let length = self.bits.len() as u64 * 64;
+
+        (0..self.hashes as u64)
+            .map(move |round| (first.wrapping_add(round.wrapping_mul(step)) % length) as usize)
+    }
+}
+
+impl<H: MerkleHasher> GenericMerkleTree<H> {
+    /// Maintains a Bloom filter over the leaves with `bits_per_leaf` bits
+    /// each, so `contains_hash` answers most negatives without scanning the
+    /// leaves. The filter grows with the tree.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::MerkleTree;
+    ///
+    /// let mut tree = MerkleTree::build(&["Shadowfax", "Bill", "Arod"]).unwrap();
+    /// tree.enable_bloom_filter(10);
+    /// tree.insert(&"Hasufel").unwrap();
+    ///
+    /// assert!(tree.contains_hash(&MerkleTree::hash(b"Hasufel")));
+    /// assert!(!tree.contains_hash(&MerkleTree::hash(b"Brego")));
+    /// ```
+    pub fn enable_bloom_filter(&mut self, bits_per_leaf: usize) {
+        self.bloom = Some(self.filled_bloom_filter(bits_per_leaf));
+    }
+
+    pub fn disable_bloom_filter(&mut self) {
+        self.bloom = None;
+    }
+
+    pub fn bloom_filter(&self) -> Option<&BloomFilter> {
+        self.bloom.as_ref()
+    }
+
+    /// Updates the filter after the leaves changed, `appended` being the
+    /// only new leaf if the others did not change.
+    pub(crate) fn refresh_bloom_filter(&mut self, appended: Option<&Hash>) {
+        let leaf_count = self.leaf_count();
+        let Some(bloom) = &mut self.bloom else {
+            return;
+        };
+
+        match appended {
+            Some(leaf) if leaf_count <= bloom.capacity => bloom.insert(leaf),
+            _ => {
+                let bits_per_leaf = bloom.bits_per_item;
+                self.bloom = Some(self.filled_bloom_filter(bits_per_leaf));
+            }
+        }
+    }
+
+    /// A filter over the current leaves, with room for as many more.
+    fn filled_bloom_filter(&self, bits_per_leaf: usize) -> BloomFilter {
+        let mut bloom = BloomFilter::new(2 * self.leaf_count(), bits_per_leaf);
+        for leaf in self.leaves() {
+            bloom.insert(leaf);
+        }
+        bloom
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::merkle_tree::MerkleTree;
+
+    #[test]
+    fn test_filter_has_no_false_negatives() {
+        let mut tree = MerkleTree::build(&["leaf 0"]).unwrap();
+        tree.enable_bloom_filter(10);
+
+        for leaf in 1..500 {
+            tree.insert(&format!("leaf {}", leaf)).unwrap();
+        }
+
+        assert!(tree.bloom_filter().unwrap().capacity() >= 500);
+        for leaf in 0..500 {
+            assert!(tree.contains_hash(&MerkleTree::hash(format!("leaf {}", leaf).as_bytes())));
+        }
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        let mut bloom = BloomFilter::new(1000, 10);
+        for item in 0..1000 {
+            bloom.insert(&MerkleTree::hash(format!("member {}", item).as_bytes()));
+        }
+
+        let false_positives = (0..10_000)
+            .filter(|item| {
+                bloom.may_contain(&MerkleTree::hash(format!("stranger {}", item).as_bytes()))
+            })
+            .count();
+
+        assert!(false_positives < 300, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn test_filter_can_be_disabled() {
+        let mut tree = MerkleTree::build(&["Shadowfax"]).unwrap();
+        tree.enable_bloom_filter(8);
+        tree.disable_bloom_filter();
+
+        assert!(tree.bloom_filter().is_none());
+        assert!(tree.contains_hash(&MerkleTree::hash(b"Shadowfax")));
+    }
+}
@@ -0,0 +1,290 @@
+use std::marker::PhantomData;
+
+use crate::config::{DuplicatePolicy, MutationGuard, TreeConfig};
+use crate::error::Error;
+use crate::leaf::MerkleLeaf;
+use crate::limits::Limits;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::profile::{Profile, ProfileTree};
+use crate::proof::Proof;
+
+/// Builds trees with non default options.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{MerkleTree, MutationGuard};
+///
+/// let builder = MerkleTree::builder().mutation_guard(MutationGuard::CommitLeafCount);
+///
+/// let three = builder.build(&["Huey", "Dewey", "Louie"]).unwrap();
+/// let four = builder.build(&["Huey", "Dewey", "Louie", "Louie"]).unwrap();
+///
+/// assert_ne!(three.root(), four.root());
+/// ```
+///
+/// The type parameter records which leaves the builder accepts. A builder
+/// restricted with `items_only` hashes every input itself and takes strings
+/// and byte vectors or slices, but not the byte arrays an already hashed
+/// leaf is, so hashed leaves can't be passed as raw items by mistake:
+///
+/// ```compile_fail
+/// use merkle_tree::MerkleTree;
+///
+/// let leaves = vec![MerkleTree::hash(b"Huey"), MerkleTree::hash(b"Dewey")];
+///
+/// MerkleTree::builder().items_only().build(&leaves);
+/// ```
+///
+/// And the other way around:
+///
+/// ```compile_fail
+/// use merkle_tree::MerkleTree;
+///
+/// let leaves = vec![MerkleTree::hash(b"Huey"), MerkleTree::hash(b"Dewey")];
+///
+/// MerkleTree::builder().items_only().build_from_hashes(leaves);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MerkleTreeBuilder<I = AnyInput> {
+    config: TreeConfig,
+    input: PhantomData<I>,
+}
+
+/// The input of a builder accepting both raw items and hashed leaves.
+#[derive(Debug, Clone, Copy)]
+pub struct AnyInput;
+
+/// The input of a builder accepting only raw items, which it hashes.
+#[derive(Debug, Clone, Copy)]
+pub struct ItemInput;
+
+/// The input of a builder accepting only already hashed leaves.
+#[derive(Debug, Clone, Copy)]
+pub struct HashInput;
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for super::AnyInput {}
+    impl Sealed for super::ItemInput {}
+    impl Sealed for super::HashInput {}
+}
+
+/// The builder inputs that accept raw items.
+pub trait AcceptsItems: sealed::Sealed {}
+
+impl AcceptsItems for AnyInput {}
+impl AcceptsItems for ItemInput {}
+
+/// The builder inputs that accept hashed leaves.
+pub trait AcceptsHashes: sealed::Sealed {}
+
+impl AcceptsHashes for AnyInput {}
+impl AcceptsHashes for HashInput {}
+
+/// The raw items a builder of input `I` hashes into leaves: anything
+/// representable as bytes for `AnyInput`, and for `ItemInput` strings and
+/// byte vectors or slices, but not byte arrays such as a `Hash`.
+pub trait RawItem<I> {
+    fn item_bytes(&self) -> &[u8];
+}
+
+impl<T: AsRef<[u8]>> RawItem<AnyInput> for T {
+    fn item_bytes(&self) -> &[u8] {
+        self.as_ref()
+    }
+}
+
+impl RawItem<ItemInput> for str {
+    fn item_bytes(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl RawItem<ItemInput> for String {
+    fn item_bytes(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl RawItem<ItemInput> for [u8] {
+    fn item_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl RawItem<ItemInput> for Vec<u8> {
+    fn item_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<T: RawItem<ItemInput> + ?Sized> RawItem<ItemInput> for &T {
+    fn item_bytes(&self) -> &[u8] {
+        T::item_bytes(self)
+    }
+}
+
+impl Default for MerkleTreeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MerkleTreeBuilder {
+    pub fn new() -> Self {
+        Self {
+            config: TreeConfig::default(),
+            input: PhantomData,
+        }
+    }
+
+    /// Restricts the builder to raw items, see `RawItem`.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::MerkleTree;
+    ///
+    /// let names = vec!["Huey".to_string(), "Dewey".to_string()];
+    /// let tree = MerkleTree::builder().items_only().build(&names).unwrap();
+    ///
+    /// assert_eq!(tree.root(), MerkleTree::build(&names).unwrap().root());
+    /// ```
+    pub fn items_only(self) -> MerkleTreeBuilder<ItemInput> {
+        self.with_input()
+    }
+
+    /// Restricts the builder to already hashed leaves.
+    pub fn hashes_only(self) -> MerkleTreeBuilder<HashInput> {
+        self.with_input()
+    }
+}
+
+impl<I> MerkleTreeBuilder<I> {
+    fn with_input<J>(self) -> MerkleTreeBuilder<J> {
+        MerkleTreeBuilder {
+            config: self.config,
+            input: PhantomData,
+        }
+    }
+
+    #[cfg(feature = "json")]
+    pub(crate) fn config(&self) -> &TreeConfig {
+        &self.config
+    }
+
+    /// Sets the defense against the duplicate leaf root mutation.
+    pub fn mutation_guard(mut self, guard: MutationGuard) -> Self {
+        self.config.mutation_guard = guard;
+        self
+    }
+
+    /// Sets the bounds the items, insertions, proofs and peers of the tree must respect.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.config.limits = limits;
+        self
+    }
+
+    /// Sets what to do with items whose leaves are identical.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::{DuplicatePolicy, Error, MerkleTree};
+    ///
+    /// let ids = ["frodo", "sam", "frodo", "merry", "sam"];
+    /// let result = MerkleTree::builder().duplicates(DuplicatePolicy::Reject).build(&ids);
+    ///
+    /// assert_eq!(result.err(), Some(Error::DuplicateLeaves(vec![vec![0, 2], vec![1, 4]])));
+    /// ```
+    pub fn duplicates(mut self, policy: DuplicatePolicy) -> Self {
+        self.config.duplicates = policy;
+        self
+    }
+}
+
+impl<I: AcceptsItems> MerkleTreeBuilder<I> {
+    /// Builds a tree from items representable as bytes.
+    pub fn build<T: RawItem<I>>(&self, items: &[T]) -> Result<MerkleTree, Error> {
+        let items: Vec<&[u8]> = items.iter().map(RawItem::item_bytes).collect();
+        self.config.limits.check_items(&items)?;
+
+        let leaves = items.iter().map(|item| MerkleTree::hash(item)).collect();
+        let config = TreeConfig {
+            total_size: items.iter().map(|item| item.len()).sum(),
+            ..self.config.clone()
+        };
+
+        MerkleTree::from_leaves_with(leaves, config)
+    }
+
+    /// Builds a tree from items with a canonical encoding.
+    pub fn build_leaves<T: MerkleLeaf>(&self, items: &[T]) -> Result<MerkleTree, Error> {
+        self.config.limits.check_leaf_count(items.len())?;
+
+        let mut total_size = 0usize;
+        let leaves = items
+            .iter()
+            .map(|item| {
+                let bytes = item.leaf_bytes();
+                self.config.limits.check_item_size(bytes.len())?;
+                total_size = total_size.saturating_add(bytes.len());
+                self.config.limits.check_total_size(total_size)?;
+                Ok(MerkleTree::hash(&bytes))
+            })
+            .collect::<Result<Vec<Hash>, Error>>()?;
+        let config = TreeConfig {
+            total_size,
+            ..self.config.clone()
+        };
+
+        MerkleTree::from_leaves_with(leaves, config)
+    }
+
+    /// Builds a tree of `profile` from items representable as bytes, each
+    /// hashed with the leaf hash of the profile.
+    ///
+    /// The limits and the duplicate policy of the builder apply, the
+    /// mutation guard doesn't as the profile decides how levels are padded.
+    pub fn build_profile<T: RawItem<I>>(
+        &self,
+        profile: Profile,
+        items: &[T],
+    ) -> Result<ProfileTree, Error> {
+        let items: Vec<&[u8]> = items.iter().map(RawItem::item_bytes).collect();
+        self.config.limits.check_items(&items)?;
+
+        let leaves = items.iter().map(|item| profile.hash_leaf(item)).collect();
+        self.profile_tree(profile, leaves)
+    }
+}
+
+impl<I> MerkleTreeBuilder<I> {
+    fn profile_tree(&self, profile: Profile, leaves: Vec<Hash>) -> Result<ProfileTree, Error> {
+        if leaves.is_empty() {
+            return Err(Error::EmptyTree);
+        }
+        self.config.limits.check_leaf_count(leaves.len())?;
+        let leaves = self.config.duplicates.apply(leaves)?;
+        self.config
+            .limits
+            .check_proof_length(Proof::expected_length(leaves.len()))?;
+
+        ProfileTree::new(profile, leaves)
+    }
+}
+
+impl<I: AcceptsHashes> MerkleTreeBuilder<I> {
+    /// Builds a tree from already hashed leaves.
+    pub fn build_from_hashes(&self, leaves: Vec<Hash>) -> Result<MerkleTree, Error> {
+        MerkleTree::from_leaves_with(leaves, self.config.clone())
+    }
+
+    /// Builds a tree of `profile` from leaves already hashed with its leaf hash.
+    pub fn build_profile_from_hashes(
+        &self,
+        profile: Profile,
+        leaves: Vec<Hash>,
+    ) -> Result<ProfileTree, Error> {
+        self.profile_tree(profile, leaves)
+    }
+}
@@ -0,0 +1,410 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use crate::error::Error;
+use crate::merkle_tree::Hash;
+use crate::store::{NodeId, NodeStore};
+
+/// The hits and misses of a `CachedNodeStore`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+impl CacheStats {
+    /// The share of reads served from memory, 0 before any read.
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            reads => self.hits as f64 / reads as f64,
+        }
+    }
+}
+
+/// The bound on the memory of a `CachedNodeStore`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheLimit {
+    /// At most this many nodes.
+    Nodes(usize),
+    /// At most this many bytes of cached nodes, their keys and bookkeeping.
+    Bytes(usize),
+}
+
+impl CacheLimit {
+    fn admits(&self, nodes: usize, bytes: usize) -> bool {
+        match *self {
+            CacheLimit::Nodes(limit) => nodes <= limit,
+            CacheLimit::Bytes(limit) => bytes <= limit,
+        }
+    }
+}
+
+type CacheKey = (String, NodeId);
+
+/// The bytes an entry takes: its key in both maps, the hash and the ticks.
+fn entry_size(key: &CacheKey) -> usize {
+    2 * (std::mem::size_of::<CacheKey>() + key.0.len())
+        + std::mem::size_of::<Hash>()
+        + 2 * std::mem::size_of::<u64>()
+}
+
+#[derive(Debug, Default)]
+struct Cache {
+    entries: HashMap<CacheKey, (Hash, u64)>,
+    /// The keys by the tick of their last use, the oldest first.
+    recency: BTreeMap<u64, CacheKey>,
+    tick: u64,
+    bytes: usize,
+    stats: CacheStats,
+}
+
+impl Cache {
+    fn touch(&mut self, key: &CacheKey) -> Option<Hash> {
+        let (hash, used) = self.entries.get_mut(key)?;
+        self.recency.remove(used);
+        self.tick += 1;
+        *used = self.tick;
+        self.recency.insert(self.tick, key.clone());
+        Some(*hash)
+    }
+
+    fn insert(&mut self, key: CacheKey, hash: Hash, limit: CacheLimit) {
+        let size = entry_size(&key);
+        if !limit.admits(1, size) {
+            return;
+        }
+        if let Some((_, used)) = self.entries.remove(&key) {
+            self.recency.remove(&used);
+            self.bytes -= size;
+        }
+        while !limit.admits(self.entries.len() + 1, self.bytes + size) {
+            let (_, oldest) = self.recency.pop_first().expect("The cache is not empty.");
+            self.entries.remove(&oldest);
+            self.bytes -= entry_size(&oldest);
+            self.stats.evictions += 1;
+        }
+
+        self.bytes += size;
+        self.tick += 1;
+        self.recency.insert(self.tick, key.clone());
+        self.entries.insert(key, (hash, self.tick));
+    }
+}
+
+/// A size-bounded cache of the least recently used nodes over a slower
+/// `NodeStore`, so the proof paths of popular leaves are read from memory.
+///
+/// Writes go through to the inner store and refresh the cached nodes.
+/// The cache is bounded by a number of nodes or, with `with_limit`, by the
+/// bytes it takes, to cap the memory spent on huge trees.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{CachedNodeStore, MemoryNodeStore, MerkleTree};
+///
+/// let tree = MerkleTree::build(&["Fili", "Kili", "Oin", "Gloin"]).unwrap();
+/// let mut store = CachedNodeStore::new(MemoryNodeStore::new(), 64);
+/// tree.store(&mut store, "company").unwrap();
+///
+/// MerkleTree::stored_proof(&store, "company", 1).unwrap();
+/// MerkleTree::stored_proof(&store, "company", 1).unwrap();
+///
+/// assert_eq!(store.stats().hits, 2);
+/// ```
+#[derive(Debug)]
+pub struct CachedNodeStore<S> {
+    inner: S,
+    limit: CacheLimit,
+    cache: Mutex<Cache>,
+}
+
+impl<S: NodeStore> CachedNodeStore<S> {
+    /// Caches up to `capacity` nodes of `inner`.
+    pub fn new(inner: S, capacity: usize) -> Self {
+        Self::with_limit(inner, CacheLimit::Nodes(capacity))
+    }
+
+    /// Caches nodes of `inner` within `limit`.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::{CacheLimit, CachedNodeStore, MemoryNodeStore, MerkleTree};
+    ///
+    /// let items: Vec<String> = (0..64).map(|item| format!("stone {}", item)).collect();
+    /// let tree = MerkleTree::build(&items).unwrap();
+    /// let mut store = CachedNodeStore::with_limit(MemoryNodeStore::new(), CacheLimit::Bytes(1024));
+    /// tree.store(&mut store, "moria").unwrap();
+    ///
+    /// for index in 0..64 {
+    ///     MerkleTree::stored_proof(&store, "moria", index).unwrap();
+    /// }
+    ///
+    /// assert!(store.memory_usage() <= 1024);
+    /// ```
+    pub fn with_limit(inner: S, limit: CacheLimit) -> Self {
+        Self {
+            inner,
+            limit,
+            cache: Mutex::new(Cache::default()),
+        }
+    }
+
+    pub fn limit(&self) -> CacheLimit {
+        self.limit
+    }
+
+    /// The most nodes the cache can hold.
+    pub fn capacity(&self) -> usize {
+        match self.limit {
+            CacheLimit::Nodes(capacity) => capacity,
+            CacheLimit::Bytes(bytes) => bytes / entry_size(&(String::new(), NodeId::new(0, 0))),
+        }
+    }
+
+    /// The bytes taken by the cached nodes, the measure of `CacheLimit::Bytes`.
+    pub fn memory_usage(&self) -> usize {
+        self.cache().bytes
+    }
+
+    /// The number of cached nodes.
+    pub fn len(&self) -> usize {
+        self.cache().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.cache().stats
+    }
+
+    /// Forgets every cached node, keeping the statistics.
+    pub fn clear(&self) {
+        let mut cache = self.cache();
+        cache.entries.clear();
+        cache.recency.clear();
+        cache.bytes = 0;
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn cache(&self) -> std::sync::MutexGuard<'_, Cache> {
+        self.cache.lock().expect("The cache lock is not poisoned.")
+    }
+}
+
+impl<S: NodeStore> NodeStore for CachedNodeStore<S> {
+    fn get(&self, tree: &str, id: NodeId) -> Result<Option<Hash>, Error> {
+        let key = (tree.to_string(), id);
+        {
+            let mut cache = self.cache();
+            if let Some(hash) = cache.touch(&key) {
+                cache.stats.hits += 1;
+                return Ok(Some(hash));
+            }
+        }
+
+        let hash = self.inner.get(tree, id)?;
+        let mut cache = self.cache();
+        cache.stats.misses += 1;
+        if let Some(hash) = hash {
+            cache.insert(key, hash, self.limit);
+        }
+        Ok(hash)
+    }
+
+    /// Serves the cached nodes and reads the others in one batch of the inner store.
+    fn get_batch(&self, tree: &str, ids: &[NodeId]) -> Result<Vec<Option<Hash>>, Error> {
+        let mut nodes = Vec::with_capacity(ids.len());
+        let mut missing = Vec::new();
+        {
+            let mut cache = self.cache();
+            for id in ids {
+                let hash = cache.touch(&(tree.to_string(), *id));
+                match hash {
+                    Some(_) => cache.stats.hits += 1,
+                    None => missing.push(*id),
+                }
+                nodes.push(hash);
+            }
+        }
+        if missing.is_empty() {
+            return Ok(nodes);
+        }
+
+        let fetched = self.inner.get_batch(tree, &missing)?;
+        let mut cache = self.cache();
+        cache.stats.misses += missing.len() as u64;
+        let mut fetched = missing.into_iter().zip(fetched);
+        for node in nodes.iter_mut().filter(|node| node.is_none()) {
+            let Some((id, hash)) = fetched.next() else {
+                break;
+            };
+            if let Some(hash) = hash {
+                cache.insert((tree.to_string(), id), hash, self.limit);
+            }
+            *node = hash;
+        }
+        Ok(nodes)
+    }
+
+    fn put(&mut self, tree: &str, nodes: &[(NodeId, Hash)]) -> Result<(), Error> {
+        self.inner.put(tree, nodes)?;
+
+        let mut cache = self.cache();
+        for (id, hash) in nodes {
+            let key = (tree.to_string(), *id);
+            if cache.entries.contains_key(&key) {
+                cache.insert(key, *hash, self.limit);
+            }
+        }
+        Ok(())
+    }
+
+    fn leaf_count(&self, tree: &str) -> Result<usize, Error> {
+        self.inner.leaf_count(tree)
+    }
+
+    fn set_leaf_count(&mut self, tree: &str, leaf_count: usize) -> Result<(), Error> {
+        self.inner.set_leaf_count(tree, leaf_count)
+    }
+
+    fn remove_tree(&mut self, tree: &str) -> Result<(), Error> {
+        self.inner.remove_tree(tree)?;
+
+        let mut cache = self.cache();
+        let Cache {
+            entries,
+            recency,
+            bytes,
+            ..
+        } = &mut *cache;
+        entries.retain(|key, (_, used)| {
+            let keep = key.0 != tree;
+            if !keep {
+                recency.remove(used);
+                *bytes -= entry_size(key);
+            }
+            keep
+        });
+        Ok(())
+    }
+
+    fn trees(&self) -> Result<Vec<String>, Error> {
+        self.inner.trees()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::merkle_tree::MerkleTree;
+    use crate::store::MemoryNodeStore;
+
+    fn store(capacity: usize) -> (MerkleTree, CachedNodeStore<MemoryNodeStore>) {
+        let items: Vec<String> = (0..32).map(|item| format!("palantir {}", item)).collect();
+        let tree = MerkleTree::build(&items).unwrap();
+        let mut store = CachedNodeStore::new(MemoryNodeStore::new(), capacity);
+        tree.store(&mut store, "orthanc").unwrap();
+        (tree, store)
+    }
+
+    #[test]
+    fn test_hot_paths_are_served_from_memory() {
+        let (tree, store) = store(16);
+
+        for _ in 0..10 {
+            assert_eq!(
+                MerkleTree::stored_proof(&store, "orthanc", 7),
+                tree.proof(7)
+            );
+        }
+
+        assert_eq!(
+            store.stats(),
+            CacheStats {
+                hits: 45,
+                misses: 5,
+                evictions: 0
+            }
+        );
+        assert_eq!(store.stats().hit_rate(), 0.9);
+        assert_eq!(store.len(), 5);
+    }
+
+    #[test]
+    fn test_least_recently_used_nodes_are_evicted() {
+        let (tree, store) = store(6);
+
+        for index in [0, 31, 0] {
+            assert_eq!(
+                MerkleTree::stored_proof(&store, "orthanc", index),
+                tree.proof(index)
+            );
+        }
+
+        // The last proof finds the top sibling of the first one before
+        // its misses evict it.
+        assert_eq!(store.len(), 6);
+        assert_eq!(store.stats().evictions, 4 + 4);
+        assert_eq!(store.stats().hits, 1);
+
+        store.clear();
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_writes_refresh_cached_nodes() {
+        let (_, mut store) = store(64);
+        MerkleTree::stored_proof(&store, "orthanc", 3).unwrap();
+
+        let other = MerkleTree::build(&["Ithil", "Anor", "Osgiliath"]).unwrap();
+        other.store(&mut store, "orthanc").unwrap();
+
+        assert_eq!(
+            MerkleTree::stored_proof(&store, "orthanc", 2),
+            other.proof(2)
+        );
+        assert_eq!(
+            MerkleTree::load(&store, "orthanc").unwrap().root(),
+            other.root()
+        );
+    }
+
+    #[test]
+    fn test_byte_limits_bound_the_memory_usage() {
+        let items: Vec<String> = (0..32).map(|item| format!("palantir {}", item)).collect();
+        let tree = MerkleTree::build(&items).unwrap();
+        let entry = entry_size(&("orthanc".to_string(), NodeId::new(0, 0)));
+        let mut store =
+            CachedNodeStore::with_limit(MemoryNodeStore::new(), CacheLimit::Bytes(6 * entry + 1));
+        tree.store(&mut store, "orthanc").unwrap();
+
+        for index in [0, 31, 0] {
+            assert_eq!(
+                MerkleTree::stored_proof(&store, "orthanc", index),
+                tree.proof(index)
+            );
+        }
+
+        assert_eq!(store.len(), 6);
+        assert_eq!(store.memory_usage(), 6 * entry);
+        assert_eq!(store.stats().evictions, 4 + 4);
+
+        store.remove_tree("orthanc").unwrap();
+        assert_eq!(store.memory_usage(), 0);
+
+        let tiny = CachedNodeStore::with_limit(MemoryNodeStore::new(), CacheLimit::Bytes(8));
+        assert_eq!(tiny.capacity(), 0);
+    }
+}
@@ -0,0 +1,222 @@
+use ciborium::Value;
+use half::f16;
+use serde::Serialize;
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+
+/// Encodes a value as deterministic CBOR (RFC 8949, section 4.2.1).
+///
+/// Integers, lengths and tags use the shortest argument encoding, floats use
+/// the shortest of half, single or double precision that keeps their value,
+/// all lengths are definite and map keys are sorted by the bytewise order of
+/// their encodings. Any implementation following those rules produces the same bytes.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::encode_cbor;
+///
+/// let bytes = encode_cbor(&("Sting", 1.5)).unwrap();
+///
+/// assert_eq!(bytes, vec![0x82, 0x65, b'S', b't', b'i', b'n', b'g', 0xf9, 0x3e, 0x00]);
+/// ```
+pub fn encode_cbor<T: Serialize>(item: &T) -> Result<Vec<u8>, Error> {
+    let value = Value::serialized(item).map_err(|error| Error::Encoding(error.to_string()))?;
+
+    let mut out = Vec::new();
+    write_value(&value, &mut out)?;
+    Ok(out)
+}
+
+fn write_head(major: u8, argument: u64, out: &mut Vec<u8>) {
+    let major = major << 5;
+
+    if argument < 24 {
+        out.push(major | argument as u8);
+    } else if argument <= u8::MAX as u64 {
+        out.push(major | 24);
+        out.push(argument as u8);
+    } else if argument <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend_from_slice(&(argument as u16).to_be_bytes());
+    } else if argument <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend_from_slice(&(argument as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&argument.to_be_bytes());
+    }
+}
+
+fn write_float(float: f64, out: &mut Vec<u8>) {
+    if float.is_nan() {
+        // The canonical NaN is the quiet half precision NaN.
+        out.extend_from_slice(&[0xf9, 0x7e, 0x00]);
+        return;
+    }
+
+    let half = f16::from_f64(float);
+    if half.to_f64() == float {
+        out.push(0xf9);
+        out.extend_from_slice(&half.to_be_bytes());
+        return;
+    }
+
+    let single = float as f32;
+    if single as f64 == float {
+        out.push(0xfa);
+        out.extend_from_slice(&single.to_be_bytes());
+        return;
+    }
+
+    out.push(0xfb);
+    out.extend_from_slice(&float.to_be_bytes());
+}
+
+fn write_value(value: &Value, out: &mut Vec<u8>) -> Result<(), Error> {
+    match value {
+        Value::Integer(integer) => {
+            let integer = i128::from(*integer);
+            if integer >= 0 {
+                write_head(0, integer as u64, out);
+            } else {
+                write_head(1, (-1 - integer) as u64, out);
+            }
+        }
+        Value::Bytes(bytes) => {
+            write_head(2, bytes.len() as u64, out);
+            out.extend_from_slice(bytes);
+        }
+        Value::Text(text) => {
+            write_head(3, text.len() as u64, out);
+            out.extend_from_slice(text.as_bytes());
+        }
+        Value::Array(values) => {
+            write_head(4, values.len() as u64, out);
+            for value in values {
+                write_value(value, out)?;
+            }
+        }
+        Value::Map(entries) => {
+            let mut encoded = Vec::with_capacity(entries.len());
+            for (key, value) in entries {
+                let mut key_bytes = Vec::new();
+                write_value(key, &mut key_bytes)?;
+                let mut value_bytes = Vec::new();
+                write_value(value, &mut value_bytes)?;
+                encoded.push((key_bytes, value_bytes));
+            }
+            encoded.sort();
+
+            if encoded.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+                return Err(Error::Encoding("duplicate map key".to_string()));
+            }
+
+            write_head(5, encoded.len() as u64, out);
+            for (key, value) in encoded {
+                out.extend_from_slice(&key);
+                out.extend_from_slice(&value);
+            }
+        }
+        Value::Tag(tag, value) => {
+            write_head(6, *tag, out);
+            write_value(value, out)?;
+        }
+        Value::Bool(false) => out.push(0xf4),
+        Value::Bool(true) => out.push(0xf5),
+        Value::Null => out.push(0xf6),
+        Value::Float(float) => write_float(*float, out),
+        _ => return Err(Error::Encoding("unsupported CBOR value".to_string())),
+    }
+
+    Ok(())
+}
+
+impl MerkleTree {
+    /// Create a new MerkleTree from serializable items.
+    /// Each item is encoded as deterministic CBOR before hashing.
+    /// The creation will fail if the items list is empty or an item can't be encoded.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::MerkleTree;
+    /// use std::collections::HashMap;
+    ///
+    /// let realms = vec![
+    ///     HashMap::from([("realm", "Gondor"), ("steward", "Denethor")]),
+    ///     HashMap::from([("realm", "Rohan"), ("king", "Théoden")]),
+    /// ];
+    ///
+    /// let merkle_tree = MerkleTree::build_cbor(&realms).unwrap();
+    /// ```
+    pub fn build_cbor<T: Serialize>(items: &[T]) -> Result<Self, Error> {
+        let leaves = items
+            .iter()
+            .map(|item| encode_cbor(item).map(|bytes| Self::hash(&bytes)))
+            .collect::<Result<Vec<Hash>, Error>>()?;
+
+        Self::from_leaves(leaves).ok_or(Error::EmptyTree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::collections::{BTreeMap, HashMap};
+
+    #[test]
+    fn test_integers_use_shortest_heads() {
+        assert_eq!(encode_cbor(&10u64).unwrap(), vec![0x0a]);
+        assert_eq!(encode_cbor(&24u64).unwrap(), vec![0x18, 0x18]);
+        assert_eq!(encode_cbor(&1000u64).unwrap(), vec![0x19, 0x03, 0xe8]);
+        assert_eq!(encode_cbor(&-1000i64).unwrap(), vec![0x39, 0x03, 0xe7]);
+        assert_eq!(
+            encode_cbor(&u64::MAX).unwrap(),
+            vec![0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]
+        );
+    }
+
+    #[test]
+    fn test_floats_use_shortest_exact_width() {
+        assert_eq!(encode_cbor(&0.0f64).unwrap(), vec![0xf9, 0x00, 0x00]);
+        assert_eq!(
+            encode_cbor(&100000.0f64).unwrap(),
+            vec![0xfa, 0x47, 0xc3, 0x50, 0x00]
+        );
+        assert_eq!(
+            encode_cbor(&1.1f64).unwrap(),
+            vec![0xfb, 0x3f, 0xf1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9a]
+        );
+        assert_eq!(encode_cbor(&f64::NAN).unwrap(), vec![0xf9, 0x7e, 0x00]);
+    }
+
+    #[test]
+    fn test_map_keys_are_sorted_by_encoding() {
+        // "z" (0x61 0x7a) sorts before "aa" (0x62 0x61 0x61) because it is shorter.
+        let map = BTreeMap::from([("aa", 1u8), ("z", 2u8)]);
+
+        assert_eq!(
+            encode_cbor(&map).unwrap(),
+            vec![0xa2, 0x61, b'z', 0x02, 0x62, b'a', b'a', 0x01]
+        );
+    }
+
+    #[test]
+    fn test_insertion_order_does_not_change_the_root() {
+        let mut first = HashMap::new();
+        first.insert("realm", "Gondor");
+        first.insert("steward", "Denethor");
+        first.insert("city", "Minas Tirith");
+
+        let mut second = HashMap::new();
+        second.insert("city", "Minas Tirith");
+        second.insert("steward", "Denethor");
+        second.insert("realm", "Gondor");
+
+        let first_tree = MerkleTree::build_cbor(&[first]).unwrap();
+        let second_tree = MerkleTree::build_cbor(&[second]).unwrap();
+
+        assert_eq!(first_tree.root(), second_tree.root());
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::delta::ChunkTree;
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// A file of a `ChunkStore`: the hashes of its chunks and the tree over them.
+#[derive(Debug, Clone)]
+struct StoredFile {
+    tree: MerkleTree,
+    chunks: Vec<Hash>,
+    length: usize,
+}
+
+/// A content-addressed store of deduplicated chunks, holding each file as
+/// a tree over the hashes of its fixed size chunks, as a backup tool keeps
+/// successive snapshots of mostly unchanged files.
+///
+/// Chunks are stored by the hash of their data, while a file's root is the
+/// root of a `ChunkTree` over its data with the same chunk size, whose
+/// leaves also commit to the index of each chunk. Removing a file keeps its chunks until `collect_garbage`.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::ChunkStore;
+///
+/// let mut store = ChunkStore::new(8).unwrap();
+/// store.put("red book v1", b"There and Back Again").unwrap();
+/// store.put("red book v2", b"There and Back Again, by Frodo").unwrap();
+///
+/// assert_eq!(store.chunk_count(), 5);
+///
+/// store.remove("red book v1");
+/// assert_eq!(store.collect_garbage(), 1);
+/// assert_eq!(store.get("red book v2").unwrap(), b"There and Back Again, by Frodo");
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChunkStore {
+    chunk_size: usize,
+    chunks: HashMap<Hash, Vec<u8>>,
+    files: BTreeMap<String, StoredFile>,
+}
+
+impl ChunkStore {
+    pub fn new(chunk_size: usize) -> Result<Self, Error> {
+        if chunk_size == 0 {
+            return Err(Error::InvalidInput("chunks can't be empty".to_string()));
+        }
+
+        Ok(Self {
+            chunk_size,
+            chunks: HashMap::new(),
+            files: BTreeMap::new(),
+        })
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// The number of distinct chunks stored.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// The number of bytes of the distinct chunks stored.
+    pub fn stored_bytes(&self) -> usize {
+        self.chunks.values().map(Vec::len).sum()
+    }
+
+    /// The names of the files, sorted.
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(String::as_str)
+    }
+
+    /// Stores `data` as the file `name`, replacing any file of that name,
+    /// and returns its root. Only the chunks not stored yet take space.
+    pub fn put(&mut self, name: &str, data: &[u8]) -> Result<Hash, Error> {
+        let empty: &[u8] = &[];
+        let chunks = data
+            .chunks(self.chunk_size)
+            .chain(data.is_empty().then_some(empty));
+
+        let mut hashes = Vec::new();
+        let mut leaves = Vec::new();
+        for (index, chunk) in chunks.enumerate() {
+            let hash = MerkleTree::hash(chunk);
+            self.chunks.entry(hash).or_insert_with(|| chunk.to_vec());
+            hashes.push(hash);
+            leaves.push(ChunkTree::leaf_hash(index, chunk));
+        }
+
+        let tree = MerkleTree::from_leaves(leaves).ok_or(Error::EmptyTree)?;
+        let root = tree.root().expect("A file has a root.");
+        self.files.insert(
+            name.to_string(),
+            StoredFile {
+                tree,
+                chunks: hashes,
+                length: data.len(),
+            },
+        );
+        Ok(root)
+    }
+
+    fn file(&self, name: &str) -> Result<&StoredFile, Error> {
+        self.files
+            .get(name)
+            .ok_or_else(|| Error::InvalidInput(format!("there is no file {}", name)))
+    }
+
+    pub fn root(&self, name: &str) -> Result<Hash, Error> {
+        Ok(self.file(name)?.tree.root().expect("A file has a root."))
+    }
+
+    /// Reassembles the file `name`, checking every chunk against its hash.
+    pub fn get(&self, name: &str) -> Result<Vec<u8>, Error> {
+        let file = self.file(name)?;
+        let mut data = Vec::with_capacity(file.length);
+
+        for hash in &file.chunks {
+            let chunk = self.chunks.get(hash).ok_or_else(|| {
+                Error::InvalidInput(format!("the chunk {} is missing", hex::encode(hash)))
+            })?;
+            if MerkleTree::hash(chunk) != *hash {
+                return Err(Error::RootMismatch);
+            }
+            data.extend_from_slice(chunk);
+        }
+
+        Ok(data)
+    }
+
+    /// Returns the chunk at `index` of the file `name` with its proof against the file's root.
+    pub fn chunk(&self, name: &str, index: usize) -> Result<(&[u8], Proof), Error> {
+        let file = self.file(name)?;
+        let proof = file.tree.proof(index)?;
+        let hash = &file.chunks[index];
+        let chunk = self.chunks.get(hash).ok_or_else(|| {
+            Error::InvalidInput(format!("the chunk {} is missing", hex::encode(hash)))
+        })?;
+        Ok((chunk, proof))
+    }
+
+    /// Removes the file `name`, returning whether it existed. Its chunks
+    /// stay until the next `collect_garbage`.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.files.remove(name).is_some()
+    }
+
+    /// Drops the chunks no file refers to, returning how many were dropped.
+    pub fn collect_garbage(&mut self) -> usize {
+        let referenced: HashSet<&Hash> =
+            self.files.values().flat_map(|file| &file.chunks).collect();
+
+        let before = self.chunks.len();
+        self.chunks.retain(|hash, _| referenced.contains(hash));
+        before - self.chunks.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn snapshot(edits: usize) -> Vec<u8> {
+        let mut data: Vec<u8> = (0..500)
+            .flat_map(|line| format!("entry {} of the red book\n", line).into_bytes())
+            .collect();
+        for edit in 0..edits {
+            data[edit * 1000] = b'#';
+        }
+        data
+    }
+
+    #[test]
+    fn test_unchanged_chunks_are_stored_once() {
+        let mut store = ChunkStore::new(256).unwrap();
+
+        let first = store.put("monday", &snapshot(0)).unwrap();
+        let chunks = store.chunk_count();
+        store.put("tuesday", &snapshot(2)).unwrap();
+        store.put("wednesday", &snapshot(2)).unwrap();
+
+        assert_eq!(store.chunk_count(), chunks + 2);
+        assert_eq!(first, ChunkTree::new(&snapshot(0), 256).unwrap().root());
+        assert_eq!(
+            store.root("tuesday").unwrap(),
+            store.root("wednesday").unwrap()
+        );
+        assert_eq!(store.get("tuesday").unwrap(), snapshot(2));
+        assert_eq!(
+            store.files().collect::<Vec<_>>(),
+            ["monday", "tuesday", "wednesday"]
+        );
+
+        let (chunk, proof) = store.chunk("monday", 3).unwrap();
+        assert!(proof
+            .verify(&ChunkTree::leaf_hash(3, chunk), &first)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_garbage_collection_keeps_referenced_chunks() {
+        let mut store = ChunkStore::new(256).unwrap();
+        store.put("monday", &snapshot(0)).unwrap();
+        store.put("tuesday", &snapshot(3)).unwrap();
+        store.put("empty", b"").unwrap();
+
+        assert_eq!(store.collect_garbage(), 0);
+        assert!(store.remove("monday"));
+        assert!(!store.remove("monday"));
+        assert_eq!(store.collect_garbage(), 3);
+
+        assert_eq!(store.get("tuesday").unwrap(), snapshot(3));
+        assert_eq!(store.get("empty").unwrap(), b"");
+        assert!(store.get("monday").is_err());
+
+        store.put("tuesday", b"overwritten").unwrap();
+        store.collect_garbage();
+        assert_eq!(store.chunk_count(), 2);
+        assert_eq!(store.stored_bytes(), 11);
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashSet;
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// A value blinded by a salt, committed as the leaf `H(salt || value)`.
+///
+/// The leaf reveals nothing about the value as long as the salt is secret
+/// and uniformly random, so it must come from a cryptographic RNG and never
+/// be reused across values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SealedValue {
+    pub salt: Hash,
+    pub value: Vec<u8>,
+}
+
+impl SealedValue {
+    pub fn new(salt: Hash, value: impl AsRef<[u8]>) -> Self {
+        Self {
+            salt,
+            value: value.as_ref().to_vec(),
+        }
+    }
+
+    /// The committed leaf, `H(salt || value)`.
+    pub fn leaf(&self) -> Hash {
+        let mut bytes = self.salt.to_vec();
+        bytes.extend_from_slice(&self.value);
+        MerkleTree::hash(&bytes)
+    }
+
+    /// Checks the reveal phase: the salt and value open the `committed`
+    /// leaf, and `proof` leads from that leaf to `root`.
+    pub fn verify(&self, committed: &Hash, proof: &Proof, root: &Hash) -> Result<(), Error> {
+        if self.leaf() != *committed {
+            return Err(Error::InvalidInput(
+                "the salt and value do not open the committed leaf".to_string(),
+            ));
+        }
+        proof.verify(committed, root)
+    }
+}
+
+/// A tree over blinded values for commit-reveal protocols such as sealed
+/// bids or lotteries.
+///
+/// In the commit phase only the root, and if needed the leaves, are
+/// published. In the reveal phase each participant is handed its salt,
+/// value and proof, checked with `SealedValue::verify` against the
+/// leaf and root published before.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{SealedTree, SealedValue};
+///
+/// // The salts come from a cryptographic RNG in practice.
+/// let bids = vec![
+///     SealedValue::new([1; 32], "Bilbo bids 12 silver pennies"),
+///     SealedValue::new([2; 32], "Lobelia bids 15 silver pennies"),
+/// ];
+/// let tree = SealedTree::commit(bids).unwrap();
+/// let root = tree.root();
+/// let committed = tree.leaf(1).unwrap();
+///
+/// let (bid, proof) = tree.reveal(1).unwrap();
+/// assert!(bid.verify(&committed, &proof, &root).is_ok());
+/// ```
+#[derive(Debug, Clone)]
+pub struct SealedTree {
+    values: Vec<SealedValue>,
+    tree: MerkleTree,
+}
+
+impl SealedTree {
+    /// Commits to the values, failing if there are none or two share a salt.
+    pub fn commit(values: Vec<SealedValue>) -> Result<Self, Error> {
+        let mut salts = HashSet::new();
+        if let Some(index) = values.iter().position(|value| !salts.insert(value.salt)) {
+            return Err(Error::InvalidInput(format!(
+                "the value {} reuses the salt of another",
+                index
+            )));
+        }
+
+        let leaves = values.iter().map(SealedValue::leaf).collect();
+        let tree = MerkleTree::from_leaves(leaves).ok_or(Error::EmptyTree)?;
+        Ok(Self { values, tree })
+    }
+
+    /// The commitment to publish in the commit phase.
+    pub fn root(&self) -> Hash {
+        self.tree.root().expect("A tree has a root.")
+    }
+
+    /// The committed leaf of the value at `index`.
+    pub fn leaf(&self, index: usize) -> Option<Hash> {
+        self.tree.node(0, index)
+    }
+
+    pub fn tree(&self) -> &MerkleTree {
+        &self.tree
+    }
+
+    /// The opening of the value at `index` for the reveal phase.
+    pub fn reveal(&self, index: usize) -> Result<(SealedValue, Proof), Error> {
+        let proof = self.tree.proof(index)?;
+        Ok((self.values[index].clone(), proof))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn bids(count: u8) -> Vec<SealedValue> {
+        (0..count)
+            .map(|bidder| SealedValue::new([bidder; 32], format!("bid of {} pennies", bidder)))
+            .collect()
+    }
+
+    #[test]
+    fn test_reveals_open_the_commitment() {
+        let tree = SealedTree::commit(bids(7)).unwrap();
+        let root = tree.root();
+
+        for index in 0..7 {
+            let committed = tree.leaf(index).unwrap();
+            let (bid, proof) = tree.reveal(index).unwrap();
+            assert_eq!(bid.verify(&committed, &proof, &root), Ok(()));
+
+            let mut raised = bid.clone();
+            raised.value = b"bid of 1000 pennies".to_vec();
+            assert!(raised.verify(&committed, &proof, &root).is_err());
+
+            let mut resalted = bid;
+            resalted.salt[0] ^= 1;
+            assert!(resalted.verify(&committed, &proof, &root).is_err());
+        }
+        assert!(tree.reveal(7).is_err());
+    }
+
+    #[test]
+    fn test_leaves_hide_equal_values() {
+        let tree = SealedTree::commit(vec![
+            SealedValue::new([1; 32], "heads"),
+            SealedValue::new([2; 32], "heads"),
+        ])
+        .unwrap();
+
+        assert_ne!(tree.leaf(0), tree.leaf(1));
+        assert_ne!(tree.leaf(0), Some(MerkleTree::hash(b"heads")));
+    }
+
+    #[test]
+    fn test_reused_salts_are_rejected() {
+        let mut values = bids(3);
+        values[2].salt = values[0].salt;
+
+        assert!(SealedTree::commit(values).is_err());
+        assert!(matches!(
+            SealedTree::commit(Vec::new()),
+            Err(Error::EmptyTree)
+        ));
+    }
+}
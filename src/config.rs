@@ -0,0 +1,111 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::error::Error;
+use crate::hashing::MerkleHasher;
+use crate::limits::Limits;
+use crate::merkle_tree::{GenericMerkleTree, Hash};
+
+/// How a tree defends against the duplicate leaf root mutation (CVE-2012-2459).
+///
+/// Odd levels duplicate their last node, so the leaves `[a, b, c]` and
+/// `[a, b, c, c]` lead to the same root. Without a guard, a root does not
+/// identify a single leaf set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MutationGuard {
+    /// The root is the plain tree root, as in previous versions.
+    #[default]
+    None,
+    /// Refuses leaf sets where a level ends with two identical nodes, the
+    /// shape a duplicated tail produces, as Bitcoin rejects mutated blocks.
+    RejectDuplicateTrailingPairs,
+    /// The root is `H(leaf_count ‖ tree root)`, so leaf sets of different
+    /// sizes never share a root. Verifiers need the leaf count to check proofs.
+    CommitLeafCount,
+}
+
+impl MutationGuard {
+    pub(crate) fn check_levels(&self, levels: &[Vec<Hash>]) -> Result<(), Error> {
+        if *self != MutationGuard::RejectDuplicateTrailingPairs {
+            return Ok(());
+        }
+
+        for (level, nodes) in levels.iter().enumerate() {
+            if nodes.len() >= 2
+                && nodes.len() % 2 == 0
+                && nodes[nodes.len() - 1] == nodes[nodes.len() - 2]
+            {
+                return Err(Error::InvalidInput(format!(
+                    "level {} ends with a duplicated pair, the leaves are ambiguous",
+                    level
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn commit_root<H: MerkleHasher>(&self, root: Hash, leaf_count: usize) -> Hash {
+        match self {
+            MutationGuard::CommitLeafCount => {
+                GenericMerkleTree::<H>::commit_leaf_count(&root, leaf_count)
+            }
+            _ => root,
+        }
+    }
+}
+
+/// What a tree does with items whose leaves are identical.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// Duplicates are separate leaves, as in previous versions.
+    #[default]
+    Allow,
+    /// Only the first occurrence of each leaf is kept.
+    Deduplicate,
+    /// Duplicates are an error listing the indices of the colliding items.
+    Reject,
+}
+
+impl DuplicatePolicy {
+    pub(crate) fn apply(&self, leaves: Vec<Hash>) -> Result<Vec<Hash>, Error> {
+        match self {
+            DuplicatePolicy::Allow => Ok(leaves),
+            DuplicatePolicy::Deduplicate => {
+                let mut seen = HashSet::with_capacity(leaves.len());
+                Ok(leaves
+                    .into_iter()
+                    .filter(|leaf| seen.insert(*leaf))
+                    .collect())
+            }
+            DuplicatePolicy::Reject => {
+                let mut occurrences: HashMap<Hash, Vec<usize>> =
+                    HashMap::with_capacity(leaves.len());
+                for (index, leaf) in leaves.iter().enumerate() {
+                    occurrences.entry(*leaf).or_default().push(index);
+                }
+
+                let mut collisions: Vec<Vec<usize>> = occurrences
+                    .into_values()
+                    .filter(|indices| indices.len() > 1)
+                    .collect();
+                if collisions.is_empty() {
+                    return Ok(leaves);
+                }
+
+                collisions.sort();
+                Err(Error::DuplicateLeaves(collisions))
+            }
+        }
+    }
+}
+
+/// The options a tree was built with, kept so later insertions follow them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct TreeConfig {
+    pub(crate) mutation_guard: MutationGuard,
+    pub(crate) limits: Limits,
+    pub(crate) duplicates: DuplicatePolicy,
+    /// The sum of the sizes of the items added so far, bounded by
+    /// `limits.max_total_size`. Leaves added as hashes count as empty items.
+    pub(crate) total_size: usize,
+}
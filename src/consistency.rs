@@ -0,0 +1,150 @@
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// A proof that a tree of `new_size` leaves extends a tree of `old_size`
+/// leaves, i.e. the first `old_size` leaves of both are the same.
+///
+/// It is the path of the last old leaf in the new tree. On that path, the
+/// left siblings cover every other old leaf, and they are complete subtrees,
+/// so they are the same nodes in both trees. Folding the path with only
+/// those siblings leads to the old root, folding it whole to the new one.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::MerkleTree;
+///
+/// let old = MerkleTree::build(&["Isildur", "Valandil"]).unwrap();
+/// let new = MerkleTree::build(&["Isildur", "Valandil", "Eldacar", "Arantar"]).unwrap();
+///
+/// let proof = new.consistency_proof(old.leaf_count()).unwrap();
+/// assert!(proof.verify(&old.root().unwrap(), &new.root().unwrap()).is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsistencyProof {
+    pub old_size: usize,
+    pub new_size: usize,
+    /// The last leaf of the old tree.
+    pub leaf: Hash,
+    /// The proof of inclusion of that leaf in the new tree.
+    pub siblings: Vec<Hash>,
+}
+
+impl ConsistencyProof {
+    /// Checks the proof against the roots of both trees.
+    pub fn verify(&self, old_root: &Hash, new_root: &Hash) -> Result<(), Error> {
+        if self.old_size == 0 || self.old_size > self.new_size {
+            return Err(Error::InvalidInput(format!(
+                "a tree of {} leaves can't extend one of {}",
+                self.new_size, self.old_size
+            )));
+        }
+
+        let path = Proof {
+            leaf_index: self.old_size - 1,
+            leaf_count: self.new_size,
+            siblings: self.siblings.clone(),
+        };
+        path.verify(&self.leaf, new_root)?;
+
+        let mut node = self.leaf;
+        let mut index = self.old_size - 1;
+
+        for sibling in &self.siblings[..Proof::expected_length(self.old_size)] {
+            // The node is the last of its level in the old tree: it has a
+            // sibling on its left or is paired with itself.
+            let sibling = if index % 2 == 1 { *sibling } else { node };
+            node = MerkleTree::merkle_parent(&[node, sibling]);
+            index /= 2;
+        }
+
+        if node != *old_root {
+            return Err(Error::RootMismatch);
+        }
+
+        Ok(())
+    }
+}
+
+impl MerkleTree {
+    /// Proves this tree extends its prefix of `old_size` leaves.
+    /// The proof is checked against the roots without the leaf count commitment.
+    pub fn consistency_proof(&self, old_size: usize) -> Result<ConsistencyProof, Error> {
+        if old_size == 0 || old_size > self.leaf_count() {
+            return Err(Error::InvalidInput(format!(
+                "a tree of {} leaves has no prefix of {}",
+                self.leaf_count(),
+                old_size
+            )));
+        }
+
+        Ok(ConsistencyProof {
+            old_size,
+            new_size: self.leaf_count(),
+            leaf: self
+                .node(0, old_size - 1)
+                .expect("The leaf is in the tree."),
+            siblings: self.proof(old_size - 1)?.siblings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn items(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|item| format!("king of Gondor {}", item))
+            .collect()
+    }
+
+    fn root(count: usize) -> Hash {
+        MerkleTree::build(&items(count)).unwrap().root().unwrap()
+    }
+
+    #[test]
+    fn test_every_prefix_is_consistent() {
+        for new_size in 1..=33 {
+            let tree = MerkleTree::build(&items(new_size)).unwrap();
+
+            for old_size in 1..=new_size {
+                let proof = tree.consistency_proof(old_size).unwrap();
+                assert_eq!(
+                    proof.verify(&root(old_size), &tree.root().unwrap()),
+                    Ok(()),
+                    "{} -> {}",
+                    old_size,
+                    new_size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_rewritten_history_is_rejected() {
+        let mut rewritten = items(12);
+        rewritten[3] = "the steward".to_string();
+        let tree = MerkleTree::build(&rewritten).unwrap();
+
+        let proof = tree.consistency_proof(7).unwrap();
+
+        assert_eq!(
+            proof.verify(&root(7), &tree.root().unwrap()),
+            Err(Error::RootMismatch)
+        );
+    }
+
+    #[test]
+    fn test_invalid_sizes_are_rejected() {
+        let tree = MerkleTree::build(&items(5)).unwrap();
+
+        assert!(tree.consistency_proof(0).is_err());
+        assert!(tree.consistency_proof(6).is_err());
+
+        let mut proof = tree.consistency_proof(3).unwrap();
+        proof.new_size = 2;
+        assert!(proof.verify(&root(3), &root(5)).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+use std::io::Write;
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+
+/// Generates a Rust source file of `const` items holding the root of a
+/// tree, optionally its leaves, and the proofs of selected leaves, for
+/// firmware and bootloaders that embed verification data at build time
+/// and have no room for a parser.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{ConstExport, MerkleTree};
+///
+/// let tree = MerkleTree::build(&["bootloader", "kernel", "initrd"]).unwrap();
+///
+/// let source = ConstExport::new(&tree).prefix("IMAGES").proof(1).render().unwrap();
+///
+/// assert!(source.contains("pub const IMAGES_ROOT: [u8; 32] = ["));
+/// assert!(source.contains("pub const IMAGES_PROOF_1: [[u8; 32]; 2] = ["));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConstExport<'a> {
+    tree: &'a MerkleTree,
+    prefix: String,
+    leaves: bool,
+    proofs: Vec<usize>,
+}
+
+impl<'a> ConstExport<'a> {
+    pub fn new(tree: &'a MerkleTree) -> Self {
+        Self {
+            tree,
+            prefix: "MERKLE".to_string(),
+            leaves: false,
+            proofs: Vec::new(),
+        }
+    }
+
+    /// Sets the prefix of the generated names, `MERKLE` by default.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Also exports every leaf hash.
+    pub fn with_leaves(mut self) -> Self {
+        self.leaves = true;
+        self
+    }
+
+    /// Also exports the proof of the leaf at `index`.
+    pub fn proof(mut self, index: usize) -> Self {
+        self.proofs.push(index);
+        self
+    }
+
+    /// Returns the source of the file.
+    pub fn render(&self) -> Result<String, Error> {
+        let valid_prefix = self
+            .prefix
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_uppercase())
+            && self
+                .prefix
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+        if !valid_prefix {
+            return Err(Error::InvalidInput(format!(
+                "{} is not an upper case Rust identifier",
+                self.prefix
+            )));
+        }
+
+        let prefix = &self.prefix;
+        let tree = self.tree;
+        let mut source = String::from("// Generated by merkle-tree, do not edit.\n\n");
+
+        source.push_str(&format!(
+            "pub const {}_LEAF_COUNT: usize = {};\n\n",
+            prefix,
+            tree.leaf_count()
+        ));
+        source.push_str(&format!(
+            "pub const {}_ROOT: [u8; 32] = {};\n",
+            prefix,
+            hash_literal(&tree.root().expect("A tree has a root."))
+        ));
+
+        if self.leaves {
+            source.push_str(&format!(
+                "\npub const {}_LEAVES: [[u8; 32]; {}] = {};\n",
+                prefix,
+                tree.leaf_count(),
+                hashes_literal(tree.leaves())
+            ));
+        }
+
+        for &index in &self.proofs {
+            let proof = tree.proof(index)?;
+            source.push_str(&format!(
+                "\npub const {}_PROOF_{}_INDEX: usize = {};\n",
+                prefix, index, index
+            ));
+            source.push_str(&format!(
+                "pub const {}_PROOF_{}: [[u8; 32]; {}] = {};\n",
+                prefix,
+                index,
+                proof.siblings.len(),
+                hashes_literal(&proof.siblings)
+            ));
+        }
+
+        Ok(source)
+    }
+
+    /// Writes the source of the file, e.g. to `OUT_DIR` from a build script.
+    pub fn write_to<W: Write>(&self, mut out: W) -> Result<(), Error> {
+        out.write_all(self.render()?.as_bytes())
+            .map_err(|error| Error::InvalidInput(error.to_string()))
+    }
+}
+
+fn hash_literal(hash: &Hash) -> String {
+    let bytes: Vec<String> = hash.iter().map(|byte| format!("0x{:02x}", byte)).collect();
+    format!("[{}]", bytes.join(", "))
+}
+
+fn hashes_literal(hashes: &[Hash]) -> String {
+    let hashes: Vec<String> = hashes
+        .iter()
+        .map(|hash| format!("    {},\n", hash_literal(hash)))
+        .collect();
+    format!("[\n{}]", hashes.concat())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /// Reads the hashes of a generated array back.
+    fn parse_hashes(source: &str, name: &str) -> Vec<Hash> {
+        let start = source.find(&format!("pub const {}:", name)).unwrap();
+        let body = &source[start..];
+        let body = &body[body.find("= ").unwrap() + 2..body.find(";\n").unwrap()];
+
+        let bytes: Vec<u8> = body
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter_map(|token| token.strip_prefix("0x"))
+            .map(|byte| u8::from_str_radix(byte, 16).unwrap())
+            .collect();
+        bytes
+            .chunks(32)
+            .map(|hash| hash.try_into().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_exported_constants_hold_the_tree() {
+        let tree =
+            MerkleTree::build(&["Valinor", "Tol Eressea", "Numenor", "Middle-earth", "Arda"])
+                .unwrap();
+        let source = ConstExport::new(&tree)
+            .with_leaves()
+            .proof(0)
+            .proof(4)
+            .render()
+            .unwrap();
+
+        assert_eq!(
+            parse_hashes(&source, "MERKLE_ROOT"),
+            vec![tree.root().unwrap()]
+        );
+        assert_eq!(parse_hashes(&source, "MERKLE_LEAVES"), tree.leaves());
+        assert!(source.contains("pub const MERKLE_LEAF_COUNT: usize = 5;"));
+        for index in [0, 4] {
+            let siblings = parse_hashes(&source, &format!("MERKLE_PROOF_{}", index));
+            assert_eq!(siblings, tree.proof(index).unwrap().siblings);
+            assert!(source.contains(&format!(
+                "pub const MERKLE_PROOF_{}_INDEX: usize = {};",
+                index, index
+            )));
+        }
+    }
+
+    #[test]
+    fn test_invalid_exports_are_rejected() {
+        let tree = MerkleTree::build(&["Valinor"]).unwrap();
+
+        assert!(ConstExport::new(&tree).proof(1).render().is_err());
+        assert!(ConstExport::new(&tree)
+            .prefix("boot image")
+            .render()
+            .is_err());
+        assert!(ConstExport::new(&tree).prefix("").render().is_err());
+
+        let mut out = Vec::new();
+        ConstExport::new(&tree)
+            .prefix("BOOT_2")
+            .write_to(&mut out)
+            .unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("BOOT_2_ROOT"));
+    }
+}
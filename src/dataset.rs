@@ -0,0 +1,223 @@
+#[cfg(feature = "csv")]
+use std::io::Read;
+
+use crate::error::Error;
+use crate::leaf::MerkleLeaf;
+use crate::merkle_tree::{Hash, MerkleTree};
+
+/// The leaf of a row of a dataset: the `MerkleLeaf` encoding of its
+/// selected values, in the order of the selection, as a list of byte
+/// strings. The same row has the same leaf whether it was read from CSV
+/// or Parquet, as every value is committed as its text.
+pub fn row_leaf<T: AsRef<[u8]>>(values: &[T]) -> Hash {
+    let values: Vec<&[u8]> = values.iter().map(AsRef::as_ref).collect();
+    MerkleTree::hash(&values.leaf_bytes())
+}
+
+/// The positions of the selected columns among `names`.
+fn select_columns<'a>(
+    names: impl Iterator<Item = &'a str> + Clone,
+    columns: &[&str],
+) -> Result<Vec<usize>, Error> {
+    if columns.is_empty() {
+        return Err(Error::InvalidInput("no column is selected".to_string()));
+    }
+    columns
+        .iter()
+        .map(|column| {
+            names
+                .clone()
+                .position(|name| name == *column)
+                .ok_or_else(|| Error::InvalidInput(format!("the dataset has no column {}", column)))
+        })
+        .collect()
+}
+
+impl MerkleTree {
+    /// Builds the tree over the rows of a CSV file with a header, one leaf
+    /// per row as `row_leaf` of the selected `columns`. Rows are streamed
+    /// and `progress` is called with the number of rows read so far.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::{row_leaf, MerkleTree};
+    ///
+    /// let csv = "id,name,realm\n1,Elrond,Rivendell\n2,Galadriel,Lothlórien\n";
+    ///
+    /// let tree = MerkleTree::from_csv(csv.as_bytes(), &["realm", "name"], |_| {}).unwrap();
+    ///
+    /// let leaf = row_leaf(&["Lothlórien", "Galadriel"]);
+    /// assert!(tree.proof(1).unwrap().verify(&leaf, &tree.root().unwrap()).is_ok());
+    /// ```
+    #[cfg(feature = "csv")]
+    pub fn from_csv<R: Read>(
+        reader: R,
+        columns: &[&str],
+        mut progress: impl FnMut(usize),
+    ) -> Result<Self, Error> {
+        let csv_error = |error: csv::Error| Error::InvalidInput(error.to_string());
+        let mut reader = csv::Reader::from_reader(reader);
+        let selected = select_columns(reader.headers().map_err(csv_error)?.iter(), columns)?;
+
+        let mut leaves = Vec::new();
+        for record in reader.byte_records() {
+            let record = record.map_err(csv_error)?;
+            let values = selected
+                .iter()
+                .map(|position| record.get(*position).unwrap_or_default())
+                .collect::<Vec<_>>();
+            leaves.push(row_leaf(&values));
+            progress(leaves.len());
+        }
+
+        Self::from_leaves(leaves).ok_or(Error::EmptyTree)
+    }
+
+    /// Builds the tree over the rows of a Parquet file, one leaf per row as
+    /// `row_leaf` of the text of the selected `columns`. Strings and binary
+    /// values are committed as their bytes, nulls as empty values and other
+    /// values as their display. Rows are streamed and `progress` is called
+    /// with the number of rows read so far.
+    #[cfg(feature = "parquet")]
+    pub fn from_parquet<R: parquet::file::reader::ChunkReader + 'static>(
+        reader: R,
+        columns: &[&str],
+        mut progress: impl FnMut(usize),
+    ) -> Result<Self, Error> {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+        use parquet::record::Field;
+
+        let parquet_error =
+            |error: parquet::errors::ParquetError| Error::InvalidInput(error.to_string());
+        let reader = SerializedFileReader::new(reader).map_err(parquet_error)?;
+        let schema = reader.metadata().file_metadata().schema_descr();
+        let names = schema
+            .root_schema()
+            .get_fields()
+            .iter()
+            .map(|field| field.name());
+        let selected = select_columns(names, columns)?;
+
+        let mut leaves = Vec::new();
+        for row in reader.get_row_iter(None).map_err(parquet_error)? {
+            let row = row.map_err(parquet_error)?;
+            let fields: Vec<&Field> = row.get_column_iter().map(|(_, field)| field).collect();
+            let values = selected
+                .iter()
+                .map(|position| match fields.get(*position) {
+                    None | Some(Field::Null) => Vec::new(),
+                    Some(Field::Str(text)) => text.as_bytes().to_vec(),
+                    Some(Field::Bytes(bytes)) => bytes.data().to_vec(),
+                    Some(field) => field.to_string().into_bytes(),
+                })
+                .collect::<Vec<_>>();
+            leaves.push(row_leaf(&values));
+            progress(leaves.len());
+        }
+
+        Self::from_leaves(leaves).ok_or(Error::EmptyTree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const CSV: &str = "name,house,age\n\
+                       Frodo,Baggins,50\n\
+                       Samwise,Gamgee,38\n\
+                       Meriadoc,Brandybuck,36\n\
+                       Peregrin,Took,28\n";
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn test_csv_rows_are_committed_by_column() {
+        let mut reported = Vec::new();
+        let tree = MerkleTree::from_csv(CSV.as_bytes(), &["house", "age"], |rows| {
+            reported.push(rows)
+        })
+        .unwrap();
+
+        assert_eq!(reported, vec![1, 2, 3, 4]);
+        let leaf = row_leaf(&["Gamgee", "38"]);
+        assert_eq!(
+            tree.proof(1).unwrap().verify(&leaf, &tree.root().unwrap()),
+            Ok(())
+        );
+
+        assert!(MerkleTree::from_csv(CSV.as_bytes(), &["ring"], |_| {}).is_err());
+        assert!(MerkleTree::from_csv(CSV.as_bytes(), &[], |_| {}).is_err());
+        assert_eq!(
+            MerkleTree::from_csv("name\n".as_bytes(), &["name"], |_| {}).err(),
+            Some(Error::EmptyTree)
+        );
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_parquet_rows_match_csv_rows() {
+        use std::sync::Arc;
+
+        use parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+        use parquet::file::properties::WriterProperties;
+        use parquet::file::writer::SerializedFileWriter;
+        use parquet::schema::parser::parse_message_type;
+
+        let schema = parse_message_type(
+            "message hobbits { required binary name (UTF8); required binary house (UTF8); required int64 age; }",
+        )
+        .unwrap();
+        let path = std::env::temp_dir().join(format!(
+            "merkle-tree-hobbits-{}.parquet",
+            std::process::id()
+        ));
+        let file = std::fs::File::create(&path).unwrap();
+        let mut writer = SerializedFileWriter::new(
+            file,
+            Arc::new(schema),
+            Arc::new(WriterProperties::builder().build()),
+        )
+        .unwrap();
+        let rows: Vec<Vec<&str>> = CSV
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').collect())
+            .collect();
+        let mut group = writer.next_row_group().unwrap();
+        for column in 0..2 {
+            let values: Vec<ByteArray> = rows
+                .iter()
+                .map(|row| ByteArray::from(row[column]))
+                .collect();
+            let mut writer = group.next_column().unwrap().unwrap();
+            writer
+                .typed::<ByteArrayType>()
+                .write_batch(&values, None, None)
+                .unwrap();
+            writer.close().unwrap();
+        }
+        let ages: Vec<i64> = rows.iter().map(|row| row[2].parse().unwrap()).collect();
+        let mut column = group.next_column().unwrap().unwrap();
+        column
+            .typed::<Int64Type>()
+            .write_batch(&ages, None, None)
+            .unwrap();
+        column.close().unwrap();
+        group.close().unwrap();
+        writer.close().unwrap();
+
+        let mut reported = 0;
+        let tree = MerkleTree::from_parquet(
+            std::fs::File::open(&path).unwrap(),
+            &["house", "age"],
+            |rows| reported = rows,
+        )
+        .unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(reported, 4);
+        let leaves = rows.iter().map(|row| row_leaf(&[row[1], row[2]])).collect();
+        assert_eq!(tree.root(), MerkleTree::from_leaves(leaves).unwrap().root());
+    }
+}
@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::error::Error;
+use crate::limits::Limits;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+use crate::sync::Transport;
+
+/// What the sender of a new file version publishes for receivers to sync against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeltaSignature {
+    pub root: Hash,
+    pub chunk_size: usize,
+    pub length: usize,
+}
+
+/// A chunk sent to a receiver, with its proof against the sender's root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkTransfer {
+    pub index: usize,
+    pub data: Vec<u8>,
+    pub proof: Proof,
+}
+
+/// A tree over the fixed size chunks of a file, for rsync-like delta synchronization.
+///
+/// The receiver compares its tree with the sender's level by level through a
+/// `Transport`, finds the chunk ranges that differ, and the sender transfers
+/// only those chunks, each with an inclusion proof checked on receipt.
+///
+/// The tree hashes sorted pairs, which don't authenticate positions, so each
+/// leaf commits to the index of its chunk as well as to its data.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::ChunkTree;
+///
+/// let old = b"Three Rings for the Elven-kings under the sky".to_vec();
+/// let new = b"Three Rings for the Elven-kings under the SKY!".to_vec();
+///
+/// let sender = ChunkTree::new(&new, 8).unwrap();
+/// let receiver = ChunkTree::new(&old, 8).unwrap();
+///
+/// let ranges = receiver.differing_ranges(&mut sender.tree()).unwrap();
+/// let transfers = sender.transfer(&new, &ranges).unwrap();
+///
+/// let synced = receiver.apply(&old, &sender.signature(), &transfers).unwrap();
+/// assert_eq!(synced, new);
+/// assert_eq!(transfers.len(), 1);
+/// ```
+pub struct ChunkTree {
+    tree: MerkleTree,
+    chunk_size: usize,
+    length: usize,
+    limits: Limits,
+}
+
+impl ChunkTree {
+    /// Builds the tree over the chunks of `data`. An empty file has a single empty chunk.
+    pub fn new(data: &[u8], chunk_size: usize) -> Result<Self, Error> {
+        if chunk_size == 0 {
+            return Err(Error::InvalidInput("chunks can't be empty".to_string()));
+        }
+
+        let leaves = Self::chunks(data, chunk_size)
+            .enumerate()
+            .map(|(index, chunk)| Self::leaf_hash(index, chunk))
+            .collect();
+
+        Ok(Self {
+            tree: MerkleTree::from_leaves(leaves).ok_or(Error::EmptyTree)?,
+            chunk_size,
+            length: data.len(),
+            limits: Limits::default(),
+        })
+    }
+
+    /// Sets the bounds the signatures of senders must respect in `apply`.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// The leaf of the chunk at `index`: the hash of the index, as 8 bytes
+    /// big-endian, followed by the chunk.
+    pub fn leaf_hash(index: usize, chunk: &[u8]) -> Hash {
+        MerkleTree::hash(&[&(index as u64).to_be_bytes(), chunk].concat())
+    }
+
+    fn chunks(data: &[u8], chunk_size: usize) -> impl Iterator<Item = &[u8]> {
+        let empty: &[u8] = &[];
+        data.chunks(chunk_size)
+            .chain(data.is_empty().then_some(empty))
+    }
+
+    fn chunk_count(length: usize, chunk_size: usize) -> usize {
+        length.div_ceil(chunk_size).max(1)
+    }
+
+    pub fn root(&self) -> Hash {
+        self.tree.root().expect("A chunk tree has a root.")
+    }
+
+    /// The tree over the chunks, which serves the receiver's requests through `Transport`.
+    pub fn tree(&self) -> &MerkleTree {
+        &self.tree
+    }
+
+    pub fn signature(&self) -> DeltaSignature {
+        DeltaSignature {
+            root: self.root(),
+            chunk_size: self.chunk_size,
+            length: self.length,
+        }
+    }
+
+    /// The ranges of chunk indices that differ from the sender's tree, on the receiver's side.
+    pub fn differing_ranges<T: Transport>(
+        &self,
+        sender: &mut T,
+    ) -> Result<Vec<Range<usize>>, Error> {
+        self.tree.reconcile_ranges(sender)
+    }
+
+    /// The chunks in `ranges` of the data this tree was built over, on the sender's side.
+    /// Chunks past the end of the file, which only the receiver has, are skipped.
+    pub fn transfer(
+        &self,
+        data: &[u8],
+        ranges: &[Range<usize>],
+    ) -> Result<Vec<ChunkTransfer>, Error> {
+        let chunks: Vec<&[u8]> = Self::chunks(data, self.chunk_size).collect();
+        if chunks.len() != self.tree.leaf_count() {
+            return Err(Error::InvalidInput(
+                "the data is not the one the tree was built over".to_string(),
+            ));
+        }
+
+        ranges
+            .iter()
+            .flat_map(|range| range.clone())
+            .filter(|index| *index < chunks.len())
+            .map(|index| {
+                Ok(ChunkTransfer {
+                    index,
+                    data: chunks[index].to_vec(),
+                    proof: self.tree.proof(index)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Rebuilds the sender's version from the receiver's data and the transferred chunks.
+    ///
+    /// Every transferred chunk must prove its inclusion under the sender's
+    /// root, the other chunks are copied from the local version, and the
+    /// result is checked against the root as a whole.
+    pub fn apply(
+        &self,
+        data: &[u8],
+        signature: &DeltaSignature,
+        transfers: &[ChunkTransfer],
+    ) -> Result<Vec<u8>, Error> {
+        if signature.chunk_size != self.chunk_size {
+            return Err(Error::InvalidInput(format!(
+                "the sender uses chunks of {} bytes, not {}",
+                signature.chunk_size, self.chunk_size
+            )));
+        }
+
+        let local: Vec<&[u8]> = Self::chunks(data, self.chunk_size).collect();
+        let chunk_count = Self::chunk_count(signature.length, signature.chunk_size);
+        // Checked before allocating the file, which a forged length could make huge.
+        self.limits.check_leaf_count(chunk_count)?;
+        self.limits.check_total_size(signature.length)?;
+        if chunk_count > local.len().saturating_add(transfers.len()) {
+            return Err(Error::InvalidInput(format!(
+                "the {} chunks of the sender can't be rebuilt from {} local and {} transferred chunks",
+                chunk_count,
+                local.len(),
+                transfers.len()
+            )));
+        }
+
+        let mut received: HashMap<usize, &[u8]> = HashMap::new();
+
+        for transfer in transfers {
+            if transfer.proof.leaf_index != transfer.index
+                || transfer.proof.leaf_count != chunk_count
+            {
+                return Err(Error::InvalidInput(format!(
+                    "the proof of chunk {} is not for its position",
+                    transfer.index
+                )));
+            }
+            transfer.proof.verify(
+                &Self::leaf_hash(transfer.index, &transfer.data),
+                &signature.root,
+            )?;
+            received.insert(transfer.index, &transfer.data);
+        }
+
+        let mut synced = Vec::with_capacity(signature.length);
+
+        for index in 0..chunk_count {
+            let chunk = received
+                .get(&index)
+                .copied()
+                .or_else(|| local.get(index).copied())
+                .ok_or_else(|| Error::InvalidInput(format!("the chunk {} is missing", index)))?;
+            synced.extend_from_slice(chunk);
+        }
+
+        if synced.len() != signature.length
+            || ChunkTree::new(&synced, self.chunk_size)?.root() != signature.root
+        {
+            return Err(Error::RootMismatch);
+        }
+
+        Ok(synced)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn text(words: usize) -> Vec<u8> {
+        (0..words)
+            .map(|word| format!("word{} ", word))
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    fn sync(old: &[u8], new: &[u8], chunk_size: usize) -> (Vec<u8>, usize) {
+        let sender = ChunkTree::new(new, chunk_size).unwrap();
+        let receiver = ChunkTree::new(old, chunk_size).unwrap();
+
+        let ranges = receiver.differing_ranges(&mut sender.tree()).unwrap();
+        let transfers = sender.transfer(new, &ranges).unwrap();
+
+        let synced = receiver
+            .apply(old, &sender.signature(), &transfers)
+            .unwrap();
+        (synced, transfers.len())
+    }
+
+    #[test]
+    fn test_only_changed_chunks_are_transferred() {
+        let old = text(1000);
+        let mut new = old.clone();
+        new[100] = b'X';
+        new[5000] = b'Y';
+
+        let (synced, transferred) = sync(&old, &new, 64);
+
+        assert_eq!(synced, new);
+        assert_eq!(transferred, 2);
+    }
+
+    #[test]
+    fn test_growing_and_shrinking_files() {
+        let old = text(300);
+        let mut longer = old.clone();
+        longer.extend_from_slice(b"and a few more words at the end");
+        let shorter = old[..1000].to_vec();
+
+        assert_eq!(sync(&old, &longer, 32).0, longer);
+        assert_eq!(sync(&old, &shorter, 32).0, shorter);
+        assert_eq!(sync(&old, &[], 32).0, Vec::<u8>::new());
+        assert_eq!(sync(&[], &old, 32).0, old);
+    }
+
+    #[test]
+    fn test_tampered_chunks_are_rejected() {
+        let old = text(200);
+        let mut new = old.clone();
+        new[10] = b'!';
+
+        let sender = ChunkTree::new(&new, 16).unwrap();
+        let receiver = ChunkTree::new(&old, 16).unwrap();
+        let ranges = receiver.differing_ranges(&mut sender.tree()).unwrap();
+
+        let mut transfers = sender.transfer(&new, &ranges).unwrap();
+        transfers[0].data[0] ^= 1;
+        assert!(receiver
+            .apply(&old, &sender.signature(), &transfers)
+            .is_err());
+
+        // Without the changed chunk the local copy does not match the root.
+        assert_eq!(
+            receiver.apply(&old, &sender.signature(), &[]),
+            Err(Error::RootMismatch)
+        );
+    }
+
+    #[test]
+    fn test_reordered_chunks_are_rejected() {
+        let old = b"AAAABBBBCCCCDDDD".to_vec();
+        let new = b"AAAABBBBCCCCEEEE".to_vec();
+        let sender = ChunkTree::new(&new, 4).unwrap();
+        let receiver = ChunkTree::new(&old, 4).unwrap();
+
+        // Chunks 0 and 1 swapped, data and proofs, but each keeping the other's index.
+        let mut transfers = sender.transfer(&new, &[0..1, 1..2]).unwrap();
+        transfers.swap(0, 1);
+        transfers[0].index = 0;
+        transfers[0].proof.leaf_index = 0;
+        transfers[1].index = 1;
+        transfers[1].proof.leaf_index = 1;
+
+        assert!(receiver
+            .apply(&old, &sender.signature(), &transfers)
+            .is_err());
+    }
+
+    #[test]
+    fn test_forged_lengths_are_rejected_before_allocating() {
+        let sender = ChunkTree::new(b"Mithril", 4).unwrap();
+        let receiver = ChunkTree::new(b"Mithril", 4).unwrap();
+        let forged = DeltaSignature {
+            length: usize::MAX,
+            ..sender.signature()
+        };
+
+        assert!(receiver.apply(b"Mithril", &forged, &[]).is_err());
+
+        let limited = ChunkTree::new(b"Mithril", 4).unwrap().limits(Limits {
+            max_total_size: 4,
+            ..Limits::default()
+        });
+        assert!(matches!(
+            limited.apply(b"Mithril", &sender.signature(), &[]),
+            Err(Error::LimitExceeded { .. })
+        ));
+    }
+}
@@ -0,0 +1,435 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::error::Error;
+use crate::ignore::IgnoreRules;
+use crate::manifest::{FileMetadata, ManifestEntry, PathOrdering, SnapshotManifest};
+
+/// How `SnapshotManifest::from_dir` walks and hashes a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirOptions {
+    concurrency: usize,
+    mode: bool,
+    mtime: bool,
+    symlinks: bool,
+    ignore: IgnoreRules,
+    ordering: PathOrdering,
+}
+
+impl Default for DirOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 8,
+            mode: false,
+            mtime: false,
+            symlinks: false,
+            ignore: IgnoreRules::new(),
+            ordering: PathOrdering::Bytewise,
+        }
+    }
+}
+
+impl DirOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of files read at once with the `rayon` feature, 8
+    /// by default. Files are read one at a time without it.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Binds the permission bits of every file into its leaf.
+    pub fn with_mode(mut self) -> Self {
+        self.mode = true;
+        self
+    }
+
+    /// Binds the modification time of every file into its leaf.
+    pub fn with_mtime(mut self) -> Self {
+        self.mtime = true;
+        self
+    }
+
+    /// Includes symbolic links as entries over their target, which are
+    /// skipped otherwise. Links are never followed.
+    pub fn with_symlinks(mut self) -> Self {
+        self.symlinks = true;
+        self
+    }
+
+    /// Sets the order of the entries, recorded in the manifest, byte-wise by default.
+    pub fn ordering(mut self, ordering: PathOrdering) -> Self {
+        self.ordering = ordering;
+        self
+    }
+
+    /// Leaves the files and directories matching a `gitignore`-style pattern out.
+    pub fn exclude(mut self, pattern: &str) -> Self {
+        self.ignore.add(pattern);
+        self
+    }
+
+    /// Includes back what matches a pattern excluded before.
+    pub fn include(mut self, pattern: &str) -> Self {
+        self.ignore.add(&format!("!{}", pattern));
+        self
+    }
+
+    /// Adds the patterns of a `.gitignore` file, after the ones set before.
+    pub fn ignore_rules(mut self, rules: &IgnoreRules) -> Self {
+        self.ignore.extend(rules);
+        self
+    }
+}
+
+/// A file found in the directory, by its path relative to the directory.
+struct DirFile {
+    path: String,
+    full_path: PathBuf,
+    symlink: bool,
+}
+
+fn io_error(path: &Path, error: std::io::Error) -> Error {
+    Error::InvalidInput(format!("{}: {}", path.display(), error))
+}
+
+/// Lists the regular files under `dir`, with `/` separated relative paths.
+fn walk(
+    dir: &Path,
+    prefix: &str,
+    options: &DirOptions,
+    files: &mut Vec<DirFile>,
+) -> Result<(), Error> {
+    for entry in fs::read_dir(dir).map_err(|error| io_error(dir, error))? {
+        let entry = entry.map_err(|error| io_error(dir, error))?;
+        let full_path = entry.path();
+        let name = entry.file_name().into_string().map_err(|name| {
+            Error::InvalidInput(format!("the path {:?} is not UTF-8", dir.join(name)))
+        })?;
+        let path = format!("{}{}", prefix, name);
+
+        let file_type = entry
+            .file_type()
+            .map_err(|error| io_error(&full_path, error))?;
+        if options.ignore.is_ignored(&path, file_type.is_dir()) {
+            continue;
+        }
+        if file_type.is_dir() {
+            walk(&full_path, &format!("{}/", path), options, files)?;
+        } else if file_type.is_file() || (file_type.is_symlink() && options.symlinks) {
+            files.push(DirFile {
+                path,
+                full_path,
+                symlink: file_type.is_symlink(),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn permission_bits(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o7777
+}
+
+/// Reports read-only files as `0o444` and others as `0o644` where there are no permission bits.
+#[cfg(not(unix))]
+fn permission_bits(metadata: &fs::Metadata) -> u32 {
+    if metadata.permissions().readonly() {
+        0o444
+    } else {
+        0o644
+    }
+}
+
+fn file_metadata(file: &DirFile, options: &DirOptions) -> Result<FileMetadata, Error> {
+    let error = |error| io_error(&file.full_path, error);
+    let metadata = fs::symlink_metadata(&file.full_path).map_err(error)?;
+
+    let mtime = match options.mtime {
+        false => None,
+        true => Some(
+            match metadata
+                .modified()
+                .map_err(error)?
+                .duration_since(UNIX_EPOCH)
+            {
+                Ok(after) => after.as_secs() as i64,
+                Err(before) => -(before.duration().as_secs_f64().ceil() as i64),
+            },
+        ),
+    };
+    let symlink_target = match file.symlink {
+        false => None,
+        true => {
+            let target = fs::read_link(&file.full_path).map_err(error)?;
+            Some(target.into_os_string().into_string().map_err(|target| {
+                Error::InvalidInput(format!("the link target {:?} is not UTF-8", target))
+            })?)
+        }
+    };
+
+    Ok(FileMetadata {
+        mode: options.mode.then(|| permission_bits(&metadata)),
+        mtime,
+        symlink_target,
+    })
+}
+
+fn hash_file(file: &DirFile, options: &DirOptions) -> Result<ManifestEntry, Error> {
+    let metadata = file_metadata(file, options)?;
+    if let Some(target) = &metadata.symlink_target {
+        return Ok(ManifestEntry::new(file.path.clone(), target.as_bytes()).with_metadata(metadata));
+    }
+
+    let error = |error| io_error(&file.full_path, error);
+    let mut reader = File::open(&file.full_path).map_err(error)?;
+
+    let mut hasher = hmac_sha256::Hash::new();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut length = 0;
+    loop {
+        let read = reader.read(&mut buffer).map_err(error)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        length += read as u64;
+    }
+
+    Ok(ManifestEntry {
+        path: file.path.clone(),
+        length,
+        hash: hasher.finalize(),
+        metadata,
+    })
+}
+
+#[cfg(feature = "rayon")]
+fn hash_files(files: &[DirFile], options: &DirOptions) -> Result<Vec<ManifestEntry>, Error> {
+    use rayon::prelude::*;
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.concurrency)
+        .build()
+        .map_err(|error| Error::InvalidInput(error.to_string()))?;
+    pool.install(|| {
+        files
+            .par_iter()
+            .map(|file| hash_file(file, options))
+            .collect()
+    })
+}
+
+#[cfg(not(feature = "rayon"))]
+fn hash_files(files: &[DirFile], options: &DirOptions) -> Result<Vec<ManifestEntry>, Error> {
+    files.iter().map(|file| hash_file(file, options)).collect()
+}
+
+impl SnapshotManifest {
+    /// Creates the manifest of the regular files under `dir`, by their
+    /// `/` separated paths relative to it. With the `rayon` feature the
+    /// files are hashed in parallel, the entries in path order regardless.
+    /// The options may bind metadata of the files into their leaves and
+    /// leave out what matches `gitignore`-style patterns.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use merkle_tree::{DirOptions, SnapshotManifest};
+    ///
+    /// let options = DirOptions::new().concurrency(32);
+    /// let manifest = SnapshotManifest::from_dir("target/release", 1, &options).unwrap();
+    ///
+    /// println!("{}", hex::encode(manifest.root()));
+    /// ```
+    pub fn from_dir(
+        dir: impl AsRef<Path>,
+        version: u64,
+        options: &DirOptions,
+    ) -> Result<Self, Error> {
+        if options.concurrency == 0 {
+            return Err(Error::InvalidInput(
+                "at least one file must be read at once".to_string(),
+            ));
+        }
+
+        let mut files = Vec::new();
+        walk(dir.as_ref(), "", options, &mut files)?;
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Self::with_ordering(version, hash_files(&files, options)?, options.ordering)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /// A directory under the system temporary directory, removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("merkle-tree-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            for (path, content) in files {
+                let path = dir.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, content).unwrap();
+            }
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const FILES: [(&str, &str); 4] = [
+        ("Shire/Hobbiton/Bag End", "Bilbo"),
+        ("Shire/Buckland", "Merry"),
+        ("Rivendell", "Elrond"),
+        ("Shire/Hobbiton/Gamgee", ""),
+    ];
+
+    #[test]
+    fn test_directories_hash_like_their_files() {
+        let dir = TempDir::new("directory-files", &FILES);
+
+        let manifest = SnapshotManifest::from_dir(&dir.0, 3, &DirOptions::new()).unwrap();
+
+        let entries = FILES
+            .iter()
+            .map(|(path, content)| ManifestEntry::new(*path, content.as_bytes()))
+            .collect();
+        let expected = SnapshotManifest::new(3, entries).unwrap();
+        assert_eq!(manifest.root(), expected.root());
+        assert_eq!(manifest.entries(), expected.entries());
+    }
+
+    #[test]
+    fn test_roots_do_not_depend_on_concurrency() {
+        let files: Vec<(String, String)> = (0..100)
+            .map(|file| (format!("Moria/hall {}", file), format!("mithril {}", file)))
+            .collect();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(path, content)| (path.as_str(), content.as_str()))
+            .collect();
+        let dir = TempDir::new("directory-concurrency", &files);
+
+        let roots: Vec<_> = [1, 3, 16]
+            .into_iter()
+            .map(|concurrency| {
+                let options = DirOptions::new().concurrency(concurrency);
+                SnapshotManifest::from_dir(&dir.0, 1, &options)
+                    .unwrap()
+                    .root()
+            })
+            .collect();
+
+        assert!(roots.windows(2).all(|pair| pair[0] == pair[1]));
+        assert!(SnapshotManifest::from_dir(&dir.0, 1, &DirOptions::new().concurrency(0)).is_err());
+        assert!(SnapshotManifest::from_dir(dir.0.join("Mordor"), 1, &DirOptions::new()).is_err());
+    }
+
+    #[test]
+    fn test_ignored_files_are_left_out() {
+        let dir = TempDir::new(
+            "directory-ignore",
+            &[
+                ("Shire/Hobbiton/Bag End", "Bilbo"),
+                ("Shire/Hobbiton/Bag End.bak", "Lobelia"),
+                ("Shire/target/ring.o", "gold"),
+                ("target/ring.o", "gold"),
+                ("Rivendell.bak", "Elrond"),
+            ],
+        );
+        let manifest = |options: &DirOptions| {
+            let manifest = SnapshotManifest::from_dir(&dir.0, 1, options).unwrap();
+            let paths: Vec<String> = manifest
+                .entries()
+                .iter()
+                .map(|entry| entry.path.clone())
+                .collect();
+            paths
+        };
+
+        let options = DirOptions::new()
+            .ignore_rules(&IgnoreRules::parse("/target/\n*.bak"))
+            .include("Rivendell.bak");
+        assert_eq!(
+            manifest(&options),
+            [
+                "Rivendell.bak",
+                "Shire/Hobbiton/Bag End",
+                "Shire/target/ring.o"
+            ]
+        );
+        assert_eq!(
+            manifest(&options.exclude("target")),
+            ["Rivendell.bak", "Shire/Hobbiton/Bag End"]
+        );
+    }
+
+    #[test]
+    fn test_ordering_is_recorded_in_the_manifest() {
+        let dir = TempDir::new("directory-ordering", &FILES);
+        let options = DirOptions::new().ordering(PathOrdering::CaseInsensitive);
+
+        let manifest = SnapshotManifest::from_dir(&dir.0, 1, &options).unwrap();
+
+        assert_eq!(manifest.ordering(), PathOrdering::CaseInsensitive);
+        assert_eq!(manifest.entries()[2].path, "Shire/Hobbiton/Bag End");
+        assert!(manifest.prove("Shire/Buckland").is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_metadata_changes_the_root_only_when_bound() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        use crate::merkle_tree::MerkleTree;
+
+        let dir = TempDir::new("directory-metadata", &FILES);
+        let bag_end = dir.0.join("Shire/Hobbiton/Bag End");
+        let root = |options: &DirOptions| {
+            SnapshotManifest::from_dir(&dir.0, 1, options)
+                .unwrap()
+                .root()
+        };
+        let with_mode = DirOptions::new().with_mode();
+
+        fs::set_permissions(&bag_end, fs::Permissions::from_mode(0o644)).unwrap();
+        let (plain, private) = (root(&DirOptions::new()), root(&with_mode));
+        fs::set_permissions(&bag_end, fs::Permissions::from_mode(0o600)).unwrap();
+        assert_eq!(root(&DirOptions::new()), plain);
+        assert_ne!(root(&with_mode), private);
+
+        let with_mtime = DirOptions::new().with_mtime();
+        assert_eq!(root(&with_mtime), root(&with_mtime));
+        assert_ne!(root(&with_mtime), plain);
+
+        symlink("Hobbiton/Bag End", dir.0.join("Shire/home")).unwrap();
+        assert_eq!(root(&DirOptions::new()), plain);
+        let manifest =
+            SnapshotManifest::from_dir(&dir.0, 1, &DirOptions::new().with_symlinks()).unwrap();
+        let link = manifest.prove("Shire/home").unwrap().entry;
+        assert_eq!(link.hash, MerkleTree::hash(b"Hobbiton/Bag End"));
+        assert_eq!(
+            link.metadata.symlink_target.as_deref(),
+            Some("Hobbiton/Bag End")
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use std::ops::Range;
+
+use crate::delta::{ChunkTransfer, ChunkTree, DeltaSignature};
+use crate::error::Error;
+use crate::sync::coalesce;
+
+const STATE_VERSION: u8 = 1;
+const STATE_HEADER_LENGTH: usize = 1 + 32 + 8 + 8;
+
+/// The progress of a download of the chunks of a file published with a
+/// `DeltaSignature`, each checked against the root on receipt.
+///
+/// The state holds which chunks were verified, not their data, and
+/// serializes with `to_bytes`, so an interrupted download resumes from
+/// `parse` and fetches and verifies only the missing chunks.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{ChunkTree, VerifiedDownload};
+///
+/// let file = b"One Ring to rule them all, One Ring to find them".to_vec();
+/// let sender = ChunkTree::new(&file, 8).unwrap();
+///
+/// let mut download = VerifiedDownload::new(sender.signature());
+/// for transfer in sender.transfer(&file, &[0..3]).unwrap() {
+///     download.receive(&transfer).unwrap();
+/// }
+///
+/// // Interrupted, and resumed later.
+/// let mut download = VerifiedDownload::parse(&download.to_bytes()).unwrap();
+/// assert_eq!(download.missing(), vec![3..6]);
+///
+/// for transfer in sender.transfer(&file, &download.missing()).unwrap() {
+///     download.receive(&transfer).unwrap();
+/// }
+/// assert!(download.is_complete());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedDownload {
+    signature: DeltaSignature,
+    verified: Vec<bool>,
+}
+
+impl VerifiedDownload {
+    pub fn new(signature: DeltaSignature) -> Self {
+        let chunk_count = if signature.chunk_size == 0 {
+            0
+        } else {
+            signature.length.div_ceil(signature.chunk_size).max(1)
+        };
+
+        Self {
+            signature,
+            verified: vec![false; chunk_count],
+        }
+    }
+
+    pub fn signature(&self) -> &DeltaSignature {
+        &self.signature
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.verified.len()
+    }
+
+    pub fn is_verified(&self, index: usize) -> bool {
+        self.verified.get(index).copied().unwrap_or(false)
+    }
+
+    /// The number of chunks verified from the start of the file, which may
+    /// be written out in order.
+    pub fn frontier(&self) -> usize {
+        self.verified
+            .iter()
+            .position(|verified| !verified)
+            .unwrap_or(self.verified.len())
+    }
+
+    /// The ranges of chunk indices still to be fetched, as `ChunkTree::transfer` takes them.
+    pub fn missing(&self) -> Vec<Range<usize>> {
+        let missing: Vec<usize> = (0..self.chunk_count())
+            .filter(|index| !self.verified[*index])
+            .collect();
+        coalesce(&missing)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.verified.iter().all(|verified| *verified)
+    }
+
+    /// Checks a received chunk against the root, through a leaf committing to
+    /// its index as well as its data, and marks it verified.
+    /// Returns whether the chunk was missing until now.
+    pub fn receive(&mut self, transfer: &ChunkTransfer) -> Result<bool, Error> {
+        if transfer.proof.leaf_index != transfer.index
+            || transfer.proof.leaf_count != self.chunk_count()
+        {
+            return Err(Error::InvalidInput(format!(
+                "the proof of chunk {} is not for its position",
+                transfer.index
+            )));
+        }
+        transfer.proof.verify(
+            &ChunkTree::leaf_hash(transfer.index, &transfer.data),
+            &self.signature.root,
+        )?;
+
+        let newly_verified = !self.verified[transfer.index];
+        self.verified[transfer.index] = true;
+        Ok(newly_verified)
+    }
+
+    /// Encodes the state as a version byte, the signature and a bitmap of
+    /// the verified chunks, the lowest bit of the first byte for chunk 0.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(STATE_HEADER_LENGTH + self.chunk_count().div_ceil(8));
+        bytes.push(STATE_VERSION);
+        bytes.extend_from_slice(&self.signature.root);
+        bytes.extend_from_slice(&(self.signature.chunk_size as u64).to_be_bytes());
+        bytes.extend_from_slice(&(self.signature.length as u64).to_be_bytes());
+        for chunks in self.verified.chunks(8) {
+            let bits = chunks
+                .iter()
+                .enumerate()
+                .fold(0u8, |bits, (bit, verified)| bits | (*verified as u8) << bit);
+            bytes.push(bits);
+        }
+
+        bytes
+    }
+
+    /// Decodes a state written by `to_bytes`, rejecting truncated or
+    /// trailing bytes and bits set past the last chunk.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let invalid = |reason: &str| Error::InvalidInput(reason.to_string());
+
+        if bytes.len() < STATE_HEADER_LENGTH {
+            return Err(invalid("unexpected end of download state"));
+        }
+        if bytes[0] != STATE_VERSION {
+            return Err(Error::InvalidInput(format!(
+                "unknown download state version {}",
+                bytes[0]
+            )));
+        }
+
+        let number = |at: usize| {
+            let number = u64::from_be_bytes(bytes[at..at + 8].try_into().unwrap());
+            usize::try_from(number).map_err(|_| invalid("the file is too large"))
+        };
+        let signature = DeltaSignature {
+            root: bytes[1..33].try_into().unwrap(),
+            chunk_size: number(33)?,
+            length: number(41)?,
+        };
+        if signature.chunk_size == 0 {
+            return Err(invalid("chunks can't be empty"));
+        }
+
+        // Checked before allocating the chunks, which a forged length could make huge.
+        let bitmap = &bytes[STATE_HEADER_LENGTH..];
+        let chunk_count = signature.length.div_ceil(signature.chunk_size).max(1);
+        if bitmap.len() != chunk_count.div_ceil(8) {
+            return Err(invalid("the bitmap does not match the number of chunks"));
+        }
+        let mut download = Self::new(signature);
+        for (index, byte) in bitmap.iter().enumerate() {
+            for bit in 0..8 {
+                if byte >> bit & 1 == 0 {
+                    continue;
+                }
+                match download.verified.get_mut(index * 8 + bit) {
+                    Some(verified) => *verified = true,
+                    None => return Err(invalid("a chunk past the end is marked verified")),
+                }
+            }
+        }
+
+        Ok(download)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn file() -> Vec<u8> {
+        (0..200)
+            .map(|line| format!("the road goes ever on {}\n", line))
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    #[test]
+    fn test_resumed_downloads_fetch_only_missing_chunks() {
+        let file = file();
+        let sender = ChunkTree::new(&file, 100).unwrap();
+        let mut download = VerifiedDownload::new(sender.signature());
+        let transfers = sender.transfer(&file, &download.missing()).unwrap();
+        for transfer in transfers.iter().filter(|transfer| transfer.index % 3 != 1) {
+            assert_eq!(download.receive(transfer), Ok(true));
+        }
+        assert_eq!(download.frontier(), 1);
+
+        let mut resumed = VerifiedDownload::parse(&download.to_bytes()).unwrap();
+        assert_eq!(resumed, download);
+
+        let missing = resumed.missing();
+        assert_eq!(missing[..2], [1..2, 4..5]);
+        for transfer in sender.transfer(&file, &missing).unwrap() {
+            assert_eq!(resumed.receive(&transfer), Ok(true));
+        }
+        assert!(resumed.is_complete());
+        assert_eq!(resumed.frontier(), resumed.chunk_count());
+        assert_eq!(resumed.receive(&transfers[0]), Ok(false));
+    }
+
+    #[test]
+    fn test_tampered_chunks_are_not_marked_verified() {
+        let file = file();
+        let sender = ChunkTree::new(&file, 64).unwrap();
+        let mut transfers = sender.transfer(&file, &[0..1, 1..2]).unwrap();
+
+        let mut download = VerifiedDownload::new(sender.signature());
+        transfers[0].data[0] ^= 1;
+        transfers[1].index = 5;
+
+        assert!(download.receive(&transfers[0]).is_err());
+        assert!(download.receive(&transfers[1]).is_err());
+        assert!(!download.is_verified(0) && !download.is_verified(1));
+
+        // A chunk and its proof presented at the position of its sibling.
+        let mut swapped = sender.transfer(&file, &[0..1, 1..2]).unwrap().remove(1);
+        swapped.index = 0;
+        swapped.proof.leaf_index = 0;
+        assert!(download.receive(&swapped).is_err());
+        assert!(!download.is_verified(0));
+    }
+
+    #[test]
+    fn test_invalid_states_are_rejected() {
+        let file = file();
+        let sender = ChunkTree::new(&file, 1000).unwrap();
+        let bytes = VerifiedDownload::new(sender.signature()).to_bytes();
+
+        assert!(VerifiedDownload::parse(&bytes[..bytes.len() - 1]).is_err());
+        assert!(VerifiedDownload::parse(&[bytes.as_slice(), &[0]].concat()).is_err());
+
+        let mut past_the_end = bytes.clone();
+        *past_the_end.last_mut().unwrap() = 0x80;
+        assert!(VerifiedDownload::parse(&past_the_end).is_err());
+
+        let mut unknown_version = bytes.clone();
+        unknown_version[0] = 2;
+        assert!(VerifiedDownload::parse(&unknown_version).is_err());
+
+        let mut huge = bytes;
+        huge[33..41].copy_from_slice(&1u64.to_be_bytes());
+        huge[41..49].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert!(VerifiedDownload::parse(&huge).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+use crate::signature::{SignatureVerifier, Signer};
+
+/// A proof of inclusion with what a relying party needs to use it: the
+/// size and root of the tree it was issued against, when it was issued,
+/// and optionally the signature of the issuer over all of it.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{MerkleTree, SignatureVerifier, Signer};
+///
+/// struct Key;
+///
+/// impl Signer for Key {
+///     fn sign(&self, message: &[u8]) -> Vec<u8> {
+///         MerkleTree::hash(message).to_vec()
+///     }
+/// }
+///
+/// impl SignatureVerifier for Key {
+///     fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+///         MerkleTree::hash(message) == signature
+///     }
+/// }
+///
+/// let tree = MerkleTree::build(&["Anduril", "Sting", "Glamdring"]).unwrap();
+/// let envelope = tree.envelope(1, 1_700_000_000).unwrap().sign(&Key);
+///
+/// let leaf = MerkleTree::hash(b"Sting");
+/// assert!(envelope.verify_signed(&leaf, 1_700_000_060, &Key).is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofEnvelope {
+    pub proof: Proof,
+    pub tree_size: usize,
+    pub root: Hash,
+    /// Seconds since the Unix epoch.
+    pub issued_at: u64,
+    pub signature: Option<Vec<u8>>,
+}
+
+impl ProofEnvelope {
+    const DOMAIN: &'static [u8] = b"merkle-tree proof envelope";
+
+    /// Wraps a proof issued against a tree with `root` at `issued_at`.
+    pub fn new(proof: Proof, root: Hash, issued_at: u64) -> Self {
+        Self {
+            tree_size: proof.leaf_count,
+            proof,
+            root,
+            issued_at,
+            signature: None,
+        }
+    }
+
+    /// The bytes the issuer signs: every field but the signature.
+    pub fn message(&self) -> Vec<u8> {
+        let mut message = Self::DOMAIN.to_vec();
+        message.extend_from_slice(&(self.tree_size as u64).to_be_bytes());
+        message.extend_from_slice(&self.root);
+        message.extend_from_slice(&self.issued_at.to_be_bytes());
+        message.extend_from_slice(&self.proof.to_bytes());
+        message
+    }
+
+    /// Signs the envelope as its issuer.
+    pub fn sign<S: Signer>(mut self, signer: &S) -> Self {
+        self.signature = Some(signer.sign(&self.message()));
+        self
+    }
+
+    /// Checks that the proof matches the tree size and leads from `leaf`
+    /// to the root, and that the envelope was not issued after `now`.
+    /// The signature, if any, is not checked.
+    pub fn verify(&self, leaf: &Hash, now: u64) -> Result<(), Error> {
+        if self.proof.leaf_count != self.tree_size {
+            return Err(Error::InvalidInput(format!(
+                "the proof is for a tree of {} leaves, the envelope has {}",
+                self.proof.leaf_count, self.tree_size
+            )));
+        }
+        if self.issued_at > now {
+            return Err(Error::InvalidInput(format!(
+                "the envelope is issued at {}, after {}",
+                self.issued_at, now
+            )));
+        }
+
+        self.proof.verify(leaf, &self.root)
+    }
+
+    /// Checks the envelope like `verify` and that the issuer signed it.
+    pub fn verify_signed<V: SignatureVerifier>(
+        &self,
+        leaf: &Hash,
+        now: u64,
+        verifier: &V,
+    ) -> Result<(), Error> {
+        match &self.signature {
+            Some(signature) if verifier.verify(&self.message(), signature) => {}
+            _ => return Err(Error::InvalidSignature),
+        }
+
+        self.verify(leaf, now)
+    }
+}
+
+impl MerkleTree {
+    /// Returns the proof of the leaf at `index` in an envelope issued at `issued_at`.
+    pub fn envelope(&self, index: usize, issued_at: u64) -> Result<ProofEnvelope, Error> {
+        Ok(ProofEnvelope::new(
+            self.proof(index)?,
+            self.root().expect("A tree has a root."),
+            issued_at,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    struct HmacKey(&'static [u8]);
+
+    impl Signer for HmacKey {
+        fn sign(&self, message: &[u8]) -> Vec<u8> {
+            hmac_sha256::HMAC::mac(message, self.0).to_vec()
+        }
+    }
+
+    impl SignatureVerifier for HmacKey {
+        fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+            hmac_sha256::HMAC::mac(message, self.0) == signature
+        }
+    }
+
+    fn tree() -> MerkleTree {
+        MerkleTree::build(&["Narsil", "Orcrist", "Herugrim", "Gurthang"]).unwrap()
+    }
+
+    #[test]
+    fn test_envelopes_are_checked_holistically() {
+        let key = HmacKey(b"Istari");
+        let envelope = tree().envelope(2, 1_000).unwrap().sign(&key);
+        let leaf = MerkleTree::hash(b"Herugrim");
+
+        assert_eq!(envelope.verify_signed(&leaf, 1_000, &key), Ok(()));
+        assert!(envelope.verify(&leaf, 999).is_err());
+        assert_eq!(
+            envelope.verify_signed(&leaf, 1_000, &HmacKey(b"Nazgul")),
+            Err(Error::InvalidSignature)
+        );
+
+        let mut resized = envelope.clone();
+        resized.tree_size = 5;
+        assert!(resized.verify(&leaf, 1_000).is_err());
+    }
+
+    #[test]
+    fn test_signatures_cover_every_field() {
+        let key = HmacKey(b"Istari");
+        let envelope = tree().envelope(0, 1_000).unwrap().sign(&key);
+        let leaf = MerkleTree::hash(b"Narsil");
+
+        let mut backdated = envelope.clone();
+        backdated.issued_at = 10;
+        assert_eq!(
+            backdated.verify_signed(&leaf, 1_000, &key),
+            Err(Error::InvalidSignature)
+        );
+        assert_eq!(backdated.verify(&leaf, 1_000), Ok(()));
+
+        let unsigned = tree().envelope(0, 1_000).unwrap();
+        assert_eq!(
+            unsigned.verify_signed(&leaf, 1_000, &key),
+            Err(Error::InvalidSignature)
+        );
+    }
+}
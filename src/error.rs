@@ -0,0 +1,71 @@
+use std::fmt;
+
+use crate::proof::ProofError;
+use crate::wire::WireError;
+
+/// Errors returned by the fallible operations of the crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// A tree needs at least one item.
+    EmptyTree,
+    /// An item could not be encoded into bytes before hashing.
+    Encoding(String),
+    /// The provided input is malformed.
+    InvalidInput(String),
+    /// The requested leaf index is not in the tree.
+    IndexOutOfRange { index: usize, leaf_count: usize },
+    /// The provided hashes do not lead to the expected root.
+    RootMismatch,
+    /// The proof does not have the shape of a proof in the claimed tree.
+    InvalidProof(ProofError),
+    /// The bytes are not a proof in the binary format of `Proof::to_bytes`.
+    MalformedProof(WireError),
+    /// Items share a leaf in a tree that rejects duplicates.
+    /// Each group lists the indices of the items with the same leaf.
+    DuplicateLeaves(Vec<Vec<usize>>),
+    /// The input is larger than the configured `Limits` allow.
+    LimitExceeded {
+        limit: &'static str,
+        value: usize,
+        max: usize,
+    },
+    /// A signature does not match the signed content or the key.
+    InvalidSignature,
+    /// Fewer witnesses than required signed a checkpoint.
+    QuorumNotReached { signatures: usize, threshold: usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::EmptyTree => write!(f, "a Merkle tree needs at least one item"),
+            Error::Encoding(reason) => write!(f, "could not encode item: {}", reason),
+            Error::InvalidInput(reason) => write!(f, "invalid input: {}", reason),
+            Error::IndexOutOfRange { index, leaf_count } => write!(
+                f,
+                "leaf index {} is out of range for a tree of {} leaves",
+                index, leaf_count
+            ),
+            Error::RootMismatch => write!(f, "the hashes do not lead to the expected root"),
+            Error::InvalidProof(reason) => write!(f, "invalid proof: {}", reason),
+            Error::MalformedProof(reason) => write!(f, "malformed proof: {}", reason),
+            Error::DuplicateLeaves(collisions) => {
+                write!(f, "duplicate leaves at indices {:?}", collisions)
+            }
+            Error::LimitExceeded { limit, value, max } => {
+                write!(f, "the input exceeds {}, {} > {}", limit, value, max)
+            }
+            Error::InvalidSignature => write!(f, "the signature is not valid"),
+            Error::QuorumNotReached {
+                signatures,
+                threshold,
+            } => write!(
+                f,
+                "{} valid witness signatures, {} required",
+                signatures, threshold
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
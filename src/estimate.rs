@@ -0,0 +1,120 @@
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// The encodings a proof is commonly sent in, to estimate its size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofEncoding {
+    /// The sibling hashes concatenated, 32 bytes each.
+    Raw,
+    /// A JSON array of 0x-prefixed hex strings, as in the airdrop export.
+    HexJson,
+    /// A dynamic `bytes32[]` argument in the calldata of a Solidity call:
+    /// its offset, its length and one word per hash.
+    ///
+    /// Calldata costs 16 gas per nonzero byte and 4 per zero byte (EIP-2028),
+    /// so sending a proof costs at most 16 gas per byte. Verifying it calls
+    /// the SHA-256 precompile once per hash, 60 + 12 * 2 = 84 gas for the
+    /// 64 bytes of a pair, plus the cost of the call itself.
+    SolidityCalldata,
+    /// The stable binary format of `Proof::to_bytes`.
+    Wire,
+}
+
+impl ProofEncoding {
+    /// The size in bytes of a proof of `hashes` hashes in this encoding.
+    pub fn proof_bytes(self, hashes: usize) -> usize {
+        let hash = std::mem::size_of::<Hash>();
+        match self {
+            ProofEncoding::Raw => hashes * hash,
+            // The brackets, a quoted "0x" string per hash and the commas.
+            ProofEncoding::HexJson => 2 + hashes * (2 * hash + 4) + hashes.saturating_sub(1),
+            ProofEncoding::SolidityCalldata => 2 * 32 + hashes * 32,
+            ProofEncoding::Wire => Proof::wire_length(hashes),
+        }
+    }
+}
+
+impl MerkleTree {
+    /// The number of hashes in the proof of the leaf at `index`, without
+    /// generating it.
+    pub fn proof_len_for(&self, index: usize) -> Result<usize, Error> {
+        if index >= self.leaf_count() {
+            return Err(Error::IndexOutOfRange {
+                index,
+                leaf_count: self.leaf_count(),
+            });
+        }
+        Ok(Proof::expected_length(self.leaf_count()))
+    }
+
+    /// The size in bytes of any proof of inclusion in this tree, every proof
+    /// having one hash per level, so services can budget their responses.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::{MerkleTree, ProofEncoding};
+    ///
+    /// let tree = MerkleTree::build(&["Anduin", "Isen", "Entwash", "Gwathló", "Baranduin"]).unwrap();
+    ///
+    /// assert_eq!(tree.proof_len_for(4), Ok(3));
+    /// assert_eq!(tree.estimated_proof_bytes(ProofEncoding::Raw), 96);
+    /// ```
+    pub fn estimated_proof_bytes(&self, encoding: ProofEncoding) -> usize {
+        encoding.proof_bytes(Proof::expected_length(self.leaf_count()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_estimates_match_the_encoded_proofs() {
+        for count in [1, 2, 5, 64, 1000] {
+            let items: Vec<String> = (0..count).map(|item| format!("ford {}", item)).collect();
+            let tree = MerkleTree::build(&items).unwrap();
+            let proof = tree.proof(count - 1).unwrap();
+
+            assert_eq!(tree.proof_len_for(count - 1), Ok(proof.siblings.len()));
+
+            let hex: Vec<String> = proof
+                .siblings
+                .iter()
+                .map(|sibling| format!("0x{}", hex::encode(sibling)))
+                .collect();
+            let json = format!(
+                "[{}]",
+                hex.iter()
+                    .map(|hash| format!("\"{}\"", hash))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            );
+
+            assert_eq!(
+                tree.estimated_proof_bytes(ProofEncoding::HexJson),
+                json.len()
+            );
+            assert_eq!(
+                tree.estimated_proof_bytes(ProofEncoding::Raw),
+                proof.siblings.concat().len()
+            );
+            assert_eq!(
+                tree.estimated_proof_bytes(ProofEncoding::Wire),
+                proof.to_bytes().len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_calldata_size() {
+        let tree = MerkleTree::build(&["Mithrandir", "Curunír", "Radagast"]).unwrap();
+
+        assert_eq!(
+            tree.estimated_proof_bytes(ProofEncoding::SolidityCalldata),
+            128
+        );
+        assert!(tree.proof_len_for(3).is_err());
+    }
+}
@@ -0,0 +1,607 @@
+use crate::error::Error;
+use crate::keccak::keccak256;
+use crate::merkle_tree::Hash;
+use serde_json::Value;
+
+/// The root of a trie without entries, `keccak256(rlp(""))`.
+pub const EMPTY_TRIE_ROOT: Hash = [
+    0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6, 0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8, 0x6e,
+    0x5b, 0x48, 0xe0, 0x1b, 0x99, 0x6c, 0xad, 0xc0, 0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63, 0xb4, 0x21,
+];
+
+/// The code hash of accounts without code, `keccak256("")`.
+pub const EMPTY_CODE_HASH: Hash = [
+    0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7, 0x03, 0xc0,
+    0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04, 0x5d, 0x85, 0xa4, 0x70,
+];
+
+/// An RLP item: a byte string or a list of items.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rlp {
+    Bytes(Vec<u8>),
+    List(Vec<Rlp>),
+}
+
+impl Rlp {
+    /// Decodes exactly one item. Non canonical encodings and trailing bytes are rejected,
+    /// so every item has a single encoding and hashes of nodes can't be forged around.
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let (item, rest) = Self::decode_item(bytes)?;
+        if !rest.is_empty() {
+            return Err(Error::Encoding("trailing bytes after RLP item".to_string()));
+        }
+        Ok(item)
+    }
+
+    fn decode_item(bytes: &[u8]) -> Result<(Self, &[u8]), Error> {
+        let invalid = |reason: &str| Error::Encoding(format!("invalid RLP: {}", reason));
+
+        let (&prefix, rest) = bytes.split_first().ok_or_else(|| invalid("empty input"))?;
+
+        let (is_list, length, rest) = match prefix {
+            0x00..=0x7f => return Ok((Rlp::Bytes(vec![prefix]), rest)),
+            0x80..=0xb7 => (false, (prefix - 0x80) as usize, rest),
+            0xc0..=0xf7 => (true, (prefix - 0xc0) as usize, rest),
+            0xb8..=0xbf | 0xf8..=0xff => {
+                let length_of_length = (prefix & 0x07) as usize + 1;
+                if rest.len() < length_of_length {
+                    return Err(invalid("truncated length"));
+                }
+                let (length_bytes, rest) = rest.split_at(length_of_length);
+                if length_bytes[0] == 0 || length_of_length > 8 {
+                    return Err(invalid("non canonical length"));
+                }
+                let length = length_bytes
+                    .iter()
+                    .fold(0u64, |length, byte| (length << 8) | *byte as u64);
+                if length <= 55 {
+                    return Err(invalid("non canonical length"));
+                }
+                (prefix >= 0xf8, length as usize, rest)
+            }
+        };
+
+        if rest.len() < length {
+            return Err(invalid("truncated item"));
+        }
+        let (payload, rest) = rest.split_at(length);
+
+        if !is_list {
+            if length == 1 && payload[0] < 0x80 {
+                return Err(invalid("single byte encoded as a string"));
+            }
+            return Ok((Rlp::Bytes(payload.to_vec()), rest));
+        }
+
+        let mut items = Vec::new();
+        let mut remaining = payload;
+        while !remaining.is_empty() {
+            let (item, next) = Self::decode_item(remaining)?;
+            items.push(item);
+            remaining = next;
+        }
+
+        Ok((Rlp::List(items), rest))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_into(&mut out);
+        out
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        match self {
+            Rlp::Bytes(bytes) if bytes.len() == 1 && bytes[0] < 0x80 => out.push(bytes[0]),
+            Rlp::Bytes(bytes) => {
+                write_length(bytes.len(), 0x80, out);
+                out.extend_from_slice(bytes);
+            }
+            Rlp::List(items) => {
+                let mut payload = Vec::new();
+                for item in items {
+                    item.encode_into(&mut payload);
+                }
+                write_length(payload.len(), 0xc0, out);
+                out.extend_from_slice(&payload);
+            }
+        }
+    }
+}
+
+fn write_length(length: usize, offset: u8, out: &mut Vec<u8>) {
+    if length <= 55 {
+        out.push(offset + length as u8);
+    } else {
+        let length_bytes = trim_leading_zeros(&length.to_be_bytes()).to_vec();
+        out.push(offset + 55 + length_bytes.len() as u8);
+        out.extend_from_slice(&length_bytes);
+    }
+}
+
+fn trim_leading_zeros(bytes: &[u8]) -> &[u8] {
+    let first = bytes
+        .iter()
+        .position(|byte| *byte != 0)
+        .unwrap_or(bytes.len());
+    &bytes[first..]
+}
+
+/// Walks a Merkle Patricia Trie proof from the root down the path of `key`.
+///
+/// Returns the value stored at the key, or `None` if the proof shows the key
+/// is absent. Fails if a node does not match the hash its parent commits to,
+/// a node is malformed, or the proof has nodes that are not on the path.
+pub fn verify_trie_proof(
+    root: &Hash,
+    key: &[u8],
+    proof: &[Vec<u8>],
+) -> Result<Option<Vec<u8>>, Error> {
+    let mut nibbles: Vec<u8> = key
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0f])
+        .collect();
+    nibbles.reverse();
+
+    let mut nodes = proof.iter();
+    let mut next = NodeReference::Hash(*root);
+
+    let value = loop {
+        let node = match next {
+            NodeReference::Hash(hash) => {
+                let encoded = match nodes.next() {
+                    Some(encoded) => encoded,
+                    None if hash == EMPTY_TRIE_ROOT && nibbles.len() == 2 * key.len() => {
+                        break None
+                    }
+                    None => return Err(Error::InvalidInput("incomplete trie proof".to_string())),
+                };
+                if keccak256(encoded) != hash {
+                    return Err(Error::RootMismatch);
+                }
+                Rlp::decode(encoded)?
+            }
+            NodeReference::Inline(node) => node,
+            NodeReference::Empty => break None,
+        };
+
+        let mut items = match node {
+            Rlp::List(items) => items,
+            // Some clients prove an empty trie with the encoding of its empty root node.
+            Rlp::Bytes(bytes) if bytes.is_empty() => break None,
+            Rlp::Bytes(_) => return Err(malformed_node()),
+        };
+
+        match items.len() {
+            17 => match nibbles.pop() {
+                Some(nibble) => {
+                    next = NodeReference::from_item(items.swap_remove(nibble as usize))?
+                }
+                None => {
+                    break Some(bytes_item(items.swap_remove(16))?)
+                        .filter(|value| !value.is_empty())
+                }
+            },
+            2 => {
+                let child = items.pop().unwrap();
+                let (path, is_leaf) = decode_hex_prefix(&bytes_item(items.pop().unwrap())?)?;
+
+                let matches = path.len() <= nibbles.len()
+                    && path.iter().zip(nibbles.iter().rev()).all(|(a, b)| a == b);
+
+                if is_leaf {
+                    break (matches && path.len() == nibbles.len())
+                        .then(|| bytes_item(child))
+                        .transpose()?;
+                }
+                if !matches {
+                    break None;
+                }
+
+                nibbles.truncate(nibbles.len() - path.len());
+                next = NodeReference::from_item(child)?;
+            }
+            _ => return Err(malformed_node()),
+        }
+    };
+
+    if nodes.next().is_some() {
+        return Err(Error::InvalidInput(
+            "trie proof has nodes off the path".to_string(),
+        ));
+    }
+
+    Ok(value)
+}
+
+enum NodeReference {
+    Empty,
+    Hash(Hash),
+    /// Nodes whose encoding is shorter than a hash are embedded in their parent.
+    Inline(Rlp),
+}
+
+impl NodeReference {
+    fn from_item(item: Rlp) -> Result<Self, Error> {
+        match item {
+            Rlp::Bytes(bytes) if bytes.is_empty() => Ok(NodeReference::Empty),
+            Rlp::Bytes(bytes) => bytes
+                .try_into()
+                .map(NodeReference::Hash)
+                .map_err(|_| malformed_node()),
+            list => Ok(NodeReference::Inline(list)),
+        }
+    }
+}
+
+fn malformed_node() -> Error {
+    Error::InvalidInput("malformed trie node".to_string())
+}
+
+fn bytes_item(item: Rlp) -> Result<Vec<u8>, Error> {
+    match item {
+        Rlp::Bytes(bytes) => Ok(bytes),
+        Rlp::List(_) => Err(malformed_node()),
+    }
+}
+
+/// Decodes the compact path of leaf and extension nodes: the first nibble
+/// flags a leaf and an odd number of nibbles.
+fn decode_hex_prefix(encoded: &[u8]) -> Result<(Vec<u8>, bool), Error> {
+    let (&first, rest) = encoded.split_first().ok_or_else(malformed_node)?;
+    let flag = first >> 4;
+    if flag > 3 || (flag & 1 == 0 && first & 0x0f != 0) {
+        return Err(malformed_node());
+    }
+
+    let mut path = Vec::with_capacity(2 * encoded.len());
+    if flag & 1 == 1 {
+        path.push(first & 0x0f);
+    }
+    path.extend(rest.iter().flat_map(|byte| [byte >> 4, byte & 0x0f]));
+
+    Ok((path, flag >= 2))
+}
+
+/// A storage slot and its proof, as returned by `eth_getProof`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageProof {
+    pub key: Hash,
+    /// The big-endian 256 bits value of the slot.
+    pub value: Hash,
+    pub proof: Vec<Vec<u8>>,
+}
+
+/// An account, its storage slots and their proofs, as returned by `eth_getProof` (EIP-1186).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountProof {
+    pub address: [u8; 20],
+    /// The big-endian 256 bits balance in wei.
+    pub balance: Hash,
+    pub nonce: u64,
+    pub code_hash: Hash,
+    pub storage_hash: Hash,
+    pub account_proof: Vec<Vec<u8>>,
+    pub storage_proof: Vec<StorageProof>,
+}
+
+impl AccountProof {
+    /// Parses the `result` of an `eth_getProof` call.
+    pub fn from_json(json: &Value) -> Result<Self, Error> {
+        let field = |object: &Value, name: &str| -> Result<Vec<u8>, Error> {
+            object
+                .get(name)
+                .and_then(Value::as_str)
+                .ok_or_else(|| Error::InvalidInput(format!("missing field {}", name)))
+                .and_then(decode_hex)
+        };
+        let nodes = |object: &Value, name: &str| -> Result<Vec<Vec<u8>>, Error> {
+            object
+                .get(name)
+                .and_then(Value::as_array)
+                .ok_or_else(|| Error::InvalidInput(format!("missing field {}", name)))?
+                .iter()
+                .map(|node| {
+                    node.as_str()
+                        .ok_or_else(|| Error::InvalidInput(format!("invalid node in {}", name)))
+                        .and_then(decode_hex)
+                })
+                .collect()
+        };
+
+        let storage_proof = json
+            .get("storageProof")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .map(|slot| {
+                Ok(StorageProof {
+                    key: left_pad(&field(slot, "key")?)?,
+                    value: left_pad(&field(slot, "value")?)?,
+                    proof: nodes(slot, "proof")?,
+                })
+            })
+            .collect::<Result<Vec<StorageProof>, Error>>()?;
+
+        let nonce = field(json, "nonce")?;
+        if trim_leading_zeros(&nonce).len() > 8 {
+            return Err(Error::InvalidInput("nonce overflows".to_string()));
+        }
+
+        Ok(Self {
+            address: field(json, "address")?
+                .try_into()
+                .map_err(|_| Error::InvalidInput("invalid address".to_string()))?,
+            balance: left_pad(&field(json, "balance")?)?,
+            nonce: u64::from_be_bytes(left_pad(&nonce)?[24..].try_into().unwrap()),
+            code_hash: left_pad(&field(json, "codeHash")?)?,
+            storage_hash: left_pad(&field(json, "storageHash")?)?,
+            account_proof: nodes(json, "accountProof")?,
+            storage_proof,
+        })
+    }
+
+    /// The RLP encoding the state trie stores for the account.
+    fn encoded_account(&self) -> Vec<u8> {
+        Rlp::List(vec![
+            Rlp::Bytes(trim_leading_zeros(&self.nonce.to_be_bytes()).to_vec()),
+            Rlp::Bytes(trim_leading_zeros(&self.balance).to_vec()),
+            Rlp::Bytes(self.storage_hash.to_vec()),
+            Rlp::Bytes(self.code_hash.to_vec()),
+        ])
+        .encode()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.nonce == 0
+            && self.balance == [0; 32]
+            && self.storage_hash == EMPTY_TRIE_ROOT
+            && self.code_hash == EMPTY_CODE_HASH
+    }
+
+    /// Checks the account against the state root of a block, then every
+    /// storage slot against the storage root of the account.
+    ///
+    /// An account or slot the proof shows to be absent verifies only if it
+    /// is claimed empty, or zero.
+    pub fn verify(&self, state_root: &Hash) -> Result<(), Error> {
+        let stored = verify_trie_proof(state_root, &keccak256(&self.address), &self.account_proof)?;
+
+        let matches = match stored {
+            Some(stored) => stored == self.encoded_account(),
+            None => self.is_empty(),
+        };
+        if !matches {
+            return Err(Error::InvalidInput(
+                "the account does not match the state trie".to_string(),
+            ));
+        }
+
+        for slot in &self.storage_proof {
+            let stored = verify_trie_proof(&self.storage_hash, &keccak256(&slot.key), &slot.proof)?;
+
+            let matches = match stored {
+                Some(stored) => {
+                    stored == Rlp::Bytes(trim_leading_zeros(&slot.value).to_vec()).encode()
+                }
+                None => slot.value == [0; 32],
+            };
+            if !matches {
+                return Err(Error::InvalidInput(format!(
+                    "the storage slot 0x{} does not match the storage trie",
+                    hex::encode(slot.key)
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn decode_hex(string: &str) -> Result<Vec<u8>, Error> {
+    let digits = string
+        .strip_prefix("0x")
+        .ok_or_else(|| Error::InvalidInput(format!("invalid hex {}", string)))?;
+
+    // Quantities such as balances drop their leading zeros, down to an odd number of digits.
+    let padded = if digits.len() % 2 == 1 {
+        format!("0{}", digits)
+    } else {
+        digits.to_string()
+    };
+
+    hex::decode(padded).map_err(|_| Error::InvalidInput(format!("invalid hex {}", string)))
+}
+
+fn left_pad(bytes: &[u8]) -> Result<Hash, Error> {
+    let bytes = trim_leading_zeros(bytes);
+    if bytes.len() > 32 {
+        return Err(Error::InvalidInput(
+            "value longer than 32 bytes".to_string(),
+        ));
+    }
+
+    let mut padded = [0; 32];
+    padded[32 - bytes.len()..].copy_from_slice(bytes);
+    Ok(padded)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use serde_json::json;
+
+    fn leaf(path: &[u8], value: Vec<u8>) -> Rlp {
+        let mut encoded_path = vec![0x20];
+        encoded_path.extend_from_slice(path);
+        Rlp::List(vec![Rlp::Bytes(encoded_path), Rlp::Bytes(value)])
+    }
+
+    fn odd_leaf(nibble: u8, value: Vec<u8>) -> Rlp {
+        Rlp::List(vec![Rlp::Bytes(vec![0x30 | nibble]), Rlp::Bytes(value)])
+    }
+
+    fn account(balance: u8) -> AccountProof {
+        let mut balance_bytes = [0; 32];
+        balance_bytes[31] = balance;
+
+        AccountProof {
+            address: [0x42; 20],
+            balance: balance_bytes,
+            nonce: 7,
+            code_hash: EMPTY_CODE_HASH,
+            storage_hash: EMPTY_TRIE_ROOT,
+            account_proof: Vec::new(),
+            storage_proof: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_known_hashes() {
+        assert_eq!(keccak256(&[]), EMPTY_CODE_HASH);
+        assert_eq!(keccak256(&Rlp::Bytes(Vec::new()).encode()), EMPTY_TRIE_ROOT);
+    }
+
+    #[test]
+    fn test_rlp_round_trip_and_canonical_form() {
+        let item = Rlp::List(vec![
+            Rlp::Bytes(b"dog".to_vec()),
+            Rlp::Bytes(vec![0x0f]),
+            Rlp::Bytes(vec![0xaa; 60]),
+            Rlp::List(Vec::new()),
+        ]);
+
+        assert_eq!(Rlp::decode(&item.encode()).unwrap(), item);
+        assert_eq!(
+            Rlp::Bytes(b"dog".to_vec()).encode(),
+            vec![0x83, b'd', b'o', b'g']
+        );
+
+        assert!(Rlp::decode(&[0x81, 0x0f]).is_err());
+        assert!(Rlp::decode(&[0xb8, 0x01, 0xaa]).is_err());
+        assert!(Rlp::decode(&[0x83, b'd', b'o']).is_err());
+        assert!(Rlp::decode(&[0x0f, 0x0f]).is_err());
+    }
+
+    #[test]
+    fn test_trie_proof_through_branch_and_extension() {
+        // Two keys sharing their first byte: an extension over a branch on the next nibble.
+        let first_key = [0xab, 0x12];
+        let second_key = [0xab, 0x34];
+
+        let first_leaf = odd_leaf(0x2, b"Narya".to_vec());
+        let second_leaf = odd_leaf(0x4, b"Nenya".to_vec());
+
+        let mut children = vec![Rlp::Bytes(Vec::new()); 17];
+        children[1] = Rlp::Bytes(keccak256(&first_leaf.encode()).to_vec());
+        children[3] = Rlp::Bytes(keccak256(&second_leaf.encode()).to_vec());
+        let branch = Rlp::List(children);
+
+        let extension = Rlp::List(vec![
+            Rlp::Bytes(vec![0x00, 0xab]),
+            Rlp::Bytes(keccak256(&branch.encode()).to_vec()),
+        ]);
+        let root = keccak256(&extension.encode());
+
+        let proof = vec![extension.encode(), branch.encode(), first_leaf.encode()];
+        assert_eq!(
+            verify_trie_proof(&root, &first_key, &proof).unwrap(),
+            Some(b"Narya".to_vec())
+        );
+
+        let proof = vec![extension.encode(), branch.encode(), second_leaf.encode()];
+        assert_eq!(
+            verify_trie_proof(&root, &second_key, &proof).unwrap(),
+            Some(b"Nenya".to_vec())
+        );
+
+        let absent = vec![extension.encode(), branch.encode()];
+        assert_eq!(
+            verify_trie_proof(&root, &[0xab, 0x56], &absent).unwrap(),
+            None
+        );
+        assert_eq!(
+            verify_trie_proof(&root, &[0xcd, 0x12], &absent[..1]).unwrap(),
+            None
+        );
+
+        let forged = odd_leaf(0x2, b"Vilya".to_vec()).encode();
+        let proof = vec![extension.encode(), branch.encode(), forged];
+        assert_eq!(
+            verify_trie_proof(&root, &first_key, &proof),
+            Err(Error::RootMismatch)
+        );
+
+        let extra = vec![
+            extension.encode(),
+            branch.encode(),
+            first_leaf.encode(),
+            first_leaf.encode(),
+        ];
+        assert!(verify_trie_proof(&root, &first_key, &extra).is_err());
+    }
+
+    #[test]
+    fn test_account_and_storage_proofs() {
+        let slot = [0x01; 32];
+        let mut value = [0; 32];
+        value[30..].copy_from_slice(&[0x04, 0xd2]);
+
+        let storage_leaf = leaf(&keccak256(&slot), Rlp::Bytes(vec![0x04, 0xd2]).encode());
+        let storage_root = keccak256(&storage_leaf.encode());
+
+        let mut proof = account(100);
+        proof.storage_hash = storage_root;
+        proof.storage_proof = vec![StorageProof {
+            key: slot,
+            value,
+            proof: vec![storage_leaf.encode()],
+        }];
+
+        let account_leaf = leaf(&keccak256(&proof.address), proof.encoded_account());
+        let state_root = keccak256(&account_leaf.encode());
+        proof.account_proof = vec![account_leaf.encode()];
+
+        assert_eq!(proof.verify(&state_root), Ok(()));
+
+        let mut richer = proof.clone();
+        richer.balance[31] = 200;
+        assert!(richer.verify(&state_root).is_err());
+
+        let mut other_value = proof.clone();
+        other_value.storage_proof[0].value[31] = 0;
+        assert!(other_value.verify(&state_root).is_err());
+
+        // The state trie proves another address is absent, so only an empty account verifies.
+        let mut absent = account(0);
+        absent.nonce = 0;
+        absent.address = [0x43; 20];
+        absent.account_proof = proof.account_proof.clone();
+        assert_eq!(absent.verify(&state_root), Ok(()));
+        absent.nonce = 1;
+        assert!(absent.verify(&state_root).is_err());
+    }
+
+    #[test]
+    fn test_from_json() {
+        let response = json!({
+            "address": "0x4242424242424242424242424242424242424242",
+            "accountProof": ["0x80"],
+            "balance": "0x64",
+            "codeHash": format!("0x{}", hex::encode(EMPTY_CODE_HASH)),
+            "nonce": "0x7",
+            "storageHash": format!("0x{}", hex::encode(EMPTY_TRIE_ROOT)),
+            "storageProof": [{ "key": "0x0", "value": "0x0", "proof": [] }],
+        });
+
+        let proof = AccountProof::from_json(&response).unwrap();
+
+        assert_eq!(proof.address, [0x42; 20]);
+        assert_eq!(proof.nonce, 7);
+        assert_eq!(proof.balance, account(100).balance);
+        assert_eq!(proof.account_proof, vec![vec![0x80]]);
+        assert_eq!(proof.storage_proof[0].key, [0; 32]);
+    }
+}
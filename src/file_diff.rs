@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// How a file is split into the chunks of its tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chunking {
+    /// Chunks of the same size, the last one may be shorter.
+    Fixed(usize),
+    /// Chunks cut where a rolling hash of the content matches a mask, so an
+    /// insertion only changes the chunks around it. Chunks are between
+    /// `min` and `max` bytes, `average` on random data.
+    ContentDefined {
+        min: usize,
+        average: usize,
+        max: usize,
+    },
+}
+
+impl Chunking {
+    /// The byte ranges of the chunks of `data`. An empty file has a single empty chunk.
+    pub fn split(&self, data: &[u8]) -> Result<Vec<Range<usize>>, Error> {
+        let mut chunks = match *self {
+            Chunking::Fixed(0) => {
+                return Err(Error::InvalidInput("chunks can't be empty".to_string()));
+            }
+            Chunking::Fixed(size) => (0..data.len())
+                .step_by(size)
+                .map(|start| start..start.saturating_add(size).min(data.len()))
+                .collect(),
+            Chunking::ContentDefined { min, average, max } => {
+                if min == 0 || min > average || average > max {
+                    return Err(Error::InvalidInput(format!(
+                        "content defined chunks need 0 < min <= average <= max, not {} {} {}",
+                        min, average, max
+                    )));
+                }
+                content_defined_chunks(data, min, average, max)
+            }
+        };
+        if chunks.is_empty() {
+            chunks.push(0..0);
+        }
+        Ok(chunks)
+    }
+}
+
+/// The gear table of the rolling hash, derived from SHA-256 so it is the
+/// same in every build.
+fn gear_table() -> [u64; 256] {
+    let mut table = [0; 256];
+    for (byte, gear) in table.iter_mut().enumerate() {
+        let hash = MerkleTree::hash(&[byte as u8]);
+        *gear = u64::from_be_bytes(hash[..8].try_into().expect("A hash has 8 bytes."));
+    }
+    table
+}
+
+fn content_defined_chunks(
+    data: &[u8],
+    min: usize,
+    average: usize,
+    max: usize,
+) -> Vec<Range<usize>> {
+    let gear = gear_table();
+    let mask = (average.next_power_of_two() as u64).wrapping_sub(1);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+
+    while start < data.len() {
+        let end = start.saturating_add(max).min(data.len());
+        let mut hash: u64 = 0;
+        let mut cut = end;
+        for (position, byte) in data[start..end].iter().enumerate() {
+            hash = (hash << 1).wrapping_add(gear[*byte as usize]);
+            if position + 1 >= min && hash & mask == 0 {
+                cut = start + position + 1;
+                break;
+            }
+        }
+        chunks.push(start..cut);
+        start = cut;
+    }
+
+    chunks
+}
+
+/// A chunk of one version of a file and its proof against the root of that version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkProof {
+    pub index: usize,
+    /// The bytes of the chunk in the file.
+    pub bytes: Range<usize>,
+    pub proof: Proof,
+}
+
+impl ChunkProof {
+    /// Checks that the bytes of the chunk in `data` are the leaf the proof
+    /// leads from to `root`.
+    pub fn verify(&self, data: &[u8], root: &Hash) -> Result<(), Error> {
+        let chunk = data.get(self.bytes.clone()).ok_or_else(|| {
+            Error::InvalidInput(format!(
+                "the file has {} bytes, not {}",
+                data.len(),
+                self.bytes.end
+            ))
+        })?;
+        if self.proof.leaf_index != self.index {
+            return Err(Error::InvalidInput(format!(
+                "the proof of chunk {} is not for its position",
+                self.index
+            )));
+        }
+        self.proof.verify(&MerkleTree::hash(chunk), root)
+    }
+}
+
+/// A run of chunks of the old version replaced by a run of chunks of the
+/// new one. Either run may be empty, for insertions and deletions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkHunk {
+    pub old_chunks: Range<usize>,
+    pub new_chunks: Range<usize>,
+    pub old: Vec<ChunkProof>,
+    pub new: Vec<ChunkProof>,
+}
+
+/// The chunks that differ between two versions of a file, with the proofs
+/// of each side against the root of its version, so backup and patching
+/// tools ship a minimal delta the other end can verify.
+///
+/// Chunks are matched by content, so with `Chunking::ContentDefined` an
+/// insertion in the middle of a file only changes the chunks around it.
+/// The alignment is greedy: after a mismatch the next new chunk found
+/// further in the old version resynchronizes both sides.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{ChunkDiff, Chunking};
+///
+/// let old = b"One Ring to rule them all, One Ring to find them".to_vec();
+/// let new = b"One Ring to rule them all, One Ring to bind them".to_vec();
+///
+/// let diff = ChunkDiff::new(&old, &new, Chunking::Fixed(8)).unwrap();
+///
+/// assert_eq!(diff.hunks().len(), 1);
+/// let hunk = &diff.hunks()[0];
+/// assert_eq!(hunk.new_chunks, 4..5);
+/// assert!(hunk.new[0].verify(&new, &diff.new_root()).is_ok());
+/// assert!(hunk.old[0].verify(&old, &diff.old_root()).is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkDiff {
+    old_root: Hash,
+    new_root: Hash,
+    hunks: Vec<ChunkHunk>,
+}
+
+impl ChunkDiff {
+    /// Chunks both versions, builds their trees and collects the differing chunks.
+    pub fn new(old: &[u8], new: &[u8], chunking: Chunking) -> Result<Self, Error> {
+        let old_side = Side::new(old, chunking)?;
+        let new_side = Side::new(new, chunking)?;
+
+        let hunks = align(&old_side.tree, &new_side.tree)
+            .into_iter()
+            .map(|(old_chunks, new_chunks)| {
+                Ok(ChunkHunk {
+                    old: old_side.proofs(old_chunks.clone())?,
+                    new: new_side.proofs(new_chunks.clone())?,
+                    old_chunks,
+                    new_chunks,
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(Self {
+            old_root: old_side.root()?,
+            new_root: new_side.root()?,
+            hunks,
+        })
+    }
+
+    pub fn old_root(&self) -> Hash {
+        self.old_root
+    }
+
+    pub fn new_root(&self) -> Hash {
+        self.new_root
+    }
+
+    pub fn hunks(&self) -> &[ChunkHunk] {
+        &self.hunks
+    }
+
+    /// Whether both versions have the same chunks.
+    pub fn is_empty(&self) -> bool {
+        self.hunks.is_empty()
+    }
+
+    /// The bytes of the new version to ship, the sum of its differing chunks.
+    pub fn delta_size(&self) -> usize {
+        self.hunks
+            .iter()
+            .flat_map(|hunk| &hunk.new)
+            .map(|chunk| chunk.bytes.len())
+            .sum()
+    }
+}
+
+/// One version of the file, its chunks and their tree.
+struct Side {
+    chunks: Vec<Range<usize>>,
+    tree: MerkleTree,
+}
+
+impl Side {
+    fn new(data: &[u8], chunking: Chunking) -> Result<Self, Error> {
+        let chunks = chunking.split(data)?;
+        let leaves = chunks
+            .iter()
+            .map(|chunk| MerkleTree::hash(&data[chunk.clone()]))
+            .collect();
+
+        Ok(Self {
+            chunks,
+            tree: MerkleTree::from_leaves(leaves).ok_or(Error::EmptyTree)?,
+        })
+    }
+
+    fn root(&self) -> Result<Hash, Error> {
+        self.tree.root().ok_or(Error::EmptyTree)
+    }
+
+    fn proofs(&self, indices: Range<usize>) -> Result<Vec<ChunkProof>, Error> {
+        indices
+            .map(|index| {
+                Ok(ChunkProof {
+                    index,
+                    bytes: self.chunks[index].clone(),
+                    proof: self.tree.proof(index)?,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Pairs the runs of differing chunks of both trees, in order.
+fn align(old: &MerkleTree, new: &MerkleTree) -> Vec<(Range<usize>, Range<usize>)> {
+    let (old, new) = (old.leaves(), new.leaves());
+    let mut positions: HashMap<Hash, Vec<usize>> = HashMap::new();
+    for (index, leaf) in old.iter().enumerate() {
+        positions.entry(*leaf).or_default().push(index);
+    }
+    // The first position of `leaf` in the old chunks at or after `from`.
+    let next_in_old = |leaf: &Hash, from: usize| {
+        let found = positions.get(leaf)?;
+        found
+            .get(found.partition_point(|index| *index < from))
+            .copied()
+    };
+
+    let mut hunks = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+            continue;
+        }
+
+        let resync = (j..new.len())
+            .find_map(|k| next_in_old(&new[k], i).map(|found| (found, k)))
+            .unwrap_or((old.len(), new.len()));
+        hunks.push((i..resync.0, j..resync.1));
+        (i, j) = resync;
+    }
+
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn text(words: usize) -> Vec<u8> {
+        (0..words)
+            .map(|word| format!("word{} ", word * 7919 % 1000))
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    const CDC: Chunking = Chunking::ContentDefined {
+        min: 16,
+        average: 64,
+        max: 256,
+    };
+
+    fn assert_verified(diff: &ChunkDiff, old: &[u8], new: &[u8]) {
+        for hunk in diff.hunks() {
+            assert_eq!(hunk.old.len(), hunk.old_chunks.len());
+            assert_eq!(hunk.new.len(), hunk.new_chunks.len());
+            for chunk in &hunk.old {
+                assert_eq!(chunk.verify(old, &diff.old_root()), Ok(()));
+            }
+            for chunk in &hunk.new {
+                assert_eq!(chunk.verify(new, &diff.new_root()), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_content_defined_chunks_survive_insertions() {
+        let old = text(2000);
+        let mut new = old.clone();
+        new.splice(5000..5000, b"an inserted sentence".iter().copied());
+
+        let fixed = ChunkDiff::new(&old, &new, Chunking::Fixed(64)).unwrap();
+        let defined = ChunkDiff::new(&old, &new, CDC).unwrap();
+
+        assert_verified(&fixed, &old, &new);
+        assert_verified(&defined, &old, &new);
+        assert_eq!(defined.hunks().len(), 1);
+        assert!(defined.delta_size() < 3 * 256);
+        assert!(fixed.delta_size() > new.len() / 2);
+    }
+
+    #[test]
+    fn test_chunks_cover_the_file() {
+        let data = text(500);
+        for chunking in [Chunking::Fixed(100), CDC] {
+            let chunks = chunking.split(&data).unwrap();
+            assert_eq!(chunks[0].start, 0);
+            assert_eq!(chunks.last().unwrap().end, data.len());
+            assert!(chunks.windows(2).all(|pair| pair[0].end == pair[1].start));
+        }
+        assert!(CDC
+            .split(&data)
+            .unwrap()
+            .iter()
+            .all(|chunk| chunk.len() <= 256));
+        assert_eq!(CDC.split(&[]).unwrap(), vec![0..0]);
+        assert!(Chunking::Fixed(0).split(&data).is_err());
+    }
+
+    #[test]
+    fn test_deletions_and_identical_files() {
+        let old = text(1000);
+        let new = [&old[..1000], &old[3000..]].concat();
+
+        let diff = ChunkDiff::new(&old, &new, CDC).unwrap();
+        assert_verified(&diff, &old, &new);
+        assert!(diff.hunks().iter().all(|hunk| hunk.new.len() <= 2));
+
+        assert!(ChunkDiff::new(&old, &old, CDC).unwrap().is_empty());
+        let emptied = ChunkDiff::new(&old, &[], CDC).unwrap();
+        assert_eq!(emptied.hunks().len(), 1);
+        assert_verified(&emptied, &old, &[]);
+    }
+}
@@ -0,0 +1,304 @@
+use serde_json::Value;
+
+use crate::config::MutationGuard;
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// A proof of a fixture this crate does not agree with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofMismatch {
+    pub leaf_index: usize,
+    /// Why the proof of the fixture does not verify, if it does not.
+    pub verification: Result<(), Error>,
+    /// Whether this crate generates the same proof.
+    pub reproduced: bool,
+}
+
+/// How this crate compares with one tree of a fixture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeReport {
+    pub leaf_count: usize,
+    /// The root this crate computes over the leaves of the fixture.
+    pub root: Hash,
+    pub root_matches: bool,
+    /// The levels of the fixture, if any, that differ from this crate's.
+    pub mismatched_levels: Vec<usize>,
+    pub proofs_checked: usize,
+    pub mismatched_proofs: Vec<ProofMismatch>,
+}
+
+impl TreeReport {
+    pub fn is_compatible(&self) -> bool {
+        self.root_matches && self.mismatched_levels.is_empty() && self.mismatched_proofs.is_empty()
+    }
+}
+
+/// The result of checking the trees of another implementation against
+/// this crate: whether it reproduces every root, level and proof of a
+/// fixture, and verifies every proof.
+///
+/// A fixture is a JSON object with a `profile` and either a single tree
+/// or a `vectors` array of trees, as `TestVector::generate_json` writes. A
+/// tree has hex `items`, hashed by this crate, or already hashed `leaves`,
+/// a hex `root`, and optionally its `levels` and the `proofs` of its
+/// leaves as `leaf_index`, `leaf_count` and hex `siblings`.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::CompatibilityReport;
+///
+/// let fixture = r#"{
+///     "profile": { "leaf": "sha256(item)", "node": "sha256(sorted pair)" },
+///     "items": ["4d6f726961", "4c6f7269656e"],
+///     "root": "0000000000000000000000000000000000000000000000000000000000000000"
+/// }"#;
+///
+/// let report = CompatibilityReport::check_fixture(fixture).unwrap();
+///
+/// assert!(!report.is_compatible());
+/// assert!(!report.trees[0].root_matches);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatibilityReport {
+    pub mutation_guard: MutationGuard,
+    pub trees: Vec<TreeReport>,
+}
+
+impl CompatibilityReport {
+    pub fn is_compatible(&self) -> bool {
+        self.trees.iter().all(TreeReport::is_compatible)
+    }
+
+    /// Checks the trees of a fixture, failing only if it is malformed or
+    /// describes a profile this crate does not implement.
+    pub fn check_fixture(fixture: &str) -> Result<Self, Error> {
+        let value: Value = serde_json::from_str(fixture)
+            .map_err(|error| Error::InvalidInput(error.to_string()))?;
+        let mutation_guard = parse_profile(&value["profile"])?;
+
+        let trees = match value.get("vectors") {
+            Some(vectors) => vectors
+                .as_array()
+                .ok_or_else(|| invalid("the vectors are not an array"))?
+                .iter()
+                .map(|tree| check_tree(tree, mutation_guard))
+                .collect::<Result<_, _>>()?,
+            None => vec![check_tree(&value, mutation_guard)?],
+        };
+
+        Ok(Self {
+            mutation_guard,
+            trees,
+        })
+    }
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidInput(format!("invalid fixture: {}", reason))
+}
+
+fn parse_profile(profile: &Value) -> Result<MutationGuard, Error> {
+    let expect = |field: &str, supported: &[&str]| match profile[field].as_str() {
+        None => Ok(()),
+        Some(value) if supported.contains(&value) => Ok(()),
+        Some(value) => Err(Error::InvalidInput(format!(
+            "the {} profile {} is not supported",
+            field, value
+        ))),
+    };
+    expect("leaf", &["sha256(item)"])?;
+    expect("node", &["sha256(sorted pair)"])?;
+    expect("odd_level", &["duplicate last node"])?;
+
+    match profile["mutation_guard"].as_str() {
+        None | Some("None") => Ok(MutationGuard::None),
+        Some("RejectDuplicateTrailingPairs") => Ok(MutationGuard::RejectDuplicateTrailingPairs),
+        Some("CommitLeafCount") => Ok(MutationGuard::CommitLeafCount),
+        Some(guard) => Err(Error::InvalidInput(format!(
+            "the mutation guard {} is not supported",
+            guard
+        ))),
+    }
+}
+
+fn parse_hash(value: &Value) -> Result<Hash, Error> {
+    value
+        .as_str()
+        .and_then(|hash| hex::decode(hash).ok())
+        .and_then(|hash| hash.try_into().ok())
+        .ok_or_else(|| invalid(&format!("{} is not a hex hash", value)))
+}
+
+fn parse_hashes(value: &Value) -> Result<Vec<Hash>, Error> {
+    value
+        .as_array()
+        .ok_or_else(|| invalid(&format!("{} is not an array of hashes", value)))?
+        .iter()
+        .map(parse_hash)
+        .collect()
+}
+
+fn parse_index(value: &Value) -> Result<usize, Error> {
+    value
+        .as_u64()
+        .and_then(|index| usize::try_from(index).ok())
+        .ok_or_else(|| invalid(&format!("{} is not an index", value)))
+}
+
+fn check_tree(tree: &Value, mutation_guard: MutationGuard) -> Result<TreeReport, Error> {
+    let leaves = match (tree.get("items"), tree.get("leaves")) {
+        (Some(items), None) => items
+            .as_array()
+            .ok_or_else(|| invalid("the items are not an array"))?
+            .iter()
+            .map(|item| {
+                item.as_str()
+                    .and_then(|item| hex::decode(item).ok())
+                    .map(|item| MerkleTree::hash(&item))
+                    .ok_or_else(|| invalid(&format!("{} is not a hex item", item)))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        (None, Some(leaves)) => parse_hashes(leaves)?,
+        _ => return Err(invalid("a tree needs either items or leaves")),
+    };
+    let ours = MerkleTree::builder()
+        .mutation_guard(mutation_guard)
+        .build_from_hashes(leaves)?;
+    let root = ours.root().expect("A tree has a root.");
+    let claimed_root = parse_hash(&tree["root"])?;
+
+    let mut mismatched_levels = Vec::new();
+    if let Some(levels) = tree.get("levels") {
+        let levels = levels
+            .as_array()
+            .ok_or_else(|| invalid("the levels are not an array"))?;
+        let our_levels: Vec<&[Hash]> = ours.level_roots().collect();
+        for (level, nodes) in levels.iter().enumerate() {
+            if our_levels.get(level).copied() != Some(parse_hashes(nodes)?.as_slice()) {
+                mismatched_levels.push(level);
+            }
+        }
+        mismatched_levels.extend(levels.len()..our_levels.len());
+    }
+
+    let proofs = match tree.get("proofs") {
+        Some(proofs) => proofs
+            .as_array()
+            .ok_or_else(|| invalid("the proofs are not an array"))?
+            .clone(),
+        None => Vec::new(),
+    };
+    let mut mismatched_proofs = Vec::new();
+    for proof in &proofs {
+        let proof = Proof {
+            leaf_index: parse_index(&proof["leaf_index"])?,
+            leaf_count: parse_index(&proof["leaf_count"])?,
+            siblings: parse_hashes(&proof["siblings"])?,
+        };
+
+        let verification = match ours.node(0, proof.leaf_index) {
+            None => Err(Error::IndexOutOfRange {
+                index: proof.leaf_index,
+                leaf_count: ours.leaf_count(),
+            }),
+            Some(leaf) if mutation_guard == MutationGuard::CommitLeafCount => {
+                proof.verify_committed(&leaf, &claimed_root)
+            }
+            Some(leaf) => proof.verify(&leaf, &claimed_root),
+        };
+        let reproduced = ours.proof(proof.leaf_index).as_ref() == Ok(&proof);
+
+        if verification.is_err() || !reproduced {
+            mismatched_proofs.push(ProofMismatch {
+                leaf_index: proof.leaf_index,
+                verification,
+                reproduced,
+            });
+        }
+    }
+
+    Ok(TreeReport {
+        leaf_count: ours.leaf_count(),
+        root,
+        root_matches: root == claimed_root,
+        mismatched_levels,
+        proofs_checked: proofs.len(),
+        mismatched_proofs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::vectors::{TestVector, VECTOR_SIZES};
+
+    #[test]
+    fn test_generated_vectors_are_compatible() {
+        for guard in [MutationGuard::None, MutationGuard::CommitLeafCount] {
+            let builder = MerkleTree::builder().mutation_guard(guard);
+            let fixture = TestVector::generate_json(&builder).unwrap();
+
+            let report = CompatibilityReport::check_fixture(&fixture).unwrap();
+
+            assert!(report.is_compatible());
+            assert_eq!(report.mutation_guard, guard);
+            assert_eq!(report.trees.len(), VECTOR_SIZES.len());
+            assert_eq!(report.trees[4].proofs_checked, 5);
+        }
+    }
+
+    #[test]
+    fn test_mismatches_are_reported() {
+        let vector = TestVector::generate(&MerkleTree::builder()).unwrap()[5].to_json();
+        let mut fixture = serde_json::json!({ "profile": {}, "vectors": [vector] });
+        let tree = &mut fixture["vectors"][0];
+        tree["levels"][1][2] = Value::String(hex::encode(MerkleTree::hash(b"Mordor")));
+        tree["proofs"][3]["siblings"][0] = tree["proofs"][3]["siblings"][1].clone();
+        tree["proofs"][5]["leaf_index"] = 4.into();
+
+        let report = CompatibilityReport::check_fixture(&fixture.to_string()).unwrap();
+
+        let tree = &report.trees[0];
+        assert!(!report.is_compatible());
+        assert!(tree.root_matches);
+        assert_eq!(tree.mismatched_levels, [1]);
+        let mismatched: Vec<(usize, bool, bool)> = tree
+            .mismatched_proofs
+            .iter()
+            .map(|mismatch| {
+                (
+                    mismatch.leaf_index,
+                    mismatch.verification.is_ok(),
+                    mismatch.reproduced,
+                )
+            })
+            .collect();
+        assert_eq!(mismatched, [(3, false, false), (4, false, false)]);
+    }
+
+    #[test]
+    fn test_malformed_fixtures_are_rejected() {
+        let root = hex::encode(MerkleTree::hash(b"Moria"));
+        let fixtures = [
+            "not json".to_string(),
+            format!(r#"{{ "root": "{}" }}"#, root),
+            format!(r#"{{ "items": ["zz"], "root": "{}" }}"#, root),
+            format!(r#"{{ "items": [], "root": "{}" }}"#, root),
+            r#"{ "items": ["4d6f726961"], "root": "4d6f726961" }"#.to_string(),
+            format!(
+                r#"{{ "profile": {{ "node": "keccak256(pair)" }}, "items": ["00"], "root": "{}" }}"#,
+                root
+            ),
+        ];
+
+        for fixture in fixtures {
+            assert!(
+                CompatibilityReport::check_fixture(&fixture).is_err(),
+                "{}",
+                fixture
+            );
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use crate::append::AppendProof;
+use crate::audit_log::Checkpoint;
+use crate::consistency::ConsistencyProof;
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+
+/// The reader's side of a `MerkleLogWriter`: tails the log and only moves
+/// to a new checkpoint once the new lines and the consistency proof from
+/// the last verified checkpoint are checked.
+///
+/// Besides the checkpoint it keeps the path of the last line, enough to
+/// compute the root after any appended lines without the earlier ones.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{LogFollower, MerkleLogWriter};
+///
+/// let mut writer = MerkleLogWriter::new(Vec::new(), Vec::new(), 0);
+/// writer.append("the beacon of Amon Din is lit").unwrap();
+/// let first = writer.checkpoint().unwrap();
+///
+/// let mut follower = LogFollower::new(first, writer.append_proof(first.size).unwrap()).unwrap();
+///
+/// writer.append("the beacon of Eilenach is lit").unwrap();
+/// let second = writer.checkpoint().unwrap();
+/// let consistency = writer.prove_consistency(first.size, second.size).unwrap();
+///
+/// follower
+///     .advance(&["the beacon of Eilenach is lit"], second, &consistency)
+///     .unwrap();
+/// assert_eq!(follower.checkpoint(), second);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LogFollower {
+    checkpoint: Checkpoint,
+    frontier: AppendProof,
+}
+
+impl LogFollower {
+    /// Starts following from a trusted checkpoint and the append proof of
+    /// the log at that checkpoint.
+    pub fn new(checkpoint: Checkpoint, frontier: AppendProof) -> Result<Self, Error> {
+        if frontier.old_size != checkpoint.size {
+            return Err(Error::InvalidInput(format!(
+                "the append proof is for a log of {} lines, the checkpoint has {}",
+                frontier.old_size, checkpoint.size
+            )));
+        }
+        frontier.verify(&checkpoint.root, &[], &checkpoint.root)?;
+
+        Ok(Self {
+            checkpoint,
+            frontier,
+        })
+    }
+
+    /// Starts following after reading every line of the log so far.
+    pub fn from_lines<T: AsRef<str>>(lines: &[T]) -> Result<Self, Error> {
+        let leaves = Self::leaves(0, lines);
+        let tree = MerkleTree::from_leaves(leaves).ok_or(Error::EmptyTree)?;
+
+        Ok(Self {
+            checkpoint: Checkpoint {
+                size: tree.leaf_count(),
+                root: tree.root().expect("A tree has a root."),
+            },
+            frontier: tree.append_proof(),
+        })
+    }
+
+    /// The last verified checkpoint.
+    pub fn checkpoint(&self) -> Checkpoint {
+        self.checkpoint
+    }
+
+    /// Moves to `checkpoint` if it extends the last verified one, as shown
+    /// by `consistency`, and `lines` are exactly the lines appended since.
+    /// Nothing changes when a check fails.
+    pub fn advance<T: AsRef<str>>(
+        &mut self,
+        lines: &[T],
+        checkpoint: Checkpoint,
+        consistency: &ConsistencyProof,
+    ) -> Result<(), Error> {
+        let expected = self.checkpoint.size + lines.len();
+        if checkpoint.size != expected {
+            return Err(Error::InvalidInput(format!(
+                "{} lines after {} make a log of {}, the checkpoint has {}",
+                lines.len(),
+                self.checkpoint.size,
+                expected,
+                checkpoint.size
+            )));
+        }
+        if consistency.old_size != self.checkpoint.size || consistency.new_size != checkpoint.size {
+            return Err(Error::InvalidInput(format!(
+                "the proof is from {} to {} lines, not from {} to {}",
+                consistency.old_size, consistency.new_size, self.checkpoint.size, checkpoint.size
+            )));
+        }
+        consistency.verify(&self.checkpoint.root, &checkpoint.root)?;
+
+        let leaves = Self::leaves(self.checkpoint.size, lines);
+        self.frontier
+            .verify(&self.checkpoint.root, &leaves, &checkpoint.root)?;
+
+        self.frontier = self.frontier.extended(&leaves)?;
+        self.checkpoint = checkpoint;
+        Ok(())
+    }
+
+    /// The leaves of `lines`, the first one at index `first`.
+    fn leaves<T: AsRef<str>>(first: usize, lines: &[T]) -> Vec<Hash> {
+        lines
+            .iter()
+            .enumerate()
+            .map(|(offset, line)| Checkpoint::leaf_hash(first + offset, line.as_ref()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::audit_log::MerkleLogWriter;
+
+    fn line(index: usize) -> String {
+        format!("errand rider {} passed the Rammas Echor", index)
+    }
+
+    #[test]
+    fn test_follower_tails_a_growing_log() {
+        let mut writer = MerkleLogWriter::new(Vec::new(), Vec::new(), 0);
+        let lines: Vec<String> = (0..60).map(line).collect();
+        writer.append(&lines[0]).unwrap();
+        writer.append(&lines[1]).unwrap();
+        let mut follower = LogFollower::from_lines(&lines[..2]).unwrap();
+
+        let mut size = 2;
+        for batch in 1..10 {
+            for line in &lines[size..size + batch] {
+                writer.append(line).unwrap();
+            }
+            let checkpoint = writer.checkpoint().unwrap();
+            let consistency = writer.prove_consistency(size, checkpoint.size).unwrap();
+
+            follower
+                .advance(&lines[size..size + batch], checkpoint, &consistency)
+                .unwrap();
+            size += batch;
+        }
+
+        assert_eq!(follower.checkpoint(), *writer.checkpoints().last().unwrap());
+    }
+
+    #[test]
+    fn test_rewritten_lines_are_rejected() {
+        let mut writer = MerkleLogWriter::new(Vec::new(), Vec::new(), 0);
+        for index in 0..5 {
+            writer.append(&line(index)).unwrap();
+        }
+        let start = writer.checkpoint().unwrap();
+        let mut follower = LogFollower::new(start, writer.append_proof(5).unwrap()).unwrap();
+
+        for index in 5..8 {
+            writer.append(&line(index)).unwrap();
+        }
+        let checkpoint = writer.checkpoint().unwrap();
+        let consistency = writer.prove_consistency(5, 8).unwrap();
+
+        let forged = [line(5), "the rider was never sent".to_string(), line(7)];
+        assert_eq!(
+            follower.advance(&forged, checkpoint, &consistency),
+            Err(Error::RootMismatch)
+        );
+        assert!(follower
+            .advance(&forged[..2], checkpoint, &consistency)
+            .is_err());
+        assert_eq!(follower.checkpoint(), start);
+
+        let lines: Vec<String> = (5..8).map(line).collect();
+        assert_eq!(follower.advance(&lines, checkpoint, &consistency), Ok(()));
+    }
+
+    #[test]
+    fn test_frontier_must_match_the_checkpoint() {
+        let mut writer = MerkleLogWriter::new(Vec::new(), Vec::new(), 0);
+        for index in 0..4 {
+            writer.append(&line(index)).unwrap();
+        }
+        let checkpoint = writer.checkpoint().unwrap();
+
+        assert!(LogFollower::new(checkpoint, writer.append_proof(3).unwrap()).is_err());
+        assert!(LogFollower::from_lines::<String>(&[]).is_err());
+    }
+}
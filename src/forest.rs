@@ -0,0 +1,231 @@
+use std::collections::BTreeMap;
+
+use crate::error::Error;
+use crate::leaf::MerkleLeaf;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+use crate::store::NodeStore;
+
+/// The leaf of a tree in the meta-tree of a forest, its name followed by its root.
+fn tree_leaf(name: &str, root: &Hash) -> Hash {
+    let mut bytes = name.leaf_bytes();
+    bytes.extend_from_slice(root);
+    MerkleTree::hash(&bytes)
+}
+
+/// A proof that a leaf is in a named tree of a forest with a given meta-root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForestProof {
+    pub tree: String,
+    pub tree_root: Hash,
+    /// The proof of the leaf in its tree.
+    pub proof: Proof,
+    /// The proof of the tree in the meta-tree.
+    pub meta_proof: Proof,
+}
+
+impl ForestProof {
+    /// Checks that `leaf` is in the named tree under `meta_root`.
+    pub fn verify(&self, leaf: &Hash, meta_root: &Hash) -> Result<(), Error> {
+        self.proof.verify(leaf, &self.tree_root)?;
+        self.meta_proof
+            .verify(&tree_leaf(&self.tree, &self.tree_root), meta_root)
+    }
+}
+
+/// Many named trees over one storage backend, committed together by a
+/// meta-root over their names and roots, for multi-tenant services.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{Forest, MemoryNodeStore, MerkleTree};
+///
+/// let mut forest = Forest::open(MemoryNodeStore::new()).unwrap();
+/// forest.append("elves", &"Glorfindel").unwrap();
+/// forest.append("elves", &"Erestor").unwrap();
+/// forest.append("dwarves", &"Dáin").unwrap();
+///
+/// let proof = forest.prove("elves", 1).unwrap();
+///
+/// let meta_root = forest.meta_root().unwrap();
+/// assert!(proof.verify(&MerkleTree::hash(b"Erestor"), &meta_root).is_ok());
+/// ```
+#[derive(Debug)]
+pub struct Forest<S: NodeStore> {
+    store: S,
+    roots: BTreeMap<String, Hash>,
+}
+
+impl<S: NodeStore> Forest<S> {
+    /// Opens the forest of the trees already in `store`.
+    pub fn open(store: S) -> Result<Self, Error> {
+        let roots = store
+            .trees()?
+            .into_iter()
+            .map(|name| {
+                let root = MerkleTree::stored_root(&store, &name)?;
+                Ok((name, root))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { store, roots })
+    }
+
+    /// Stores `tree` under `name`, replacing the tree of that name.
+    pub fn insert_tree(&mut self, name: &str, tree: &MerkleTree) -> Result<(), Error> {
+        tree.store(&mut self.store, name)?;
+        self.roots
+            .insert(name.to_string(), tree.root().expect("A tree has a root."));
+        Ok(())
+    }
+
+    /// Appends an item to the tree `name`, creating it if needed, and
+    /// returns its new root. Only the nodes that change are written.
+    pub fn append<T: AsRef<[u8]>>(&mut self, name: &str, item: &T) -> Result<Hash, Error> {
+        let tree = match self.roots.contains_key(name) {
+            true => {
+                let mut tree = MerkleTree::load(&self.store, name)?;
+                tree.insert(item)?;
+                tree
+            }
+            false => MerkleTree::build(&[item]).expect("The tree has an item."),
+        };
+
+        tree.store_from(&mut self.store, name, tree.leaf_count() - 1)?;
+        let root = tree.root().expect("A tree has a root.");
+        self.roots.insert(name.to_string(), root);
+        Ok(root)
+    }
+
+    /// Removes the tree `name` and returns whether it existed.
+    pub fn remove_tree(&mut self, name: &str) -> Result<bool, Error> {
+        self.store.remove_tree(name)?;
+        Ok(self.roots.remove(name).is_some())
+    }
+
+    /// The root of the tree `name`.
+    pub fn root(&self, name: &str) -> Option<Hash> {
+        self.roots.get(name).copied()
+    }
+
+    /// The names of the trees, sorted.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.roots.keys().map(String::as_str)
+    }
+
+    /// The root of the meta-tree over the name and root of every tree,
+    /// sorted by name, `None` for an empty forest.
+    pub fn meta_root(&self) -> Option<Hash> {
+        self.meta_tree().and_then(|tree| tree.root())
+    }
+
+    fn meta_tree(&self) -> Option<MerkleTree> {
+        MerkleTree::from_leaves(
+            self.roots
+                .iter()
+                .map(|(name, root)| tree_leaf(name, root))
+                .collect(),
+        )
+    }
+
+    /// Proves the leaf at `index` of the tree `name` against the meta-root.
+    pub fn prove(&self, name: &str, index: usize) -> Result<ForestProof, Error> {
+        let tree_root = self
+            .root(name)
+            .ok_or_else(|| Error::InvalidInput(format!("the forest has no tree {}", name)))?;
+        let position = self.roots.keys().position(|key| key == name).unwrap();
+        let meta_tree = self.meta_tree().expect("The forest has a tree.");
+
+        Ok(ForestProof {
+            tree: name.to_string(),
+            tree_root,
+            proof: MerkleTree::stored_proof(&self.store, name, index)?,
+            meta_proof: meta_tree.proof(position)?,
+        })
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn into_store(self) -> S {
+        self.store
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::store::MemoryNodeStore;
+
+    fn forest() -> Forest<MemoryNodeStore> {
+        let mut forest = Forest::open(MemoryNodeStore::new()).unwrap();
+        for tenant in ["rohan", "gondor", "dale"] {
+            for record in 0..7 {
+                forest
+                    .append(tenant, &format!("{} record {}", tenant, record))
+                    .unwrap();
+            }
+        }
+        forest
+    }
+
+    #[test]
+    fn test_every_leaf_is_proven_against_the_meta_root() {
+        let forest = forest();
+        let meta_root = forest.meta_root().unwrap();
+
+        for tenant in ["dale", "gondor", "rohan"] {
+            for record in 0..7 {
+                let proof = forest.prove(tenant, record).unwrap();
+                let leaf = MerkleTree::hash(format!("{} record {}", tenant, record).as_bytes());
+                assert_eq!(proof.verify(&leaf, &meta_root), Ok(()));
+            }
+        }
+
+        let mut proof = forest.prove("dale", 0).unwrap();
+        proof.tree = "gondor".to_string();
+        assert!(proof
+            .verify(&MerkleTree::hash(b"dale record 0"), &meta_root)
+            .is_err());
+    }
+
+    #[test]
+    fn test_reopened_forests_keep_their_roots() {
+        let forest = forest();
+        let meta_root = forest.meta_root();
+        let roots: Vec<_> = forest.names().map(|name| forest.root(name)).collect();
+
+        let reopened = Forest::open(forest.into_store()).unwrap();
+
+        assert_eq!(reopened.meta_root(), meta_root);
+        assert_eq!(
+            reopened.names().collect::<Vec<_>>(),
+            vec!["dale", "gondor", "rohan"]
+        );
+        assert_eq!(
+            reopened
+                .names()
+                .map(|name| reopened.root(name))
+                .collect::<Vec<_>>(),
+            roots
+        );
+    }
+
+    #[test]
+    fn test_tree_changes_update_the_meta_root() {
+        let mut forest = forest();
+        let meta_root = forest.meta_root();
+
+        forest
+            .insert_tree("rohan", &MerkleTree::build(&["Edoras"]).unwrap())
+            .unwrap();
+        assert_ne!(forest.meta_root(), meta_root);
+
+        assert_eq!(forest.remove_tree("rohan"), Ok(true));
+        assert_eq!(forest.remove_tree("rohan"), Ok(false));
+        assert!(forest.prove("rohan", 0).is_err());
+        assert_eq!(forest.names().count(), 2);
+    }
+}
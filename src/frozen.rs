@@ -0,0 +1,218 @@
+use crate::config::MutationGuard;
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// An immutable tree laid out for serving proofs, made by `MerkleTree::freeze`.
+///
+/// Every level is stored in one contiguous slice, a proof walk reads one
+/// node per level at a computed offset, and the leaves are indexed by hash.
+/// The tree has no mutation API nor observers, so it is `Send + Sync` and
+/// can be shared between the threads of a server behind an `Arc`.
+///
+/// # Examples
+/// ```
+/// use std::sync::Arc;
+/// use merkle_tree::MerkleTree;
+///
+/// let tree = MerkleTree::build(&["Erebor", "Dale", "Esgaroth"]).unwrap();
+/// let root = tree.root().unwrap();
+/// let frozen = Arc::new(tree.freeze());
+///
+/// let server = Arc::clone(&frozen);
+/// let proof = std::thread::spawn(move || server.proof(1)).join().unwrap().unwrap();
+///
+/// assert_eq!(frozen.root(), root);
+/// assert!(proof.verify(&MerkleTree::hash("Dale".as_bytes()), &root).is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrozenMerkleTree {
+    /// The nodes of every level, from the leaves up to the root.
+    nodes: Box<[Hash]>,
+    /// The offset in `nodes` of every level.
+    offsets: Box<[usize]>,
+    /// The leaves sorted by hash with their index, the first on duplicates.
+    index: Box<[(Hash, usize)]>,
+    root: Hash,
+    mutation_guard: MutationGuard,
+}
+
+impl MerkleTree {
+    /// Freezes the tree into an immutable, query-optimized `FrozenMerkleTree`
+    /// with the same root and proofs.
+    pub fn freeze(&self) -> FrozenMerkleTree {
+        let mut nodes = Vec::new();
+        let mut offsets = Vec::with_capacity(self.height() + 1);
+        for level in self.level_roots() {
+            offsets.push(nodes.len());
+            nodes.extend_from_slice(level);
+        }
+
+        let mut index: Vec<(Hash, usize)> = self
+            .leaves()
+            .iter()
+            .enumerate()
+            .map(|(position, leaf)| (*leaf, position))
+            .collect();
+        index.sort();
+        index.dedup_by_key(|(leaf, _)| *leaf);
+
+        FrozenMerkleTree {
+            nodes: nodes.into_boxed_slice(),
+            offsets: offsets.into_boxed_slice(),
+            index: index.into_boxed_slice(),
+            root: self.root().expect("A tree has a root."),
+            mutation_guard: self.mutation_guard(),
+        }
+    }
+}
+
+impl FrozenMerkleTree {
+    /// The root, committed to the number of leaves with `MutationGuard::CommitLeafCount`.
+    pub fn root(&self) -> Hash {
+        self.root
+    }
+
+    pub fn mutation_guard(&self) -> MutationGuard {
+        self.mutation_guard
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.offsets.get(1).copied().unwrap_or(self.nodes.len())
+    }
+
+    /// The number of levels above the leaves.
+    pub fn height(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// The node at `index` of `level`, the leaves being level 0.
+    pub fn node(&self, level: usize, index: usize) -> Option<Hash> {
+        let start = *self.offsets.get(level)?;
+        let end = self
+            .offsets
+            .get(level + 1)
+            .copied()
+            .unwrap_or(self.nodes.len());
+        self.nodes[start..end].get(index).copied()
+    }
+
+    /// The index of the first leaf with the given hash.
+    pub fn index_of(&self, leaf: &Hash) -> Option<usize> {
+        let found = self
+            .index
+            .binary_search_by(|(candidate, _)| candidate.cmp(leaf))
+            .ok()?;
+        Some(self.index[found].1)
+    }
+
+    /// Returns the proof of inclusion of the leaf at `index`.
+    pub fn proof(&self, index: usize) -> Result<Proof, Error> {
+        let mut siblings = vec![[0; 32]; self.height()];
+        self.proof_into(index, &mut siblings)?;
+
+        Ok(Proof {
+            leaf_index: index,
+            leaf_count: self.leaf_count(),
+            siblings,
+        })
+    }
+
+    /// Writes the siblings of the proof of the leaf at `index` at the start
+    /// of `buffer` and returns their number, as `MerkleTree::proof_into`.
+    pub fn proof_into(&self, index: usize, buffer: &mut [Hash]) -> Result<usize, Error> {
+        let leaf_count = self.leaf_count();
+        if index >= leaf_count {
+            return Err(Error::IndexOutOfRange { index, leaf_count });
+        }
+
+        let height = self.height();
+        if buffer.len() < height {
+            return Err(Error::InvalidInput(format!(
+                "the proof has {} hashes, the buffer holds {}",
+                height,
+                buffer.len()
+            )));
+        }
+
+        for (level, sibling) in buffer[..height].iter_mut().enumerate() {
+            let position = index >> level;
+            let width = self.offsets[level + 1] - self.offsets[level];
+            let neighbour = if position ^ 1 < width {
+                position ^ 1
+            } else {
+                position
+            };
+            *sibling = self.nodes[self.offsets[level] + neighbour];
+        }
+
+        Ok(height)
+    }
+
+    /// Checks that `proof` leads from `leaf` to the root of this tree.
+    pub fn verify_proof(&self, leaf: &Hash, proof: &Proof) -> Result<(), Error> {
+        if proof.leaf_count != self.leaf_count() {
+            return Err(Error::InvalidInput(format!(
+                "the proof is for a tree of {} leaves, not {}",
+                proof.leaf_count,
+                self.leaf_count()
+            )));
+        }
+
+        match self.mutation_guard {
+            MutationGuard::CommitLeafCount => proof.verify_committed(leaf, &self.root),
+            _ => proof.verify(leaf, &self.root),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_frozen_trees_serve_the_same_proofs() {
+        for count in [1, 2, 3, 7, 8, 100] {
+            let items: Vec<String> = (0..count).map(|item| format!("beacon {}", item)).collect();
+            let tree = MerkleTree::build(&items).unwrap();
+            let frozen = tree.freeze();
+
+            assert_eq!(Some(frozen.root()), tree.root());
+            assert_eq!(frozen.leaf_count(), count);
+            assert_eq!(frozen.height(), tree.height());
+            for (index, item) in items.iter().enumerate() {
+                let leaf = MerkleTree::hash(item.as_bytes());
+                assert_eq!(frozen.proof(index), tree.proof(index));
+                assert_eq!(frozen.index_of(&leaf), Some(index));
+                assert_eq!(
+                    frozen.verify_proof(&leaf, &frozen.proof(index).unwrap()),
+                    Ok(())
+                );
+            }
+            assert_eq!(frozen.node(0, count), None);
+            assert_eq!(frozen.node(tree.height(), 0), Some(frozen.root()));
+            assert!(frozen.proof(count).is_err());
+        }
+    }
+
+    #[test]
+    fn test_frozen_trees_keep_the_mutation_guard() {
+        let tree = MerkleTree::builder()
+            .mutation_guard(MutationGuard::CommitLeafCount)
+            .build(&["Amon Dîn", "Eilenach", "Nardol"])
+            .unwrap();
+        let frozen = tree.freeze();
+        let leaf = MerkleTree::hash("Nardol".as_bytes());
+
+        assert_eq!(Some(frozen.root()), tree.root());
+        assert_eq!(
+            frozen.verify_proof(&leaf, &frozen.proof(2).unwrap()),
+            Ok(())
+        );
+        assert!(frozen.index_of(&MerkleTree::hash(b"Halifirien")).is_none());
+
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<FrozenMerkleTree>();
+    }
+}
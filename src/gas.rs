@@ -0,0 +1,317 @@
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::ProofError;
+
+/// Calldata costs 4 gas per zero byte and 16 per nonzero byte (EIP-2028).
+const ZERO_BYTE_GAS: u64 = 4;
+const NONZERO_BYTE_GAS: u64 = 16;
+/// A warm `STATICCALL` to the SHA-256 precompile, 100 gas, plus the
+/// precompile itself on the 64 bytes of a pair, 60 + 12 * 2 gas.
+const PAIR_HASH_GAS: u64 = 100 + 84;
+/// The loop of a verifier around each hash: reading the bits of the step,
+/// loading the sibling and writing the pair to memory.
+const STEP_GAS: u64 = 40;
+
+/// A proof laid out for cheap verification by a smart contract, as the
+/// ABI encoding of `(uint256 path, bytes32[] siblings)`.
+///
+/// The `path` word packs every per-step flag instead of sending one word
+/// per step: the number of steps in its top byte, a bit per step telling
+/// whether the node is hashed second in bits 0 to 63, and a bit per step
+/// telling whether the node has no sibling in bits 64 to 127. The node is
+/// then hashed with itself and its sibling is left out of the calldata.
+///
+/// A verifier folds from the leaf: at step `i` the sibling is the node if
+/// bit `64 + i` is set, else the next word of `siblings`, and the pair is
+/// `sha256(sibling || node)` if bit `i` is set, else `sha256(node || sibling)`.
+/// The bits follow the order of the hashes, so the contract sorts nothing.
+///
+/// A proof truncated at a level stops at the ancestor there, for contracts
+/// that already store the roots of subtrees.
+///
+/// Nothing in the proof fixes the number of steps, and a proof with fewer
+/// steps passes an inner node for a leaf. A contract must hard-code the
+/// height of its tree, or the level of the ancestors it stores, and reject
+/// any other number of steps, as `verify` does with the height it is given.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::MerkleTree;
+///
+/// let tree = MerkleTree::build(&["Elendil", "Isildur", "Anárion", "Meneldil", "Valandil"]).unwrap();
+///
+/// let proof = tree.gas_proof(4).unwrap();
+/// assert_eq!(proof.siblings.len(), 1);
+///
+/// let leaf = MerkleTree::hash(b"Valandil");
+/// assert!(proof.verify(&leaf, &tree.root().unwrap(), tree.height()).is_ok());
+/// assert_eq!(proof.calldata().len() % 32, 0);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasProof {
+    pub steps: usize,
+    /// Bit `i` is set when the node of step `i` is hashed second.
+    pub order: u64,
+    /// Bit `i` is set when the node of step `i` has no sibling.
+    pub padding: u64,
+    /// The siblings of the steps that have one, from the leaf up.
+    pub siblings: Vec<Hash>,
+}
+
+impl GasProof {
+    /// The `path` word of the calldata.
+    pub fn path(&self) -> Hash {
+        let mut word = [0; 32];
+        word[0] = self.steps as u8;
+        word[16..24].copy_from_slice(&self.padding.to_be_bytes());
+        word[24..].copy_from_slice(&self.order.to_be_bytes());
+        word
+    }
+
+    /// The ABI encoding of `(uint256 path, bytes32[] siblings)`: the path,
+    /// the offset and the length of the array, then one word per sibling.
+    pub fn calldata(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(32 * (3 + self.siblings.len()));
+        out.extend_from_slice(&self.path());
+        out.extend_from_slice(&word(64));
+        out.extend_from_slice(&word(self.siblings.len() as u64));
+        for sibling in &self.siblings {
+            out.extend_from_slice(sibling);
+        }
+        out
+    }
+
+    /// Decodes the output of `calldata`, checking its layout and that the
+    /// number of siblings matches the steps that have one.
+    pub fn from_calldata(bytes: &[u8]) -> Result<Self, Error> {
+        let invalid = |reason: &str| Error::InvalidInput(format!("invalid gas proof: {}", reason));
+        if bytes.len() < 96 || !bytes.len().is_multiple_of(32) {
+            return Err(invalid("the calldata is not whole words"));
+        }
+        let (path, rest) = bytes.split_at(32);
+        let (head, siblings) = rest.split_at(64);
+        if head[..32] != word(64) || head[32..] != word((siblings.len() / 32) as u64) {
+            return Err(invalid("the siblings are not a single array"));
+        }
+        if path[1..16].iter().any(|byte| *byte != 0) {
+            return Err(invalid("unknown bits in the path"));
+        }
+
+        let steps = path[0] as usize;
+        let order = u64::from_be_bytes(path[24..].try_into().expect("The path has 8 bytes."));
+        let padding = u64::from_be_bytes(path[16..24].try_into().expect("The path has 8 bytes."));
+        let beyond = u64::MAX.checked_shl(steps as u32).unwrap_or(0);
+        if steps > 64 || (order | padding) & beyond != 0 {
+            return Err(invalid("flags beyond the last step"));
+        }
+        if siblings.len() / 32 + padding.count_ones() as usize != steps {
+            return Err(invalid("the siblings do not match the steps"));
+        }
+
+        Ok(Self {
+            steps,
+            order,
+            padding,
+            siblings: siblings
+                .chunks_exact(32)
+                .map(|sibling| sibling.try_into().expect("A word has 32 bytes."))
+                .collect(),
+        })
+    }
+
+    /// Folds the proof from `leaf` as a contract would, rejecting a proof
+    /// whose number of steps is not `height`.
+    pub fn compute_root(&self, leaf: &Hash, height: usize) -> Result<Hash, Error> {
+        if self.steps != height {
+            return Err(Error::InvalidProof(ProofError::LengthMismatch {
+                expected: height,
+                actual: self.steps,
+            }));
+        }
+
+        let mut siblings = self.siblings.iter();
+        let mut node = *leaf;
+        for step in 0..self.steps.min(64) {
+            let sibling = if self.padding >> step & 1 == 1 {
+                node
+            } else {
+                *siblings.next().ok_or_else(|| {
+                    Error::InvalidInput(format!("the step {} has no sibling", step))
+                })?
+            };
+            node = if self.order >> step & 1 == 1 {
+                MerkleTree::hash(&[sibling, node].concat())
+            } else {
+                MerkleTree::hash(&[node, sibling].concat())
+            };
+        }
+        if self.steps > 64 || siblings.next().is_some() {
+            return Err(Error::InvalidInput(
+                "the proof has more siblings than steps".to_string(),
+            ));
+        }
+        Ok(node)
+    }
+
+    /// Checks that the proof leads from `leaf` to `root` in a tree of
+    /// `height`, or to the known ancestor at level `height` it was truncated at.
+    pub fn verify(&self, leaf: &Hash, root: &Hash, height: usize) -> Result<(), Error> {
+        if self.compute_root(leaf, height)? != *root {
+            return Err(Error::RootMismatch);
+        }
+        Ok(())
+    }
+
+    /// An estimate of the gas a contract spends on the proof: its calldata
+    /// at the EIP-2028 prices and one SHA-256 precompile call per step.
+    /// The leaf, the root and the call itself are not counted.
+    pub fn estimated_gas(&self) -> u64 {
+        let calldata: u64 = self
+            .calldata()
+            .iter()
+            .map(|byte| match byte {
+                0 => ZERO_BYTE_GAS,
+                _ => NONZERO_BYTE_GAS,
+            })
+            .sum();
+        calldata + self.steps as u64 * (PAIR_HASH_GAS + STEP_GAS)
+    }
+}
+
+fn word(value: u64) -> Hash {
+    let mut word = [0; 32];
+    word[24..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+impl MerkleTree {
+    /// Returns the gas optimized proof of the leaf at `index` up to the root.
+    pub fn gas_proof(&self, index: usize) -> Result<GasProof, Error> {
+        self.gas_proof_to_level(index, self.height())
+    }
+
+    /// Returns the gas optimized proof of the leaf at `index` truncated at
+    /// its ancestor at `level`, for a contract that stores that ancestor.
+    pub fn gas_proof_to_level(&self, index: usize, level: usize) -> Result<GasProof, Error> {
+        let proof = self.proof_to_level(index, level)?;
+        let mut node = self.node(0, index).ok_or(Error::IndexOutOfRange {
+            index,
+            leaf_count: self.leaf_count(),
+        })?;
+
+        let mut gas_proof = GasProof {
+            steps: proof.siblings.len(),
+            order: 0,
+            padding: 0,
+            siblings: Vec::new(),
+        };
+        for (step, sibling) in proof.siblings.iter().enumerate() {
+            if *sibling == node {
+                gas_proof.padding |= 1 << step;
+            } else {
+                gas_proof.siblings.push(*sibling);
+            }
+            if node > *sibling {
+                gas_proof.order |= 1 << step;
+            }
+            node = Self::merkle_parent(&[node, *sibling]);
+        }
+
+        Ok(gas_proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::estimate::ProofEncoding;
+
+    fn tree(count: usize) -> MerkleTree {
+        let items: Vec<String> = (0..count)
+            .map(|item| format!("beacon of Gondor {}", item))
+            .collect();
+        MerkleTree::build(&items).unwrap()
+    }
+
+    #[test]
+    fn test_gas_proofs_fold_to_the_root() {
+        for count in [1, 2, 7, 33] {
+            let tree = tree(count);
+            let root = tree.root().unwrap();
+            for index in 0..count {
+                let proof = tree.gas_proof(index).unwrap();
+                let leaf = tree.node(0, index).unwrap();
+                assert_eq!(proof.verify(&leaf, &root, tree.height()), Ok(()));
+
+                let decoded = GasProof::from_calldata(&proof.calldata()).unwrap();
+                assert_eq!(decoded, proof);
+                assert!(
+                    proof.calldata().len()
+                        <= ProofEncoding::SolidityCalldata.proof_bytes(proof.steps) + 32
+                );
+            }
+        }
+
+        let proof = tree(7).gas_proof(6).unwrap();
+        assert_eq!(proof.padding, 0b1);
+        assert!(proof
+            .verify(
+                &MerkleTree::hash(b"beacon of Rohan"),
+                &tree(7).root().unwrap(),
+                3
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_inner_nodes_do_not_pass_for_leaves() {
+        let tree = tree(4);
+        let root = tree.root().unwrap();
+        let forged = GasProof {
+            steps: 1,
+            order: 0,
+            padding: 0,
+            siblings: vec![tree.node(1, 1).unwrap()],
+        };
+        let inner = tree.node(1, 0).unwrap();
+
+        assert_eq!(
+            forged.verify(&inner, &root, tree.height()),
+            Err(Error::InvalidProof(ProofError::LengthMismatch {
+                expected: 2,
+                actual: 1
+            }))
+        );
+    }
+
+    #[test]
+    fn test_truncated_proofs_are_cheaper() {
+        let tree = tree(64);
+        let leaf = tree.node(0, 37).unwrap();
+
+        let full = tree.gas_proof(37).unwrap();
+        let truncated = tree.gas_proof_to_level(37, 4).unwrap();
+
+        assert_eq!(truncated.steps, 4);
+        assert_eq!(
+            truncated.verify(&leaf, &tree.node(4, 2).unwrap(), 4),
+            Ok(())
+        );
+        assert!(truncated.estimated_gas() < full.estimated_gas());
+        assert!(tree.gas_proof_to_level(37, 7).is_err());
+    }
+
+    #[test]
+    fn test_malformed_calldata_is_rejected() {
+        let mut calldata = tree(5).gas_proof(1).unwrap().calldata();
+
+        assert!(GasProof::from_calldata(&calldata[..calldata.len() - 32]).is_err());
+        assert!(GasProof::from_calldata(&calldata[..95]).is_err());
+        calldata[0] = 65;
+        assert!(GasProof::from_calldata(&calldata).is_err());
+        calldata[0] = 3;
+        calldata[31] |= 0b1000;
+        assert!(GasProof::from_calldata(&calldata).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use sha1::{Digest, Sha1};
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+
+/// The hash function of the objects of a Git repository.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitObjectFormat {
+    /// The 20 byte object IDs of most repositories.
+    Sha1,
+    /// The 32 byte object IDs of repositories created with `--object-format=sha256`.
+    Sha256,
+}
+
+impl GitObjectFormat {
+    /// The length of an object ID in bytes.
+    pub fn id_length(&self) -> usize {
+        match self {
+            Self::Sha1 => 20,
+            Self::Sha256 => 32,
+        }
+    }
+
+    /// Hashes `content` as a Git blob object, behind the `blob <len>\0`
+    /// header. SHA-1 IDs are padded with zeros to the length of a `Hash`.
+    pub fn blob_id(&self, content: &[u8]) -> Hash {
+        let object = [format!("blob {}\0", content.len()).as_bytes(), content].concat();
+
+        match self {
+            Self::Sha1 => {
+                let mut id = [0; 32];
+                id[..20].copy_from_slice(&Sha1::digest(&object));
+                id
+            }
+            Self::Sha256 => MerkleTree::hash(&object),
+        }
+    }
+
+    /// Formats a leaf as Git prints its object ID.
+    pub fn to_hex(&self, id: &Hash) -> String {
+        hex::encode(&id[..self.id_length()])
+    }
+
+    /// Parses an object ID printed by Git, e.g. by `git ls-files -s`, into a leaf.
+    pub fn from_hex(&self, id: &str) -> Result<Hash, Error> {
+        let bytes = hex::decode(id).map_err(|error| Error::Encoding(error.to_string()))?;
+        if bytes.len() != self.id_length() {
+            return Err(Error::InvalidInput(format!(
+                "a {:?} object ID has {} bytes, not {}",
+                self,
+                self.id_length(),
+                bytes.len()
+            )));
+        }
+
+        let mut leaf = [0; 32];
+        leaf[..bytes.len()].copy_from_slice(&bytes);
+        Ok(leaf)
+    }
+}
+
+impl MerkleTree {
+    /// Builds a tree whose leaves are the Git blob object IDs of `contents`,
+    /// so they compare directly with the IDs of the files of a repository.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::{GitObjectFormat, MerkleTree};
+    ///
+    /// let contents = ["", "Three Rings for the Elven-kings"];
+    /// let tree = MerkleTree::build_git_blobs(&contents, GitObjectFormat::Sha1).unwrap();
+    ///
+    /// let leaf = tree.node(0, 1).unwrap();
+    /// assert_eq!(
+    ///     GitObjectFormat::Sha1.to_hex(&leaf),
+    ///     "fbe09e17e34f5e90d20348c8c8377dc2af84ee9f"
+    /// );
+    /// ```
+    pub fn build_git_blobs<T: AsRef<[u8]>>(
+        contents: &[T],
+        format: GitObjectFormat,
+    ) -> Option<Self> {
+        let leaves = contents
+            .iter()
+            .map(|content| format.blob_id(content.as_ref()))
+            .collect();
+        Self::from_leaves(leaves)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_blob_ids_match_git() {
+        let vectors = [
+            (
+                "",
+                "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391",
+                "473a0f4c3be8a93681a267e3b1e9a7dcda1185436fe141f7749120a303721813",
+            ),
+            (
+                "Three Rings for the Elven-kings",
+                "fbe09e17e34f5e90d20348c8c8377dc2af84ee9f",
+                "10d32287a2414a3f366c7f21b93e9d35e95a036eaf7d377daff792867fda98fb",
+            ),
+        ];
+
+        for (content, sha1, sha256) in vectors {
+            let sha1_id = GitObjectFormat::Sha1.blob_id(content.as_bytes());
+            let sha256_id = GitObjectFormat::Sha256.blob_id(content.as_bytes());
+
+            assert_eq!(GitObjectFormat::Sha1.to_hex(&sha1_id), sha1);
+            assert_eq!(GitObjectFormat::Sha256.to_hex(&sha256_id), sha256);
+            assert_eq!(GitObjectFormat::Sha1.from_hex(sha1), Ok(sha1_id));
+            assert_eq!(GitObjectFormat::Sha256.from_hex(sha256), Ok(sha256_id));
+        }
+    }
+
+    #[test]
+    fn test_trees_over_listed_object_ids_match_trees_over_contents() {
+        let contents = ["Narya", "Nenya", "Vilya"];
+        let tree = MerkleTree::build_git_blobs(&contents, GitObjectFormat::Sha1).unwrap();
+
+        let listed: Vec<String> = contents
+            .iter()
+            .map(|content| {
+                let id = GitObjectFormat::Sha1.blob_id(content.as_bytes());
+                GitObjectFormat::Sha1.to_hex(&id)
+            })
+            .collect();
+        let leaves = listed
+            .iter()
+            .map(|id| GitObjectFormat::Sha1.from_hex(id))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(MerkleTree::from_leaves(leaves).unwrap().root(), tree.root());
+        let proof = tree.proof(2).unwrap();
+        assert!(proof
+            .verify(&tree.node(0, 2).unwrap(), &tree.root().unwrap())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_object_ids_of_the_other_format_are_rejected() {
+        let sha256 = "473a0f4c3be8a93681a267e3b1e9a7dcda1185436fe141f7749120a303721813";
+
+        assert!(GitObjectFormat::Sha1.from_hex(sha256).is_err());
+        assert!(GitObjectFormat::Sha256.from_hex(&sha256[..40]).is_err());
+        assert!(matches!(
+            GitObjectFormat::Sha256.from_hex("Mordor"),
+            Err(Error::Encoding(_))
+        ));
+    }
+}
@@ -0,0 +1,161 @@
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// The hash of an item, a leaf of a tree.
+///
+/// Leaves and internal nodes are both `Hash`es, so an internal node passed
+/// where a leaf is expected compiles and only fails when the proof does not
+/// lead to the root. The typed APIs take a `LeafHash`, which an internal
+/// node can only become through an explicit `LeafHash::new`.
+///
+/// ```compile_fail
+/// use merkle_tree::MerkleTree;
+///
+/// let tree = MerkleTree::build(&["Meriadoc", "Peregrin", "Samwise"]).unwrap();
+/// let node = tree.node_hash(1, 0).unwrap();
+///
+/// tree.proof_of_leaf(&node);
+/// ```
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LeafHash(Hash);
+
+/// The hash of a node above the leaves, or of the root.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeHash(Hash);
+
+impl LeafHash {
+    /// Wraps a hash known to be a leaf.
+    pub fn new(hash: Hash) -> Self {
+        Self(hash)
+    }
+
+    /// Hashes an item into its leaf.
+    pub fn of<T: AsRef<[u8]> + ?Sized>(item: &T) -> Self {
+        Self(MerkleTree::hash(item.as_ref()))
+    }
+
+    pub fn as_hash(&self) -> &Hash {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> Hash {
+        self.0
+    }
+}
+
+impl NodeHash {
+    /// Wraps a hash known to be a node above the leaves.
+    pub fn new(hash: Hash) -> Self {
+        Self(hash)
+    }
+
+    pub fn as_hash(&self) -> &Hash {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> Hash {
+        self.0
+    }
+}
+
+impl AsRef<[u8]> for LeafHash {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for NodeHash {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Proof {
+    /// Checks that the proof leads from `leaf` to `root`.
+    pub fn verify_leaf(&self, leaf: &LeafHash, root: &NodeHash) -> Result<(), Error> {
+        self.verify(leaf.as_hash(), root.as_hash())
+    }
+}
+
+impl MerkleTree {
+    /// The leaf at `index`.
+    pub fn leaf(&self, index: usize) -> Option<LeafHash> {
+        self.node(0, index).map(LeafHash)
+    }
+
+    /// The node at `index` of `level`, above the leaves.
+    pub fn node_hash(&self, level: usize, index: usize) -> Option<NodeHash> {
+        if level == 0 {
+            return None;
+        }
+        self.node(level, index).map(NodeHash)
+    }
+
+    /// The root, as a node even in a tree of one leaf.
+    pub fn root_hash(&self) -> Option<NodeHash> {
+        self.root().map(NodeHash)
+    }
+
+    /// Returns the proof of inclusion of the first occurrence of `leaf`.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::{LeafHash, MerkleTree};
+    ///
+    /// let tree = MerkleTree::build(&["Meriadoc", "Peregrin", "Samwise"]).unwrap();
+    /// let leaf = LeafHash::of("Peregrin");
+    ///
+    /// let proof = tree.proof_of_leaf(&leaf).unwrap();
+    /// assert!(proof.verify_leaf(&leaf, &tree.root_hash().unwrap()).is_ok());
+    /// ```
+    pub fn proof_of_leaf(&self, leaf: &LeafHash) -> Option<Proof> {
+        let index = (0..self.leaf_count()).find(|index| self.leaf(*index) == Some(*leaf))?;
+        self.proof(index).ok()
+    }
+
+    /// Checks a proof of `leaf` against this tree, after validating its shape.
+    pub fn verify_leaf(&self, leaf: &LeafHash, proof: &Proof) -> Result<(), Error> {
+        self.verify_proof(leaf.as_hash(), proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_typed_proofs_match_untyped_proofs() {
+        let items = ["Arnor", "Gondor", "Rohan", "Eriador", "Rhovanion"];
+        let tree = MerkleTree::build(&items).unwrap();
+        let root = tree.root_hash().unwrap();
+
+        for (index, item) in items.iter().enumerate() {
+            let leaf = LeafHash::of(item);
+            assert_eq!(tree.leaf(index), Some(leaf));
+
+            let proof = tree.proof_of_leaf(&leaf).unwrap();
+            assert_eq!(proof, tree.proof(index).unwrap());
+            assert_eq!(proof.verify_leaf(&leaf, &root), Ok(()));
+            assert_eq!(tree.verify_leaf(&leaf, &proof), Ok(()));
+        }
+        assert!(tree.proof_of_leaf(&LeafHash::of("Mordor")).is_none());
+    }
+
+    #[test]
+    fn test_nodes_are_not_leaves() {
+        let tree = MerkleTree::build(&["Arnor", "Gondor", "Rohan"]).unwrap();
+        let node = tree.node_hash(1, 0).unwrap();
+
+        assert!(tree.node_hash(0, 0).is_none());
+        assert_eq!(node.into_inner(), tree.node(1, 0).unwrap());
+
+        // Only an explicit conversion makes a node a leaf, and it is not one of the tree.
+        assert!(tree
+            .proof_of_leaf(&LeafHash::new(node.into_inner()))
+            .is_none());
+    }
+}
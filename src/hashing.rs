@@ -0,0 +1,103 @@
+pub type Hash = [u8; 32];
+
+/// Hashes the provided bytes using SHA-256, as `MerkleTree::hash` does.
+/// It is the only way to hash a leaf without the `tree` feature.
+///
+/// # Examples
+/// ```
+/// let leaf = merkle_tree::hash(b"Not all those who wander are lost.");
+/// assert_eq!(leaf.len(), 32);
+/// ```
+pub fn hash(bytes: &[u8]) -> Hash {
+    hmac_sha256::Hash::hash(bytes)
+}
+
+/// A hash function a tree can be built with. Parents hash their sorted
+/// children, like `MerkleTree`, unless `parent` is overridden.
+pub trait MerkleHasher {
+    /// The algorithm byte of proofs encoded by `Proof::to_bytes_with` for
+    /// trees built with this hash, `None` if they have no binary encoding.
+    const WIRE_ALGORITHM: Option<u8> = None;
+
+    fn hash(bytes: &[u8]) -> Hash;
+
+    fn parent(left: &Hash, right: &Hash) -> Hash {
+        let (first, second) = if left <= right {
+            (left, right)
+        } else {
+            (right, left)
+        };
+        Self::hash([*first, *second].as_flattened())
+    }
+}
+
+/// SHA-256, the hash of `MerkleTree`.
+#[derive(Debug, Clone, Copy)]
+pub struct Sha256;
+
+impl MerkleHasher for Sha256 {
+    const WIRE_ALGORITHM: Option<u8> = Some(1);
+
+    fn hash(bytes: &[u8]) -> Hash {
+        hash(bytes)
+    }
+}
+
+/// Any `digest::Digest` with 32 bytes outputs, such as SHA-512/256 or BLAKE2s.
+#[cfg(feature = "tree")]
+impl<D> MerkleHasher for D
+where
+    D: digest::Digest + digest::OutputSizeUser<OutputSize = digest::consts::U32>,
+{
+    fn hash(bytes: &[u8]) -> Hash {
+        D::digest(bytes).into()
+    }
+}
+
+/// Returns the number of nodes at the given level of a tree with `leaf_count` leaves,
+/// 0 if the tree has no such level.
+pub(crate) fn level_width(leaf_count: usize, level: usize) -> usize {
+    let mut width = leaf_count;
+
+    for _ in 0..level {
+        if width <= 1 {
+            return 0;
+        }
+        width = width.div_ceil(2);
+    }
+
+    width
+}
+
+/// Binds a tree root to the number of leaves, as `MutationGuard::CommitLeafCount` does.
+pub(crate) fn commit_leaf_count<H: MerkleHasher>(root: &Hash, leaf_count: usize) -> Hash {
+    let mut bytes = (leaf_count as u64).to_be_bytes().to_vec();
+    bytes.extend_from_slice(root);
+    H::hash(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::proof::Proof;
+
+    #[test]
+    fn test_proofs_verify_without_a_tree() {
+        let leaves = [hash(b"Earendil"), hash(b"Elwing"), hash(b"Elros")];
+        let left = Sha256::parent(&leaves[0], &leaves[1]);
+        let right = Sha256::parent(&leaves[2], &leaves[2]);
+        let root = Sha256::parent(&right, &left);
+
+        let proof = Proof {
+            leaf_index: 2,
+            leaf_count: 3,
+            siblings: vec![leaves[2], left],
+        };
+
+        assert_eq!(proof.verify(&leaves[2], &root), Ok(()));
+        assert!(proof.verify(&leaves[1], &root).is_err());
+        assert_eq!(level_width(3, 1), 2);
+        assert_eq!(level_width(3, 3), 0);
+    }
+}
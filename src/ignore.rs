@@ -0,0 +1,216 @@
+/// A `gitignore`-style pattern, matched against `/` separated relative paths.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IgnoreRule {
+    negated: bool,
+    dir_only: bool,
+    segments: Vec<String>,
+}
+
+impl IgnoreRule {
+    fn parse(pattern: &str) -> Option<Self> {
+        let (negated, pattern) = match pattern.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        let (dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        if pattern.is_empty() {
+            return None;
+        }
+
+        // Patterns without an inner slash match at any depth.
+        let anchored = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+        let mut segments: Vec<String> = pattern.split('/').map(str::to_string).collect();
+        if !anchored {
+            segments.insert(0, "**".to_string());
+        }
+
+        Some(Self {
+            negated,
+            dir_only,
+            segments,
+        })
+    }
+
+    fn matches(&self, path: &[&str], is_dir: bool) -> bool {
+        (is_dir || !self.dir_only) && match_segments(&self.segments, path)
+    }
+}
+
+fn match_segments(pattern: &[String], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        // A trailing `**` matches everything inside, not the directory itself.
+        Some((first, [])) if first == "**" => !path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skipped| match_segments(rest, &path[skipped..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            Some((name, path)) => {
+                let pattern: Vec<char> = first.chars().collect();
+                let name: Vec<char> = name.chars().collect();
+                match_glob(&pattern, &name) && match_segments(rest, path)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Matches a path segment against `*`, `?`, `[a-z]` and `[!a]` wildcards
+/// and `\` escapes. An unterminated `[` matches itself.
+fn match_glob(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skipped| match_glob(rest, &name[skipped..])),
+        Some(('?', rest)) => !name.is_empty() && match_glob(rest, &name[1..]),
+        Some(('[', rest)) => match rest.iter().skip(1).position(|c| *c == ']') {
+            Some(end) => {
+                let Some((c, name)) = name.split_first() else {
+                    return false;
+                };
+                let (class, rest) = (&rest[..end + 1], &rest[end + 2..]);
+                let (negated, class) = match class.split_first() {
+                    Some(('!' | '^', class)) => (true, class),
+                    _ => (false, class),
+                };
+
+                let mut matched = false;
+                let mut index = 0;
+                while index < class.len() {
+                    if index + 2 < class.len() && class[index + 1] == '-' {
+                        matched |= (class[index]..=class[index + 2]).contains(c);
+                        index += 3;
+                    } else {
+                        matched |= class[index] == *c;
+                        index += 1;
+                    }
+                }
+                matched != negated && match_glob(rest, name)
+            }
+            None => name.first() == Some(&'[') && match_glob(rest, &name[1..]),
+        },
+        Some(('\\', [escaped, rest @ ..])) => {
+            name.first() == Some(escaped) && match_glob(rest, &name[1..])
+        }
+        Some((literal, rest)) => name.first() == Some(literal) && match_glob(rest, &name[1..]),
+    }
+}
+
+/// Include and exclude patterns with the syntax of `.gitignore` files,
+/// which leave build artifacts and caches out of directory manifests.
+///
+/// Later patterns take precedence, `!` includes back what an earlier
+/// pattern excluded, a trailing `/` matches directories only, and a
+/// pattern with a `/` other than a trailing one is relative to the root
+/// rather than matched at any depth. As with Git, a file inside an
+/// excluded directory can't be included back.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::IgnoreRules;
+///
+/// let rules = IgnoreRules::parse("# build outputs\ntarget/\n*.log\n!keep.log\n");
+///
+/// assert!(rules.is_ignored("target", true));
+/// assert!(rules.is_ignored("logs/forge.log", false));
+/// assert!(!rules.is_ignored("logs/keep.log", false));
+/// assert!(!rules.is_ignored("src/target", false));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IgnoreRules {
+    rules: Vec<IgnoreRule>,
+}
+
+impl IgnoreRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the patterns of a `.gitignore` file, skipping blank lines and `#` comments.
+    pub fn parse(text: &str) -> Self {
+        let mut rules = Self::new();
+        for line in text.lines() {
+            let line = line.trim_end();
+            if !line.starts_with('#') {
+                rules.add(line);
+            }
+        }
+        rules
+    }
+
+    /// Adds a pattern, which is excluded unless it starts with `!`.
+    pub fn add(&mut self, pattern: &str) {
+        self.rules.extend(IgnoreRule::parse(pattern));
+    }
+
+    pub(crate) fn extend(&mut self, other: &Self) {
+        self.rules.extend(other.rules.iter().cloned());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether the file or directory at the `/` separated relative `path` is excluded.
+    pub fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        let segments: Vec<&str> = path.split('/').collect();
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(&segments, is_dir))
+            .is_some_and(|rule| !rule.negated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_wildcards() {
+        let rules =
+            IgnoreRules::parse("*.o\nbuild-?/\ncache/**\n/docs/*.html\n[Mm]akefile\n\\!bang\n[x");
+
+        assert!(rules.is_ignored("main.o", false));
+        assert!(rules.is_ignored("src/rings/one.o", false));
+        assert!(!rules.is_ignored("main.orc", false));
+        assert!(rules.is_ignored("build-1", true));
+        assert!(!rules.is_ignored("build-1", false));
+        assert!(!rules.is_ignored("build-10", true));
+        assert!(rules.is_ignored("cache/a/b", false));
+        assert!(!rules.is_ignored("cache", true));
+        assert!(rules.is_ignored("docs/index.html", false));
+        assert!(!rules.is_ignored("api/docs/index.html", false));
+        assert!(!rules.is_ignored("docs/api/index.html", false));
+        assert!(rules.is_ignored("Makefile", false) && rules.is_ignored("makefile", false));
+        assert!(!rules.is_ignored("Rakefile", false));
+        assert!(rules.is_ignored("!bang", false));
+        assert!(rules.is_ignored("[x", false) && !rules.is_ignored("x", false));
+    }
+
+    #[test]
+    fn test_later_patterns_take_precedence() {
+        let mut rules = IgnoreRules::new();
+        rules.add("*.log");
+        rules.add("!important.log");
+        assert!(rules.is_ignored("debug.log", false));
+        assert!(!rules.is_ignored("var/important.log", false));
+
+        rules.add("var/**/*.log");
+        assert!(rules.is_ignored("var/important.log", false));
+        assert!(rules.is_ignored("var/a/b/important.log", false));
+        assert!(!rules.is_ignored("important.log", false));
+    }
+
+    #[test]
+    fn test_comments_and_blank_lines_are_skipped() {
+        let rules = IgnoreRules::parse("# Mordor\n\n   \n/\n!\n");
+
+        assert!(rules.is_empty());
+        assert!(!rules.is_ignored("# Mordor", false));
+    }
+}
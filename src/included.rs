@@ -0,0 +1,70 @@
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+
+/// A Merkle tree computed at compile time by the `include_merkle!` macro.
+/// Only the root and the leaf hashes are embedded in the binary.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{include_merkle, IncludedTree, MerkleTree};
+///
+/// const VERSES: IncludedTree = include_merkle!["In a hole in the ground", "there lived a hobbit."];
+///
+/// assert!(VERSES.contains(&"there lived a hobbit."));
+///
+/// let items = vec!["In a hole in the ground", "there lived a hobbit."];
+/// let merkle_tree = MerkleTree::build(&items).unwrap();
+///
+/// assert_eq!(VERSES.root, merkle_tree.root().unwrap());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IncludedTree {
+    pub root: Hash,
+    pub leaves: &'static [Hash],
+}
+
+impl IncludedTree {
+    /// Checks whether the item was one of the items the tree was built from.
+    pub fn contains<T: AsRef<[u8]>>(&self, item: &T) -> bool {
+        self.contains_hash(&MerkleTree::hash(item.as_ref()))
+    }
+
+    pub fn contains_hash(&self, hash: &Hash) -> bool {
+        self.leaves.contains(hash)
+    }
+
+    /// Rebuilds the full tree at runtime, e.g. to generate proofs.
+    pub fn to_tree(&self) -> Result<MerkleTree, Error> {
+        MerkleTree::from_leaves(self.leaves.to_vec()).ok_or(Error::EmptyTree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_included_tree_matches_runtime_tree() {
+        let items = vec![
+            "One Ring to rule them all,",
+            "One Ring to find them,",
+            "One Ring to bring them all",
+        ];
+
+        let tree = MerkleTree::build(&items).unwrap();
+        let leaves: Vec<Hash> = items
+            .iter()
+            .map(|item| MerkleTree::hash(item.as_bytes()))
+            .collect();
+
+        let included = IncludedTree {
+            root: tree.root().unwrap(),
+            leaves: leaves.leak(),
+        };
+
+        assert_eq!(included.to_tree().unwrap().root(), tree.root());
+        assert!(included.contains(&"One Ring to find them,"));
+        assert!(!included.contains(&"and in the darkness bind them."));
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+#[derive(Debug, Clone, Copy)]
+struct InternedNode {
+    hash: Hash,
+    /// The left and right children, the same node twice where the level is padded.
+    children: Option<(usize, usize)>,
+}
+
+/// A tree storing each distinct subtree once.
+///
+/// Nodes are interned by level and hash, so identical subtrees, common with
+/// repeated or default-padded leaves, share their storage and the memory
+/// grows with the number of distinct subtrees instead of the number of
+/// leaves. Nodes are found by walking down from the root, in `O(log n)`.
+/// The roots and proofs are those of `MerkleTree`.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{InternedTree, MerkleTree};
+///
+/// let mut items = vec!["empty"; 1024];
+/// items[5] = "Anduril";
+///
+/// let tree = InternedTree::build(&items).unwrap();
+///
+/// assert!(tree.distinct_nodes() < 30);
+/// assert_eq!(Some(tree.root()), MerkleTree::build(&items).unwrap().root());
+/// ```
+#[derive(Debug, Clone)]
+pub struct InternedTree {
+    nodes: Vec<InternedNode>,
+    root: usize,
+    leaf_count: usize,
+    height: usize,
+}
+
+impl InternedTree {
+    /// Builds the tree from items representable as bytes.
+    /// The creation will fail if the items list is empty.
+    pub fn build<T: AsRef<[u8]>>(items: &[T]) -> Option<Self> {
+        Self::from_hashes(
+            items
+                .iter()
+                .map(|item| MerkleTree::hash(item.as_ref()))
+                .collect(),
+        )
+    }
+
+    /// Builds the tree from already hashed leaves.
+    pub fn from_hashes(leaves: Vec<Hash>) -> Option<Self> {
+        if leaves.is_empty() {
+            return None;
+        }
+
+        let mut tree = Self {
+            nodes: Vec::new(),
+            root: 0,
+            leaf_count: leaves.len(),
+            height: 0,
+        };
+        let mut interned = HashMap::new();
+
+        let mut level: Vec<usize> = leaves
+            .into_iter()
+            .map(|hash| tree.intern(&mut interned, 0, hash, None))
+            .collect();
+
+        while level.len() > 1 {
+            tree.height += 1;
+            level = level
+                .chunks(2)
+                .map(|pair| {
+                    let (left, right) = (pair[0], *pair.get(1).unwrap_or(&pair[0]));
+                    let hash =
+                        MerkleTree::merkle_parent(&[tree.nodes[left].hash, tree.nodes[right].hash]);
+                    tree.intern(&mut interned, tree.height, hash, Some((left, right)))
+                })
+                .collect();
+        }
+
+        tree.root = level[0];
+        Some(tree)
+    }
+
+    fn intern(
+        &mut self,
+        interned: &mut HashMap<(usize, Hash), usize>,
+        level: usize,
+        hash: Hash,
+        children: Option<(usize, usize)>,
+    ) -> usize {
+        *interned.entry((level, hash)).or_insert_with(|| {
+            self.nodes.push(InternedNode { hash, children });
+            self.nodes.len() - 1
+        })
+    }
+
+    pub fn root(&self) -> Hash {
+        self.nodes[self.root].hash
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    /// The number of levels above the leaves.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The number of nodes stored, each distinct subtree counting once.
+    pub fn distinct_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The node at `index` of `level`, the leaves being level 0.
+    pub fn node(&self, level: usize, index: usize) -> Option<Hash> {
+        if index >= MerkleTree::level_width(self.leaf_count, level) {
+            return None;
+        }
+
+        let mut node = self.root;
+        for depth in (level..self.height).rev() {
+            let (left, right) = self.nodes[node]
+                .children
+                .expect("Only leaves have no children.");
+            node = if (index >> depth.saturating_sub(level)) & 1 == 0 {
+                left
+            } else {
+                right
+            };
+        }
+        Some(self.nodes[node].hash)
+    }
+
+    /// Returns the proof of inclusion of the leaf at `index`.
+    pub fn proof(&self, index: usize) -> Result<Proof, Error> {
+        if index >= self.leaf_count {
+            return Err(Error::IndexOutOfRange {
+                index,
+                leaf_count: self.leaf_count,
+            });
+        }
+
+        let mut siblings = vec![[0; 32]; self.height];
+        let mut node = self.root;
+        for level in (0..self.height).rev() {
+            let (left, right) = self.nodes[node]
+                .children
+                .expect("Only leaves have no children.");
+            let (next, sibling) = if (index >> level) & 1 == 0 {
+                (left, right)
+            } else {
+                (right, left)
+            };
+            siblings[level] = self.nodes[sibling].hash;
+            node = next;
+        }
+
+        Ok(Proof {
+            leaf_index: index,
+            leaf_count: self.leaf_count,
+            siblings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn padded(count: usize, filled: &[usize]) -> Vec<String> {
+        (0..count)
+            .map(|index| {
+                if filled.contains(&index) {
+                    format!("record {}", index)
+                } else {
+                    "empty".to_string()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_interned_trees_match_merkle_trees() {
+        for count in [1, 2, 3, 5, 8, 13, 100] {
+            let items = padded(count, &[0, 4, 11, 50]);
+            let expected = MerkleTree::build(&items).unwrap();
+            let tree = InternedTree::build(&items).unwrap();
+
+            assert_eq!(Some(tree.root()), expected.root());
+            assert_eq!(tree.height(), expected.height());
+            for level in 0..=tree.height() {
+                for index in 0..=count {
+                    assert_eq!(tree.node(level, index), expected.node(level, index));
+                }
+            }
+            for index in 0..count {
+                assert_eq!(tree.proof(index), expected.proof(index));
+            }
+        }
+    }
+
+    #[test]
+    fn test_identical_subtrees_are_stored_once() {
+        let tree = InternedTree::build(&padded(1 << 16, &[3, 40_000])).unwrap();
+
+        // Two paths of distinct nodes, and one padded node per level.
+        assert!(tree.distinct_nodes() <= 3 * (tree.height() + 1));
+        assert!(tree.proof(1 << 16).is_err());
+        assert!(InternedTree::build::<&str>(&[]).is_none());
+    }
+}
@@ -0,0 +1,149 @@
+use crate::commit_reveal::{SealedTree, SealedValue};
+use crate::error::Error;
+use crate::item_tree::ItemTree;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// A proof of inclusion bundled with the preimage of its leaf, so a
+/// relying party checks a payload against a root without knowing how the
+/// tree hashes its items.
+///
+/// The leaf is `H(item)`, or `H(salt || item)` for the blinded values of a
+/// `SealedTree`.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::ItemTree;
+///
+/// let tree = ItemTree::build(vec!["Bree", "Weathertop", "Rivendell"]).unwrap();
+///
+/// let bundle = tree.proof_with_item(1).unwrap();
+/// assert_eq!(bundle.item, b"Weathertop");
+/// assert!(bundle.verify(&tree.root(), false).is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofWithItem {
+    pub item: Vec<u8>,
+    pub salt: Option<Hash>,
+    pub leaf: Hash,
+    pub proof: Proof,
+}
+
+impl ProofWithItem {
+    /// The leaf the item and salt hash to.
+    pub fn derived_leaf(&self) -> Hash {
+        match self.salt {
+            Some(salt) => SealedValue::new(salt, &self.item).leaf(),
+            None => MerkleTree::hash(&self.item),
+        }
+    }
+
+    /// Checks that the item and salt hash to the leaf, and that the proof
+    /// leads from the leaf to `root`.
+    ///
+    /// `salted` says whether the tree of `root` salts its leaves, which the
+    /// bundle can't be trusted to tell: the salted leaf `H(salt || value)`
+    /// is also the leaf of the unsalted item `salt || value`.
+    pub fn verify(&self, root: &Hash, salted: bool) -> Result<(), Error> {
+        if self.salt.is_some() != salted {
+            return Err(Error::InvalidInput(
+                if salted {
+                    "the item has no salt"
+                } else {
+                    "the item is salted"
+                }
+                .to_string(),
+            ));
+        }
+        if self.derived_leaf() != self.leaf {
+            return Err(Error::InvalidInput(
+                "the item does not hash to the leaf".to_string(),
+            ));
+        }
+        self.proof.verify(&self.leaf, root)
+    }
+}
+
+impl<T: AsRef<[u8]>> ItemTree<T> {
+    /// The proof of the item at `index`, bundled with the item.
+    pub fn proof_with_item(&self, index: usize) -> Result<ProofWithItem, Error> {
+        let proof = self.proof(index)?;
+        let item = self.items()[index].as_ref().to_vec();
+
+        Ok(ProofWithItem {
+            leaf: MerkleTree::hash(&item),
+            item,
+            salt: None,
+            proof,
+        })
+    }
+}
+
+impl SealedTree {
+    /// The opening of the value at `index`, bundled with its salt.
+    pub fn proof_with_item(&self, index: usize) -> Result<ProofWithItem, Error> {
+        let (value, proof) = self.reveal(index)?;
+
+        Ok(ProofWithItem {
+            leaf: value.leaf(),
+            item: value.value,
+            salt: Some(value.salt),
+            proof,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_bundles_verify_from_the_preimage() {
+        let tree = ItemTree::build(vec!["Fangorn", "Old Forest", "Mirkwood"]).unwrap();
+        let root = tree.root();
+
+        for index in 0..3 {
+            let bundle = tree.proof_with_item(index).unwrap();
+            assert_eq!(bundle.verify(&root, false), Ok(()));
+            assert!(bundle.verify(&root, true).is_err());
+
+            let mut swapped = bundle.clone();
+            swapped.item = b"Chetwood".to_vec();
+            assert!(swapped.verify(&root, false).is_err());
+
+            let mut salted = bundle;
+            salted.salt = Some([1; 32]);
+            assert!(salted.verify(&root, false).is_err());
+            assert!(salted.verify(&root, true).is_err());
+        }
+        assert!(tree.proof_with_item(3).is_err());
+    }
+
+    #[test]
+    fn test_sealed_bundles_carry_their_salt() {
+        let tree = SealedTree::commit(vec![
+            SealedValue::new([3; 32], "Ered Luin"),
+            SealedValue::new([4; 32], "Ered Mithrin"),
+        ])
+        .unwrap();
+
+        let bundle = tree.proof_with_item(1).unwrap();
+        assert_eq!(bundle.salt, Some([4; 32]));
+        assert_eq!(Some(bundle.leaf), tree.leaf(1));
+        assert_eq!(bundle.verify(&tree.root(), true), Ok(()));
+
+        let mut unsalted = bundle.clone();
+        unsalted.salt = None;
+        assert!(unsalted.verify(&tree.root(), true).is_err());
+
+        // The salt moved into the item hashes to the same leaf.
+        let forged = ProofWithItem {
+            item: [bundle.salt.unwrap().as_slice(), &bundle.item].concat(),
+            salt: None,
+            ..bundle
+        };
+        assert_eq!(forged.derived_leaf(), forged.leaf);
+        assert!(forged.verify(&tree.root(), true).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// A tree that keeps the items it commits to, not only their hashes.
+///
+/// Applications that serve the committed data along with its proofs can
+/// keep it here instead of in a second collection kept in sync by hand.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::ItemTree;
+///
+/// let tree = ItemTree::build(vec!["Bag End", "Rivendell", "Lothlórien"]).unwrap();
+///
+/// assert_eq!(tree.get_item(1), Some(&"Rivendell"));
+///
+/// let proof = tree.proof_of_item(&"Lothlórien").unwrap();
+/// assert!(tree.verify_item(&"Lothlórien", &proof).is_ok());
+/// ```
+pub struct ItemTree<T> {
+    items: Vec<T>,
+    tree: MerkleTree,
+}
+
+impl<T: AsRef<[u8]>> ItemTree<T> {
+    /// Builds the tree, taking ownership of the items.
+    /// The creation will fail if the items list is empty.
+    pub fn build(items: Vec<T>) -> Option<Self> {
+        let leaves = items
+            .iter()
+            .map(|item| MerkleTree::hash(item.as_ref()))
+            .collect();
+
+        Some(Self {
+            tree: MerkleTree::from_leaves(leaves)?,
+            items,
+        })
+    }
+
+    /// Inserts an item and returns its index.
+    pub fn insert(&mut self, item: T) -> Result<usize, Error> {
+        self.tree.insert(&item)?;
+        self.items.push(item);
+
+        Ok(self.items.len() - 1)
+    }
+
+    pub fn root(&self) -> Hash {
+        self.tree.root().expect("A tree has a root.")
+    }
+
+    pub fn tree(&self) -> &MerkleTree {
+        &self.tree
+    }
+
+    /// The item at `index`.
+    pub fn get_item(&self, index: usize) -> Option<&T> {
+        self.items.get(index)
+    }
+
+    /// The items, in the order of their leaves.
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Returns the items, dropping the tree.
+    pub fn into_items(self) -> Vec<T> {
+        self.items
+    }
+
+    /// The index of the first item equal to `item`, compared by their bytes.
+    pub fn position(&self, item: &T) -> Option<usize> {
+        let leaf = MerkleTree::hash(item.as_ref());
+        (0..self.items.len()).find(|index| self.tree.node(0, *index) == Some(leaf))
+    }
+
+    /// Returns the proof of inclusion of the item at `index`.
+    pub fn proof(&self, index: usize) -> Result<Proof, Error> {
+        self.tree.proof(index)
+    }
+
+    /// Returns the proof of inclusion of `item`, if the tree holds it.
+    pub fn proof_of_item(&self, item: &T) -> Option<Proof> {
+        self.proof(self.position(item)?).ok()
+    }
+
+    /// Checks a proof of `item` against this tree.
+    pub fn verify_item(&self, item: &T, proof: &Proof) -> Result<(), Error> {
+        self.tree
+            .verify_proof(&MerkleTree::hash(item.as_ref()), proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn fellowship() -> Vec<String> {
+        [
+            "Frodo", "Sam", "Merry", "Pippin", "Gandalf", "Aragorn", "Legolas", "Gimli", "Boromir",
+        ]
+        .iter()
+        .map(|member| member.to_string())
+        .collect()
+    }
+
+    #[test]
+    fn test_items_are_served_with_their_proofs() {
+        let tree = ItemTree::build(fellowship()).unwrap();
+
+        assert_eq!(tree.items(), fellowship().as_slice());
+        assert_eq!(
+            tree.root(),
+            MerkleTree::build(&fellowship()).unwrap().root().unwrap()
+        );
+
+        for (index, member) in fellowship().iter().enumerate() {
+            assert_eq!(tree.get_item(index), Some(member));
+            let proof = tree.proof_of_item(member).unwrap();
+            assert_eq!(proof.leaf_index, index);
+            assert_eq!(tree.verify_item(member, &proof), Ok(()));
+        }
+        assert!(tree.proof_of_item(&"Saruman".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_inserted_items_are_kept() {
+        let mut tree = ItemTree::build(vec![b"Elrond".to_vec()]).unwrap();
+
+        let index = tree.insert(b"Arwen".to_vec()).unwrap();
+
+        assert_eq!(index, 1);
+        assert_eq!(tree.get_item(1), Some(&b"Arwen".to_vec()));
+        assert_eq!(tree.tree().leaf_count(), 2);
+        assert_eq!(tree.into_items().len(), 2);
+    }
+
+    #[test]
+    fn test_empty_trees_are_rejected() {
+        assert!(ItemTree::<Vec<u8>>::build(Vec::new()).is_none());
+    }
+}
@@ -0,0 +1,247 @@
+use serde_json::Value;
+
+use crate::merkle_tree::{Hash, MerkleTree};
+
+/// Serializes a JSON value following the JSON Canonicalization Scheme (RFC 8785).
+///
+/// Object members are sorted by the UTF-16 code units of their names, no
+/// whitespace is emitted, strings use the minimal escaping of ECMAScript's
+/// `JSON.stringify` and numbers are formatted as ECMAScript doubles.
+/// Semantically equal documents are canonicalized to the same string.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::canonicalize_json;
+///
+/// let document = serde_json::json!({ "ring": "One", "bearer": "Frodo", "weight": 1.0 });
+///
+/// assert_eq!(canonicalize_json(&document), r#"{"bearer":"Frodo","ring":"One","weight":1}"#);
+/// ```
+pub fn canonicalize_json(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(value, &mut out);
+    out
+}
+
+fn write_value(value: &Value, out: &mut String) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(true) => out.push_str("true"),
+        Value::Bool(false) => out.push_str("false"),
+        Value::Number(number) => {
+            // JSON numbers are IEEE 754 doubles in RFC 8785, including integers.
+            let number = number
+                .as_f64()
+                .expect("JSON numbers are representable as f64.");
+            out.push_str(&format_number(number));
+        }
+        Value::String(string) => write_string(string, out),
+        Value::Array(values) => {
+            out.push('[');
+            for (position, value) in values.iter().enumerate() {
+                if position > 0 {
+                    out.push(',');
+                }
+                write_value(value, out);
+            }
+            out.push(']');
+        }
+        Value::Object(members) => {
+            let mut members: Vec<(&String, &Value)> = members.iter().collect();
+            members.sort_by(|(left, _), (right, _)| left.encode_utf16().cmp(right.encode_utf16()));
+
+            out.push('{');
+            for (position, (name, value)) in members.into_iter().enumerate() {
+                if position > 0 {
+                    out.push(',');
+                }
+                write_string(name, out);
+                out.push(':');
+                write_value(value, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_string(string: &str, out: &mut String) {
+    out.push('"');
+    for character in string.chars() {
+        match character {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{08}' => out.push_str("\\b"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\u{0c}' => out.push_str("\\f"),
+            '\r' => out.push_str("\\r"),
+            control if (control as u32) < 0x20 => {
+                out.push_str(&format!("\\u{:04x}", control as u32));
+            }
+            other => out.push(other),
+        }
+    }
+    out.push('"');
+}
+
+/// Formats a finite double as ECMAScript's `Number.prototype.toString` does.
+fn format_number(number: f64) -> String {
+    if number == 0.0 {
+        return "0".to_string();
+    }
+
+    // `{:e}` yields the shortest round-tripping digits, e.g. "-1.2345e-7".
+    let scientific = format!("{:e}", number.abs());
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("Scientific notation has an exponent.");
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let exponent: i32 = exponent.parse().expect("The exponent is an integer.");
+
+    // The value is 0.digits * 10^point.
+    let length = digits.len() as i32;
+    let point = exponent + 1;
+
+    let mut out = String::new();
+    if number < 0.0 {
+        out.push('-');
+    }
+
+    if length <= point && point <= 21 {
+        out.push_str(&digits);
+        out.push_str(&"0".repeat((point - length) as usize));
+    } else if 0 < point && point <= 21 {
+        out.push_str(&digits[..point as usize]);
+        out.push('.');
+        out.push_str(&digits[point as usize..]);
+    } else if -6 < point && point <= 0 {
+        out.push_str("0.");
+        out.push_str(&"0".repeat(-point as usize));
+        out.push_str(&digits);
+    } else {
+        out.push_str(&digits[..1]);
+        if length > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+        out.push('e');
+        out.push(if point - 1 < 0 { '-' } else { '+' });
+        out.push_str(&(point - 1).abs().to_string());
+    }
+
+    out
+}
+
+impl MerkleTree {
+    /// Create a new MerkleTree from JSON documents.
+    /// Each document is hashed over its RFC 8785 canonical form, so documents
+    /// that only differ in member order or whitespace produce the same leaf.
+    /// The creation will fail if the documents list is empty.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::MerkleTree;
+    ///
+    /// let documents = vec![
+    ///     serde_json::from_str(r#"{"name": "Gandalf", "colour": "Grey"}"#).unwrap(),
+    ///     serde_json::from_str(r#"{"name": "Saruman", "colour": "White"}"#).unwrap(),
+    /// ];
+    ///
+    /// let merkle_tree = MerkleTree::build_json(&documents).unwrap();
+    /// ```
+    pub fn build_json(documents: &[Value]) -> Option<Self> {
+        let leaves: Vec<Hash> = documents
+            .iter()
+            .map(|document| Self::hash(canonicalize_json(document).as_bytes()))
+            .collect();
+
+        Self::from_leaves(leaves)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_canonicalize_sorts_members_and_strips_whitespace() {
+        let document: Value = serde_json::from_str(
+            r#"{
+                "weapon": "Sting",
+                "bearer": { "name": "Bilbo", "age": 111 },
+                "glows": [true, null]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            canonicalize_json(&document),
+            r#"{"bearer":{"age":111,"name":"Bilbo"},"glows":[true,null],"weapon":"Sting"}"#
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_sorts_members_by_utf16_code_units() {
+        // U+1F600 is encoded as the surrogates D83D DE00, which sort before U+FB33.
+        let document: Value = serde_json::from_str(r#"{"דּ": 1, "😀": 2}"#).unwrap();
+
+        assert_eq!(
+            canonicalize_json(&document),
+            "{\"\u{1f600}\":2,\"\u{fb33}\":1}"
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_escapes_strings_minimally() {
+        let document = Value::String("\"Mellon\"\n\u{1f}\u{e9}/".to_string());
+
+        assert_eq!(
+            canonicalize_json(&document),
+            "\"\\\"Mellon\\\"\\n\\u001f\u{e9}/\""
+        );
+    }
+
+    #[test]
+    fn test_numbers_are_formatted_as_ecmascript() {
+        let cases = [
+            (0.0, "0"),
+            (-0.0, "0"),
+            (1.0, "1"),
+            (-1.5, "-1.5"),
+            (333333333.3333333, "333333333.3333333"),
+            (1e21, "1e+21"),
+            (1e20, "100000000000000000000"),
+            (1e-7, "1e-7"),
+            (0.000001, "0.000001"),
+            (123e-20, "1.23e-18"),
+            (4.50, "4.5"),
+            (2e-3, "0.002"),
+            (9007199254740992.0, "9007199254740992"),
+            (5e-324, "5e-324"),
+            (1.7976931348623157e308, "1.7976931348623157e+308"),
+        ];
+
+        for (number, expected) in cases {
+            assert_eq!(format_number(number), expected);
+        }
+    }
+
+    #[test]
+    fn test_equal_documents_share_a_root() {
+        let first = vec![
+            serde_json::from_str(r#"{"name": "Gandalf", "colour": "Grey"}"#).unwrap(),
+            serde_json::from_str(r#"{"name": "Saruman", "colour": "White", "rings": 1.0}"#)
+                .unwrap(),
+        ];
+        let second = vec![
+            serde_json::from_str(r#"{"colour":"Grey","name":"Gandalf"}"#).unwrap(),
+            serde_json::from_str(r#"{"rings":1,"colour":"White","name":"Saruman"}"#).unwrap(),
+        ];
+
+        let first_tree = MerkleTree::build_json(&first).unwrap();
+        let second_tree = MerkleTree::build_json(&second).unwrap();
+
+        assert_eq!(first_tree.root(), second_tree.root());
+    }
+}
@@ -0,0 +1,13 @@
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::merkle_tree::Hash;
+
+/// The original Keccak-256 used by Ethereum and Solana, not the standardized SHA3-256.
+pub fn keccak256(bytes: &[u8]) -> Hash {
+    let mut hasher = Keccak::v256();
+    hasher.update(bytes);
+
+    let mut hash = [0; 32];
+    hasher.finalize(&mut hash);
+    hash
+}
@@ -0,0 +1,178 @@
+/// Canonical byte encoding of a value that is committed as a tree item.
+///
+/// Integers are encoded as fixed width big-endian, variable length values
+/// (strings, vectors) are prefixed by their length as a big-endian `u64`,
+/// and struct fields are encoded in declaration order. Two services using
+/// this trait always produce the same bytes, and thus the same leaf, for
+/// the same value.
+///
+/// With the `macros` feature, `#[derive(MerkleLeaf)]` implements it for structs.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{MerkleLeaf, MerkleTree};
+///
+/// struct Hobbit {
+///     name: String,
+///     age: u16,
+/// }
+///
+/// impl MerkleLeaf for Hobbit {
+///     fn encode_leaf(&self, out: &mut Vec<u8>) {
+///         self.name.encode_leaf(out);
+///         self.age.encode_leaf(out);
+///     }
+/// }
+///
+/// let hobbits = vec![
+///     Hobbit { name: "Bilbo".to_string(), age: 111 },
+///     Hobbit { name: "Frodo".to_string(), age: 33 },
+/// ];
+///
+/// let merkle_tree = MerkleTree::build_leaves(&hobbits).unwrap();
+/// ```
+///
+/// The derived implementation encodes the same bytes:
+/// ```
+/// # #[cfg(feature = "macros")]
+/// # {
+/// use merkle_tree::MerkleLeaf;
+///
+/// #[derive(MerkleLeaf)]
+/// struct Hobbit {
+///     name: String,
+///     age: u16,
+/// }
+///
+/// let bilbo = Hobbit { name: "Bilbo".to_string(), age: 111 };
+///
+/// let mut expected = "Bilbo".leaf_bytes();
+/// 111u16.encode_leaf(&mut expected);
+///
+/// assert_eq!(bilbo.leaf_bytes(), expected);
+/// # }
+/// ```
+pub trait MerkleLeaf {
+    /// Appends the canonical encoding of the value to `out`.
+    fn encode_leaf(&self, out: &mut Vec<u8>);
+
+    /// Returns the canonical encoding of the value.
+    fn leaf_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_leaf(&mut out);
+        out
+    }
+}
+
+macro_rules! impl_merkle_leaf_for_integers {
+    ($($integer:ty),*) => {
+        $(
+            impl MerkleLeaf for $integer {
+                fn encode_leaf(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_be_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_merkle_leaf_for_integers!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl MerkleLeaf for bool {
+    fn encode_leaf(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+}
+
+impl MerkleLeaf for str {
+    fn encode_leaf(&self, out: &mut Vec<u8>) {
+        self.as_bytes().encode_leaf(out);
+    }
+}
+
+impl MerkleLeaf for String {
+    fn encode_leaf(&self, out: &mut Vec<u8>) {
+        self.as_str().encode_leaf(out);
+    }
+}
+
+impl<T: MerkleLeaf> MerkleLeaf for [T] {
+    fn encode_leaf(&self, out: &mut Vec<u8>) {
+        (self.len() as u64).encode_leaf(out);
+        for item in self {
+            item.encode_leaf(out);
+        }
+    }
+}
+
+impl<T: MerkleLeaf> MerkleLeaf for Vec<T> {
+    fn encode_leaf(&self, out: &mut Vec<u8>) {
+        self.as_slice().encode_leaf(out);
+    }
+}
+
+/// Arrays have a fixed length, so they are not length prefixed.
+impl<T: MerkleLeaf, const N: usize> MerkleLeaf for [T; N] {
+    fn encode_leaf(&self, out: &mut Vec<u8>) {
+        for item in self {
+            item.encode_leaf(out);
+        }
+    }
+}
+
+impl<T: MerkleLeaf> MerkleLeaf for Option<T> {
+    fn encode_leaf(&self, out: &mut Vec<u8>) {
+        match self {
+            None => out.push(0),
+            Some(value) => {
+                out.push(1);
+                value.encode_leaf(out);
+            }
+        }
+    }
+}
+
+impl<T: MerkleLeaf + ?Sized> MerkleLeaf for &T {
+    fn encode_leaf(&self, out: &mut Vec<u8>) {
+        (**self).encode_leaf(out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_integers_are_fixed_width_big_endian() {
+        assert_eq!(1u16.leaf_bytes(), vec![0, 1]);
+        assert_eq!((-1i32).leaf_bytes(), vec![0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(true.leaf_bytes(), vec![1]);
+    }
+
+    #[test]
+    fn test_strings_are_length_prefixed() {
+        let bytes = "Sam".leaf_bytes();
+
+        assert_eq!(bytes, vec![0, 0, 0, 0, 0, 0, 0, 3, b'S', b'a', b'm']);
+        assert_eq!(String::from("Sam").leaf_bytes(), bytes);
+        assert_eq!(b"Sam".to_vec().leaf_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_length_prefixes_keep_concatenations_apart() {
+        let mut left = "Merry".leaf_bytes();
+        "Pippin".encode_leaf(&mut left);
+
+        let mut right = "MerryPip".leaf_bytes();
+        "pin".encode_leaf(&mut right);
+
+        assert_ne!(left, right);
+    }
+
+    #[test]
+    fn test_options_are_tagged() {
+        assert_eq!(None::<u8>.leaf_bytes(), vec![0]);
+        assert_eq!(Some(7u8).leaf_bytes(), vec![1, 7]);
+    }
+}
@@ -1,3 +1,340 @@
+#[cfg(feature = "tree")]
+mod aggregate;
+#[cfg(feature = "airdrop")]
+mod airdrop;
+#[cfg(feature = "tree")]
+mod annotated;
+#[cfg(feature = "tree")]
+mod append;
+#[cfg(feature = "tree")]
+mod archive;
+#[cfg(feature = "arena")]
+mod arena;
+#[cfg(feature = "tree")]
+mod async_store;
+#[cfg(feature = "attestation")]
+mod attestation;
+#[cfg(feature = "tree")]
+mod audit_log;
+#[cfg(feature = "tree")]
+mod audit_trail;
+#[cfg(feature = "rayon")]
+mod batch;
+#[cfg(feature = "tree")]
+mod bisect;
+#[cfg(feature = "bitcoin")]
+mod bitcoin;
+#[cfg(feature = "tree")]
+mod bloom;
+#[cfg(feature = "tree")]
+mod builder;
+#[cfg(feature = "tree")]
+mod cache;
+#[cfg(feature = "cbor")]
+mod cbor;
+#[cfg(feature = "tree")]
+mod chunk_store;
+#[cfg(feature = "tree")]
+mod commit_reveal;
+#[cfg(feature = "tree")]
+mod config;
+#[cfg(feature = "tree")]
+mod consistency;
+#[cfg(feature = "tree")]
+mod const_export;
+#[cfg(any(feature = "csv", feature = "parquet"))]
+mod dataset;
+#[cfg(feature = "tree")]
+mod delta;
+#[cfg(feature = "tree")]
+mod directory;
+#[cfg(feature = "tree")]
+mod download;
+#[cfg(feature = "tree")]
+mod envelope;
+mod error;
+#[cfg(feature = "tree")]
+mod estimate;
+#[cfg(feature = "ethereum")]
+mod ethereum;
+#[cfg(feature = "tree")]
+mod file_diff;
+#[cfg(feature = "json")]
+mod fixture;
+#[cfg(feature = "tree")]
+mod follower;
+#[cfg(feature = "tree")]
+mod forest;
+#[cfg(feature = "tree")]
+mod frozen;
+#[cfg(feature = "tree")]
+mod gas;
+#[cfg(feature = "git")]
+mod git;
+#[cfg(feature = "tree")]
+mod hashes;
+mod hashing;
+#[cfg(feature = "tree")]
+mod ignore;
+#[cfg(feature = "macros")]
+mod included;
+#[cfg(feature = "tree")]
+mod interned;
+#[cfg(feature = "tree")]
+mod item_proof;
+#[cfg(feature = "tree")]
+mod item_tree;
+#[cfg(feature = "json")]
+mod jcs;
+#[cfg(any(feature = "ethereum", feature = "solana"))]
+mod keccak;
+#[cfg(feature = "tree")]
+mod leaf;
+mod limits;
+#[cfg(feature = "tree")]
+mod manifest;
+#[cfg(feature = "tree")]
+mod map;
+#[cfg(feature = "tree")]
 mod merkle_tree;
+#[cfg(feature = "tree")]
+mod multipart;
+#[cfg(feature = "tree")]
+mod nested;
+#[cfg(feature = "tree")]
+mod observer;
+#[cfg(feature = "tree")]
+mod oplog;
+#[cfg(feature = "tree")]
+mod packed;
+#[cfg(feature = "tree")]
+mod partial;
+#[cfg(feature = "tree")]
+mod profile;
+mod proof;
+#[cfg(feature = "tree")]
+mod quorum;
+#[cfg(feature = "tree")]
+mod rehash;
+#[cfg(feature = "tree")]
+mod replication;
+#[cfg(feature = "tree")]
+mod reserves;
+#[cfg(feature = "tree")]
+mod rolling;
+#[cfg(feature = "tree")]
+mod sampling;
+#[cfg(feature = "serde")]
+mod serde_leaf;
+#[cfg(feature = "tree")]
+mod sharded;
+#[cfg(feature = "tree")]
+mod signature;
+#[cfg(feature = "tree")]
+mod snapshot;
+#[cfg(feature = "solana")]
+mod solana;
+#[cfg(feature = "tree")]
+mod stable;
+#[cfg(feature = "tree")]
+mod store;
+#[cfg(feature = "stream")]
+mod stream;
+#[cfg(feature = "substrate")]
+mod substrate;
+#[cfg(feature = "tree")]
+mod sync;
+#[cfg(feature = "tree")]
+mod table;
+#[cfg(feature = "testing")]
+mod testing;
+#[cfg(feature = "tree")]
+mod text;
+#[cfg(feature = "tree")]
+mod transition;
+#[cfg(feature = "tree")]
+mod transparency;
+mod untrusted;
+#[cfg(feature = "tree")]
+mod vectors;
+#[cfg(feature = "tree")]
+mod versioned;
+#[cfg(feature = "tokio")]
+mod watch;
+mod wire;
+#[cfg(feature = "tree")]
+mod witness;
 
-pub use merkle_tree::MerkleTree;
+#[cfg(feature = "macros")]
+pub use included::IncludedTree;
+#[cfg(feature = "macros")]
+pub use merkle_tree_macros::{include_merkle, MerkleLeaf};
+
+#[cfg(feature = "tree")]
+pub use aggregate::{Aggregate, AggregateNode, AggregateProof, AggregateTree, MinMax};
+#[cfg(feature = "airdrop")]
+pub use airdrop::{Airdrop, AirdropRecord, Claim, RecordEncoding};
+#[cfg(feature = "tree")]
+pub use annotated::{AnnotatedProof, AnnotatedTree, LeafMetadata, MetadataMode};
+#[cfg(feature = "tree")]
+pub use append::AppendProof;
+#[cfg(feature = "tree")]
+pub use archive::ArchiveBundle;
+#[cfg(feature = "arena")]
+pub use arena::ArenaTree;
+#[cfg(feature = "tree")]
+pub use async_store::AsyncNodeStore;
+#[cfg(feature = "attestation")]
+pub use attestation::{BuildAttestation, Subject};
+#[cfg(feature = "tree")]
+pub use audit_log::{Checkpoint, MerkleLogWriter};
+#[cfg(feature = "tree")]
+pub use audit_trail::{AuditEntry, AuditTrail, TreeMutation};
+#[cfg(feature = "bitcoin")]
+pub use bitcoin::{
+    hash_from_display_hex, hash_to_display_hex, sha256d, verify_merkle_branch, MerkleBlock,
+    PartialMerkleTree,
+};
+#[cfg(feature = "tree")]
+pub use bloom::BloomFilter;
+#[cfg(feature = "tree")]
+pub use builder::{
+    AcceptsHashes, AcceptsItems, AnyInput, HashInput, ItemInput, MerkleTreeBuilder, RawItem,
+};
+#[cfg(feature = "tree")]
+pub use cache::{CacheLimit, CacheStats, CachedNodeStore};
+#[cfg(feature = "cbor")]
+pub use cbor::encode_cbor;
+#[cfg(feature = "tree")]
+pub use chunk_store::ChunkStore;
+#[cfg(feature = "tree")]
+pub use commit_reveal::{SealedTree, SealedValue};
+#[cfg(feature = "tree")]
+pub use config::{DuplicatePolicy, MutationGuard};
+#[cfg(feature = "tree")]
+pub use consistency::ConsistencyProof;
+#[cfg(feature = "tree")]
+pub use const_export::ConstExport;
+#[cfg(any(feature = "csv", feature = "parquet"))]
+pub use dataset::row_leaf;
+#[cfg(feature = "tree")]
+pub use delta::{ChunkTransfer, ChunkTree, DeltaSignature};
+#[cfg(feature = "tree")]
+pub use directory::DirOptions;
+#[cfg(feature = "tree")]
+pub use download::VerifiedDownload;
+#[cfg(feature = "tree")]
+pub use envelope::ProofEnvelope;
+pub use error::Error;
+#[cfg(feature = "tree")]
+pub use estimate::ProofEncoding;
+#[cfg(feature = "ethereum")]
+pub use ethereum::{
+    verify_trie_proof, AccountProof, Rlp, StorageProof, EMPTY_CODE_HASH, EMPTY_TRIE_ROOT,
+};
+#[cfg(feature = "tree")]
+pub use file_diff::{ChunkDiff, ChunkHunk, ChunkProof, Chunking};
+#[cfg(feature = "json")]
+pub use fixture::{CompatibilityReport, ProofMismatch, TreeReport};
+#[cfg(feature = "tree")]
+pub use follower::LogFollower;
+#[cfg(feature = "tree")]
+pub use forest::{Forest, ForestProof};
+#[cfg(feature = "tree")]
+pub use frozen::FrozenMerkleTree;
+#[cfg(feature = "tree")]
+pub use gas::GasProof;
+#[cfg(feature = "git")]
+pub use git::GitObjectFormat;
+#[cfg(feature = "tree")]
+pub use hashes::{LeafHash, NodeHash};
+pub use hashing::{hash, Hash, MerkleHasher, Sha256};
+#[cfg(feature = "tree")]
+pub use ignore::IgnoreRules;
+#[cfg(feature = "tree")]
+pub use interned::InternedTree;
+#[cfg(feature = "tree")]
+pub use item_proof::ProofWithItem;
+#[cfg(feature = "tree")]
+pub use item_tree::ItemTree;
+#[cfg(feature = "json")]
+pub use jcs::canonicalize_json;
+#[cfg(any(feature = "ethereum", feature = "solana"))]
+pub use keccak::keccak256;
+#[cfg(feature = "tree")]
+pub use leaf::MerkleLeaf;
+pub use limits::Limits;
+#[cfg(feature = "tree")]
+pub use manifest::{
+    FileMetadata, FileProof, ManifestEntry, PathOrdering, SignedManifest, SnapshotManifest,
+};
+#[cfg(feature = "tree")]
+pub use map::{MapProof, MerkleMap};
+#[cfg(feature = "tree")]
+pub use merkle_tree::{GenericMerkleTree, MerkleTree};
+#[cfg(feature = "tree")]
+pub use multipart::{FinishedUpload, MultipartUpload};
+#[cfg(feature = "tree")]
+pub use nested::NestedProof;
+#[cfg(feature = "tree")]
+pub use oplog::{LogEntry, LoggedTree, Operation, OperationLog};
+#[cfg(feature = "tree")]
+pub use packed::{PackedProof, PackedProofs};
+#[cfg(feature = "tree")]
+pub use partial::{AsyncNodeProvider, NodeProvider, PartialTree};
+#[cfg(feature = "tree")]
+pub use profile::{Profile, ProfileTree};
+pub use proof::{LevelProof, Proof, ProofError, SubtreeProof};
+#[cfg(feature = "tree")]
+pub use quorum::{Cosignature, CosignedCheckpoint, WitnessQuorum};
+#[cfg(any(feature = "ethereum", feature = "solana"))]
+pub use rehash::Keccak256;
+#[cfg(feature = "tree")]
+pub use replication::{Confirmation, Follower, ReplicaChannel, ReplicationReport};
+#[cfg(feature = "tree")]
+pub use reserves::{Liability, LiabilityProof, ReservesCommitment, ReservesTree, SumNode};
+#[cfg(feature = "tree")]
+pub use rolling::{RollingTree, WindowRoot};
+#[cfg(feature = "tree")]
+pub use sampling::{Sample, SamplingBundle};
+#[cfg(feature = "serde")]
+pub use serde_leaf::encode_serde;
+#[cfg(feature = "tree")]
+pub use sharded::ShardedTree;
+#[cfg(feature = "tree")]
+pub use signature::{SignatureVerifier, Signer};
+#[cfg(feature = "tree")]
+pub use snapshot::{export_snapshot, import_snapshot};
+#[cfg(feature = "solana")]
+pub use solana::{
+    concurrent_tree_proof, concurrent_tree_root, empty_node, ChangeLog, ConcurrentMerkleTree,
+    EMPTY_NODE,
+};
+#[cfg(feature = "tree")]
+pub use stable::{DeletionProof, LeafId, StableTree};
+#[cfg(feature = "tree")]
+pub use store::{MemoryNodeStore, NodeId, NodeStore};
+#[cfg(feature = "stream")]
+pub use stream::WindowedRoots;
+#[cfg(feature = "substrate")]
+pub use substrate::{blake2_256, read_proof_check, SubstrateChild, SubstrateNode, SubstrateValue};
+#[cfg(feature = "tree")]
+pub use sync::Transport;
+#[cfg(feature = "tree")]
+pub use table::TableDigest;
+#[cfg(feature = "testing")]
+pub use testing::{leaf_sets, MutatedProof, ProofCase, ProofMutation};
+#[cfg(feature = "tree")]
+pub use text::TextEncoding;
+#[cfg(feature = "tree")]
+pub use transition::UpdateProof;
+#[cfg(feature = "tree")]
+pub use transparency::{SignedCheckpoint, TransparencyLog, TransparencyMonitor};
+pub use untrusted::ParsedProof;
+#[cfg(feature = "tree")]
+pub use vectors::{TestVector, VECTOR_SIZES};
+#[cfg(feature = "tree")]
+pub use versioned::{TreeVersion, VersionedTree};
+pub use wire::WireError;
+#[cfg(feature = "tree")]
+pub use witness::{CircuitWitness, HashLimbs};
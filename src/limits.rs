@@ -0,0 +1,87 @@
+use crate::error::Error;
+
+/// Bounds on the size of untrusted input, checked before anything is allocated for it.
+///
+/// The default has no bounds. Set the ones relevant to the input with struct
+/// update syntax.
+///
+/// # Examples
+/// ```
+/// # #[cfg(feature = "tree")] {
+/// use merkle_tree::{Limits, MerkleTree};
+///
+/// let limits = Limits {
+///     max_leaves: 2,
+///     ..Limits::default()
+/// };
+///
+/// let result = MerkleTree::builder().limits(limits).build(&["Merry", "Pippin", "Sam"]);
+///
+/// assert!(result.is_err());
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The most leaves a tree may have, including the trees of peers.
+    pub max_leaves: usize,
+    /// The largest item, in bytes.
+    pub max_item_size: usize,
+    /// The most hashes a proof may have.
+    pub max_proof_length: usize,
+    /// The largest sum of the sizes of the items of a tree, in bytes.
+    pub max_total_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_leaves: usize::MAX,
+            max_item_size: usize::MAX,
+            max_proof_length: usize::MAX,
+            max_total_size: usize::MAX,
+        }
+    }
+}
+
+impl Limits {
+    fn check(limit: &'static str, value: usize, max: usize) -> Result<(), Error> {
+        if value > max {
+            return Err(Error::LimitExceeded { limit, value, max });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_leaf_count(&self, leaf_count: usize) -> Result<(), Error> {
+        Self::check("max_leaves", leaf_count, self.max_leaves)
+    }
+
+    #[cfg(feature = "tree")]
+    pub(crate) fn check_item_size(&self, size: usize) -> Result<(), Error> {
+        Self::check("max_item_size", size, self.max_item_size)
+    }
+
+    pub(crate) fn check_proof_length(&self, length: usize) -> Result<(), Error> {
+        Self::check("max_proof_length", length, self.max_proof_length)
+    }
+
+    #[cfg(feature = "tree")]
+    pub(crate) fn check_total_size(&self, size: usize) -> Result<(), Error> {
+        Self::check("max_total_size", size, self.max_total_size)
+    }
+
+    /// Checks the number and sizes of items before they are hashed.
+    #[cfg(feature = "tree")]
+    pub(crate) fn check_items<T: AsRef<[u8]>>(&self, items: &[T]) -> Result<(), Error> {
+        self.check_leaf_count(items.len())?;
+
+        let mut total_size = 0usize;
+        for item in items {
+            let size = item.as_ref().len();
+            self.check_item_size(size)?;
+            total_size = total_size.saturating_add(size);
+            self.check_total_size(total_size)?;
+        }
+
+        Ok(())
+    }
+}
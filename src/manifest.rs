@@ -0,0 +1,517 @@
+use std::cmp::Ordering;
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+use crate::signature::{SignatureVerifier, Signer};
+
+/// The canonical order of the entries of a manifest, which fixes its root
+/// whatever order the filesystem lists files in.
+///
+/// `Nfc` only exists with the `unicode` feature, hence `#[non_exhaustive]`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PathOrdering {
+    /// By the bytes of the paths.
+    #[default]
+    Bytewise,
+    /// By the lower case paths, rejecting paths equal but for case, which
+    /// collide on case-insensitive filesystems.
+    CaseInsensitive,
+    /// By the bytes of the paths in Unicode Normalization Form C, to which
+    /// paths are normalized first, as macOS stores them decomposed.
+    #[cfg(feature = "unicode")]
+    Nfc,
+}
+
+impl PathOrdering {
+    /// The name of the ordering, as recorded along a root.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Bytewise => "bytewise",
+            Self::CaseInsensitive => "case-insensitive",
+            #[cfg(feature = "unicode")]
+            Self::Nfc => "nfc",
+        }
+    }
+
+    fn normalize(&self, path: &str) -> String {
+        match self {
+            #[cfg(feature = "unicode")]
+            Self::Nfc => unicode_normalization::UnicodeNormalization::nfc(path).collect(),
+            _ => path.to_string(),
+        }
+    }
+
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            Self::CaseInsensitive => a.to_lowercase().cmp(&b.to_lowercase()),
+            _ => a.cmp(b),
+        }
+    }
+}
+
+/// Metadata of a file bound into its leaf next to its content, so a
+/// snapshot also covers permission changes. Absent fields are not encoded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// The permission bits, e.g. `0o755`.
+    pub mode: Option<u32>,
+    /// The modification time in whole seconds since the Unix epoch.
+    pub mtime: Option<i64>,
+    /// The target of a symbolic link, whose entry hashes the target.
+    pub symlink_target: Option<String>,
+}
+
+impl FileMetadata {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Encodes the present fields in a fixed order, each behind a tag byte:
+    /// 1 and the mode as 4 big-endian bytes, 2 and the mtime as 8, and 3,
+    /// the length of the symlink target as 8 and the target.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        if let Some(mode) = self.mode {
+            bytes.push(1);
+            bytes.extend_from_slice(&mode.to_be_bytes());
+        }
+        if let Some(mtime) = self.mtime {
+            bytes.push(2);
+            bytes.extend_from_slice(&mtime.to_be_bytes());
+        }
+        if let Some(target) = &self.symlink_target {
+            bytes.push(3);
+            bytes.extend_from_slice(&(target.len() as u64).to_be_bytes());
+            bytes.extend_from_slice(target.as_bytes());
+        }
+        bytes
+    }
+}
+
+/// A file of a snapshot: its path, length, hash and optional metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub length: u64,
+    pub hash: Hash,
+    pub metadata: FileMetadata,
+}
+
+impl ManifestEntry {
+    /// Describes the given file content.
+    pub fn new(path: impl Into<String>, content: &[u8]) -> Self {
+        Self {
+            path: path.into(),
+            length: content.len() as u64,
+            hash: MerkleTree::hash(content),
+            metadata: FileMetadata::default(),
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: FileMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// The leaf of the entry, binding its path, length and metadata to its hash.
+    pub fn leaf_hash(&self) -> Hash {
+        let mut bytes = (self.path.len() as u64).to_be_bytes().to_vec();
+        bytes.extend_from_slice(self.path.as_bytes());
+        bytes.extend_from_slice(&self.length.to_be_bytes());
+        bytes.extend_from_slice(&self.hash);
+        bytes.extend_from_slice(&self.metadata.to_bytes());
+        MerkleTree::hash(&bytes)
+    }
+}
+
+/// The proof that a file is part of a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileProof {
+    pub entry: ManifestEntry,
+    pub proof: Proof,
+}
+
+/// A snapshot of a software release, committing to the hash of every file via a tree root.
+///
+/// The publisher signs the root and the version once. Updaters then verify
+/// each downloaded file with its proof against the signed root, without
+/// waiting for the whole bundle.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{ManifestEntry, SignatureVerifier, Signer, SnapshotManifest};
+///
+/// struct Key;
+///
+/// impl Signer for Key {
+///     fn sign(&self, message: &[u8]) -> Vec<u8> {
+///         message.iter().rev().copied().collect()
+///     }
+/// }
+///
+/// impl SignatureVerifier for Key {
+///     fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+///         message.iter().rev().eq(signature.iter())
+///     }
+/// }
+///
+/// let files = [("bin/palantir", &b"seeing stone"[..]), ("README", &b"do not look"[..])];
+/// let manifest = SnapshotManifest::new(
+///     7,
+///     files.iter().map(|(path, content)| ManifestEntry::new(*path, content)).collect(),
+/// )
+/// .unwrap();
+///
+/// let signed = manifest.sign(&Key);
+/// let proof = manifest.prove("bin/palantir").unwrap();
+///
+/// assert!(signed.verify_file(&Key, "bin/palantir", b"seeing stone", &proof).is_ok());
+/// ```
+pub struct SnapshotManifest {
+    version: u64,
+    entries: Vec<ManifestEntry>,
+    ordering: PathOrdering,
+    tree: MerkleTree,
+}
+
+impl SnapshotManifest {
+    /// Creates the manifest of the given files, sorted by path.
+    /// Fails if there are no files or if a path appears twice.
+    pub fn new(version: u64, entries: Vec<ManifestEntry>) -> Result<Self, Error> {
+        Self::with_ordering(version, entries, PathOrdering::Bytewise)
+    }
+
+    /// Creates the manifest of the given files, sorted by path in `ordering`.
+    /// Fails if there are no files or if two paths are equal in `ordering`.
+    pub fn with_ordering(
+        version: u64,
+        mut entries: Vec<ManifestEntry>,
+        ordering: PathOrdering,
+    ) -> Result<Self, Error> {
+        for entry in &mut entries {
+            entry.path = ordering.normalize(&entry.path);
+        }
+        entries.sort_by(|a, b| ordering.compare(&a.path, &b.path));
+
+        if let Some(pair) = entries
+            .windows(2)
+            .find(|pair| ordering.compare(&pair[0].path, &pair[1].path) == Ordering::Equal)
+        {
+            return Err(Error::InvalidInput(match pair[0].path == pair[1].path {
+                true => format!("the path {} appears twice", pair[0].path),
+                false => format!(
+                    "the paths {} and {} are equal in {} order",
+                    pair[0].path,
+                    pair[1].path,
+                    ordering.name()
+                ),
+            }));
+        }
+
+        let tree = MerkleTree::from_leaves(entries.iter().map(ManifestEntry::leaf_hash).collect())
+            .ok_or(Error::EmptyTree)?;
+
+        Ok(Self {
+            version,
+            entries,
+            ordering,
+            tree,
+        })
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// The order of the entries the root was computed in.
+    pub fn ordering(&self) -> PathOrdering {
+        self.ordering
+    }
+
+    pub fn root(&self) -> Hash {
+        self.tree.root().expect("A manifest has a root.")
+    }
+
+    /// The files of the manifest, sorted by path in its ordering.
+    pub fn entries(&self) -> &[ManifestEntry] {
+        &self.entries
+    }
+
+    /// Proves the file at `path` is part of the snapshot.
+    pub fn prove(&self, path: &str) -> Result<FileProof, Error> {
+        let path = self.ordering.normalize(path);
+        let index = self
+            .entries
+            .binary_search_by(|entry| self.ordering.compare(&entry.path, &path))
+            .ok()
+            .filter(|index| self.entries[*index].path == path)
+            .ok_or_else(|| {
+                Error::InvalidInput(format!("the path {} is not in the manifest", path))
+            })?;
+
+        Ok(FileProof {
+            entry: self.entries[index].clone(),
+            proof: self.tree.proof(index)?,
+        })
+    }
+
+    /// Signs the version, path ordering and root of the manifest.
+    pub fn sign<S: Signer>(&self, signer: &S) -> SignedManifest {
+        let root = self.root();
+        let message = SignedManifest::message(self.version, self.ordering, &root);
+
+        SignedManifest {
+            version: self.version,
+            ordering: self.ordering,
+            root,
+            signature: signer.sign(&message),
+        }
+    }
+}
+
+/// The version, path ordering and root of a manifest with the publisher's
+/// signature, all an updater needs to verify files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedManifest {
+    pub version: u64,
+    /// The ordering the entries were sorted and their paths normalized in.
+    pub ordering: PathOrdering,
+    pub root: Hash,
+    pub signature: Vec<u8>,
+}
+
+impl SignedManifest {
+    const DOMAIN: &'static [u8] = b"merkle-tree snapshot manifest";
+
+    fn message(version: u64, ordering: PathOrdering, root: &Hash) -> Vec<u8> {
+        let mut message = Self::DOMAIN.to_vec();
+        message.extend_from_slice(&version.to_be_bytes());
+        message.extend_from_slice(root);
+        message.extend_from_slice(ordering.name().as_bytes());
+        message
+    }
+
+    /// Checks the publisher's signature over the version, ordering and root.
+    pub fn verify_signature<V: SignatureVerifier>(&self, verifier: &V) -> Result<(), Error> {
+        let message = Self::message(self.version, self.ordering, &self.root);
+        if verifier.verify(&message, &self.signature) {
+            Ok(())
+        } else {
+            Err(Error::InvalidSignature)
+        }
+    }
+
+    /// Checks that `content` is the file at `path` of the signed snapshot.
+    pub fn verify_file<V: SignatureVerifier>(
+        &self,
+        verifier: &V,
+        path: &str,
+        content: &[u8],
+        proof: &FileProof,
+    ) -> Result<(), Error> {
+        self.verify_signature(verifier)?;
+
+        let path = self.ordering.normalize(path);
+        let entry = ManifestEntry::new(path, content).with_metadata(proof.entry.metadata.clone());
+        if proof.entry != entry {
+            return Err(Error::InvalidInput(format!(
+                "the file does not match the entry of {}",
+                proof.entry.path
+            )));
+        }
+
+        proof.proof.verify(&proof.entry.leaf_hash(), &self.root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    struct HmacKey(&'static [u8]);
+
+    impl Signer for HmacKey {
+        fn sign(&self, message: &[u8]) -> Vec<u8> {
+            hmac_sha256::HMAC::mac(message, self.0).to_vec()
+        }
+    }
+
+    impl SignatureVerifier for HmacKey {
+        fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+            hmac_sha256::HMAC::mac(message, self.0) == signature
+        }
+    }
+
+    fn files() -> Vec<(String, Vec<u8>)> {
+        (0..9)
+            .map(|file| {
+                (
+                    format!("lib/ring-{}.so", file),
+                    format!("one ring to rule them all, copy {}", file).into_bytes(),
+                )
+            })
+            .collect()
+    }
+
+    fn manifest() -> SnapshotManifest {
+        let entries = files()
+            .iter()
+            .map(|(path, content)| ManifestEntry::new(path.as_str(), content))
+            .collect();
+        SnapshotManifest::new(3, entries).unwrap()
+    }
+
+    #[test]
+    fn test_files_are_verified_individually() {
+        let manifest = manifest();
+        let signed = manifest.sign(&HmacKey(b"Sauron"));
+
+        for (path, content) in files() {
+            let proof = manifest.prove(&path).unwrap();
+            assert_eq!(
+                signed.verify_file(&HmacKey(b"Sauron"), &path, &content, &proof),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn test_tampered_files_and_signatures_are_rejected() {
+        let manifest = manifest();
+        let signed = manifest.sign(&HmacKey(b"Sauron"));
+        let proof = manifest.prove("lib/ring-4.so").unwrap();
+
+        assert!(signed
+            .verify_file(&HmacKey(b"Sauron"), "lib/ring-4.so", b"a copy", &proof)
+            .is_err());
+        assert!(signed
+            .verify_file(&HmacKey(b"Sauron"), "lib/ring-5.so", &files()[4].1, &proof)
+            .is_err());
+        assert_eq!(
+            signed.verify_file(&HmacKey(b"Saruman"), "lib/ring-4.so", &files()[4].1, &proof),
+            Err(Error::InvalidSignature)
+        );
+
+        let mut rolled_back = signed.clone();
+        rolled_back.version = 2;
+        assert_eq!(
+            rolled_back.verify_signature(&HmacKey(b"Sauron")),
+            Err(Error::InvalidSignature)
+        );
+
+        let mut reordered = signed.clone();
+        reordered.ordering = PathOrdering::CaseInsensitive;
+        assert_eq!(
+            reordered.verify_signature(&HmacKey(b"Sauron")),
+            Err(Error::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_metadata_is_bound_into_leaves() {
+        let entry = ManifestEntry::new("bin/palantir", b"seeing stone");
+        let plain = entry.clone().with_metadata(FileMetadata::default());
+        let executable = entry.clone().with_metadata(FileMetadata {
+            mode: Some(0o755),
+            ..FileMetadata::default()
+        });
+        let private = entry.clone().with_metadata(FileMetadata {
+            mode: Some(0o700),
+            ..FileMetadata::default()
+        });
+
+        assert_eq!(plain.leaf_hash(), entry.leaf_hash());
+        assert_ne!(executable.leaf_hash(), entry.leaf_hash());
+        assert_ne!(executable.leaf_hash(), private.leaf_hash());
+
+        let manifest = SnapshotManifest::new(1, vec![executable]).unwrap();
+        let signed = manifest.sign(&HmacKey(b"Sauron"));
+        let proof = manifest.prove("bin/palantir").unwrap();
+        assert_eq!(
+            signed.verify_file(&HmacKey(b"Sauron"), "bin/palantir", b"seeing stone", &proof),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_orderings_fix_the_root() {
+        let entries = |paths: &[&str]| -> Vec<ManifestEntry> {
+            paths
+                .iter()
+                .map(|path| ManifestEntry::new(*path, path.as_bytes()))
+                .collect()
+        };
+        let listed = ["bin/Orc", "bin/ent", "README", "bin/Dwarf", "bin/balrog"];
+        let relisted = ["bin/balrog", "bin/Dwarf", "bin/ent", "README", "bin/Orc"];
+
+        let manifest =
+            SnapshotManifest::with_ordering(1, entries(&listed), PathOrdering::CaseInsensitive)
+                .unwrap();
+        let paths: Vec<&str> = manifest
+            .entries()
+            .iter()
+            .map(|entry| entry.path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            ["bin/balrog", "bin/Dwarf", "bin/ent", "bin/Orc", "README"]
+        );
+        assert_eq!(manifest.ordering(), PathOrdering::CaseInsensitive);
+        assert_eq!(
+            SnapshotManifest::with_ordering(1, entries(&relisted), PathOrdering::CaseInsensitive)
+                .unwrap()
+                .root(),
+            manifest.root()
+        );
+        assert_ne!(
+            SnapshotManifest::new(1, entries(&listed)).unwrap().root(),
+            manifest.root()
+        );
+
+        assert!(manifest.prove("bin/Orc").is_ok());
+        assert!(manifest.prove("bin/orc").is_err());
+        assert!(SnapshotManifest::with_ordering(
+            1,
+            entries(&["bin/Orc", "bin/orc"]),
+            PathOrdering::CaseInsensitive
+        )
+        .is_err());
+        assert!(SnapshotManifest::new(1, entries(&["bin/Orc", "bin/orc"])).is_ok());
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_nfc_ordering_normalizes_paths() {
+        let composed = || vec![ManifestEntry::new("L\u{f3}rien", b"mallorn")];
+        let decomposed = || vec![ManifestEntry::new("Lo\u{301}rien", b"mallorn")];
+
+        let manifest = SnapshotManifest::with_ordering(1, decomposed(), PathOrdering::Nfc).unwrap();
+        assert_eq!(
+            SnapshotManifest::with_ordering(1, composed(), PathOrdering::Nfc)
+                .unwrap()
+                .root(),
+            manifest.root()
+        );
+        assert_eq!(manifest.entries()[0].path, "L\u{f3}rien");
+        assert!(manifest.prove("Lo\u{301}rien").is_ok());
+        assert_ne!(
+            SnapshotManifest::new(1, decomposed()).unwrap().root(),
+            SnapshotManifest::new(1, composed()).unwrap().root()
+        );
+    }
+
+    #[test]
+    fn test_invalid_manifests_are_rejected() {
+        let entry = ManifestEntry::new("bin/orc", b"");
+
+        assert!(SnapshotManifest::new(1, vec![entry.clone(), entry]).is_err());
+        assert!(matches!(
+            SnapshotManifest::new(1, Vec::new()),
+            Err(Error::EmptyTree)
+        ));
+        assert!(manifest().prove("bin/missing").is_err());
+    }
+}
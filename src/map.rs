@@ -0,0 +1,223 @@
+use std::collections::BTreeMap;
+
+use crate::error::Error;
+use crate::leaf::MerkleLeaf;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// The leaf of an entry, the `MerkleLeaf` encoding of its key followed by its value.
+fn entry_hash(key: &[u8], value: &[u8]) -> Hash {
+    let mut bytes = key.leaf_bytes();
+    value.encode_leaf(&mut bytes);
+    MerkleTree::hash(&bytes)
+}
+
+/// A proof that a key has a value under the root of a `MerkleMap`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapProof {
+    pub key: Vec<u8>,
+    pub value: Vec<u8>,
+    pub proof: Proof,
+}
+
+impl MapProof {
+    /// Checks that the key has the value under `root`.
+    pub fn verify(&self, root: &Hash) -> Result<(), Error> {
+        self.proof.verify(&entry_hash(&self.key, &self.value), root)
+    }
+}
+
+/// A map committed by a tree over its entries, sorted by key bytes.
+///
+/// Each leaf commits to a key and its value, so a proof shows that key K has
+/// value V under root R.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::MerkleMap;
+///
+/// let mut map = MerkleMap::new();
+/// map.insert("Sting", "glows blue near orcs");
+/// map.insert("Glamdring", "the Foe-hammer");
+///
+/// let proof = map.prove("Sting").unwrap();
+///
+/// assert_eq!(proof.value, b"glows blue near orcs");
+/// assert!(proof.verify(&map.root().unwrap()).is_ok());
+/// ```
+#[derive(Default)]
+pub struct MerkleMap {
+    entries: BTreeMap<Vec<u8>, Vec<u8>>,
+    tree: Option<MerkleTree>,
+}
+
+impl MerkleMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a map from entries. A key appearing twice keeps its last value.
+    pub fn from_entries<I, K, V>(entries: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        let mut map = Self {
+            entries: entries
+                .into_iter()
+                .map(|(key, value)| (key.as_ref().to_vec(), value.as_ref().to_vec()))
+                .collect(),
+            tree: None,
+        };
+        map.rebuild();
+        map
+    }
+
+    fn rebuild(&mut self) {
+        self.tree = MerkleTree::from_leaves(
+            self.entries
+                .iter()
+                .map(|(key, value)| entry_hash(key, value))
+                .collect(),
+        );
+    }
+
+    /// Sets the value of a key and returns its previous value.
+    pub fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&mut self, key: K, value: V) -> Option<Vec<u8>> {
+        let previous = self
+            .entries
+            .insert(key.as_ref().to_vec(), value.as_ref().to_vec());
+        self.rebuild();
+        previous
+    }
+
+    /// Removes a key and returns its value.
+    pub fn remove<K: AsRef<[u8]>>(&mut self, key: K) -> Option<Vec<u8>> {
+        let previous = self.entries.remove(key.as_ref());
+        if previous.is_some() {
+            self.rebuild();
+        }
+        previous
+    }
+
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<&[u8]> {
+        self.entries.get(key.as_ref()).map(Vec::as_slice)
+    }
+
+    pub fn contains_key<K: AsRef<[u8]>>(&self, key: K) -> bool {
+        self.entries.contains_key(key.as_ref())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entries, sorted by key bytes.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        self.entries
+            .iter()
+            .map(|(key, value)| (key.as_slice(), value.as_slice()))
+    }
+
+    /// The root over every entry, `None` for an empty map.
+    pub fn root(&self) -> Option<Hash> {
+        self.tree.as_ref().and_then(MerkleTree::root)
+    }
+
+    /// The tree over the entries, `None` for an empty map.
+    pub fn tree(&self) -> Option<&MerkleTree> {
+        self.tree.as_ref()
+    }
+
+    /// Proves the value of `key`, if the map holds it.
+    pub fn prove<K: AsRef<[u8]>>(&self, key: K) -> Option<MapProof> {
+        let (index, (key, value)) = self
+            .entries
+            .iter()
+            .enumerate()
+            .find(|(_, (entry, _))| entry.as_slice() == key.as_ref())?;
+
+        Some(MapProof {
+            key: key.clone(),
+            value: value.clone(),
+            proof: self.tree.as_ref()?.proof(index).ok()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn map() -> MerkleMap {
+        MerkleMap::from_entries((0..20).map(|ring| {
+            (
+                format!("ring-{:02}", ring),
+                format!("given to the lords of {}", ring),
+            )
+        }))
+    }
+
+    #[test]
+    fn test_every_key_is_proven() {
+        let map = map();
+        let root = map.root().unwrap();
+
+        for (key, value) in map.iter() {
+            let proof = map.prove(key).unwrap();
+            assert_eq!(proof.value, value);
+            assert_eq!(proof.verify(&root), Ok(()));
+        }
+        assert!(map.prove("ring-20").is_none());
+    }
+
+    #[test]
+    fn test_proofs_bind_keys_to_values() {
+        let map = map();
+        let root = map.root().unwrap();
+
+        let mut proof = map.prove("ring-03").unwrap();
+        proof.value = b"given to the dwarves".to_vec();
+        assert!(proof.verify(&root).is_err());
+
+        let mut proof = map.prove("ring-03").unwrap();
+        proof.key = b"ring-04".to_vec();
+        assert!(proof.verify(&root).is_err());
+    }
+
+    #[test]
+    fn test_updates_change_the_root() {
+        let mut map = map();
+        let root = map.root().unwrap();
+
+        assert_eq!(
+            map.insert("ring-05", "lost"),
+            Some(b"given to the lords of 5".to_vec())
+        );
+        assert_ne!(map.root().unwrap(), root);
+        assert_eq!(map.get("ring-05"), Some(&b"lost"[..]));
+
+        map.insert("ring-05", "given to the lords of 5");
+        assert_eq!(map.root().unwrap(), root);
+
+        let mut empty = MerkleMap::new();
+        empty.insert("one", "ring");
+        empty.remove("one");
+        assert!(empty.is_empty());
+        assert_eq!(empty.root(), None);
+    }
+
+    #[test]
+    fn test_insertion_order_does_not_matter() {
+        let forward = MerkleMap::from_entries([("a", "1"), ("b", "2"), ("c", "3")]);
+        let backward = MerkleMap::from_entries([("c", "3"), ("b", "2"), ("a", "1")]);
+
+        assert_eq!(forward.root(), backward.root());
+    }
+}
@@ -1,6 +1,18 @@
-use hmac_sha256;
-
-type Hash = [u8; 32];
+use std::marker::PhantomData;
+
+use crate::audit_trail::{AuditTrail, TreeMutation};
+use crate::bloom::BloomFilter;
+use crate::builder::MerkleTreeBuilder;
+use crate::config::{DuplicatePolicy, MutationGuard, TreeConfig};
+use crate::error::Error;
+use crate::hashes::LeafHash;
+use crate::hashing;
+use crate::hashing::{MerkleHasher, Sha256};
+use crate::leaf::MerkleLeaf;
+use crate::limits::Limits;
+use crate::observer::RootObservers;
+
+pub use crate::hashing::Hash;
 
 struct TreePosition {
     level: usize,
@@ -8,11 +20,47 @@ struct TreePosition {
     hash: Hash,
 }
 
-pub struct MerkleTree {
+/// A Merkle tree hashing its leaves and nodes with `H`, any `MerkleHasher`
+/// such as a `digest::Digest` with 32 bytes outputs.
+///
+/// Most of the crate works on `MerkleTree`, the tree over SHA-256. Proofs of
+/// other trees are checked with `Proof::verify_with`, and the `_with`
+/// counterparts of the methods of the other proof types.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{GenericMerkleTree, MerkleTree};
+/// use sha2::Sha512_256;
+///
+/// let items = ["Glamdring", "Orcrist", "Sting"];
+/// let tree = GenericMerkleTree::<Sha512_256>::build(&items).unwrap();
+///
+/// let leaf = GenericMerkleTree::<Sha512_256>::hash(b"Sting");
+/// let proof = tree.proof(2).unwrap();
+/// assert!(proof.verify_with::<Sha512_256>(&leaf, &tree.root().unwrap()).is_ok());
+/// assert_ne!(tree.root(), MerkleTree::build(&items).unwrap().root());
+/// ```
+#[derive(Debug, Clone)]
+pub struct GenericMerkleTree<H> {
     levels: Vec<Vec<Hash>>,
+    config: TreeConfig,
+    observers: RootObservers,
+    pub(crate) bloom: Option<BloomFilter>,
+    pub(crate) audit: Option<AuditTrail>,
+    hasher: PhantomData<H>,
 }
 
+/// The tree over SHA-256.
+pub type MerkleTree = GenericMerkleTree<Sha256>;
+
 impl MerkleTree {
+    /// Returns a builder to create trees with non default options.
+    pub fn builder() -> MerkleTreeBuilder {
+        MerkleTreeBuilder::new()
+    }
+}
+
+impl<H: MerkleHasher> GenericMerkleTree<H> {
     /// Create a new MerkleTree from the provided items.
     /// Each item should be representable as bytes.
     /// The creation will fail if the items list is empty.
@@ -31,29 +79,133 @@ impl MerkleTree {
 
         let leaves: Vec<Hash> = items.iter().map(|item| Self::hash(item.as_ref())).collect();
 
-        let levels = Self::construct_levels(leaves);
+        Self::from_leaves(leaves)
+    }
+
+    /// Create a new MerkleTree from items with a canonical encoding.
+    /// Each item is hashed over the bytes of its `MerkleLeaf` encoding.
+    /// The creation will fail if the items list is empty.
+    pub fn build_leaves<T: MerkleLeaf>(items: &[T]) -> Option<Self> {
+        let leaves: Vec<Hash> = items
+            .iter()
+            .map(|item| Self::hash(&item.leaf_bytes()))
+            .collect();
+
+        Self::from_leaves(leaves)
+    }
+
+    /// Creates a tree from already hashed leaves.
+    pub(crate) fn from_leaves(leaves: Vec<Hash>) -> Option<Self> {
+        Self::from_leaves_with(leaves, TreeConfig::default()).ok()
+    }
+
+    /// Creates a tree from already hashed leaves, enforcing the options of `config`.
+    pub(crate) fn from_leaves_with(leaves: Vec<Hash>, config: TreeConfig) -> Result<Self, Error> {
+        if leaves.is_empty() {
+            return Err(Error::EmptyTree);
+        }
+        config.limits.check_leaf_count(leaves.len())?;
+        let leaves = config.duplicates.apply(leaves)?;
 
-        Some(Self { levels })
+        let levels = Self::construct_levels(leaves);
+        config.limits.check_proof_length(levels.len() - 1)?;
+        config.mutation_guard.check_levels(&levels)?;
+
+        Ok(Self {
+            levels,
+            config,
+            observers: RootObservers::default(),
+            bloom: None,
+            audit: None,
+            hasher: PhantomData,
+        })
     }
 
     /// Insert a new item into the Merkle tree.
     /// The tree will be updated to include the new item's hash.
     ///
+    /// Fails without modifying the tree if the result would violate the
+    /// options the tree was built with.
+    ///
     /// # Examples
     /// ```
     /// use merkle_tree::MerkleTree;
     /// let items = vec!["In a hole in the ground", "there lived a hobbit."];
     /// let mut merkle_tree = MerkleTree::build(&items).unwrap();
     ///
-    /// merkle_tree.insert(&"Gandalf the Grey");
+    /// merkle_tree.insert(&"Gandalf the Grey").unwrap();
     /// ```
-    pub fn insert<T: AsRef<[u8]>>(&mut self, item: &T) {
-        let mut leaves = self.levels[0].clone();
-        leaves.push(Self::hash(item.as_ref()));
+    pub fn insert<T: AsRef<[u8]>>(&mut self, item: &T) -> Result<(), Error> {
+        self.config.limits.check_item_size(item.as_ref().len())?;
+
+        self.try_insert_leaf(Self::hash(item.as_ref()), item.as_ref().len())
+    }
+
+    #[deprecated(note = "`insert` returns the error itself now")]
+    pub fn try_insert<T: AsRef<[u8]>>(&mut self, item: &T) -> Result<(), Error> {
+        self.insert(item)
+    }
+
+    /// Inserts an already hashed leaf of an item of `size` bytes, with the
+    /// same checks as `insert`.
+    pub(crate) fn try_insert_leaf(&mut self, leaf: Hash, size: usize) -> Result<(), Error> {
+        self.config.limits.check_leaf_count(self.leaf_count() + 1)?;
+        let total_size = self.config.total_size.saturating_add(size);
+        self.config.limits.check_total_size(total_size)?;
+
+        if self.contains_hash(&leaf) {
+            match self.config.duplicates {
+                DuplicatePolicy::Allow => {}
+                DuplicatePolicy::Deduplicate => return Ok(()),
+                DuplicatePolicy::Reject => {
+                    let index = self.leaves().iter().position(|h| *h == leaf);
+                    let indices = index.into_iter().chain([self.leaf_count()]).collect();
+                    return Err(Error::DuplicateLeaves(vec![indices]));
+                }
+            }
+        }
+
+        let mut leaves = self.leaves().to_vec();
+        leaves.push(leaf);
 
         let levels = Self::construct_levels(leaves);
+        self.config.limits.check_proof_length(levels.len() - 1)?;
+        self.config.mutation_guard.check_levels(&levels)?;
 
         self.levels = levels;
+        self.config.total_size = total_size;
+        self.refresh_bloom_filter(Some(&leaf));
+        self.record_mutation(TreeMutation::Insert { leaf });
+        self.notify_root_change();
+        Ok(())
+    }
+
+    /// Rebuilds the tree over new leaves with the options it was built
+    /// with, failing without modifying the tree if they violate them.
+    pub(crate) fn replace_leaves(&mut self, leaves: Vec<Hash>) -> Result<(), Error> {
+        let replaced = Self::from_leaves_with(leaves, self.config.clone())?;
+
+        self.levels = replaced.levels;
+        self.refresh_bloom_filter(None);
+        self.record_mutation(TreeMutation::Rebuild);
+        self.notify_root_change();
+        Ok(())
+    }
+
+    fn notify_root_change(&mut self) {
+        let leaf_count = self.leaf_count();
+        if let Some(root) = self.root() {
+            self.observers.notify(&root, leaf_count);
+        }
+    }
+
+    /// The leaves, in order.
+    pub(crate) fn leaves(&self) -> &[Hash] {
+        self.levels.first().map_or(&[], Vec::as_slice)
+    }
+
+    pub(crate) fn observers_mut(&mut self) -> &mut RootObservers {
+        &mut self.observers
     }
 
     fn construct_levels(leaves: Vec<Hash>) -> Vec<Vec<Hash>> {
@@ -63,7 +215,10 @@ impl MerkleTree {
 
         levels.push(leaves);
 
-        while let Some(level) = Self::merkle_parent_level(levels.last().unwrap()) {
+        while let Some(level) = levels
+            .last()
+            .and_then(|last| Self::merkle_parent_level(last))
+        {
             levels.push(level);
         }
 
@@ -75,22 +230,20 @@ impl MerkleTree {
     }
 
     /// Computes the parent hash for the concatenation of the children hashes.
-    fn merkle_parent(children: &[Hash]) -> Hash {
-        let mut children_vector = children.to_vec();
-        children_vector.sort();
-        Self::hash(children_vector.as_flattened())
+    pub(crate) fn merkle_parent(children: &[Hash]) -> Hash {
+        H::parent(&children[0], &children[1])
     }
 
     /// Creates the parent level for the given level.
     /// If the level has an odd number of hashes, the last hash is duplicated.
-    fn merkle_parent_level(level: &Vec<Hash>) -> Option<Vec<Hash>> {
+    fn merkle_parent_level(level: &[Hash]) -> Option<Vec<Hash>> {
         // Is root, return None.
-        if level.len() == 1 {
+        if level.len() <= 1 {
             return None;
         }
 
         // If the number of leafs is odd, duplicate the last leaf.
-        let mut parent_level = level.clone();
+        let mut parent_level = level.to_vec();
 
         if level.len() % 2 == 1 {
             parent_level.extend(parent_level.last().cloned())
@@ -105,11 +258,132 @@ impl MerkleTree {
     }
 
     /// Computes the Merkle root hash for the provided leaf hashes.
+    /// With `MutationGuard::CommitLeafCount` the root also commits to the number of leaves.
     pub fn root(&self) -> Option<Hash> {
-        Some(self.levels.last().unwrap().first().unwrap().clone())
+        let root = *self.levels.last()?.first()?;
+        Some(
+            self.config
+                .mutation_guard
+                .commit_root::<H>(root, self.leaf_count()),
+        )
     }
 
-    /// Hash the provided bytes using SHA-256.
+    /// Returns the defense against the duplicate leaf root mutation the tree was built with.
+    pub fn mutation_guard(&self) -> MutationGuard {
+        self.config.mutation_guard
+    }
+
+    /// Returns what the tree does with duplicate items.
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.config.duplicates
+    }
+
+    /// Returns the bounds the tree was built with.
+    pub fn limits(&self) -> Limits {
+        self.config.limits
+    }
+
+    /// Binds a tree root to the number of leaves, as `MutationGuard::CommitLeafCount` does.
+    pub fn commit_leaf_count(root: &Hash, leaf_count: usize) -> Hash {
+        hashing::commit_leaf_count::<H>(root, leaf_count)
+    }
+
+    /// Returns the number of leaves of the tree.
+    pub fn leaf_count(&self) -> usize {
+        self.leaves().len()
+    }
+
+    /// Returns the number of levels above the leaves, 0 for a single leaf tree.
+    pub fn height(&self) -> usize {
+        self.levels.len().saturating_sub(1)
+    }
+
+    /// Returns the hash of the node at the given level and index.
+    /// Level 0 holds the leaves and level `height()` the root.
+    pub fn node(&self, level: usize, index: usize) -> Option<Hash> {
+        self.levels.get(level)?.get(index).copied()
+    }
+
+    /// Returns the tree over the leaves below the node at `level` and `index`,
+    /// with the options of this tree, e.g. to delegate serving the proofs of a shard.
+    ///
+    /// The root of the copy is that node when the subtree is complete. A
+    /// subtree at the right edge of a tree whose size is not a multiple of
+    /// its width has fewer leaves, and the nodes repeated to pad it are not
+    /// part of the copy, so its root is the one of a tree over those leaves.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::MerkleTree;
+    ///
+    /// let tree = MerkleTree::build(&["Fornost", "Annúminas", "Weathertop", "Bree", "Tharbad"]).unwrap();
+    ///
+    /// let shard = tree.subtree(2, 0).unwrap();
+    ///
+    /// assert_eq!(shard.leaf_count(), 4);
+    /// assert_eq!(shard.root(), tree.node(2, 0));
+    /// ```
+    pub fn subtree(&self, level: usize, index: usize) -> Result<Self, Error> {
+        let width = Self::level_width(self.leaf_count(), level);
+        if index >= width {
+            return Err(Error::InvalidInput(format!(
+                "the tree has {} nodes at level {}, not {}",
+                width,
+                level,
+                index + 1
+            )));
+        }
+
+        let first = index << level;
+        let last = ((index + 1) << level).min(self.leaf_count());
+        let config = TreeConfig {
+            duplicates: DuplicatePolicy::Allow,
+            ..self.config
+        };
+
+        Self::from_leaves_with(self.leaves()[first..last].to_vec(), config)
+    }
+
+    /// Returns the nodes of every level, from the leaves up to the root.
+    ///
+    /// Replicas can compare them, or their `level_digests`, layer by layer to
+    /// narrow down where they diverge without exchanging the whole tree.
+    pub fn level_roots(&self) -> impl Iterator<Item = &[Hash]> {
+        self.levels.iter().map(Vec::as_slice)
+    }
+
+    /// Returns a digest of every level, the hash of the concatenation of its
+    /// nodes, from the leaves up to the root.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::MerkleTree;
+    ///
+    /// let primary = MerkleTree::build(&["Minas Tirith", "Osgiliath", "Dol Amroth"]).unwrap();
+    /// let replica = MerkleTree::build(&["Minas Tirith", "Osgiliath", "Pelargir"]).unwrap();
+    ///
+    /// let same: Vec<bool> = primary
+    ///     .level_digests()
+    ///     .iter()
+    ///     .zip(replica.level_digests())
+    ///     .map(|(ours, theirs)| *ours == theirs)
+    ///     .collect();
+    /// assert_eq!(same, vec![false, false, false]);
+    /// ```
+    pub fn level_digests(&self) -> Vec<Hash> {
+        self.levels
+            .iter()
+            .map(|level| Self::hash(level.as_flattened()))
+            .collect()
+    }
+
+    /// Returns the number of nodes at the given level of a tree with `leaf_count` leaves,
+    /// 0 if the tree has no such level.
+    pub(crate) fn level_width(leaf_count: usize, level: usize) -> usize {
+        hashing::level_width(leaf_count, level)
+    }
+
+    /// Hash the provided bytes using `H`, SHA-256 for `MerkleTree`.
     /// Returns the hash as a 32 bytes array.
     ///
     /// # Examples
@@ -120,14 +394,14 @@ impl MerkleTree {
     /// let hash = MerkleTree::hash(input.as_bytes());
     /// ```
     pub fn hash(bytes: &[u8]) -> Hash {
-        hmac_sha256::Hash::hash(bytes)
+        H::hash(bytes)
     }
 
     // Returns tuple (level, index, hash).
     fn get_parent(&self, level: usize, index: usize) -> Option<TreePosition> {
         let parent_index = index / 2;
         let parent_level = level + 1;
-        let parent = self.levels.get(parent_level)?.get(parent_index)?.clone();
+        let parent = *self.levels.get(parent_level)?.get(parent_index)?;
 
         Some(TreePosition {
             level: parent_level,
@@ -139,45 +413,67 @@ impl MerkleTree {
     fn get_sibling(&self, level: usize, index: usize) -> Option<TreePosition> {
         let sibling_index = if index % 2 == 1 { index - 1 } else { index + 1 };
 
-        let sibling = self.levels.get(level)?.get(sibling_index)?.clone();
+        let sibling = *self.levels.get(level)?.get(sibling_index)?;
 
         Some(TreePosition {
-            level: level,
+            level,
             index: sibling_index,
             hash: sibling,
         })
     }
 
-    pub fn proof_of_inclusion(&self, hash: &Hash) -> Option<Vec<Hash>> {
-        let index = self.levels.get(0)?.iter().position(|&h| h == *hash)?;
+    /// Returns the siblings from the first occurrence of `leaf` up to the root.
+    pub fn proof_of_inclusion(&self, leaf: &LeafHash) -> Option<Vec<Hash>> {
+        let hash = *leaf.as_hash();
+        let index = self.levels.first()?.iter().position(|&h| h == hash)?;
 
         let mut current = TreePosition {
             level: 0,
             index,
-            hash: hash.clone(),
+            hash,
         };
 
         let mut proof: Vec<Hash> = Vec::new();
 
         while let Some(parent) = self.get_parent(current.level, current.index) {
             let sibling = self.get_sibling(current.level, current.index);
-            proof.push(sibling.or(Some(current)).unwrap().hash);
+            proof.push(sibling.unwrap_or(current).hash);
             current = parent;
         }
 
         Some(proof)
     }
 
-    pub fn validate_proof(&self, hash: &Hash, proof: &[Hash]) -> bool {
-        let validation_root = proof.iter().fold(hash.clone(), |hash, sibling| {
+    /// Checks that the proof leads from the leaf to the root.
+    /// Proofs with more or fewer hashes than the tree has levels are invalid,
+    /// so an interior node can't pass as a leaf with a truncated proof.
+    pub fn validate_proof(&self, leaf: &LeafHash, proof: &[Hash]) -> bool {
+        if self.config.limits.check_proof_length(proof.len()).is_err() {
+            return false;
+        }
+        if proof.len() != self.height() {
+            return false;
+        }
+
+        let validation_root = proof.iter().fold(*leaf.as_hash(), |hash, sibling| {
             Self::merkle_parent(&[hash, *sibling])
         });
+        let validation_root = self
+            .config
+            .mutation_guard
+            .commit_root::<H>(validation_root, self.leaf_count());
 
-        validation_root == self.root().expect("The tree has no root.")
+        Some(validation_root) == self.root()
     }
 
+    /// Checks that the hash is a leaf, consulting the Bloom filter first if enabled.
     pub fn contains_hash(&self, hash: &Hash) -> bool {
-        self.levels[0].iter().any(|h| h == hash)
+        if let Some(bloom) = &self.bloom {
+            if !bloom.may_contain(hash) {
+                return false;
+            }
+        }
+        self.leaves().iter().any(|h| h == hash)
     }
 }
 
@@ -302,7 +598,7 @@ mod tests {
             "In the Land of Mordor where the Shadows lie.",
         ];
 
-        let hashes = vec![
+        let hashes = [
             MerkleTree::hash(items[0].as_bytes()),
             MerkleTree::hash(items[1].as_bytes()),
             MerkleTree::hash(items[2].as_bytes()),
@@ -382,7 +678,7 @@ mod tests {
 
         let non_existant_hash = MerkleTree::hash("Fly, you fools!".as_bytes());
 
-        let proof = tree.proof_of_inclusion(&non_existant_hash);
+        let proof = tree.proof_of_inclusion(&LeafHash::new(non_existant_hash));
 
         assert!(proof.is_none());
     }
@@ -401,7 +697,7 @@ mod tests {
 
         let hash = MerkleTree::hash(items[2].as_bytes());
 
-        let proof = tree.proof_of_inclusion(&hash).unwrap();
+        let proof = tree.proof_of_inclusion(&LeafHash::new(hash)).unwrap();
 
         assert_eq!(proof.len(), 3);
         assert_eq!(proof[0].to_vec(), tree.levels[0][3].to_vec());
@@ -422,7 +718,7 @@ mod tests {
         let corrupt_tree = MerkleTree::build(&corrupted_items).unwrap();
         let corrupt_element_hash = corrupt_tree.levels[0][2];
         let wrong_proof = corrupt_tree
-            .proof_of_inclusion(&corrupt_element_hash)
+            .proof_of_inclusion(&LeafHash::new(corrupt_element_hash))
             .unwrap();
 
         let correct_items = vec![
@@ -435,7 +731,7 @@ mod tests {
 
         let correct_tree = MerkleTree::build(&correct_items).unwrap();
 
-        assert!(!correct_tree.validate_proof(&corrupt_element_hash, &wrong_proof));
+        assert!(!correct_tree.validate_proof(&LeafHash::new(corrupt_element_hash), &wrong_proof));
     }
 
     #[test]
@@ -452,9 +748,9 @@ mod tests {
 
         let hash = MerkleTree::hash(items[2].as_bytes());
 
-        let proof = tree.proof_of_inclusion(&hash).unwrap();
+        let proof = tree.proof_of_inclusion(&LeafHash::new(hash)).unwrap();
 
-        assert!(tree.validate_proof(&hash, &proof));
+        assert!(tree.validate_proof(&LeafHash::new(hash), &proof));
     }
 
     #[test]
@@ -485,6 +781,217 @@ mod tests {
         assert!(tree.contains_hash(&hash));
     }
 
+    #[test]
+    fn test_duplicated_tail_shares_the_root_without_guard() {
+        let three = MerkleTree::build(&["Huey", "Dewey", "Louie"]).unwrap();
+        let four = MerkleTree::build(&["Huey", "Dewey", "Louie", "Louie"]).unwrap();
+
+        assert_eq!(three.root(), four.root());
+    }
+
+    #[test]
+    fn test_reject_duplicate_trailing_pairs() {
+        let builder =
+            MerkleTree::builder().mutation_guard(MutationGuard::RejectDuplicateTrailingPairs);
+
+        assert!(builder.build(&["Huey", "Dewey", "Louie"]).is_ok());
+        assert!(builder.build(&["Huey", "Dewey", "Louie", "Louie"]).is_err());
+        // Duplicating a whole subtree shows up as a duplicated pair at a higher level.
+        assert!(builder
+            .build(&["Huey", "Dewey", "Louie", "Webby", "Scrooge", "Donald", "Scrooge", "Donald"])
+            .is_err());
+
+        let mut tree = builder.build(&["Huey", "Dewey", "Louie"]).unwrap();
+        assert!(tree.insert(&"Louie").is_err());
+        assert_eq!(tree.leaf_count(), 3);
+        assert!(tree.insert(&"Webby").is_ok());
+    }
+
+    #[test]
+    fn test_commit_leaf_count() {
+        let builder = MerkleTree::builder().mutation_guard(MutationGuard::CommitLeafCount);
+
+        let three = builder.build(&["Huey", "Dewey", "Louie"]).unwrap();
+        let four = builder.build(&["Huey", "Dewey", "Louie", "Louie"]).unwrap();
+        let plain = MerkleTree::build(&["Huey", "Dewey", "Louie"]).unwrap();
+
+        assert_ne!(three.root(), four.root());
+        assert_eq!(
+            three.root().unwrap(),
+            MerkleTree::commit_leaf_count(&plain.root().unwrap(), 3)
+        );
+
+        let leaf = MerkleTree::hash(b"Dewey");
+        let proof = three.proof_of_inclusion(&LeafHash::new(leaf)).unwrap();
+        assert!(three.validate_proof(&LeafHash::new(leaf), &proof));
+    }
+
+    #[test]
+    fn test_limits_are_enforced() {
+        let limits = Limits {
+            max_leaves: 3,
+            max_item_size: 8,
+            max_proof_length: 1,
+            max_total_size: 20,
+        };
+        let builder = MerkleTree::builder().limits(limits);
+
+        assert_eq!(
+            builder.build(&["Merry", "Pippin", "Sam", "Frodo"]).err(),
+            Some(Error::LimitExceeded {
+                limit: "max_leaves",
+                value: 4,
+                max: 3
+            })
+        );
+        assert!(builder.build(&["Peregrin Took"]).is_err());
+        assert!(builder.build(&["Meriadoc", "Peregrin", "Samwise"]).is_err());
+
+        let mut tree = builder.build(&["Merry", "Pippin"]).unwrap();
+        assert_eq!(
+            tree.insert(&"Sam").err(),
+            Some(Error::LimitExceeded {
+                limit: "max_proof_length",
+                value: 2,
+                max: 1
+            })
+        );
+        assert_eq!(tree.leaf_count(), 2);
+
+        let mut tree = MerkleTree::builder()
+            .limits(Limits {
+                max_proof_length: 2,
+                ..limits
+            })
+            .build(&["Merry", "Pippin"])
+            .unwrap();
+        assert!(tree.insert(&"Sam").is_ok());
+        assert!(tree.insert(&"Frodo").is_err());
+        assert_eq!(tree.leaf_count(), 3);
+
+        let mut tree = MerkleTree::builder()
+            .limits(Limits {
+                max_leaves: 10,
+                max_proof_length: 4,
+                ..limits
+            })
+            .build(&["Meriadoc", "Peregrin"])
+            .unwrap();
+        assert_eq!(
+            tree.insert(&"Samwise").err(),
+            Some(Error::LimitExceeded {
+                limit: "max_total_size",
+                value: 23,
+                max: 20
+            })
+        );
+    }
+
+    #[test]
+    fn test_duplicate_policies() {
+        let ids = ["frodo", "sam", "frodo", "merry", "sam", "frodo"];
+
+        let allowed = MerkleTree::build(&ids).unwrap();
+        assert_eq!(allowed.leaf_count(), 6);
+
+        let deduplicated = MerkleTree::builder()
+            .duplicates(DuplicatePolicy::Deduplicate)
+            .build(&ids)
+            .unwrap();
+        let unique = MerkleTree::build(&["frodo", "sam", "merry"]).unwrap();
+        assert_eq!(deduplicated.root(), unique.root());
+
+        let rejected = MerkleTree::builder()
+            .duplicates(DuplicatePolicy::Reject)
+            .build(&ids);
+        assert_eq!(
+            rejected.err(),
+            Some(Error::DuplicateLeaves(vec![vec![0, 2, 5], vec![1, 4]]))
+        );
+    }
+
+    #[test]
+    fn test_duplicate_policies_on_insert() {
+        let mut deduplicated = MerkleTree::builder()
+            .duplicates(DuplicatePolicy::Deduplicate)
+            .build(&["frodo", "sam"])
+            .unwrap();
+        deduplicated.insert(&"sam").unwrap();
+        assert_eq!(deduplicated.leaf_count(), 2);
+
+        let mut rejecting = MerkleTree::builder()
+            .duplicates(DuplicatePolicy::Reject)
+            .build(&["frodo", "sam"])
+            .unwrap();
+        assert_eq!(
+            rejecting.insert(&"frodo"),
+            Err(Error::DuplicateLeaves(vec![vec![0, 2]]))
+        );
+        assert_eq!(rejecting.leaf_count(), 2);
+    }
+
+    #[test]
+    fn test_complete_subtrees_keep_their_root() {
+        let items: Vec<String> = (0..11).map(|item| format!("beacon {}", item)).collect();
+        let tree = MerkleTree::build(&items).unwrap();
+
+        for (level, index) in [(0, 7), (1, 2), (2, 1), (3, 0)] {
+            let subtree = tree.subtree(level, index).unwrap();
+            assert_eq!(subtree.leaf_count(), 1 << level);
+            assert_eq!(subtree.root(), tree.node(level, index));
+
+            // A proof from the copy composes with the proof of its root in the tree.
+            let leaf_index = index << level;
+            let within = subtree.proof(0).unwrap();
+            let above = &tree.proof(leaf_index).unwrap().siblings[level..];
+            let root = above.iter().fold(subtree.root().unwrap(), |node, sibling| {
+                MerkleTree::merkle_parent(&[node, *sibling])
+            });
+            assert_eq!(
+                within.compute_root(&tree.node(0, leaf_index).unwrap()),
+                Ok(subtree.root().unwrap())
+            );
+            assert_eq!(Some(root), tree.root());
+        }
+    }
+
+    #[test]
+    fn test_subtrees_at_the_right_edge() {
+        let items: Vec<String> = (0..11).map(|item| format!("beacon {}", item)).collect();
+        let tree = MerkleTree::build(&items).unwrap();
+
+        let edge = tree.subtree(2, 2).unwrap();
+
+        assert_eq!(edge.leaf_count(), 3);
+        assert_eq!(edge.root(), MerkleTree::build(&items[8..]).unwrap().root());
+        assert!(tree.subtree(2, 3).is_err());
+        assert!(tree.subtree(5, 0).is_err());
+    }
+
+    #[test]
+    fn test_level_roots_and_digests() {
+        let items: Vec<String> = (0..9).map(|item| format!("palantír {}", item)).collect();
+        let tree = MerkleTree::build(&items).unwrap();
+
+        let levels: Vec<&[Hash]> = tree.level_roots().collect();
+        assert_eq!(
+            levels.iter().map(|level| level.len()).collect::<Vec<_>>(),
+            vec![9, 5, 3, 2, 1]
+        );
+        assert_eq!(levels[4], &[tree.root().unwrap()]);
+        assert_eq!(levels[1][2], tree.node(1, 2).unwrap());
+
+        let mut changed = items.clone();
+        changed[8] = "the palantír of Orthanc".to_string();
+        let other = MerkleTree::build(&changed).unwrap();
+
+        let digests = tree.level_digests();
+        let other_digests = other.level_digests();
+        assert_eq!(digests.len(), 5);
+        assert!(digests.iter().zip(&other_digests).all(|(a, b)| a != b));
+        assert_eq!(digests, MerkleTree::build(&items).unwrap().level_digests());
+    }
+
     #[test]
     fn test_add_an_element() {
         let items = vec![
@@ -499,7 +1006,7 @@ mod tests {
 
         assert_eq!(tree.levels[0].len(), 5);
 
-        tree.insert(&"Man the mortal, master of horses:");
+        tree.insert(&"Man the mortal, master of horses:").unwrap();
 
         assert_eq!(tree.levels[0].len(), 6);
 
@@ -519,4 +1026,22 @@ mod tests {
             tree_complete.root().unwrap().to_vec()
         );
     }
+
+    #[test]
+    fn test_trees_hash_with_their_digest() {
+        let items = ["Narsil", "Andúril", "Herugrim", "Gúthwinë"];
+        let tree = MerkleTree::build(&items).unwrap();
+
+        let digested = GenericMerkleTree::<sha2::Sha256>::build(&items).unwrap();
+        assert_eq!(digested.root(), tree.root());
+
+        let other = GenericMerkleTree::<sha2::Sha512_256>::build(&items).unwrap();
+        assert_ne!(other.root(), tree.root());
+        for (index, item) in items.iter().enumerate() {
+            let leaf = GenericMerkleTree::<sha2::Sha512_256>::hash(item.as_bytes());
+            let proof = other.proof(index).unwrap();
+            assert_eq!(other.verify_proof(&leaf, &proof), Ok(()));
+            assert!(proof.verify(&leaf, &other.root().unwrap()).is_err());
+        }
+    }
 }
@@ -0,0 +1,206 @@
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// Computes a tree over the parts of a multipart object upload.
+///
+/// Parts are added as they are uploaded. Every part but the last must have
+/// the configured part size. The root can be stored as object metadata, and
+/// the per-part proofs let a downloader verify each part before assembling
+/// the object.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::MultipartUpload;
+///
+/// let mut upload = MultipartUpload::new(4).unwrap();
+/// upload.add_part(b"Mith").unwrap();
+/// upload.add_part(b"rand").unwrap();
+/// upload.add_part(b"ir").unwrap();
+/// let upload = upload.finish().unwrap();
+///
+/// let proof = upload.part_proof(1).unwrap();
+/// assert!(MultipartUpload::verify_part(&upload.root(), b"rand", &proof).is_ok());
+/// ```
+pub struct MultipartUpload {
+    part_size: usize,
+    length: usize,
+    leaves: Vec<Hash>,
+    last_is_short: bool,
+}
+
+impl MultipartUpload {
+    pub fn new(part_size: usize) -> Result<Self, Error> {
+        if part_size == 0 {
+            return Err(Error::InvalidInput("the parts can't be empty".to_string()));
+        }
+
+        Ok(Self {
+            part_size,
+            length: 0,
+            leaves: Vec::new(),
+            last_is_short: false,
+        })
+    }
+
+    /// Splits a whole object in parts and finishes the upload.
+    pub fn from_object(object: &[u8], part_size: usize) -> Result<FinishedUpload, Error> {
+        let mut upload = Self::new(part_size)?;
+        for part in object.chunks(part_size) {
+            upload.add_part(part)?;
+        }
+        upload.finish()
+    }
+
+    /// Adds the next part and returns its index.
+    pub fn add_part(&mut self, part: &[u8]) -> Result<usize, Error> {
+        if self.last_is_short {
+            return Err(Error::InvalidInput(format!(
+                "only the last part can be shorter than {} bytes",
+                self.part_size
+            )));
+        }
+        if part.is_empty() || part.len() > self.part_size {
+            return Err(Error::InvalidInput(format!(
+                "a part has between 1 and {} bytes, not {}",
+                self.part_size,
+                part.len()
+            )));
+        }
+
+        self.last_is_short = part.len() < self.part_size;
+        self.length += part.len();
+        self.leaves.push(MerkleTree::hash(part));
+
+        Ok(self.leaves.len() - 1)
+    }
+
+    /// Builds the tree over the parts added so far.
+    pub fn finish(self) -> Result<FinishedUpload, Error> {
+        let tree = MerkleTree::from_leaves(self.leaves).ok_or(Error::EmptyTree)?;
+
+        Ok(FinishedUpload {
+            part_size: self.part_size,
+            length: self.length,
+            tree,
+        })
+    }
+
+    pub fn part_size(&self) -> usize {
+        self.part_size
+    }
+
+    pub fn part_count(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// The size of the object, the sum of the sizes of its parts.
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Checks a downloaded part against the root of the object.
+    pub fn verify_part(root: &Hash, part: &[u8], proof: &Proof) -> Result<(), Error> {
+        proof.verify(&MerkleTree::hash(part), root)
+    }
+}
+
+/// A multipart upload with all of its parts, returned by
+/// `MultipartUpload::finish`.
+pub struct FinishedUpload {
+    part_size: usize,
+    length: usize,
+    tree: MerkleTree,
+}
+
+impl FinishedUpload {
+    pub fn part_size(&self) -> usize {
+        self.part_size
+    }
+
+    pub fn part_count(&self) -> usize {
+        self.tree.leaf_count()
+    }
+
+    /// The size of the object, the sum of the sizes of its parts.
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// The root over the parts.
+    pub fn root(&self) -> Hash {
+        self.tree.root().expect("A finished upload has a root.")
+    }
+
+    /// The root as a hex string, to store as object metadata.
+    pub fn metadata_value(&self) -> String {
+        hex::encode(self.root())
+    }
+
+    /// Proves the part at `index` is in the object.
+    pub fn part_proof(&self, index: usize) -> Result<Proof, Error> {
+        self.tree.proof(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn object(size: usize) -> Vec<u8> {
+        (0..size).map(|byte| (byte % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_parts_are_verified_individually() {
+        let object = object(10_000);
+        let upload = MultipartUpload::from_object(&object, 1024).unwrap();
+
+        assert_eq!(upload.part_count(), 10);
+        assert_eq!(upload.length(), 10_000);
+
+        let root: Hash = hex::decode(upload.metadata_value())
+            .unwrap()
+            .try_into()
+            .unwrap();
+        for (index, part) in object.chunks(1024).enumerate() {
+            let proof = upload.part_proof(index).unwrap();
+            assert_eq!(MultipartUpload::verify_part(&root, part, &proof), Ok(()));
+        }
+
+        let proof = upload.part_proof(3).unwrap();
+        assert!(MultipartUpload::verify_part(&root, &object[..1024], &proof).is_err());
+    }
+
+    #[test]
+    fn test_incremental_upload_matches_whole_object() {
+        let object = object(5000);
+        let mut upload = MultipartUpload::new(2048).unwrap();
+        for part in object.chunks(2048) {
+            upload.add_part(part).unwrap();
+        }
+
+        assert_eq!(
+            upload.finish().unwrap().root(),
+            MultipartUpload::from_object(&object, 2048).unwrap().root()
+        );
+    }
+
+    #[test]
+    fn test_invalid_parts_are_rejected() {
+        let mut upload = MultipartUpload::new(4).unwrap();
+
+        assert!(upload.add_part(b"").is_err());
+        assert!(upload.add_part(b"Narsil").is_err());
+        upload.add_part(b"And").unwrap();
+        assert!(upload.add_part(b"uril").is_err());
+
+        assert_eq!(
+            MultipartUpload::new(4).unwrap().finish().err(),
+            Some(Error::EmptyTree)
+        );
+        assert!(MultipartUpload::new(0).is_err());
+        assert!(MultipartUpload::from_object(b"Anduril", 0).is_err());
+    }
+}
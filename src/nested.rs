@@ -0,0 +1,151 @@
+use crate::error::Error;
+use crate::merkle_tree::Hash;
+use crate::proof::Proof;
+
+/// A proof through a hierarchy of trees, where the roots of the trees of
+/// one layer are the leaves of a tree of the layer above: a leaf is proven
+/// up to the root of its shard, the shard root up to the top root, and so on.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{MerkleTree, NestedProof};
+///
+/// let west = MerkleTree::build(&["Lindon", "Eregion"]).unwrap();
+/// let east = MerkleTree::build(&["Erebor", "Dale", "Esgaroth"]).unwrap();
+/// let top = MerkleTree::builder()
+///     .build_from_hashes(vec![west.root().unwrap(), east.root().unwrap()])
+///     .unwrap();
+///
+/// let proof = NestedProof::new(east.proof(1).unwrap()).then(top.proof(1).unwrap());
+///
+/// assert!(proof.verify(&MerkleTree::hash(b"Dale"), &top.root().unwrap()).is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NestedProof {
+    /// The proofs of every layer, from the tree of the leaf up.
+    pub layers: Vec<Proof>,
+}
+
+impl NestedProof {
+    /// Starts a proof with the proof of the leaf in its own tree.
+    pub fn new(proof: Proof) -> Self {
+        Self {
+            layers: vec![proof],
+        }
+    }
+
+    /// Adds the proof of the root reached so far in the tree of the layer above.
+    pub fn then(mut self, proof: Proof) -> Self {
+        self.layers.push(proof);
+        self
+    }
+
+    /// The number of trees the proof goes through.
+    pub fn depth(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// The positions of the leaf in every layer, from the tree of the leaf up.
+    pub fn indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.layers.iter().map(|proof| proof.leaf_index)
+    }
+
+    /// Validates every layer and folds the leaf into the top root.
+    pub fn compute_root(&self, leaf: &Hash) -> Result<Hash, Error> {
+        if self.layers.is_empty() {
+            return Err(Error::InvalidInput(
+                "a nested proof needs a layer".to_string(),
+            ));
+        }
+
+        self.layers
+            .iter()
+            .try_fold(*leaf, |node, proof| proof.compute_root(&node))
+    }
+
+    /// Checks that the proof leads from `leaf` to the top `root`.
+    pub fn verify(&self, leaf: &Hash, root: &Hash) -> Result<(), Error> {
+        if self.compute_root(leaf)? != *root {
+            return Err(Error::RootMismatch);
+        }
+        Ok(())
+    }
+}
+
+impl From<Vec<Proof>> for NestedProof {
+    fn from(layers: Vec<Proof>) -> Self {
+        Self { layers }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::merkle_tree::MerkleTree;
+
+    fn shards() -> Vec<MerkleTree> {
+        (0..5)
+            .map(|shard| {
+                let items: Vec<String> = (0..shard + 2)
+                    .map(|item| format!("shard {} item {}", shard, item))
+                    .collect();
+                MerkleTree::build(&items).unwrap()
+            })
+            .collect()
+    }
+
+    fn parent(trees: &[MerkleTree]) -> MerkleTree {
+        MerkleTree::builder()
+            .build_from_hashes(trees.iter().map(|tree| tree.root().unwrap()).collect())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_every_leaf_is_proven_through_three_layers() {
+        let shards = shards();
+        let regions = [parent(&shards[..2]), parent(&shards[2..])];
+        let top = parent(&regions);
+
+        for (shard, tree) in shards.iter().enumerate() {
+            let (region, position) = if shard < 2 {
+                (0, shard)
+            } else {
+                (1, shard - 2)
+            };
+            for index in 0..tree.leaf_count() {
+                let proof = NestedProof::new(tree.proof(index).unwrap())
+                    .then(regions[region].proof(position).unwrap())
+                    .then(top.proof(region).unwrap());
+                let leaf = MerkleTree::hash(format!("shard {} item {}", shard, index).as_bytes());
+
+                assert_eq!(proof.verify(&leaf, &top.root().unwrap()), Ok(()));
+                assert_eq!(
+                    proof.indices().collect::<Vec<_>>(),
+                    vec![index, position, region]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_tampered_layers_are_rejected() {
+        let shards = shards();
+        let top = parent(&shards);
+        let leaf = MerkleTree::hash(b"shard 3 item 1");
+        let proof = NestedProof::from(vec![shards[3].proof(1).unwrap(), top.proof(3).unwrap()]);
+        assert_eq!(proof.verify(&leaf, &top.root().unwrap()), Ok(()));
+
+        let mut moved = proof.clone();
+        moved.layers[1] = top.proof(2).unwrap();
+        assert_eq!(
+            moved.verify(&leaf, &top.root().unwrap()),
+            Err(Error::RootMismatch)
+        );
+
+        assert!(NestedProof::from(vec![])
+            .verify(&leaf, &top.root().unwrap())
+            .is_err());
+        assert_eq!(proof.depth(), 2);
+    }
+}
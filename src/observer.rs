@@ -0,0 +1,113 @@
+use std::fmt;
+
+use crate::merkle_tree::{Hash, MerkleTree};
+
+type RootCallback = Box<dyn FnMut(&Hash, u64) + Send + Sync>;
+
+/// The callbacks registered with `MerkleTree::on_root_change`.
+///
+/// Callbacks belong to the tree they were registered on, a clone of the
+/// tree starts without any.
+#[derive(Default)]
+pub(crate) struct RootObservers {
+    callbacks: Vec<RootCallback>,
+}
+
+impl RootObservers {
+    pub(crate) fn notify(&mut self, root: &Hash, leaf_count: usize) {
+        for callback in &mut self.callbacks {
+            callback(root, leaf_count as u64);
+        }
+    }
+}
+
+impl Clone for RootObservers {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl fmt::Debug for RootObservers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} root callbacks", self.callbacks.len())
+    }
+}
+
+impl MerkleTree {
+    /// Registers a callback called with the new root and leaf count every
+    /// time a mutation of the tree commits, so applications can publish
+    /// roots as they change instead of polling `root()`.
+    ///
+    /// Callbacks are not copied when the tree is cloned.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// use merkle_tree::MerkleTree;
+    ///
+    /// let mut tree = MerkleTree::build(&["Isildur"]).unwrap();
+    /// let published = Arc::new(Mutex::new(Vec::new()));
+    ///
+    /// let sink = Arc::clone(&published);
+    /// tree.on_root_change(move |root, leaf_count| sink.lock().unwrap().push((*root, leaf_count)));
+    /// tree.insert(&"Elendil").unwrap();
+    ///
+    /// assert_eq!(*published.lock().unwrap(), vec![(tree.root().unwrap(), 2)]);
+    /// ```
+    pub fn on_root_change<F>(&mut self, callback: F)
+    where
+        F: FnMut(&Hash, u64) + Send + Sync + 'static,
+    {
+        self.observers_mut().callbacks.push(Box::new(callback));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::config::DuplicatePolicy;
+
+    #[test]
+    fn test_every_committed_mutation_is_published() {
+        let mut tree = MerkleTree::builder()
+            .duplicates(DuplicatePolicy::Deduplicate)
+            .build(&["Anárion"])
+            .unwrap();
+        let roots = Arc::new(Mutex::new(Vec::new()));
+        let counts = Arc::new(Mutex::new(Vec::new()));
+
+        let sink = Arc::clone(&roots);
+        tree.on_root_change(move |root, _| sink.lock().unwrap().push(*root));
+        let sink = Arc::clone(&counts);
+        tree.on_root_change(move |_, leaf_count| sink.lock().unwrap().push(leaf_count));
+
+        let mut expected = Vec::new();
+        for king in ["Meneldil", "Cemendur", "Eärendil"] {
+            tree.insert(&king).unwrap();
+            expected.push(tree.root().unwrap());
+        }
+        // A deduplicated insertion does not change the root.
+        tree.insert(&"Meneldil").unwrap();
+
+        assert_eq!(*roots.lock().unwrap(), expected);
+        assert_eq!(*counts.lock().unwrap(), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_clones_do_not_share_callbacks() {
+        let mut tree = MerkleTree::build(&["Anárion"]).unwrap();
+        let calls = Arc::new(Mutex::new(0));
+
+        let sink = Arc::clone(&calls);
+        tree.on_root_change(move |_, _| *sink.lock().unwrap() += 1);
+        let mut clone = tree.clone();
+        clone.insert(&"Meneldil").unwrap();
+
+        assert_eq!(*calls.lock().unwrap(), 0);
+        assert_eq!(format!("{:?}", clone.observers_mut()), "0 root callbacks");
+    }
+}
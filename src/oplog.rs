@@ -0,0 +1,314 @@
+use std::io::BufRead;
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+
+/// A mutation of a tree, identified by the hash of its payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Appends a leaf.
+    Append { leaf: Hash },
+    /// Replaces the leaf at `index`.
+    Update { index: usize, leaf: Hash },
+    /// Removes the leaf at `index`, shifting the following ones.
+    Remove { index: usize },
+}
+
+impl Operation {
+    /// Formats the operation as `append <hex leaf>`, `update <index> <hex leaf>` or `remove <index>`.
+    pub fn to_line(&self) -> String {
+        match self {
+            Operation::Append { leaf } => format!("append {}", hex::encode(leaf)),
+            Operation::Update { index, leaf } => format!("update {} {}", index, hex::encode(leaf)),
+            Operation::Remove { index } => format!("remove {}", index),
+        }
+    }
+
+    /// Parses an operation written by `to_line`.
+    pub fn parse_line(line: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidInput(format!("invalid operation {}", line));
+        let leaf = |leaf: &str| -> Result<Hash, Error> {
+            hex::decode(leaf)
+                .ok()
+                .and_then(|leaf| leaf.try_into().ok())
+                .ok_or_else(invalid)
+        };
+        let index = |index: &str| index.parse().map_err(|_| invalid());
+
+        match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["append", hash] => Ok(Operation::Append { leaf: leaf(hash)? }),
+            ["update", position, hash] => Ok(Operation::Update {
+                index: index(position)?,
+                leaf: leaf(hash)?,
+            }),
+            ["remove", position] => Ok(Operation::Remove {
+                index: index(position)?,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// An operation and the root of the tree once it was applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogEntry {
+    pub operation: Operation,
+    pub root: Hash,
+}
+
+/// The history of a tree: every committed operation with the root it led to.
+///
+/// The history can be exported as lines and replayed to check that it
+/// leads to the same roots, so a tree is reproducible from its operations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OperationLog {
+    entries: Vec<LogEntry>,
+}
+
+impl OperationLog {
+    pub fn entries(&self) -> &[LogEntry] {
+        &self.entries
+    }
+
+    pub fn operations(&self) -> Vec<Operation> {
+        self.entries.iter().map(|entry| entry.operation).collect()
+    }
+
+    pub fn roots(&self) -> Vec<Hash> {
+        self.entries.iter().map(|entry| entry.root).collect()
+    }
+
+    /// Formats each entry as a line, `<hex root> <operation>`.
+    pub fn to_lines(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .map(|entry| format!("{} {}", hex::encode(entry.root), entry.operation.to_line()))
+            .collect()
+    }
+
+    /// Reads a log written by `to_lines`, skipping empty lines.
+    pub fn read<R: BufRead>(reader: R) -> Result<Self, Error> {
+        let mut entries = Vec::new();
+
+        for line in reader.lines() {
+            let line = line.map_err(|error| Error::InvalidInput(error.to_string()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let invalid = || Error::InvalidInput(format!("invalid log entry {}", line));
+            let (root, operation) = line.trim().split_once(' ').ok_or_else(invalid)?;
+            entries.push(LogEntry {
+                operation: Operation::parse_line(operation)?,
+                root: hex::decode(root)
+                    .ok()
+                    .and_then(|root| root.try_into().ok())
+                    .ok_or_else(invalid)?,
+            });
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Replays the operations and checks that they lead to the recorded roots.
+    pub fn verify(&self) -> Result<MerkleTree, Error> {
+        let (tree, roots) = MerkleTree::replay(&self.operations())?;
+
+        match roots
+            .iter()
+            .zip(&self.entries)
+            .position(|(root, entry)| *root != entry.root)
+        {
+            Some(_) => Err(Error::RootMismatch),
+            None => Ok(tree),
+        }
+    }
+}
+
+/// A tree that records every committed operation in an `OperationLog`.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{LoggedTree, MerkleTree};
+///
+/// let mut tree = LoggedTree::new();
+/// tree.append(&"Minas Anor").unwrap();
+/// tree.append(&"Minas Ithil").unwrap();
+/// tree.update(1, &"Minas Morgul").unwrap();
+///
+/// let lines = tree.log().to_lines();
+/// assert_eq!(lines.len(), 3);
+///
+/// let (replayed, roots) = MerkleTree::replay(&tree.log().operations()).unwrap();
+/// assert_eq!(roots, tree.log().roots());
+/// assert_eq!(replayed.root(), tree.root());
+/// ```
+#[derive(Debug, Default)]
+pub struct LoggedTree {
+    tree: Option<MerkleTree>,
+    log: OperationLog,
+}
+
+impl LoggedTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies an operation and records it, returning the new root.
+    /// A failed operation leaves the tree and the log unchanged.
+    pub fn apply(&mut self, operation: Operation) -> Result<Hash, Error> {
+        let root = apply_operation(&mut self.tree, &operation)?;
+        self.log.entries.push(LogEntry { operation, root });
+        Ok(root)
+    }
+
+    pub fn append<T: AsRef<[u8]>>(&mut self, item: &T) -> Result<Hash, Error> {
+        self.apply(Operation::Append {
+            leaf: MerkleTree::hash(item.as_ref()),
+        })
+    }
+
+    pub fn update<T: AsRef<[u8]>>(&mut self, index: usize, item: &T) -> Result<Hash, Error> {
+        self.apply(Operation::Update {
+            index,
+            leaf: MerkleTree::hash(item.as_ref()),
+        })
+    }
+
+    pub fn remove(&mut self, index: usize) -> Result<Hash, Error> {
+        self.apply(Operation::Remove { index })
+    }
+
+    /// The root, `None` before the first append.
+    pub fn root(&self) -> Option<Hash> {
+        self.tree.as_ref().and_then(MerkleTree::root)
+    }
+
+    pub fn tree(&self) -> Option<&MerkleTree> {
+        self.tree.as_ref()
+    }
+
+    pub fn log(&self) -> &OperationLog {
+        &self.log
+    }
+}
+
+/// Applies an operation to a tree, creating it on the first append.
+pub(crate) fn apply_operation(
+    tree: &mut Option<MerkleTree>,
+    operation: &Operation,
+) -> Result<Hash, Error> {
+    let Some(current) = tree else {
+        return match operation {
+            Operation::Append { leaf } => {
+                let created = tree.insert(MerkleTree::from_leaves(vec![*leaf]).unwrap());
+                Ok(created.root().expect("A tree has a root."))
+            }
+            _ => Err(Error::EmptyTree),
+        };
+    };
+
+    let out_of_range = |index: usize| Error::IndexOutOfRange {
+        index,
+        leaf_count: current.leaf_count(),
+    };
+    let mut leaves: Vec<Hash> = (0..current.leaf_count())
+        .map(|index| current.node(0, index).expect("The index is in the tree."))
+        .collect();
+
+    match *operation {
+        Operation::Append { leaf } => current.try_insert_leaf(leaf, 0)?,
+        Operation::Update { index, leaf } => {
+            *leaves.get_mut(index).ok_or_else(|| out_of_range(index))? = leaf;
+            current.replace_leaves(leaves)?;
+        }
+        Operation::Remove { index } => {
+            if index >= leaves.len() {
+                return Err(out_of_range(index));
+            }
+            leaves.remove(index);
+            current.replace_leaves(leaves)?;
+        }
+    }
+
+    Ok(current.root().expect("A tree has a root."))
+}
+
+impl MerkleTree {
+    /// Rebuilds a tree from the operations of its history, starting from
+    /// nothing, and returns it with the root after each operation.
+    pub fn replay(operations: &[Operation]) -> Result<(MerkleTree, Vec<Hash>), Error> {
+        let mut tree = None;
+        let roots = operations
+            .iter()
+            .map(|operation| apply_operation(&mut tree, operation))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok((tree.ok_or(Error::EmptyTree)?, roots))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn history() -> LoggedTree {
+        let mut tree = LoggedTree::new();
+        for steward in ["Mardil", "Eradan", "Herion", "Belegorn", "Húrin"] {
+            tree.append(&steward).unwrap();
+        }
+        tree.update(2, &"Herion the Second").unwrap();
+        tree.remove(0).unwrap();
+        tree.append(&"Denethor").unwrap();
+        tree
+    }
+
+    #[test]
+    fn test_replay_reproduces_every_root() {
+        let tree = history();
+        let (replayed, roots) = MerkleTree::replay(&tree.log().operations()).unwrap();
+
+        assert_eq!(roots, tree.log().roots());
+        assert_eq!(replayed.root(), tree.root());
+        assert_eq!(
+            replayed.root(),
+            MerkleTree::build(&[
+                "Eradan",
+                "Herion the Second",
+                "Belegorn",
+                "Húrin",
+                "Denethor"
+            ])
+            .unwrap()
+            .root()
+        );
+    }
+
+    #[test]
+    fn test_exported_log_is_verified() {
+        let tree = history();
+        let lines = tree.log().to_lines().join("\n");
+
+        let log = OperationLog::read(lines.as_bytes()).unwrap();
+        assert_eq!(log, *tree.log());
+        assert_eq!(log.verify().unwrap().root(), tree.root());
+
+        let tampered = lines.replacen("update 2", "update 3", 1);
+        let log = OperationLog::read(tampered.as_bytes()).unwrap();
+        assert_eq!(log.verify().err(), Some(Error::RootMismatch));
+    }
+
+    #[test]
+    fn test_failed_operations_are_not_recorded() {
+        let mut tree = LoggedTree::new();
+
+        assert_eq!(tree.remove(0), Err(Error::EmptyTree));
+        tree.append(&"Mardil").unwrap();
+        assert!(tree.update(1, &"Eradan").is_err());
+        assert!(tree.remove(0).is_err());
+
+        assert_eq!(tree.log().entries().len(), 1);
+        assert!(Operation::parse_line("rename 0").is_err());
+    }
+}
@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+use crate::wire::{WireError, MAX_SIBLINGS};
+
+/// The bytes of a proof in `PackedProofs::to_bytes` before its references.
+const PROOF_HEADER_LENGTH: usize = 17;
+
+/// A proof stored as references into the node table of a `PackedProofs`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedProof {
+    pub leaf_index: usize,
+    pub leaf_count: usize,
+    /// The index in the node table of each sibling, from the leaf up.
+    pub siblings: Vec<usize>,
+}
+
+/// Many individual proofs sharing a table of nodes, each hash stored once.
+///
+/// Proofs of nearby leaves share most of their upper siblings, so bulk
+/// distribution sends far fewer hashes than the proofs one by one. Every
+/// proof unpacks into a regular `Proof` and is verified on its own, unlike
+/// a multiproof.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::MerkleTree;
+///
+/// let tree = MerkleTree::build(&["Bilbo", "Frodo", "Sam", "Merry", "Pippin"]).unwrap();
+/// let packed = tree.packed_proofs(&[1, 2, 3]).unwrap();
+///
+/// assert!(packed.node_count() < 3 * 3);
+///
+/// let proof = packed.get(1).unwrap();
+/// assert!(proof.verify(&MerkleTree::hash(b"Sam"), &tree.root().unwrap()).is_ok());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackedProofs {
+    pub nodes: Vec<Hash>,
+    pub proofs: Vec<PackedProof>,
+}
+
+impl PackedProofs {
+    /// Packs proofs, storing each distinct sibling hash once.
+    pub fn pack(proofs: &[Proof]) -> Self {
+        let mut packed = Self::default();
+        let mut positions = HashMap::new();
+
+        for proof in proofs {
+            let siblings = proof
+                .siblings
+                .iter()
+                .map(|sibling| {
+                    *positions.entry(*sibling).or_insert_with(|| {
+                        packed.nodes.push(*sibling);
+                        packed.nodes.len() - 1
+                    })
+                })
+                .collect();
+
+            packed.proofs.push(PackedProof {
+                leaf_index: proof.leaf_index,
+                leaf_count: proof.leaf_count,
+                siblings,
+            });
+        }
+
+        packed
+    }
+
+    /// The number of packed proofs.
+    pub fn len(&self) -> usize {
+        self.proofs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.proofs.is_empty()
+    }
+
+    /// The number of distinct hashes stored for all the proofs.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Unpacks the proof at `position`, in the order they were packed.
+    /// Fails if a sibling refers to a node out of the table.
+    pub fn get(&self, position: usize) -> Result<Proof, Error> {
+        let packed = self.proofs.get(position).ok_or(Error::IndexOutOfRange {
+            index: position,
+            leaf_count: self.proofs.len(),
+        })?;
+
+        let siblings = packed
+            .siblings
+            .iter()
+            .map(|node| {
+                self.nodes.get(*node).copied().ok_or_else(|| {
+                    Error::InvalidInput(format!("node {} is not in the packed table", node))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Proof {
+            leaf_index: packed.leaf_index,
+            leaf_count: packed.leaf_count,
+            siblings,
+        })
+    }
+
+    /// Unpacks every proof, in the order they were packed.
+    pub fn unpack(&self) -> Result<Vec<Proof>, Error> {
+        (0..self.proofs.len())
+            .map(|position| self.get(position))
+            .collect()
+    }
+
+    /// The version of the binary format written by `to_bytes`.
+    pub const WIRE_VERSION: u8 = 1;
+
+    /// Encodes the table and the proofs in a compact binary format, each
+    /// node once and each sibling as a reference into the table. Integers
+    /// are big-endian:
+    ///
+    /// | bytes    | content                                     |
+    /// |----------|---------------------------------------------|
+    /// | 1        | format version, 1                           |
+    /// | 4        | number of nodes `n`                         |
+    /// | `32 * n` | the node table                              |
+    /// | 4        | number of proofs                            |
+    ///
+    /// followed by each proof:
+    ///
+    /// | bytes   | content                                     |
+    /// |---------|---------------------------------------------|
+    /// | 8       | leaf count                                  |
+    /// | 8       | leaf index                                  |
+    /// | 1       | number of siblings `k`                      |
+    /// | `4 * k` | the index in the table of each sibling      |
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::{MerkleTree, PackedProofs};
+    ///
+    /// let tree = MerkleTree::build(&["Bilbo", "Frodo", "Sam", "Merry", "Pippin"]).unwrap();
+    /// let bytes = tree.packed_proofs(&[1, 2, 3]).unwrap().to_bytes();
+    ///
+    /// let proof = PackedProofs::parse(&bytes).unwrap().get(2).unwrap();
+    /// assert!(proof.verify(&MerkleTree::hash(b"Merry"), &tree.root().unwrap()).is_ok());
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let references: usize = self.proofs.iter().map(|proof| proof.siblings.len()).sum();
+        let mut bytes = Vec::with_capacity(
+            9 + self.nodes.len() * 32 + self.proofs.len() * PROOF_HEADER_LENGTH + references * 4,
+        );
+        bytes.push(Self::WIRE_VERSION);
+        bytes.extend_from_slice(&(self.nodes.len() as u32).to_be_bytes());
+        for node in &self.nodes {
+            bytes.extend_from_slice(node);
+        }
+        bytes.extend_from_slice(&(self.proofs.len() as u32).to_be_bytes());
+        for proof in &self.proofs {
+            bytes.extend_from_slice(&(proof.leaf_count as u64).to_be_bytes());
+            bytes.extend_from_slice(&(proof.leaf_index as u64).to_be_bytes());
+            bytes.push(proof.siblings.len() as u8);
+            for sibling in &proof.siblings {
+                bytes.extend_from_slice(&(*sibling as u32).to_be_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    /// Decodes proofs written by `to_bytes`.
+    ///
+    /// Counts are checked against the remaining bytes before allocating, and
+    /// every proof must have the siblings of its tree size and refer only to
+    /// nodes of the table. Encoding errors are `Error::MalformedProof`.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader { bytes, at: 0 };
+
+        let version = reader.take(1)?[0];
+        if version != Self::WIRE_VERSION {
+            return Err(Error::MalformedProof(WireError::UnknownVersion(version)));
+        }
+
+        let node_count = reader.count(32)?;
+        let nodes = reader
+            .take(node_count * 32)?
+            .chunks_exact(32)
+            .map(|node| Hash::try_from(node).unwrap())
+            .collect::<Vec<_>>();
+
+        let proof_count = reader.count(PROOF_HEADER_LENGTH)?;
+        let mut proofs = Vec::with_capacity(proof_count);
+        for _ in 0..proof_count {
+            let leaf_count = reader.number()?;
+            let leaf_index = reader.number()?;
+            if leaf_index >= leaf_count {
+                return Err(Error::IndexOutOfRange {
+                    index: leaf_index,
+                    leaf_count,
+                });
+            }
+
+            let sibling_count = reader.take(1)?[0] as usize;
+            let expected = Proof::expected_length(leaf_count);
+            if sibling_count > MAX_SIBLINGS || sibling_count != expected {
+                return Err(Error::MalformedProof(WireError::SiblingCount {
+                    expected,
+                    actual: sibling_count,
+                }));
+            }
+            let siblings = reader
+                .take(sibling_count * 4)?
+                .chunks_exact(4)
+                .map(|reference| u32::from_be_bytes(reference.try_into().unwrap()) as usize)
+                .collect::<Vec<_>>();
+            if let Some(node) = siblings.iter().find(|node| **node >= nodes.len()) {
+                return Err(Error::InvalidInput(format!(
+                    "node {} is not in the packed table",
+                    node
+                )));
+            }
+
+            proofs.push(PackedProof {
+                leaf_index,
+                leaf_count,
+                siblings,
+            });
+        }
+
+        if reader.at < bytes.len() {
+            return Err(Error::MalformedProof(WireError::TrailingBytes {
+                expected: reader.at,
+                actual: bytes.len(),
+            }));
+        }
+
+        Ok(Self { nodes, proofs })
+    }
+}
+
+/// Reads `PackedProofs::to_bytes` from the front, failing past the end.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        let end = self.at.saturating_add(length);
+        let taken =
+            self.bytes
+                .get(self.at..end)
+                .ok_or(Error::MalformedProof(WireError::Truncated {
+                    expected: end,
+                    actual: self.bytes.len(),
+                }))?;
+        self.at = end;
+        Ok(taken)
+    }
+
+    /// Reads a count of items of at least `item_length` bytes each, which
+    /// must fit in the remaining bytes.
+    fn count(&mut self, item_length: usize) -> Result<usize, Error> {
+        let count = u32::from_be_bytes(self.take(4)?.try_into().unwrap()) as usize;
+        let expected = self.at.saturating_add(count.saturating_mul(item_length));
+        if expected > self.bytes.len() {
+            return Err(Error::MalformedProof(WireError::Truncated {
+                expected,
+                actual: self.bytes.len(),
+            }));
+        }
+        Ok(count)
+    }
+
+    fn number(&mut self) -> Result<usize, Error> {
+        let number = u64::from_be_bytes(self.take(8)?.try_into().unwrap());
+        usize::try_from(number).map_err(|_| Error::MalformedProof(WireError::TreeTooLarge))
+    }
+}
+
+impl MerkleTree {
+    /// Returns the proofs of inclusion of the leaves at `indices`, packed
+    /// in that order.
+    pub fn packed_proofs(&self, indices: &[usize]) -> Result<PackedProofs, Error> {
+        let proofs = indices
+            .iter()
+            .map(|index| self.proof(*index))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(PackedProofs::pack(&proofs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn claims(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|claim| format!("{} pipe-weed barrels", claim))
+            .collect()
+    }
+
+    #[test]
+    fn test_packed_proofs_unpack_to_the_same_proofs() {
+        let tree = MerkleTree::build(&claims(1000)).unwrap();
+        let indices: Vec<usize> = (400..464).collect();
+
+        let packed = tree.packed_proofs(&indices).unwrap();
+        let proofs = packed.unpack().unwrap();
+
+        assert_eq!(packed.len(), indices.len());
+        for (index, proof) in indices.iter().zip(&proofs) {
+            assert_eq!(*proof, tree.proof(*index).unwrap());
+        }
+
+        let separate: usize = proofs.iter().map(|proof| proof.siblings.len()).sum();
+        assert!(packed.node_count() * 4 < separate);
+    }
+
+    #[test]
+    fn test_packed_proofs_round_trip() {
+        let tree = MerkleTree::build(&claims(100)).unwrap();
+        let packed = tree.packed_proofs(&[0, 17, 18, 99]).unwrap();
+        let bytes = packed.to_bytes();
+
+        assert_eq!(PackedProofs::parse(&bytes), Ok(packed.clone()));
+        assert_eq!(
+            PackedProofs::parse(&PackedProofs::default().to_bytes()),
+            Ok(PackedProofs::default())
+        );
+        assert!(bytes.len() < packed.len() * Proof::wire_length(7));
+    }
+
+    #[test]
+    fn test_malformed_packed_proofs_are_rejected() {
+        let tree = MerkleTree::build(&claims(10)).unwrap();
+        let packed = tree.packed_proofs(&[3, 4]).unwrap();
+        let bytes = packed.to_bytes();
+
+        let mut versioned = bytes.clone();
+        versioned[0] = 2;
+        assert_eq!(
+            PackedProofs::parse(&versioned),
+            Err(Error::MalformedProof(WireError::UnknownVersion(2)))
+        );
+        assert!(matches!(
+            PackedProofs::parse(&bytes[..bytes.len() - 1]),
+            Err(Error::MalformedProof(WireError::Truncated { .. }))
+        ));
+        assert!(matches!(
+            PackedProofs::parse(&[bytes.as_slice(), &[0]].concat()),
+            Err(Error::MalformedProof(WireError::TrailingBytes { .. }))
+        ));
+
+        // A forged node count is rejected before allocating the table.
+        let mut boasting = bytes.clone();
+        boasting[1..5].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(
+            PackedProofs::parse(&boasting),
+            Err(Error::MalformedProof(WireError::Truncated { .. }))
+        ));
+
+        let mut dangling = packed.clone();
+        dangling.proofs[1].siblings[0] = dangling.nodes.len();
+        assert!(matches!(
+            PackedProofs::parse(&dangling.to_bytes()),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_dangling_references_are_rejected() {
+        let tree = MerkleTree::build(&claims(10)).unwrap();
+        let mut packed = tree.packed_proofs(&[3, 4]).unwrap();
+
+        packed.proofs[1].siblings[0] = packed.nodes.len();
+
+        assert!(packed.get(0).is_ok());
+        assert!(matches!(packed.get(1), Err(Error::InvalidInput(_))));
+        assert!(packed.unpack().is_err());
+        assert!(packed.get(2).is_err());
+        assert!(tree.packed_proofs(&[3, 10]).is_err());
+    }
+}
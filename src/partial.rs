@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+use std::future::Future;
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+
+/// A source of node hashes, typically a remote server holding the full tree.
+pub trait NodeProvider {
+    /// Returns the hashes of the nodes at the given `(level, index)` positions, in order.
+    fn fetch_nodes(&mut self, positions: &[(usize, usize)]) -> Result<Vec<Hash>, Error>;
+}
+
+/// The asynchronous counterpart of `NodeProvider`, for providers that await network I/O.
+pub trait AsyncNodeProvider {
+    /// Returns the hashes of the nodes at the given `(level, index)` positions, in order.
+    fn fetch_nodes(
+        &mut self,
+        positions: &[(usize, usize)],
+    ) -> impl Future<Output = Result<Vec<Hash>, Error>>;
+}
+
+/// A full tree in the same process can serve the nodes directly.
+impl NodeProvider for &MerkleTree {
+    fn fetch_nodes(&mut self, positions: &[(usize, usize)]) -> Result<Vec<Hash>, Error> {
+        positions
+            .iter()
+            .map(|&(level, index)| {
+                self.node(level, index)
+                    .ok_or_else(|| Error::InvalidInput(format!("no node at {}/{}", level, index)))
+            })
+            .collect()
+    }
+}
+
+/// A light client's view of a tree it only knows the root and size of.
+///
+/// Proof queries pull the nodes they need from a provider and check them
+/// against the trusted root before using them. Verified nodes are kept, so
+/// later queries only fetch what they haven't seen.
+///
+/// Positions are not authenticated. Pairs are hashed sorted, so a provider
+/// swapping two siblings still leads to the root, and the client keeps
+/// each at the position it was served for. A returned leaf is known to be
+/// in the tree, not to be at `index`. Applications keying leaves by
+/// position commit the index in the leaf, as `Checkpoint::leaf_hash` does.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{LeafHash, MerkleTree, PartialTree};
+///
+/// let items = vec!["Gondor", "Rohan", "Arnor", "Eriador", "Mordor"];
+/// let server = MerkleTree::build(&items).unwrap();
+///
+/// let mut client = PartialTree::new(server.root().unwrap(), server.leaf_count());
+///
+/// let (leaf, proof) = client.proof_of_inclusion(&mut &server, 3).unwrap();
+///
+/// assert_eq!(leaf, MerkleTree::hash(b"Eriador"));
+/// assert!(server.validate_proof(&LeafHash::new(leaf), &proof));
+/// ```
+pub struct PartialTree {
+    root: Hash,
+    leaf_count: usize,
+    verified: HashMap<(usize, usize), Hash>,
+}
+
+impl PartialTree {
+    pub fn new(root: Hash, leaf_count: usize) -> Self {
+        Self {
+            root,
+            leaf_count,
+            verified: HashMap::new(),
+        }
+    }
+
+    pub fn root(&self) -> Hash {
+        self.root
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    /// Returns the number of verified nodes held locally.
+    pub fn verified_nodes(&self) -> usize {
+        self.verified.len()
+    }
+
+    /// Returns the hash of the leaf at `index` and its proof of inclusion,
+    /// fetching the missing nodes from the provider.
+    pub fn proof_of_inclusion<P: NodeProvider>(
+        &mut self,
+        provider: &mut P,
+        index: usize,
+    ) -> Result<(Hash, Vec<Hash>), Error> {
+        let path = self.path(index)?;
+        let missing = self.missing(&path);
+        let fetched = if missing.is_empty() {
+            Vec::new()
+        } else {
+            provider.fetch_nodes(&missing)?
+        };
+
+        self.verify(index, &path, &missing, fetched)
+    }
+
+    /// The asynchronous counterpart of `proof_of_inclusion`.
+    pub async fn proof_of_inclusion_async<P: AsyncNodeProvider>(
+        &mut self,
+        provider: &mut P,
+        index: usize,
+    ) -> Result<(Hash, Vec<Hash>), Error> {
+        let path = self.path(index)?;
+        let missing = self.missing(&path);
+        let fetched = if missing.is_empty() {
+            Vec::new()
+        } else {
+            provider.fetch_nodes(&missing).await?
+        };
+
+        self.verify(index, &path, &missing, fetched)
+    }
+
+    /// The positions of the leaf and its siblings, `None` where the level
+    /// is odd and the node is paired with itself.
+    fn path(&self, index: usize) -> Result<Vec<Option<(usize, usize)>>, Error> {
+        if index >= self.leaf_count {
+            return Err(Error::IndexOutOfRange {
+                index,
+                leaf_count: self.leaf_count,
+            });
+        }
+
+        let mut path = vec![Some((0, index))];
+        let mut level = 0;
+        let mut position = index;
+
+        while MerkleTree::level_width(self.leaf_count, level) > 1 {
+            let sibling = position ^ 1;
+            if sibling < MerkleTree::level_width(self.leaf_count, level) {
+                path.push(Some((level, sibling)));
+            } else {
+                path.push(None);
+            }
+            level += 1;
+            position /= 2;
+        }
+
+        Ok(path)
+    }
+
+    fn missing(&self, path: &[Option<(usize, usize)>]) -> Vec<(usize, usize)> {
+        path.iter()
+            .flatten()
+            .filter(|position| !self.verified.contains_key(position))
+            .copied()
+            .collect()
+    }
+
+    /// Folds the path up to the root and keeps its nodes only if it matches the trusted root.
+    fn verify(
+        &mut self,
+        index: usize,
+        path: &[Option<(usize, usize)>],
+        missing: &[(usize, usize)],
+        fetched: Vec<Hash>,
+    ) -> Result<(Hash, Vec<Hash>), Error> {
+        if fetched.len() != missing.len() {
+            return Err(Error::InvalidInput(format!(
+                "requested {} nodes, received {}",
+                missing.len(),
+                fetched.len()
+            )));
+        }
+
+        // The fetched nodes are staged apart and kept only once the path is verified.
+        let staged: HashMap<(usize, usize), Hash> = missing.iter().copied().zip(fetched).collect();
+        let known = |position: &(usize, usize)| {
+            staged
+                .get(position)
+                .unwrap_or_else(|| &self.verified[position])
+        };
+
+        let leaf = *known(&(0, index));
+        let mut current = leaf;
+        let mut position = index;
+        let mut proof = Vec::with_capacity(path.len() - 1);
+        let mut ancestors = Vec::with_capacity(path.len() - 1);
+
+        for (level, sibling) in path[1..].iter().enumerate() {
+            let sibling = match sibling {
+                Some(sibling) => *known(sibling),
+                None => current,
+            };
+            proof.push(sibling);
+            current = MerkleTree::merkle_parent(&[current, sibling]);
+            position /= 2;
+            ancestors.push(((level + 1, position), current));
+        }
+
+        if current != self.root {
+            return Err(Error::RootMismatch);
+        }
+
+        self.verified.extend(staged);
+        self.verified.extend(ancestors);
+
+        Ok((leaf, proof))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::hashes::LeafHash;
+
+    /// Serves the nodes of a tree and counts how many were requested.
+    struct Server {
+        tree: MerkleTree,
+        served: usize,
+        corrupt: bool,
+    }
+
+    impl NodeProvider for Server {
+        fn fetch_nodes(&mut self, positions: &[(usize, usize)]) -> Result<Vec<Hash>, Error> {
+            self.served += positions.len();
+            let mut nodes = (&self.tree).fetch_nodes(positions)?;
+            if self.corrupt {
+                nodes[0] = MerkleTree::hash(b"LONG LIVE SAURON");
+            }
+            Ok(nodes)
+        }
+    }
+
+    impl AsyncNodeProvider for Server {
+        async fn fetch_nodes(&mut self, positions: &[(usize, usize)]) -> Result<Vec<Hash>, Error> {
+            NodeProvider::fetch_nodes(self, positions)
+        }
+    }
+
+    fn server(corrupt: bool) -> Server {
+        let items: Vec<String> = (0..11).map(|line| format!("Verse {}", line)).collect();
+
+        Server {
+            tree: MerkleTree::build(&items).unwrap(),
+            served: 0,
+            corrupt,
+        }
+    }
+
+    #[test]
+    fn test_proofs_match_the_full_tree() {
+        let mut server = server(false);
+        let mut client = PartialTree::new(server.tree.root().unwrap(), server.tree.leaf_count());
+
+        for index in 0..11 {
+            let (leaf, proof) = client.proof_of_inclusion(&mut server, index).unwrap();
+            assert_eq!(leaf, server.tree.node(0, index).unwrap());
+            assert_eq!(
+                proof,
+                server
+                    .tree
+                    .proof_of_inclusion(&LeafHash::new(leaf))
+                    .unwrap()
+            );
+        }
+    }
+
+    /// Serves each leaf in place of its sibling.
+    struct Swapping(MerkleTree);
+
+    impl NodeProvider for Swapping {
+        fn fetch_nodes(&mut self, positions: &[(usize, usize)]) -> Result<Vec<Hash>, Error> {
+            let swapped: Vec<_> = positions
+                .iter()
+                .map(|&(level, index)| (level, if level == 0 { index ^ 1 } else { index }))
+                .collect();
+            (&self.0).fetch_nodes(&swapped)
+        }
+    }
+
+    #[test]
+    fn test_swapped_siblings_are_not_detected() {
+        let mut server = Swapping(server(false).tree);
+        let mut client = PartialTree::new(server.0.root().unwrap(), server.0.leaf_count());
+
+        let (leaf, _) = client.proof_of_inclusion(&mut server, 4).unwrap();
+
+        assert_eq!(leaf, MerkleTree::hash(b"Verse 5"));
+    }
+
+    #[test]
+    fn test_verified_nodes_are_not_fetched_again() {
+        let mut server = server(false);
+        let mut client = PartialTree::new(server.tree.root().unwrap(), server.tree.leaf_count());
+
+        client.proof_of_inclusion(&mut server, 4).unwrap();
+        let served = server.served;
+        client.proof_of_inclusion(&mut server, 5).unwrap();
+
+        assert_eq!(server.served, served);
+    }
+
+    #[test]
+    fn test_corrupt_nodes_are_rejected() {
+        let mut server = server(true);
+        let mut client = PartialTree::new(server.tree.root().unwrap(), server.tree.leaf_count());
+
+        let result = client.proof_of_inclusion(&mut server, 2);
+
+        assert_eq!(result, Err(Error::RootMismatch));
+        assert_eq!(client.verified_nodes(), 0);
+    }
+
+    #[test]
+    fn test_out_of_range_index_is_rejected() {
+        let mut server = server(false);
+        let mut client = PartialTree::new(server.tree.root().unwrap(), server.tree.leaf_count());
+
+        let result = client.proof_of_inclusion(&mut server, 11);
+
+        assert!(matches!(result, Err(Error::IndexOutOfRange { .. })));
+    }
+
+    #[test]
+    fn test_async_provider() {
+        let mut server = server(false);
+        let mut client = PartialTree::new(server.tree.root().unwrap(), server.tree.leaf_count());
+
+        let (leaf, proof) =
+            futures::executor::block_on(client.proof_of_inclusion_async(&mut server, 7)).unwrap();
+
+        assert!(server.tree.validate_proof(&LeafHash::new(leaf), &proof));
+    }
+}
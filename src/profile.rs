@@ -0,0 +1,676 @@
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::{Proof, ProofError};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Below this many leaves, `par_root` splits no further as the work
+/// would cost more to share than to do.
+#[cfg(feature = "rayon")]
+const PARALLEL_LEAVES: usize = 1024;
+
+/// A named tree construction of another system: how leaves and nodes are
+/// hashed, how children are ordered and how levels are padded, chosen in
+/// one place rather than assembled from options.
+///
+/// The variants depend on the enabled features, so matches need a wildcard
+/// arm.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::Profile;
+///
+/// let items = ["Narya", "Nenya", "Vilya"];
+/// let leaves: Vec<_> = items.iter().map(|item| Profile::Rfc6962.hash_leaf(item.as_bytes())).collect();
+///
+/// let root = Profile::Rfc6962.root(&leaves).unwrap();
+/// let proof = Profile::Rfc6962.proof(&leaves, 2).unwrap();
+///
+/// assert!(Profile::Rfc6962.verify(&proof, &leaves[2], &root).is_ok());
+/// assert!(Profile::LegacySorted.verify(&proof, &leaves[2], &root).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Profile {
+    /// The construction of `MerkleTree`: SHA-256 leaves, parents over the
+    /// sorted pair of children, and odd levels duplicating their last node.
+    LegacySorted,
+    /// Certificate Transparency (RFC 6962): leaves are `SHA-256(0x00 ‖ data)`,
+    /// parents `SHA-256(0x01 ‖ left ‖ right)`, and the tree splits at the
+    /// largest power of two below its size rather than padding.
+    Rfc6962,
+    /// Tendermint and CometBFT, the construction of RFC 6962.
+    Tendermint,
+    /// Bitcoin blocks: leaves and parents are double SHA-256 of the data and
+    /// of the children in order, odd levels duplicate their last node.
+    Bitcoin,
+    /// OpenZeppelin's `StandardMerkleTree`: leaves are the double Keccak-256
+    /// of their ABI encoding, sorted, laid out as a complete binary tree, and
+    /// parents are the Keccak-256 of the sorted pair of children.
+    #[cfg(any(feature = "ethereum", feature = "solana"))]
+    OpenZeppelin,
+}
+
+/// How a profile arranges the leaves into a tree.
+enum Shape {
+    DuplicateLast,
+    LargestPowerOfTwo,
+    #[cfg(any(feature = "ethereum", feature = "solana"))]
+    SortedHeap,
+}
+
+fn sha256d(bytes: &[u8]) -> Hash {
+    MerkleTree::hash(&MerkleTree::hash(bytes))
+}
+
+impl Profile {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::LegacySorted => "legacy-sorted",
+            Self::Rfc6962 => "rfc6962",
+            Self::Tendermint => "tendermint",
+            Self::Bitcoin => "bitcoin",
+            #[cfg(any(feature = "ethereum", feature = "solana"))]
+            Self::OpenZeppelin => "openzeppelin",
+        }
+    }
+
+    fn shape(&self) -> Shape {
+        match self {
+            Self::LegacySorted | Self::Bitcoin => Shape::DuplicateLast,
+            Self::Rfc6962 | Self::Tendermint => Shape::LargestPowerOfTwo,
+            #[cfg(any(feature = "ethereum", feature = "solana"))]
+            Self::OpenZeppelin => Shape::SortedHeap,
+        }
+    }
+
+    /// Hashes the data of a leaf.
+    pub fn hash_leaf(&self, data: &[u8]) -> Hash {
+        match self {
+            Self::LegacySorted => MerkleTree::hash(data),
+            Self::Rfc6962 | Self::Tendermint => MerkleTree::hash(&[&[0], data].concat()),
+            Self::Bitcoin => sha256d(data),
+            #[cfg(any(feature = "ethereum", feature = "solana"))]
+            Self::OpenZeppelin => crate::keccak::keccak256(&crate::keccak::keccak256(data)),
+        }
+    }
+
+    /// Hashes the parent of two children.
+    pub fn hash_node(&self, left: &Hash, right: &Hash) -> Hash {
+        match self {
+            Self::LegacySorted => MerkleTree::merkle_parent(&[*left, *right]),
+            Self::Rfc6962 | Self::Tendermint => {
+                MerkleTree::hash(&[[1].as_slice(), left, right].concat())
+            }
+            Self::Bitcoin => sha256d(&[*left, *right].concat()),
+            #[cfg(any(feature = "ethereum", feature = "solana"))]
+            Self::OpenZeppelin => {
+                let (first, second) = if left <= right {
+                    (left, right)
+                } else {
+                    (right, left)
+                };
+                crate::keccak::keccak256(&[*first, *second].concat())
+            }
+        }
+    }
+
+    /// Computes the root over leaves hashed with `hash_leaf`.
+    pub fn root(&self, leaves: &[Hash]) -> Result<Hash, Error> {
+        if leaves.is_empty() {
+            return Err(Error::EmptyTree);
+        }
+
+        Ok(match self.shape() {
+            Shape::DuplicateLast => self.levels(leaves).last().unwrap()[0],
+            Shape::LargestPowerOfTwo => self.split_root(leaves),
+            #[cfg(any(feature = "ethereum", feature = "solana"))]
+            Shape::SortedHeap => self.heap(leaves)[0],
+        })
+    }
+
+    /// Computes the root `root` computes, with the nodes of each level, or
+    /// of each half of the tree, hashed in parallel.
+    ///
+    /// Parallelism only changes which thread hashes a node, never how nodes
+    /// are paired or ordered, so the roots are identical for every profile
+    /// and a service can switch between both without re-issuing roots.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::Profile;
+    ///
+    /// let leaves: Vec<_> = (0..5000u32).map(|leaf| Profile::Rfc6962.hash_leaf(&leaf.to_be_bytes())).collect();
+    ///
+    /// assert_eq!(Profile::Rfc6962.par_root(&leaves), Profile::Rfc6962.root(&leaves));
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_root(&self, leaves: &[Hash]) -> Result<Hash, Error> {
+        if leaves.is_empty() {
+            return Err(Error::EmptyTree);
+        }
+
+        Ok(match self.shape() {
+            Shape::DuplicateLast => {
+                let mut level = leaves.to_vec();
+                while level.len() > 1 {
+                    level = level
+                        .par_chunks(2)
+                        .map(|pair| self.hash_node(&pair[0], pair.last().unwrap()))
+                        .collect();
+                }
+                level[0]
+            }
+            Shape::LargestPowerOfTwo => self.par_split_root(leaves),
+            #[cfg(any(feature = "ethereum", feature = "solana"))]
+            Shape::SortedHeap => self.par_heap_root(leaves),
+        })
+    }
+
+    /// Hashes the items into leaves and computes their root.
+    pub fn root_of<T: AsRef<[u8]>>(&self, items: &[T]) -> Result<Hash, Error> {
+        let leaves: Vec<Hash> = items
+            .iter()
+            .map(|item| self.hash_leaf(item.as_ref()))
+            .collect();
+        self.root(&leaves)
+    }
+
+    /// Returns the proof of the leaf at `index`, its siblings from the
+    /// bottom up. The leaves of `OpenZeppelin` are sorted first, and the
+    /// index of the proof is the one of the leaf once sorted.
+    pub fn proof(&self, leaves: &[Hash], index: usize) -> Result<Proof, Error> {
+        if index >= leaves.len() {
+            return Err(Error::IndexOutOfRange {
+                index,
+                leaf_count: leaves.len(),
+            });
+        }
+
+        let (leaf_index, siblings) = match self.shape() {
+            Shape::DuplicateLast => {
+                let levels = self.levels(leaves);
+                let siblings = levels[..levels.len() - 1]
+                    .iter()
+                    .enumerate()
+                    .map(|(level, nodes)| {
+                        let position = index >> level;
+                        *nodes.get(position ^ 1).unwrap_or(&nodes[position])
+                    })
+                    .collect();
+                (index, siblings)
+            }
+            Shape::LargestPowerOfTwo => (index, self.split_path(leaves, index)),
+            #[cfg(any(feature = "ethereum", feature = "solana"))]
+            Shape::SortedHeap => {
+                let mut sorted = leaves.to_vec();
+                sorted.sort();
+                let sorted_index = sorted.binary_search(&leaves[index]).unwrap();
+
+                let heap = self.heap(leaves);
+                let mut siblings = Vec::new();
+                let mut position = heap.len() - 1 - sorted_index;
+                while position > 0 {
+                    let sibling = if position % 2 == 1 {
+                        position + 1
+                    } else {
+                        position - 1
+                    };
+                    siblings.push(heap[sibling]);
+                    position = (position - 1) / 2;
+                }
+                (sorted_index, siblings)
+            }
+        };
+
+        Ok(Proof {
+            leaf_index,
+            leaf_count: leaves.len(),
+            siblings,
+        })
+    }
+
+    /// Checks that a proof of `proof` leads from `leaf` to `root`.
+    pub fn verify(&self, proof: &Proof, leaf: &Hash, root: &Hash) -> Result<(), Error> {
+        if proof.leaf_index >= proof.leaf_count {
+            return Err(Error::IndexOutOfRange {
+                index: proof.leaf_index,
+                leaf_count: proof.leaf_count,
+            });
+        }
+
+        let computed = match self.shape() {
+            Shape::DuplicateLast => self.fold_duplicate_last(proof, leaf)?,
+            Shape::LargestPowerOfTwo => self
+                .fold_split(proof.leaf_index, proof.leaf_count, leaf, &proof.siblings)
+                .ok_or(Error::InvalidProof(ProofError::LengthMismatch {
+                    expected: self.split_length(proof.leaf_index, proof.leaf_count),
+                    actual: proof.siblings.len(),
+                }))?,
+            #[cfg(any(feature = "ethereum", feature = "solana"))]
+            Shape::SortedHeap => proof
+                .siblings
+                .iter()
+                .fold(*leaf, |node, sibling| self.hash_node(&node, sibling)),
+        };
+
+        if computed != *root {
+            return Err(Error::RootMismatch);
+        }
+        Ok(())
+    }
+
+    fn levels(&self, leaves: &[Hash]) -> Vec<Vec<Hash>> {
+        let mut levels = vec![leaves.to_vec()];
+        while levels.last().unwrap().len() > 1 {
+            let parents = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| self.hash_node(&pair[0], pair.last().unwrap()))
+                .collect();
+            levels.push(parents);
+        }
+        levels
+    }
+
+    fn fold_duplicate_last(&self, proof: &Proof, leaf: &Hash) -> Result<Hash, Error> {
+        let expected = Proof::expected_length(proof.leaf_count);
+        if proof.siblings.len() != expected {
+            return Err(Error::InvalidProof(ProofError::LengthMismatch {
+                expected,
+                actual: proof.siblings.len(),
+            }));
+        }
+
+        let mut node = *leaf;
+        for (level, sibling) in proof.siblings.iter().enumerate() {
+            let position = proof.leaf_index >> level;
+            if position ^ 1 >= MerkleTree::level_width(proof.leaf_count, level) && *sibling != node
+            {
+                return Err(Error::InvalidProof(ProofError::InvalidPadding { level }));
+            }
+            node = if position.is_multiple_of(2) {
+                self.hash_node(&node, sibling)
+            } else {
+                self.hash_node(sibling, &node)
+            };
+        }
+        Ok(node)
+    }
+
+    /// The largest power of two below `count`, where the tree of RFC 6962 splits.
+    fn split_point(count: usize) -> usize {
+        1 << (usize::BITS - 1 - (count - 1).leading_zeros())
+    }
+
+    fn split_root(&self, leaves: &[Hash]) -> Hash {
+        if leaves.len() == 1 {
+            return leaves[0];
+        }
+        let (left, right) = leaves.split_at(Self::split_point(leaves.len()));
+        self.hash_node(&self.split_root(left), &self.split_root(right))
+    }
+
+    #[cfg(feature = "rayon")]
+    fn par_split_root(&self, leaves: &[Hash]) -> Hash {
+        if leaves.len() <= PARALLEL_LEAVES {
+            return self.split_root(leaves);
+        }
+        let (left, right) = leaves.split_at(Self::split_point(leaves.len()));
+        let (left, right) =
+            rayon::join(|| self.par_split_root(left), || self.par_split_root(right));
+        self.hash_node(&left, &right)
+    }
+
+    fn split_path(&self, leaves: &[Hash], index: usize) -> Vec<Hash> {
+        if leaves.len() == 1 {
+            return Vec::new();
+        }
+        let split = Self::split_point(leaves.len());
+        let (left, right) = leaves.split_at(split);
+
+        let (mut path, sibling) = if index < split {
+            (self.split_path(left, index), self.split_root(right))
+        } else {
+            (self.split_path(right, index - split), self.split_root(left))
+        };
+        path.push(sibling);
+        path
+    }
+
+    fn split_length(&self, index: usize, count: usize) -> usize {
+        match count {
+            1 => 0,
+            _ => {
+                let split = Self::split_point(count);
+                1 + match index < split {
+                    true => self.split_length(index, split),
+                    false => self.split_length(index - split, count - split),
+                }
+            }
+        }
+    }
+
+    /// Folds the siblings from the top down, the last one being the sibling of the root's child.
+    fn fold_split(
+        &self,
+        index: usize,
+        count: usize,
+        leaf: &Hash,
+        siblings: &[Hash],
+    ) -> Option<Hash> {
+        if count == 1 {
+            return siblings.is_empty().then_some(*leaf);
+        }
+        let split = Self::split_point(count);
+        let (sibling, below) = siblings.split_last()?;
+
+        Some(if index < split {
+            self.hash_node(&self.fold_split(index, split, leaf, below)?, sibling)
+        } else {
+            self.hash_node(
+                sibling,
+                &self.fold_split(index - split, count - split, leaf, below)?,
+            )
+        })
+    }
+
+    /// Lays the sorted leaves out as a complete binary tree in an array,
+    /// the root first and the leaves last in reverse order.
+    #[cfg(any(feature = "ethereum", feature = "solana"))]
+    fn heap(&self, leaves: &[Hash]) -> Vec<Hash> {
+        let mut sorted = leaves.to_vec();
+        sorted.sort();
+
+        let mut heap = vec![[0; 32]; 2 * leaves.len() - 1];
+        let last = heap.len() - 1;
+        for (index, leaf) in sorted.iter().enumerate() {
+            heap[last - index] = *leaf;
+        }
+        for index in (0..heap.len() - leaves.len()).rev() {
+            heap[index] = self.hash_node(&heap[2 * index + 1], &heap[2 * index + 2]);
+        }
+        heap
+    }
+
+    /// The root of `heap`, with the nodes of each level of the heap hashed in parallel.
+    #[cfg(all(feature = "rayon", any(feature = "ethereum", feature = "solana")))]
+    fn par_heap_root(&self, leaves: &[Hash]) -> Hash {
+        let mut sorted = leaves.to_vec();
+        sorted.par_sort_unstable();
+
+        let internal = leaves.len() - 1;
+        let mut heap = vec![[0; 32]; internal];
+        heap.extend(sorted.iter().rev());
+        if internal == 0 {
+            return heap[0];
+        }
+
+        // The nodes at `depth` are at `2^depth - 1..2^(depth + 1) - 1`, and
+        // their children right after.
+        for depth in (0..=internal.ilog2()).rev() {
+            let start = (1 << depth) - 1;
+            let (parents, children) = heap.split_at_mut(2 * start + 1);
+            parents[start..(2 * start + 1).min(internal)]
+                .par_iter_mut()
+                .enumerate()
+                .for_each(|(offset, node)| {
+                    *node = self.hash_node(&children[2 * offset], &children[2 * offset + 1]);
+                });
+        }
+        heap[0]
+    }
+}
+
+/// A tree built in a `Profile`, which proves and verifies its leaves the
+/// way the system of the profile does.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{MerkleTree, Profile};
+///
+/// let tree = MerkleTree::builder().build_profile(Profile::Rfc6962, &["Narya", "Nenya", "Vilya"]).unwrap();
+/// let proof = tree.proof(2).unwrap();
+///
+/// assert_eq!(tree.root(), Profile::Rfc6962.root_of(&["Narya", "Nenya", "Vilya"]).unwrap());
+/// assert!(tree.verify(&proof, &Profile::Rfc6962.hash_leaf(b"Vilya")).is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileTree {
+    profile: Profile,
+    leaves: Vec<Hash>,
+    root: Hash,
+}
+
+impl ProfileTree {
+    pub(crate) fn new(profile: Profile, leaves: Vec<Hash>) -> Result<Self, Error> {
+        let root = profile.root(&leaves)?;
+        Ok(Self {
+            profile,
+            leaves,
+            root,
+        })
+    }
+
+    pub fn profile(&self) -> Profile {
+        self.profile
+    }
+
+    pub fn root(&self) -> Hash {
+        self.root
+    }
+
+    /// The leaves in the order they were given, before any sorting of the profile.
+    pub fn leaves(&self) -> &[Hash] {
+        &self.leaves
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Returns the proof of the leaf at `index`, see `Profile::proof`.
+    pub fn proof(&self, index: usize) -> Result<Proof, Error> {
+        self.profile.proof(&self.leaves, index)
+    }
+
+    /// Checks that `proof` leads from `leaf` to the root of the tree.
+    pub fn verify(&self, proof: &Proof, leaf: &Hash) -> Result<(), Error> {
+        self.profile.verify(proof, leaf, &self.root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    #[cfg(feature = "rayon")]
+    use proptest::prelude::*;
+
+    fn profiles() -> Vec<Profile> {
+        vec![
+            Profile::LegacySorted,
+            Profile::Rfc6962,
+            Profile::Tendermint,
+            Profile::Bitcoin,
+            #[cfg(any(feature = "ethereum", feature = "solana"))]
+            Profile::OpenZeppelin,
+        ]
+    }
+
+    #[test]
+    fn test_every_proof_verifies_in_its_profile_only() {
+        for count in 1..=17 {
+            let items: Vec<String> = (0..count).map(|item| format!("mearas {}", item)).collect();
+            for profile in profiles() {
+                let leaves: Vec<Hash> = items
+                    .iter()
+                    .map(|item| profile.hash_leaf(item.as_bytes()))
+                    .collect();
+                let root = profile.root(&leaves).unwrap();
+
+                for index in 0..count {
+                    let proof = profile.proof(&leaves, index).unwrap();
+                    let leaf = &leaves[index];
+                    assert_eq!(profile.verify(&proof, leaf, &root), Ok(()), "{:?}", profile);
+
+                    let mut forged = proof.clone();
+                    forged.siblings.push(root);
+                    assert!(profile.verify(&forged, leaf, &root).is_err());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_profiles_match_their_standards() {
+        let items = ["Shadowfax", "Hasufel", "Arod"];
+        assert_eq!(
+            Profile::LegacySorted.root_of(&items).ok(),
+            MerkleTree::build(&items).unwrap().root()
+        );
+
+        // The test vectors of the Certificate Transparency reference implementation.
+        let inputs: [&[u8]; 8] = [
+            b"",
+            &[0x00],
+            &[0x10],
+            &[0x20, 0x21],
+            &[0x30, 0x31],
+            &[0x40, 0x41, 0x42, 0x43],
+            &[0x50, 0x51, 0x52, 0x53, 0x54, 0x55, 0x56, 0x57],
+            &[
+                0x60, 0x61, 0x62, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x6b, 0x6c, 0x6d,
+                0x6e, 0x6f,
+            ],
+        ];
+        let roots = [
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+            "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+            "aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77",
+            "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+            "4e3bbb1f7b478dcfe71fb631631519a3bca12c9aefca1612bfce4c13a86264d4",
+            "76e67dadbcdf1e10e1b74ddc608abd2f98dfb16fbce75277b5232a127f2087ef",
+            "ddb89be403809e325750d3d263cd78929c2942b7942a34b77e122c9594a74c8c",
+            "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+        ];
+        for (size, root) in (1..=8).zip(roots) {
+            assert_eq!(
+                hex::encode(Profile::Rfc6962.root_of(&inputs[..size]).unwrap()),
+                root
+            );
+        }
+
+        // The transactions of block 100000, in internal byte order.
+        let txids: Vec<Hash> = [
+            "876dd0a3ef4a2816ffd1c12ab649825a958b0ff3bb3d6f3e1250f13ddbf0148c",
+            "c40297f730dd7b5a99567eb8d27b78758f607507c52292d02d4031895b52f2ff",
+            "c46e239ab7d28e2c019b6d66ad8fae98a56ef1f21aeecb94d1b1718186f05963",
+            "1d0cb83721529a062d9675b98d6e5c587e4a770fc84ed00abc5a5de04568a6e9",
+        ]
+        .iter()
+        .map(|txid| hex::decode(txid).unwrap().try_into().unwrap())
+        .collect();
+        assert_eq!(
+            hex::encode(Profile::Bitcoin.root(&txids).unwrap()),
+            "6657a9252aacd5c0b2940996ecff952228c3067cc38d4885efb5a4ac4247e9f3"
+        );
+    }
+
+    #[test]
+    fn test_builder_builds_trees_of_every_profile() {
+        let items = ["Shadowfax", "Hasufel", "Arod", "Brego", "Snowmane"];
+        for profile in profiles() {
+            let tree = MerkleTree::builder()
+                .build_profile(profile, &items)
+                .unwrap();
+            assert_eq!(tree.root(), profile.root_of(&items).unwrap());
+            assert_eq!(tree.profile(), profile);
+
+            for (index, item) in items.iter().enumerate() {
+                let proof = tree.proof(index).unwrap();
+                let leaf = profile.hash_leaf(item.as_bytes());
+                assert_eq!(tree.verify(&proof, &leaf), Ok(()), "{:?}", profile);
+                assert!(tree.verify(&proof, &profile.hash_leaf(b"Bill")).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_builder_options_apply_to_profile_trees() {
+        let builder = MerkleTree::builder()
+            .duplicates(crate::DuplicatePolicy::Reject)
+            .limits(crate::Limits {
+                max_leaves: 3,
+                ..Default::default()
+            });
+
+        assert_eq!(
+            builder
+                .build_profile(Profile::Rfc6962, &["Arod", "Arod"])
+                .err(),
+            Some(Error::DuplicateLeaves(vec![vec![0, 1]]))
+        );
+        assert!(builder
+            .build_profile(Profile::Bitcoin, &["Arod", "Brego", "Hasufel", "Snowmane"])
+            .is_err());
+        assert_eq!(
+            builder
+                .build_profile(Profile::Bitcoin, &[] as &[&str])
+                .err(),
+            Some(Error::EmptyTree)
+        );
+    }
+
+    #[cfg(any(feature = "ethereum", feature = "solana"))]
+    #[test]
+    fn test_openzeppelin_standard_tree() {
+        // The example of the README of @openzeppelin/merkle-tree.
+        let values = [
+            (
+                "1111111111111111111111111111111111111111",
+                5_000_000_000_000_000_000u128,
+            ),
+            (
+                "2222222222222222222222222222222222222222",
+                2_500_000_000_000_000_000u128,
+            ),
+        ];
+        let leaves: Vec<Hash> = values
+            .iter()
+            .map(|(address, amount)| {
+                let mut encoded = vec![0; 12];
+                encoded.extend_from_slice(&hex::decode(address).unwrap());
+                encoded.extend_from_slice(&[0; 16]);
+                encoded.extend_from_slice(&amount.to_be_bytes());
+                Profile::OpenZeppelin.hash_leaf(&encoded)
+            })
+            .collect();
+
+        assert_eq!(
+            hex::encode(Profile::OpenZeppelin.root(&leaves).unwrap()),
+            "d4dee0beab2d53f2cc83e567171bd2820e49898130a22622b10ead383e90bd77"
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn test_parallel_roots_match_serial_roots(
+            count in 1..5000usize,
+            distinct in 1..5000usize,
+            seed in any::<u64>(),
+        ) {
+            for profile in profiles() {
+                // Repeated leaves too, which the sorted profiles must order the same way.
+                let leaves: Vec<Hash> = (0..count)
+                    .map(|leaf| profile.hash_leaf(&(seed ^ (leaf % distinct) as u64).to_be_bytes()))
+                    .collect();
+                prop_assert_eq!(profile.par_root(&leaves), profile.root(&leaves), "{:?}", profile);
+            }
+        }
+    }
+}
@@ -0,0 +1,856 @@
+use std::fmt;
+
+use crate::error::Error;
+use crate::hashing::{commit_leaf_count, level_width, Hash, MerkleHasher, Sha256};
+#[cfg(feature = "tree")]
+use crate::merkle_tree::GenericMerkleTree;
+
+/// Why a proof was rejected before being folded into a root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofError {
+    /// A tree of the claimed size needs one hash per level above the leaves.
+    LengthMismatch { expected: usize, actual: usize },
+    /// The node has no sibling at this level, so the step must repeat the node itself.
+    InvalidPadding { level: usize },
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProofError::LengthMismatch { expected, actual } => write!(
+                f,
+                "the proof has {} hashes, the tree needs {}",
+                actual, expected
+            ),
+            ProofError::InvalidPadding { level } => write!(
+                f,
+                "the node at level {} has no sibling but the proof does not repeat it",
+                level
+            ),
+        }
+    }
+}
+
+/// A proof of inclusion that knows the position of its leaf, so its shape
+/// can be checked against the tree before it is folded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proof {
+    pub leaf_index: usize,
+    pub leaf_count: usize,
+    /// The hash combined with the node at each level, from the leaf up.
+    pub siblings: Vec<Hash>,
+}
+
+impl Proof {
+    /// The number of hashes of every proof in a tree of `leaf_count` leaves.
+    pub fn expected_length(leaf_count: usize) -> usize {
+        (0..)
+            .take_while(|&level| level_width(leaf_count, level) > 1)
+            .count()
+    }
+
+    /// Validates the shape of the proof and folds it into the root it leads to.
+    ///
+    /// Fails if the index is not in the tree, the proof has more or fewer
+    /// hashes than the tree has levels, or a level where the node has no
+    /// sibling does not repeat the node.
+    pub fn compute_root(&self, leaf: &Hash) -> Result<Hash, Error> {
+        self.compute_root_with::<Sha256>(leaf)
+    }
+
+    /// Folds the proof into the root it leads to in a tree built with `H`,
+    /// with the same checks as `compute_root`.
+    pub fn compute_root_with<H: MerkleHasher>(&self, leaf: &Hash) -> Result<Hash, Error> {
+        if self.leaf_index >= self.leaf_count {
+            return Err(Error::IndexOutOfRange {
+                index: self.leaf_index,
+                leaf_count: self.leaf_count,
+            });
+        }
+
+        let expected = Self::expected_length(self.leaf_count);
+        if self.siblings.len() != expected {
+            return Err(Error::InvalidProof(ProofError::LengthMismatch {
+                expected,
+                actual: self.siblings.len(),
+            }));
+        }
+
+        fold_path::<H>(0, leaf, self.leaf_index, self.leaf_count, &self.siblings)
+    }
+
+    /// Checks that the proof leads from `leaf` to `root`.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "tree")] {
+    /// use merkle_tree::MerkleTree;
+    ///
+    /// let tree = MerkleTree::build(&["Gimli", "Legolas", "Aragorn"]).unwrap();
+    /// let proof = tree.proof(1).unwrap();
+    ///
+    /// let leaf = MerkleTree::hash(b"Legolas");
+    /// assert!(proof.verify(&leaf, &tree.root().unwrap()).is_ok());
+    /// # }
+    /// ```
+    pub fn verify(&self, leaf: &Hash, root: &Hash) -> Result<(), Error> {
+        self.verify_with::<Sha256>(leaf, root)
+    }
+
+    /// Checks that the proof leads from `leaf` to `root` in a tree built with `H`.
+    pub fn verify_with<H: MerkleHasher>(&self, leaf: &Hash, root: &Hash) -> Result<(), Error> {
+        if self.compute_root_with::<H>(leaf)? != *root {
+            return Err(Error::RootMismatch);
+        }
+        Ok(())
+    }
+
+    /// Checks that the proof leads from `leaf` to a root committing to the
+    /// leaf count of the proof, as the roots of trees built with
+    /// `MutationGuard::CommitLeafCount` do, which also checks the size of
+    /// the tree the proof claims.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "tree")] {
+    /// use merkle_tree::{MerkleTree, MutationGuard};
+    ///
+    /// let tree = MerkleTree::builder()
+    ///     .mutation_guard(MutationGuard::CommitLeafCount)
+    ///     .build(&["Gimli", "Legolas", "Aragorn"])
+    ///     .unwrap();
+    /// let mut proof = tree.proof(1).unwrap();
+    ///
+    /// let leaf = MerkleTree::hash(b"Legolas");
+    /// assert!(proof.verify_committed(&leaf, &tree.root().unwrap()).is_ok());
+    ///
+    /// proof.leaf_count = 4;
+    /// assert!(proof.verify_committed(&leaf, &tree.root().unwrap()).is_err());
+    /// # }
+    /// ```
+    pub fn verify_committed(&self, leaf: &Hash, root: &Hash) -> Result<(), Error> {
+        self.verify_committed_with::<Sha256>(leaf, root)
+    }
+
+    /// The counterpart of `verify_committed` for a tree built with `H`.
+    pub fn verify_committed_with<H: MerkleHasher>(
+        &self,
+        leaf: &Hash,
+        root: &Hash,
+    ) -> Result<(), Error> {
+        let tree_root = self.compute_root_with::<H>(leaf)?;
+        if commit_leaf_count::<H>(&tree_root, self.leaf_count) != *root {
+            return Err(Error::RootMismatch);
+        }
+        Ok(())
+    }
+}
+
+/// Folds the siblings from the node at `start` level up, checking the padding of every level.
+fn fold_path<H: MerkleHasher>(
+    start: usize,
+    node: &Hash,
+    index: usize,
+    leaf_count: usize,
+    siblings: &[Hash],
+) -> Result<Hash, Error> {
+    let mut node = *node;
+    let mut index = index;
+
+    for (level, sibling) in (start..).zip(siblings) {
+        let width = level_width(leaf_count, level);
+        if index ^ 1 >= width && *sibling != node {
+            return Err(Error::InvalidProof(ProofError::InvalidPadding { level }));
+        }
+
+        node = H::parent(&node, sibling);
+        index /= 2;
+    }
+
+    Ok(node)
+}
+
+/// A proof of inclusion of a leaf in the subtree rooted at one of its
+/// ancestors, rather than in the whole tree.
+///
+/// Hierarchical systems compose them: a leaf is proven up to the root of its
+/// shard, and the shard root is proven up to the global root.
+///
+/// # Examples
+/// ```
+/// # #[cfg(feature = "tree")] {
+/// use merkle_tree::MerkleTree;
+///
+/// let tree = MerkleTree::build(&["Arnor", "Gondor", "Rohan", "Dale", "Erebor"]).unwrap();
+///
+/// let proof = tree.proof_to_level(3, 2).unwrap();
+/// let ancestor = tree.node(2, proof.ancestor_index()).unwrap();
+///
+/// assert!(proof.verify(&MerkleTree::hash(b"Dale"), &ancestor).is_ok());
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelProof {
+    pub leaf_index: usize,
+    pub leaf_count: usize,
+    /// The hash combined with the node at each level, from the leaf up to
+    /// the ancestor, which is at the level of the number of siblings.
+    pub siblings: Vec<Hash>,
+}
+
+impl LevelProof {
+    /// The level of the ancestor the proof leads to.
+    pub fn level(&self) -> usize {
+        self.siblings.len()
+    }
+
+    /// The index of the ancestor in its level.
+    pub fn ancestor_index(&self) -> usize {
+        u32::try_from(self.level())
+            .ok()
+            .and_then(|level| self.leaf_index.checked_shr(level))
+            .unwrap_or(0)
+    }
+
+    /// Validates the shape of the proof and folds it into the ancestor it leads to.
+    pub fn compute_ancestor(&self, leaf: &Hash) -> Result<Hash, Error> {
+        self.compute_ancestor_with::<Sha256>(leaf)
+    }
+
+    /// The counterpart of `compute_ancestor` for a tree built with `H`.
+    pub fn compute_ancestor_with<H: MerkleHasher>(&self, leaf: &Hash) -> Result<Hash, Error> {
+        if self.leaf_index >= self.leaf_count {
+            return Err(Error::IndexOutOfRange {
+                index: self.leaf_index,
+                leaf_count: self.leaf_count,
+            });
+        }
+
+        let height = Proof::expected_length(self.leaf_count);
+        if self.siblings.len() > height {
+            return Err(Error::InvalidProof(ProofError::LengthMismatch {
+                expected: height,
+                actual: self.siblings.len(),
+            }));
+        }
+
+        fold_path::<H>(0, leaf, self.leaf_index, self.leaf_count, &self.siblings)
+    }
+
+    /// Checks that the proof leads from `leaf` to `ancestor`.
+    pub fn verify(&self, leaf: &Hash, ancestor: &Hash) -> Result<(), Error> {
+        self.verify_with::<Sha256>(leaf, ancestor)
+    }
+
+    /// Checks that the proof leads from `leaf` to `ancestor` in a tree built with `H`.
+    pub fn verify_with<H: MerkleHasher>(&self, leaf: &Hash, ancestor: &Hash) -> Result<(), Error> {
+        if self.compute_ancestor_with::<H>(leaf)? != *ancestor {
+            return Err(Error::RootMismatch);
+        }
+        Ok(())
+    }
+}
+
+/// A proof that the root of a subtree, covering a batch of leaves aligned to
+/// a power of two, is part of the tree, so the whole batch is proven at once.
+///
+/// # Examples
+/// ```
+/// # #[cfg(feature = "tree")] {
+/// use merkle_tree::MerkleTree;
+///
+/// let batches = ["Ori", "Nori", "Dori", "Bifur", "Bofur", "Bombur", "Óin", "Glóin"];
+/// let tree = MerkleTree::build(&batches).unwrap();
+///
+/// // The batch of leaves 4..8 is the subtree at level 2 and index 1.
+/// let proof = tree.subtree_proof(2, 1).unwrap();
+/// let leaves: Vec<_> = batches[4..].iter().map(|item| MerkleTree::hash(item.as_bytes())).collect();
+///
+/// assert_eq!(proof.leaf_range(), 4..8);
+/// assert!(proof.verify_leaves(&leaves, &tree.root().unwrap()).is_ok());
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtreeProof {
+    /// The level of the subtree root, the batch has up to `2^level` leaves.
+    pub level: usize,
+    /// The index of the subtree root in its level.
+    pub index: usize,
+    pub leaf_count: usize,
+    /// The hash combined with the node at each level, from the subtree root up.
+    pub siblings: Vec<Hash>,
+}
+
+impl SubtreeProof {
+    /// The indices of the leaves of the subtree. The subtree at the right
+    /// edge of the tree may have fewer than `2^level` leaves, and a subtree
+    /// that is not in the tree has none.
+    pub fn leaf_range(&self) -> std::ops::Range<usize> {
+        if self.index >= level_width(self.leaf_count, self.level) {
+            return self.leaf_count..self.leaf_count;
+        }
+
+        // The subtree is in the tree, so its first leaf is too. Only a tree
+        // of `usize::MAX` leaves has a level 64, with a single node.
+        let shift = |value: usize| {
+            u32::try_from(self.level)
+                .ok()
+                .and_then(|level| value.checked_shl(level))
+                .unwrap_or(0)
+        };
+        let width = match shift(1) {
+            0 => usize::MAX,
+            width => width,
+        };
+        let first = shift(self.index);
+        first..first.saturating_add(width).min(self.leaf_count)
+    }
+
+    /// Validates the shape of the proof and folds it into the root it leads to.
+    pub fn compute_root(&self, subtree_root: &Hash) -> Result<Hash, Error> {
+        self.compute_root_with::<Sha256>(subtree_root)
+    }
+
+    /// The counterpart of `compute_root` for a tree built with `H`.
+    pub fn compute_root_with<H: MerkleHasher>(&self, subtree_root: &Hash) -> Result<Hash, Error> {
+        let width = level_width(self.leaf_count, self.level);
+        if self.index >= width {
+            return Err(Error::InvalidInput(format!(
+                "the tree has no node at level {} and index {}",
+                self.level, self.index
+            )));
+        }
+
+        let expected = Proof::expected_length(self.leaf_count) - self.level;
+        if self.siblings.len() != expected {
+            return Err(Error::InvalidProof(ProofError::LengthMismatch {
+                expected,
+                actual: self.siblings.len(),
+            }));
+        }
+
+        fold_path::<H>(
+            self.level,
+            subtree_root,
+            self.index,
+            self.leaf_count,
+            &self.siblings,
+        )
+    }
+
+    /// Checks that the proof leads from the subtree root to `root`.
+    pub fn verify(&self, subtree_root: &Hash, root: &Hash) -> Result<(), Error> {
+        self.verify_with::<Sha256>(subtree_root, root)
+    }
+
+    /// Checks that the proof leads from the subtree root to `root` in a tree built with `H`.
+    pub fn verify_with<H: MerkleHasher>(
+        &self,
+        subtree_root: &Hash,
+        root: &Hash,
+    ) -> Result<(), Error> {
+        if self.compute_root_with::<H>(subtree_root)? != *root {
+            return Err(Error::RootMismatch);
+        }
+        Ok(())
+    }
+
+    /// Checks that `leaves` are the leaves of the subtree, as an unordered
+    /// set of siblings: pairs are sorted before hashing, so the two leaves
+    /// of a pair may come in either order, but a leaf moved to another pair
+    /// is rejected.
+    pub fn verify_leaves(&self, leaves: &[Hash], root: &Hash) -> Result<(), Error> {
+        self.verify_leaves_with::<Sha256>(leaves, root)
+    }
+
+    /// The counterpart of `verify_leaves` for a tree built with `H`.
+    pub fn verify_leaves_with<H: MerkleHasher>(
+        &self,
+        leaves: &[Hash],
+        root: &Hash,
+    ) -> Result<(), Error> {
+        if leaves.is_empty() {
+            return Err(Error::EmptyTree);
+        }
+        if leaves.len() != self.leaf_range().len() {
+            return Err(Error::InvalidInput(format!(
+                "the subtree has {} leaves, not {}",
+                self.leaf_range().len(),
+                leaves.len()
+            )));
+        }
+
+        // The nodes of the subtree repeat their last node on odd levels, as
+        // the tree does, since the subtree starts at an even index.
+        let mut level = leaves.to_vec();
+        for _ in 0..self.level {
+            if level.len() % 2 == 1 {
+                level.extend(level.last().cloned());
+            }
+            level = level
+                .chunks_exact(2)
+                .map(|pair| H::parent(&pair[0], &pair[1]))
+                .collect();
+        }
+
+        self.verify_with::<H>(&level[0], root)
+    }
+}
+
+#[cfg(feature = "tree")]
+impl<H: MerkleHasher> GenericMerkleTree<H> {
+    /// Returns the proof of inclusion of the subtree rooted at the node at `level` and `index`.
+    pub fn subtree_proof(&self, level: usize, index: usize) -> Result<SubtreeProof, Error> {
+        if level > self.height() || index >= Self::level_width(self.leaf_count(), level) {
+            return Err(Error::InvalidInput(format!(
+                "the tree has no node at level {} and index {}",
+                level, index
+            )));
+        }
+
+        let proof = self.proof(index << level)?;
+
+        Ok(SubtreeProof {
+            level,
+            index,
+            leaf_count: proof.leaf_count,
+            siblings: proof.siblings[level..].to_vec(),
+        })
+    }
+
+    /// Returns the proof of inclusion of the leaf at `index` up to its ancestor at `level`.
+    /// A proof to level `height()` is the proof to the root.
+    pub fn proof_to_level(&self, index: usize, level: usize) -> Result<LevelProof, Error> {
+        if level > self.height() {
+            return Err(Error::InvalidInput(format!(
+                "the tree has {} levels above the leaves, not {}",
+                self.height(),
+                level
+            )));
+        }
+
+        let mut proof = self.proof(index)?;
+        proof.siblings.truncate(level);
+
+        Ok(LevelProof {
+            leaf_index: index,
+            leaf_count: proof.leaf_count,
+            siblings: proof.siblings,
+        })
+    }
+
+    /// Returns the proof of inclusion of the leaf at `index`.
+    pub fn proof(&self, index: usize) -> Result<Proof, Error> {
+        let mut siblings = vec![[0; 32]; self.height()];
+        self.proof_into(index, &mut siblings)?;
+
+        Ok(Proof {
+            leaf_index: index,
+            leaf_count: self.leaf_count(),
+            siblings,
+        })
+    }
+
+    /// Writes the siblings of the proof of the leaf at `index` at the start
+    /// of `buffer` and returns their number, without allocating. Fails if
+    /// the buffer is shorter than `height()`.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "tree")] {
+    /// use merkle_tree::MerkleTree;
+    ///
+    /// let tree = MerkleTree::build(&["Tuckborough", "Hobbiton", "Michel Delving"]).unwrap();
+    /// let mut buffer = [[0; 32]; 64];
+    ///
+    /// let length = tree.proof_into(1, &mut buffer).unwrap();
+    ///
+    /// assert_eq!(&buffer[..length], tree.proof(1).unwrap().siblings.as_slice());
+    /// # }
+    /// ```
+    pub fn proof_into(&self, index: usize, buffer: &mut [Hash]) -> Result<usize, Error> {
+        if index >= self.leaf_count() {
+            return Err(Error::IndexOutOfRange {
+                index,
+                leaf_count: self.leaf_count(),
+            });
+        }
+
+        let height = self.height();
+        if buffer.len() < height {
+            return Err(Error::InvalidInput(format!(
+                "the proof has {} hashes, the buffer holds {}",
+                height,
+                buffer.len()
+            )));
+        }
+
+        for (level, sibling) in buffer[..height].iter_mut().enumerate() {
+            let position = index >> level;
+            *sibling = self
+                .node(level, position ^ 1)
+                .or_else(|| self.node(level, position))
+                .ok_or_else(|| {
+                    Error::InvalidInput(format!("the tree has no node at level {}", level))
+                })?;
+        }
+
+        Ok(height)
+    }
+
+    /// Checks a proof against this tree, after validating its shape.
+    /// Fails if the proof is for a tree of another size.
+    pub fn verify_proof(&self, leaf: &Hash, proof: &Proof) -> Result<(), Error> {
+        self.limits().check_proof_length(proof.siblings.len())?;
+
+        if proof.leaf_count != self.leaf_count() {
+            return Err(Error::InvalidInput(format!(
+                "the proof is for a tree of {} leaves, not {}",
+                proof.leaf_count,
+                self.leaf_count()
+            )));
+        }
+
+        let root = proof.compute_root_with::<H>(leaf)?;
+        let root = self
+            .mutation_guard()
+            .commit_root::<H>(root, proof.leaf_count);
+
+        if Some(root) != self.root() {
+            return Err(Error::RootMismatch);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "tree"))]
+mod tests {
+
+    use super::*;
+    use crate::config::MutationGuard;
+    use crate::hashes::LeafHash;
+    use crate::merkle_tree::MerkleTree;
+
+    fn items() -> Vec<String> {
+        (0..11)
+            .map(|stanza| format!("The Lay of Nimrodel, stanza {}", stanza))
+            .collect()
+    }
+
+    #[test]
+    fn test_expected_length() {
+        assert_eq!(Proof::expected_length(1), 0);
+        assert_eq!(Proof::expected_length(2), 1);
+        assert_eq!(Proof::expected_length(5), 3);
+        assert_eq!(Proof::expected_length(8), 3);
+        assert_eq!(Proof::expected_length(9), 4);
+    }
+
+    #[test]
+    fn test_every_proof_verifies() {
+        let items = items();
+        let tree = MerkleTree::build(&items).unwrap();
+
+        for (index, item) in items.iter().enumerate() {
+            let proof = tree.proof(index).unwrap();
+            let leaf = MerkleTree::hash(item.as_bytes());
+
+            assert_eq!(tree.verify_proof(&leaf, &proof), Ok(()));
+            assert_eq!(
+                Some(proof.siblings.clone()),
+                tree.proof_of_inclusion(&LeafHash::new(leaf))
+            );
+        }
+    }
+
+    #[test]
+    fn test_committed_leaf_counts_are_checked() {
+        let builder = MerkleTree::builder().mutation_guard(MutationGuard::CommitLeafCount);
+        let three = builder.build(&["Huey", "Dewey", "Louie"]).unwrap();
+        let four = builder.build(&["Huey", "Dewey", "Louie", "Louie"]).unwrap();
+        let leaf = MerkleTree::hash(b"Louie");
+
+        for tree in [&three, &four] {
+            for index in 2..tree.leaf_count() {
+                let proof = tree.proof(index).unwrap();
+                assert_eq!(proof.verify_committed(&leaf, &tree.root().unwrap()), Ok(()));
+                assert!(proof.verify(&leaf, &tree.root().unwrap()).is_err());
+            }
+        }
+
+        // The padding of the third leaf is the fourth one, but not the size.
+        let mut claimed = three.proof(2).unwrap();
+        claimed.leaf_count = 4;
+        assert_eq!(
+            claimed.compute_root(&leaf),
+            three.proof(2).unwrap().compute_root(&leaf)
+        );
+        assert_eq!(
+            claimed.verify_committed(&leaf, &three.root().unwrap()),
+            Err(Error::RootMismatch)
+        );
+    }
+
+    #[test]
+    fn test_proofs_into_short_buffers_are_rejected() {
+        let tree =
+            MerkleTree::build(&(0..9).map(|leaf| leaf.to_string()).collect::<Vec<_>>()).unwrap();
+        let mut buffer = [[0; 32]; 4];
+
+        assert_eq!(tree.proof_into(8, &mut buffer), Ok(4));
+        assert!(tree.proof_into(8, &mut buffer[..3]).is_err());
+        assert!(tree.proof_into(9, &mut buffer).is_err());
+    }
+
+    #[test]
+    fn test_truncated_and_overlong_proofs_are_rejected() {
+        let items = items();
+        let tree = MerkleTree::build(&items).unwrap();
+        let leaf = MerkleTree::hash(items[3].as_bytes());
+
+        let mut truncated = tree.proof(3).unwrap();
+        truncated.siblings.pop();
+        assert_eq!(
+            tree.verify_proof(&leaf, &truncated),
+            Err(Error::InvalidProof(ProofError::LengthMismatch {
+                expected: 4,
+                actual: 3
+            }))
+        );
+
+        let mut overlong = tree.proof(3).unwrap();
+        overlong.siblings.push(leaf);
+        assert!(tree.verify_proof(&leaf, &overlong).is_err());
+        assert!(!tree.validate_proof(&LeafHash::new(leaf), &overlong.siblings));
+    }
+
+    #[test]
+    fn test_interior_node_is_not_a_leaf() {
+        let items = items();
+        let tree = MerkleTree::build(&items).unwrap();
+
+        // The parent of the first two leaves with the rest of their proof folds into the root.
+        let interior = tree.node(1, 0).unwrap();
+        let siblings = tree.proof(0).unwrap().siblings[1..].to_vec();
+
+        assert!(!tree.validate_proof(&LeafHash::new(interior), &siblings));
+        let proof = Proof {
+            leaf_index: 0,
+            leaf_count: items.len(),
+            siblings,
+        };
+        assert!(tree.verify_proof(&interior, &proof).is_err());
+    }
+
+    #[test]
+    fn test_malformed_padding_and_indices_are_rejected() {
+        let items = items();
+        let tree = MerkleTree::build(&items).unwrap();
+        let leaf = MerkleTree::hash(items[10].as_bytes());
+
+        let mut proof = tree.proof(10).unwrap();
+        proof.siblings[0] = MerkleTree::hash(b"Amroth");
+        assert_eq!(
+            proof.compute_root(&leaf),
+            Err(Error::InvalidProof(ProofError::InvalidPadding { level: 0 }))
+        );
+
+        let out_of_range = Proof {
+            leaf_index: 11,
+            leaf_count: 11,
+            siblings: Vec::new(),
+        };
+        assert!(matches!(
+            out_of_range.compute_root(&leaf),
+            Err(Error::IndexOutOfRange { .. })
+        ));
+        assert!(tree.proof(11).is_err());
+    }
+
+    #[test]
+    fn test_proofs_to_every_level() {
+        let items = items();
+        let tree = MerkleTree::build(&items).unwrap();
+
+        for (index, item) in items.iter().enumerate() {
+            let leaf = MerkleTree::hash(item.as_bytes());
+
+            for level in 0..=tree.height() {
+                let proof = tree.proof_to_level(index, level).unwrap();
+                let ancestor = tree.node(level, index >> level).unwrap();
+                assert_eq!(proof.verify(&leaf, &ancestor), Ok(()));
+            }
+        }
+
+        let proof = tree.proof_to_level(6, 2).unwrap();
+        assert!(proof
+            .verify(&MerkleTree::hash(b"Amroth"), &tree.node(2, 1).unwrap())
+            .is_err());
+        assert!(tree.proof_to_level(6, tree.height() + 1).is_err());
+    }
+
+    #[test]
+    fn test_every_subtree_is_proven() {
+        let items = items();
+        let tree = MerkleTree::build(&items).unwrap();
+        let leaves: Vec<Hash> = items
+            .iter()
+            .map(|item| MerkleTree::hash(item.as_bytes()))
+            .collect();
+        let root = tree.root().unwrap();
+
+        for level in 0..=tree.height() {
+            for index in 0..MerkleTree::level_width(items.len(), level) {
+                let proof = tree.subtree_proof(level, index).unwrap();
+
+                assert_eq!(
+                    proof.verify(&tree.node(level, index).unwrap(), &root),
+                    Ok(())
+                );
+                assert_eq!(
+                    proof.verify_leaves(&leaves[proof.leaf_range()], &root),
+                    Ok(()),
+                    "level {} index {}",
+                    level,
+                    index
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_batches_are_rejected() {
+        let items = items();
+        let tree = MerkleTree::build(&items).unwrap();
+        let mut leaves: Vec<Hash> = items
+            .iter()
+            .map(|item| MerkleTree::hash(item.as_bytes()))
+            .collect();
+        let root = tree.root().unwrap();
+
+        let proof = tree.subtree_proof(2, 1).unwrap();
+        assert_eq!(proof.verify_leaves(&leaves[4..8], &root), Ok(()));
+        leaves[6] = MerkleTree::hash(b"Amroth");
+        assert!(proof.verify_leaves(&leaves[4..8], &root).is_err());
+        assert!(proof.verify_leaves(&leaves[4..7], &root).is_err());
+
+        let mut moved = [leaves[0], leaves[1], leaves[2], leaves[3]];
+        moved.swap(1, 2);
+        let proof = tree.subtree_proof(2, 0).unwrap();
+        assert_eq!(proof.verify_leaves(&moved, &root), Err(Error::RootMismatch));
+        moved.swap(1, 2);
+        moved.swap(0, 1);
+        assert_eq!(proof.verify_leaves(&moved, &root), Ok(()));
+
+        assert!(tree.subtree_proof(2, 3).is_err());
+        assert!(tree.subtree_proof(5, 0).is_err());
+    }
+
+    #[test]
+    fn test_proofs_of_other_hashers_verify_with_them() {
+        use crate::merkle_tree::GenericMerkleTree;
+        use sha2::Sha512_256;
+
+        let items = items();
+        let tree = GenericMerkleTree::<Sha512_256>::build(&items).unwrap();
+        let root = tree.root().unwrap();
+        let leaves: Vec<Hash> = items
+            .iter()
+            .map(|item| Sha512_256::hash(item.as_bytes()))
+            .collect();
+
+        let subtree = tree.subtree_proof(1, 0).unwrap();
+        let node = tree.node(1, 0).unwrap();
+        assert_eq!(subtree.verify_with::<Sha512_256>(&node, &root), Ok(()));
+        assert_eq!(subtree.verify(&node, &root), Err(Error::RootMismatch));
+        assert_eq!(
+            subtree.verify_leaves_with::<Sha512_256>(&leaves[0..2], &root),
+            Ok(())
+        );
+
+        let level = tree.proof_to_level(0, 2).unwrap();
+        let ancestor = tree.node(2, 0).unwrap();
+        assert_eq!(
+            level.verify_with::<Sha512_256>(&leaves[0], &ancestor),
+            Ok(())
+        );
+        assert_eq!(
+            level.verify(&leaves[0], &ancestor),
+            Err(Error::RootMismatch)
+        );
+
+        let proof = tree.proof(9).unwrap();
+        let committed = GenericMerkleTree::<Sha512_256>::commit_leaf_count(&root, items.len());
+        assert_eq!(
+            proof.verify_committed_with::<Sha512_256>(&leaves[9], &committed),
+            Ok(())
+        );
+        assert!(proof.verify_committed(&leaves[9], &committed).is_err());
+    }
+
+    #[test]
+    fn test_partial_proofs_compose_across_trees() {
+        let shards: Vec<MerkleTree> = (0..3)
+            .map(|shard| MerkleTree::build(&items()[shard * 4..(shard * 4 + 4).min(11)]).unwrap())
+            .collect();
+        let global = MerkleTree::builder()
+            .build_from_hashes(shards.iter().map(|shard| shard.root().unwrap()).collect())
+            .unwrap();
+
+        let leaf = MerkleTree::hash(items()[9].as_bytes());
+        let to_shard = shards[2].proof_to_level(1, shards[2].height()).unwrap();
+        let shard_root = to_shard.compute_ancestor(&leaf).unwrap();
+        let to_global = global.proof(2).unwrap();
+
+        assert_eq!(
+            to_global.verify(&shard_root, &global.root().unwrap()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_malformed_proofs_are_errors_not_panics() {
+        let tree = MerkleTree::build(&items()).unwrap();
+        let root = tree.root().unwrap();
+        let leaf = MerkleTree::hash(b"Nimrodel");
+        let sizes = [0, 1, 2, 11, usize::MAX / 2, usize::MAX];
+        let positions = [0, 1, 10, 63, 64, 65, usize::MAX];
+        let siblings = [0, 1, 4, 64, 70].map(|count| vec![leaf; count]);
+
+        for leaf_count in sizes {
+            for position in positions {
+                for siblings in &siblings {
+                    let proof = Proof {
+                        leaf_index: position,
+                        leaf_count,
+                        siblings: siblings.clone(),
+                    };
+                    assert!(proof.verify(&leaf, &root).is_err());
+                    assert!(proof.verify_committed(&leaf, &root).is_err());
+                    assert!(tree.verify_proof(&leaf, &proof).is_err());
+
+                    let level = LevelProof {
+                        leaf_index: position,
+                        leaf_count,
+                        siblings: siblings.clone(),
+                    };
+                    level.ancestor_index();
+                    assert!(level.verify(&leaf, &root).is_err());
+
+                    for index in positions {
+                        let subtree = SubtreeProof {
+                            level: position,
+                            index,
+                            leaf_count,
+                            siblings: siblings.clone(),
+                        };
+                        assert!(subtree.leaf_range().len() <= leaf_count);
+                        assert!(subtree.verify(&leaf, &root).is_err());
+                        assert!(subtree.verify_leaves(&[], &root).is_err());
+                        assert!(subtree.verify_leaves(&[leaf], &root).is_err());
+                    }
+                }
+            }
+        }
+    }
+}
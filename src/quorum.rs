@@ -0,0 +1,255 @@
+use std::collections::BTreeMap;
+
+use crate::audit_log::Checkpoint;
+use crate::error::Error;
+use crate::signature::{SignatureVerifier, Signer};
+
+/// The signature of a checkpoint by a named witness.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cosignature {
+    pub witness: String,
+    pub signature: Vec<u8>,
+}
+
+/// A checkpoint signed by several witnesses, each attesting they saw the
+/// log at that size with that root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CosignedCheckpoint {
+    pub checkpoint: Checkpoint,
+    pub cosignatures: Vec<Cosignature>,
+}
+
+impl CosignedCheckpoint {
+    const DOMAIN: &'static [u8] = b"merkle-tree checkpoint cosignature";
+
+    pub fn new(checkpoint: Checkpoint) -> Self {
+        Self {
+            checkpoint,
+            cosignatures: Vec::new(),
+        }
+    }
+
+    /// The bytes every witness signs.
+    pub fn message(checkpoint: &Checkpoint) -> Vec<u8> {
+        let mut message = Self::DOMAIN.to_vec();
+        message.extend_from_slice(&(checkpoint.size as u64).to_be_bytes());
+        message.extend_from_slice(&checkpoint.root);
+        message
+    }
+
+    /// Adds the signature of `witness`.
+    pub fn cosign<S: Signer>(&mut self, witness: &str, signer: &S) {
+        self.cosignatures.push(Cosignature {
+            witness: witness.to_string(),
+            signature: signer.sign(&Self::message(&self.checkpoint)),
+        });
+    }
+}
+
+/// The witnesses a relying party trusts and how many of them must sign a
+/// checkpoint, so a single operator can't show different roots to
+/// different parties.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{Checkpoint, CosignedCheckpoint, MerkleTree, SignatureVerifier, Signer, WitnessQuorum};
+///
+/// struct Key(u8);
+///
+/// impl Signer for Key {
+///     fn sign(&self, message: &[u8]) -> Vec<u8> {
+///         [message, &[self.0]].concat()
+///     }
+/// }
+///
+/// impl SignatureVerifier for Key {
+///     fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+///         signature == [message, &[self.0]].concat()
+///     }
+/// }
+///
+/// let quorum = WitnessQuorum::new(2)
+///     .witness("Amon Din", Key(1))
+///     .witness("Eilenach", Key(2))
+///     .witness("Min-Rimmon", Key(3));
+///
+/// let mut cosigned = CosignedCheckpoint::new(Checkpoint { size: 3, root: MerkleTree::hash(b"lit") });
+/// cosigned.cosign("Amon Din", &Key(1));
+/// cosigned.cosign("Min-Rimmon", &Key(3));
+///
+/// assert_eq!(quorum.verify(&cosigned).unwrap(), vec!["Amon Din", "Min-Rimmon"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct WitnessQuorum<V> {
+    witnesses: BTreeMap<String, V>,
+    threshold: usize,
+}
+
+impl<V: SignatureVerifier> WitnessQuorum<V> {
+    /// A quorum of `threshold` signatures among the witnesses added next.
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            witnesses: BTreeMap::new(),
+            threshold,
+        }
+    }
+
+    /// Trusts a witness whose signatures `verifier` checks.
+    pub fn witness(mut self, name: &str, verifier: V) -> Self {
+        self.witnesses.insert(name.to_string(), verifier);
+        self
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// The trusted witnesses with a valid signature of the checkpoint, each counted once.
+    pub fn signers<'a>(&'a self, cosigned: &CosignedCheckpoint) -> Vec<&'a str> {
+        let message = CosignedCheckpoint::message(&cosigned.checkpoint);
+
+        self.witnesses
+            .iter()
+            .filter(|(name, verifier)| {
+                cosigned.cosignatures.iter().any(|cosignature| {
+                    cosignature.witness == **name
+                        && verifier.verify(&message, &cosignature.signature)
+                })
+            })
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Checks that at least the threshold of trusted witnesses signed the
+    /// checkpoint and returns them. Signatures of unknown witnesses and
+    /// invalid signatures are not counted.
+    pub fn verify<'a>(&'a self, cosigned: &CosignedCheckpoint) -> Result<Vec<&'a str>, Error> {
+        if self.threshold == 0 || self.threshold > self.witnesses.len() {
+            return Err(Error::InvalidInput(format!(
+                "a threshold of {} can't be met by {} witnesses",
+                self.threshold,
+                self.witnesses.len()
+            )));
+        }
+
+        let signers = self.signers(cosigned);
+        if signers.len() < self.threshold {
+            return Err(Error::QuorumNotReached {
+                signatures: signers.len(),
+                threshold: self.threshold,
+            });
+        }
+        Ok(signers)
+    }
+
+    /// The trusted witnesses that signed two different roots for the same
+    /// size, the evidence of a split view.
+    pub fn equivocations<'a>(
+        &'a self,
+        first: &CosignedCheckpoint,
+        second: &CosignedCheckpoint,
+    ) -> Vec<&'a str> {
+        if first.checkpoint.size != second.checkpoint.size
+            || first.checkpoint.root == second.checkpoint.root
+        {
+            return Vec::new();
+        }
+
+        let second_signers = self.signers(second);
+        self.signers(first)
+            .into_iter()
+            .filter(|name| second_signers.contains(name))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::merkle_tree::MerkleTree;
+
+    struct HmacKey(&'static [u8]);
+
+    impl Signer for HmacKey {
+        fn sign(&self, message: &[u8]) -> Vec<u8> {
+            hmac_sha256::HMAC::mac(message, self.0).to_vec()
+        }
+    }
+
+    impl SignatureVerifier for HmacKey {
+        fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+            hmac_sha256::HMAC::mac(message, self.0) == signature
+        }
+    }
+
+    fn quorum() -> WitnessQuorum<HmacKey> {
+        WitnessQuorum::new(3)
+            .witness("Elrond", HmacKey(b"Vilya"))
+            .witness("Galadriel", HmacKey(b"Nenya"))
+            .witness("Cirdan", HmacKey(b"Narya"))
+            .witness("Gandalf", HmacKey(b"Narya too"))
+    }
+
+    fn checkpoint(root: &[u8]) -> CosignedCheckpoint {
+        CosignedCheckpoint::new(Checkpoint {
+            size: 9,
+            root: MerkleTree::hash(root),
+        })
+    }
+
+    #[test]
+    fn test_threshold_of_valid_signatures_is_required() {
+        let quorum = quorum();
+        let mut cosigned = checkpoint(b"the free peoples");
+        cosigned.cosign("Elrond", &HmacKey(b"Vilya"));
+        cosigned.cosign("Elrond", &HmacKey(b"Vilya"));
+        cosigned.cosign("Galadriel", &HmacKey(b"Nenya"));
+        cosigned.cosign("Cirdan", &HmacKey(b"a forged ring"));
+        cosigned.cosign("Saruman", &HmacKey(b"his own ring"));
+
+        assert_eq!(
+            quorum.verify(&cosigned),
+            Err(Error::QuorumNotReached {
+                signatures: 2,
+                threshold: 3
+            })
+        );
+
+        cosigned.cosign("Gandalf", &HmacKey(b"Narya too"));
+        assert_eq!(
+            quorum.verify(&cosigned),
+            Ok(vec!["Elrond", "Galadriel", "Gandalf"])
+        );
+
+        cosigned.checkpoint.size = 10;
+        assert!(quorum.verify(&cosigned).is_err());
+    }
+
+    #[test]
+    fn test_split_views_expose_the_equivocating_witnesses() {
+        let quorum = quorum();
+        let mut honest = checkpoint(b"the free peoples");
+        let mut forked = checkpoint(b"the dark lord");
+        for (name, key) in [("Elrond", &b"Vilya"[..]), ("Cirdan", b"Narya")] {
+            honest.cosign(name, &HmacKey(key));
+        }
+        for (name, key) in [("Cirdan", &b"Narya"[..]), ("Gandalf", b"Narya too")] {
+            forked.cosign(name, &HmacKey(key));
+        }
+
+        assert_eq!(quorum.equivocations(&honest, &forked), vec!["Cirdan"]);
+        assert!(quorum.equivocations(&honest, &honest).is_empty());
+    }
+
+    #[test]
+    fn test_unreachable_thresholds_are_rejected() {
+        let cosigned = checkpoint(b"the free peoples");
+
+        assert!(WitnessQuorum::<HmacKey>::new(1).verify(&cosigned).is_err());
+        assert!(WitnessQuorum::new(0)
+            .witness("Elrond", HmacKey(b"Vilya"))
+            .verify(&cosigned)
+            .is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use std::marker::PhantomData;
+
+use crate::error::Error;
+use crate::hashing::{MerkleHasher, Sha256};
+use crate::merkle_tree::{GenericMerkleTree, Hash, MerkleTree};
+
+/// The original Keccak-256 of Ethereum and Solana.
+#[cfg(any(feature = "ethereum", feature = "solana"))]
+#[derive(Debug, Clone, Copy)]
+pub struct Keccak256;
+
+#[cfg(any(feature = "ethereum", feature = "solana"))]
+impl MerkleHasher for Keccak256 {
+    const WIRE_ALGORITHM: Option<u8> = Some(2);
+
+    fn hash(bytes: &[u8]) -> Hash {
+        crate::keccak::keccak256(bytes)
+    }
+}
+
+/// Computes a root from leaves given one at a time, keeping one pending
+/// node per level.
+struct StreamingRoot<H> {
+    pending: Vec<Option<Hash>>,
+    leaf_count: usize,
+    hasher: PhantomData<H>,
+}
+
+impl<H: MerkleHasher> StreamingRoot<H> {
+    fn new() -> Self {
+        Self {
+            pending: Vec::new(),
+            leaf_count: 0,
+            hasher: PhantomData,
+        }
+    }
+
+    fn push(&mut self, leaf: Hash) {
+        self.leaf_count += 1;
+
+        let mut node = leaf;
+        for level in 0.. {
+            if level == self.pending.len() {
+                self.pending.push(None);
+            }
+            match self.pending[level].take() {
+                Some(left) => node = H::parent(&left, &node),
+                None => {
+                    self.pending[level] = Some(node);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Pairs the last node of every incomplete level with itself up to the root.
+    fn finish(mut self) -> Option<Hash> {
+        let mut carry: Option<Hash> = None;
+
+        for level in 0.. {
+            let pending = self.pending.get_mut(level).and_then(Option::take);
+            if MerkleTree::level_width(self.leaf_count, level) <= 1 {
+                return carry.or(pending);
+            }
+
+            carry = match (pending, carry) {
+                (Some(left), Some(right)) => Some(H::parent(&left, &right)),
+                (Some(last), None) | (None, Some(last)) => Some(H::parent(&last, &last)),
+                (None, None) => None,
+            };
+        }
+        unreachable!()
+    }
+}
+
+impl MerkleTree {
+    /// Rebuilds the tree under the hash function `H` from its items, for a
+    /// migration off SHA-256. Every item is first checked against its leaf,
+    /// and `progress` is called with the number of items rehashed so far.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::{MerkleHasher, MerkleTree, Hash};
+    ///
+    /// struct Reversed;
+    ///
+    /// impl MerkleHasher for Reversed {
+    ///     fn hash(bytes: &[u8]) -> Hash {
+    ///         let mut hash = MerkleTree::hash(bytes);
+    ///         hash.reverse();
+    ///         hash
+    ///     }
+    /// }
+    ///
+    /// let items = ["Ori", "Nori", "Dori"];
+    /// let tree = MerkleTree::build(&items).unwrap();
+    ///
+    /// let migrated = tree.rehash_as::<Reversed, _>(&items, |_| {}).unwrap();
+    ///
+    /// let proof = migrated.proof(1).unwrap();
+    /// let root = migrated.root().unwrap();
+    /// assert!(proof.verify_with::<Reversed>(&Reversed::hash(b"Nori"), &root).is_ok());
+    /// ```
+    pub fn rehash_as<H: MerkleHasher, T: AsRef<[u8]>>(
+        &self,
+        items: &[T],
+        mut progress: impl FnMut(usize),
+    ) -> Result<GenericMerkleTree<H>, Error> {
+        if items.len() != self.leaf_count() {
+            return Err(Error::InvalidInput(format!(
+                "the tree has {} leaves, not {}",
+                self.leaf_count(),
+                items.len()
+            )));
+        }
+
+        let mut leaves = Vec::with_capacity(items.len());
+        for (index, (item, leaf)) in items.iter().zip(self.leaves()).enumerate() {
+            if Self::hash(item.as_ref()) != *leaf {
+                return Err(Error::InvalidInput(format!(
+                    "the item {} does not match its leaf",
+                    index
+                )));
+            }
+            leaves.push(H::hash(item.as_ref()));
+            progress(index + 1);
+        }
+
+        GenericMerkleTree::from_leaves(leaves).ok_or(Error::EmptyTree)
+    }
+
+    /// Computes the root under `H` of the items of a tree too large to
+    /// hold in memory, read one at a time, in memory logarithmic in their
+    /// number. The SHA-256 root of the same items is computed along and
+    /// must be `old_root`.
+    pub fn rehash_stream<H: MerkleHasher, T: AsRef<[u8]>>(
+        items: impl IntoIterator<Item = T>,
+        old_root: &Hash,
+        mut progress: impl FnMut(usize),
+    ) -> Result<Hash, Error> {
+        let mut old = StreamingRoot::<Sha256>::new();
+        let mut new = StreamingRoot::<H>::new();
+
+        for item in items {
+            old.push(Self::hash(item.as_ref()));
+            new.push(H::hash(item.as_ref()));
+            progress(new.leaf_count);
+        }
+
+        if old.finish().ok_or(Error::EmptyTree)? != *old_root {
+            return Err(Error::RootMismatch);
+        }
+        Ok(new.finish().expect("The items are not empty."))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    struct Salted;
+
+    impl MerkleHasher for Salted {
+        fn hash(bytes: &[u8]) -> Hash {
+            MerkleTree::hash([b"salt:".as_slice(), bytes].concat().as_slice())
+        }
+    }
+
+    fn items(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|item| format!("dwarf ring {}", item))
+            .collect()
+    }
+
+    #[test]
+    fn test_rehashing_with_sha256_is_the_identity() {
+        for count in 1..=20 {
+            let tree = MerkleTree::build(&items(count)).unwrap();
+            let rehashed = tree.rehash_as::<Sha256, _>(&items(count), |_| {}).unwrap();
+
+            assert_eq!(rehashed.root(), tree.root());
+            for index in 0..count {
+                assert_eq!(rehashed.proof(index), tree.proof(index));
+            }
+            let streamed =
+                MerkleTree::rehash_stream::<Sha256, _>(items(count), &tree.root().unwrap(), |_| {});
+            assert_eq!(streamed, Ok(tree.root().unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_migrated_trees_serve_proofs_under_the_new_hash() {
+        for count in [1, 2, 7, 16, 21] {
+            let tree = MerkleTree::build(&items(count)).unwrap();
+            let mut reported = Vec::new();
+            let migrated = tree
+                .rehash_as::<Salted, _>(&items(count), |done| reported.push(done))
+                .unwrap();
+
+            let root = migrated.root().unwrap();
+            assert_ne!(migrated.root(), tree.root());
+            assert_eq!(reported, (1..=count).collect::<Vec<_>>());
+            for (index, item) in items(count).iter().enumerate() {
+                let proof = migrated.proof(index).unwrap();
+                assert_eq!(
+                    proof.verify_with::<Salted>(&Salted::hash(item.as_bytes()), &root),
+                    Ok(())
+                );
+            }
+            assert_eq!(
+                MerkleTree::rehash_stream::<Salted, _>(items(count), &tree.root().unwrap(), |_| {}),
+                Ok(root)
+            );
+        }
+    }
+
+    #[test]
+    fn test_items_must_match_the_tree() {
+        let tree = MerkleTree::build(&items(5)).unwrap();
+        let mut other = items(5);
+        other[3] = "the One Ring".to_string();
+
+        assert!(tree.rehash_as::<Salted, _>(&other, |_| {}).is_err());
+        assert!(tree.rehash_as::<Salted, _>(&items(4), |_| {}).is_err());
+        assert_eq!(
+            MerkleTree::rehash_stream::<Salted, _>(other, &tree.root().unwrap(), |_| {}),
+            Err(Error::RootMismatch)
+        );
+        assert_eq!(
+            MerkleTree::rehash_stream::<Salted, String>(vec![], &tree.root().unwrap(), |_| {}),
+            Err(Error::EmptyTree)
+        );
+    }
+}
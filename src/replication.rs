@@ -0,0 +1,296 @@
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::oplog::{apply_operation, LogEntry, LoggedTree, Operation};
+use crate::sync::Transport;
+
+/// The answer of a follower to an entry or a repair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confirmation {
+    /// The follower reached the root the leader expected.
+    Applied { root: Hash },
+    /// The follower reached another root, `None` if it has no tree.
+    Diverged { root: Option<Hash> },
+}
+
+/// A connection from the leader to a follower, used by `LoggedTree::replicate`.
+///
+/// Implementations send the messages over whatever channel connects them,
+/// and `Follower` answers them on the other side. The `Transport` requests
+/// locate the leaves to repair after a divergence.
+pub trait ReplicaChannel: Transport {
+    /// Returns the number of log entries the follower applied.
+    fn applied(&mut self) -> Result<usize, Error>;
+
+    /// Sends the entry at `sequence` of the log.
+    fn send(&mut self, sequence: usize, entry: &LogEntry) -> Result<Confirmation, Error>;
+
+    /// Sends the operations that bring the follower to `root`, the state
+    /// of the leader after `applied` log entries.
+    fn send_repair(
+        &mut self,
+        applied: usize,
+        operations: &[Operation],
+        root: &Hash,
+    ) -> Result<Confirmation, Error>;
+}
+
+/// A follower in the same process can answer the messages directly.
+impl Transport for &mut Follower {
+    fn leaf_count(&mut self) -> Result<usize, Error> {
+        Ok(self.tree.as_ref().map_or(0, MerkleTree::leaf_count))
+    }
+
+    fn node_hashes(&mut self, level: usize, indices: &[usize]) -> Result<Vec<Option<Hash>>, Error> {
+        Ok(match &self.tree {
+            Some(tree) => tree.node_hashes(level, indices),
+            None => vec![None; indices.len()],
+        })
+    }
+}
+
+impl ReplicaChannel for &mut Follower {
+    fn applied(&mut self) -> Result<usize, Error> {
+        Ok(self.applied)
+    }
+
+    fn send(&mut self, sequence: usize, entry: &LogEntry) -> Result<Confirmation, Error> {
+        self.receive(sequence, entry)
+    }
+
+    fn send_repair(
+        &mut self,
+        applied: usize,
+        operations: &[Operation],
+        root: &Hash,
+    ) -> Result<Confirmation, Error> {
+        Ok(self.repair(applied, operations, root))
+    }
+}
+
+/// What a call to `LoggedTree::replicate` did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplicationReport {
+    /// The number of log entries sent.
+    pub sent: usize,
+    /// The leaves repaired after a divergence.
+    pub repaired: Vec<usize>,
+}
+
+/// The replica of a `LoggedTree`, applying the entries of its log.
+#[derive(Debug, Default)]
+pub struct Follower {
+    tree: Option<MerkleTree>,
+    applied: usize,
+}
+
+impl Follower {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The root, `None` before the first append.
+    pub fn root(&self) -> Option<Hash> {
+        self.tree.as_ref().and_then(MerkleTree::root)
+    }
+
+    pub fn tree(&self) -> Option<&MerkleTree> {
+        self.tree.as_ref()
+    }
+
+    /// The number of log entries applied.
+    pub fn applied(&self) -> usize {
+        self.applied
+    }
+
+    /// Applies the entry at `sequence` of the leader's log and compares the
+    /// root with the one the leader reached. Fails if entries are skipped.
+    pub fn receive(&mut self, sequence: usize, entry: &LogEntry) -> Result<Confirmation, Error> {
+        if sequence != self.applied {
+            return Err(Error::InvalidInput(format!(
+                "expected log entry {}, received {}",
+                self.applied, sequence
+            )));
+        }
+
+        let result = apply_operation(&mut self.tree, &entry.operation);
+        self.applied += 1;
+
+        Ok(self.confirm(result.ok(), &entry.root))
+    }
+
+    /// Applies the operations of a repair sent after a divergence.
+    pub fn repair(
+        &mut self,
+        applied: usize,
+        operations: &[Operation],
+        root: &Hash,
+    ) -> Confirmation {
+        let result = operations.iter().try_fold(self.root(), |_, operation| {
+            apply_operation(&mut self.tree, operation).map(Some)
+        });
+        self.applied = applied;
+
+        self.confirm(result.ok().flatten(), root)
+    }
+
+    fn confirm(&self, reached: Option<Hash>, expected: &Hash) -> Confirmation {
+        match reached {
+            Some(root) if root == *expected => Confirmation::Applied { root },
+            _ => Confirmation::Diverged { root: self.root() },
+        }
+    }
+}
+
+impl LoggedTree {
+    /// Sends the follower the log entries it has not applied yet. If the
+    /// follower diverges, the differing leaves are located by comparing the
+    /// trees and repaired, instead of replaying the whole log.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::{Follower, LoggedTree};
+    ///
+    /// let mut leader = LoggedTree::new();
+    /// let mut follower = Follower::new();
+    ///
+    /// leader.append(&"Elendil").unwrap();
+    /// leader.append(&"Isildur").unwrap();
+    /// leader.replicate(&mut &mut follower).unwrap();
+    ///
+    /// leader.append(&"Anárion").unwrap();
+    /// let report = leader.replicate(&mut &mut follower).unwrap();
+    ///
+    /// assert_eq!(report.sent, 1);
+    /// assert_eq!(follower.root(), leader.root());
+    /// ```
+    pub fn replicate<C: ReplicaChannel>(
+        &self,
+        channel: &mut C,
+    ) -> Result<ReplicationReport, Error> {
+        let entries = self.log().entries();
+        let start = channel.applied()?;
+        if start > entries.len() {
+            return Err(Error::InvalidInput(format!(
+                "the follower applied {} entries, the log has {}",
+                start,
+                entries.len()
+            )));
+        }
+
+        let mut report = ReplicationReport::default();
+        for (sequence, entry) in entries.iter().enumerate().skip(start) {
+            report.sent += 1;
+            if let Confirmation::Diverged { .. } = channel.send(sequence, entry)? {
+                report.repaired = self.repair(channel)?;
+                break;
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Brings a diverged follower to the current root and returns the repaired leaves.
+    fn repair<C: ReplicaChannel>(&self, channel: &mut C) -> Result<Vec<usize>, Error> {
+        let tree = self.tree().ok_or(Error::EmptyTree)?;
+        let differing = tree.reconcile(channel)?;
+
+        let ours = tree.leaf_count();
+        let theirs = channel.leaf_count()?;
+        let leaf = |index: usize| tree.node(0, index).expect("The index is in the tree.");
+
+        let mut operations: Vec<Operation> = differing
+            .iter()
+            .filter(|index| **index < ours.min(theirs))
+            .map(|index| Operation::Update {
+                index: *index,
+                leaf: leaf(*index),
+            })
+            .collect();
+        operations.extend((theirs..ours).map(|index| Operation::Append { leaf: leaf(index) }));
+        operations.extend((ours..theirs).map(|_| Operation::Remove { index: ours }));
+
+        let root = tree.root().expect("A tree has a root.");
+        match channel.send_repair(self.log().entries().len(), &operations, &root)? {
+            Confirmation::Applied { .. } => Ok(differing),
+            Confirmation::Diverged { .. } => Err(Error::RootMismatch),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn leader(count: usize) -> LoggedTree {
+        let mut leader = LoggedTree::new();
+        for block in 0..count {
+            leader.append(&format!("block {}", block)).unwrap();
+        }
+        leader
+    }
+
+    #[test]
+    fn test_followers_catch_up_incrementally() {
+        let mut leader = leader(10);
+        let mut follower = Follower::new();
+
+        let report = leader.replicate(&mut &mut follower).unwrap();
+        assert_eq!(report.sent, 10);
+        assert_eq!(follower.root(), leader.root());
+
+        leader.update(3, &"block 3, amended").unwrap();
+        leader.remove(0).unwrap();
+        let report = leader.replicate(&mut &mut follower).unwrap();
+
+        assert_eq!(
+            report,
+            ReplicationReport {
+                sent: 2,
+                repaired: Vec::new()
+            }
+        );
+        assert_eq!(follower.root(), leader.root());
+        assert_eq!(follower.applied(), 12);
+    }
+
+    #[test]
+    fn test_divergence_is_repaired() {
+        let leader = leader(12);
+        let mut follower = Follower::new();
+
+        // The first entry was corrupted on its way to the follower.
+        let corrupted = LogEntry {
+            operation: Operation::Append {
+                leaf: MerkleTree::hash(b"bit rot"),
+            },
+            root: leader.log().entries()[0].root,
+        };
+        assert_eq!(
+            follower.receive(0, &corrupted),
+            Ok(Confirmation::Diverged {
+                root: Some(MerkleTree::hash(b"bit rot"))
+            })
+        );
+
+        let report = leader.replicate(&mut &mut follower).unwrap();
+
+        assert_eq!(report.sent, 1);
+        // The corrupted leaf, and the leaves the follower did not receive yet.
+        let mut repaired = vec![0];
+        repaired.extend(2..12);
+        assert_eq!(report.repaired, repaired);
+        assert_eq!(follower.root(), leader.root());
+        assert_eq!(follower.applied(), 12);
+        assert_eq!(leader.replicate(&mut &mut follower).unwrap().sent, 0);
+    }
+
+    #[test]
+    fn test_out_of_order_entries_are_rejected() {
+        let leader = leader(3);
+        let mut follower = Follower::new();
+
+        assert!(follower.receive(1, &leader.log().entries()[1]).is_err());
+        assert_eq!(follower.applied(), 0);
+    }
+}
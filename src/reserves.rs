@@ -0,0 +1,331 @@
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+
+/// A node of the Merkle sum tree: a hash and the sum of the balances below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SumNode {
+    pub hash: Hash,
+    pub sum: u64,
+}
+
+impl SumNode {
+    /// Pads odd levels. Duplicating the last node, as `MerkleTree` does,
+    /// would count its balance twice.
+    fn empty() -> Self {
+        Self {
+            hash: MerkleTree::hash(b"reserves:empty"),
+            sum: 0,
+        }
+    }
+
+    fn leaf(account_hash: &Hash, balance: u64) -> Self {
+        let mut bytes = account_hash.to_vec();
+        bytes.extend_from_slice(&balance.to_be_bytes());
+
+        Self {
+            hash: MerkleTree::hash(&bytes),
+            sum: balance,
+        }
+    }
+
+    /// The parent commits to both children hashes and sums, in order.
+    fn parent(left: &SumNode, right: &SumNode) -> Option<Self> {
+        let mut bytes = Vec::with_capacity(80);
+        bytes.extend_from_slice(&left.hash);
+        bytes.extend_from_slice(&left.sum.to_be_bytes());
+        bytes.extend_from_slice(&right.hash);
+        bytes.extend_from_slice(&right.sum.to_be_bytes());
+
+        Some(Self {
+            hash: MerkleTree::hash(&bytes),
+            sum: left.sum.checked_add(right.sum)?,
+        })
+    }
+}
+
+/// A customer account and its balance, the liability of the exchange.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Liability {
+    pub customer: String,
+    pub balance: u64,
+}
+
+/// What the exchange publishes: the root of the sum tree and the total liabilities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReservesCommitment {
+    pub root: Hash,
+    pub total_liabilities: u64,
+    pub accounts: usize,
+}
+
+impl ReservesCommitment {
+    /// The exchange is solvent if its proven reserves cover the committed liabilities.
+    pub fn is_covered_by(&self, reserves: u64) -> bool {
+        reserves >= self.total_liabilities
+    }
+}
+
+/// The package handed privately to a customer so it can check its balance is included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiabilityProof {
+    pub customer: String,
+    pub balance: u64,
+    /// Blinds the customer identifier inside the tree.
+    pub nonce: Hash,
+    pub index: usize,
+    pub siblings: Vec<SumNode>,
+}
+
+impl LiabilityProof {
+    /// Recomputes the root from the customer's own data and checks it against the commitment.
+    /// Every sibling sum is added on the way up, so a negative or hidden balance
+    /// would make the recomputed total differ from the published one.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::{Liability, ReservesTree};
+    ///
+    /// let liabilities = vec![
+    ///     Liability { customer: "frodo@shire.me".to_string(), balance: 120 },
+    ///     Liability { customer: "sam@shire.me".to_string(), balance: 80 },
+    /// ];
+    ///
+    /// let reserves = ReservesTree::build(&liabilities, b"exchange secret").unwrap();
+    /// let commitment = reserves.commitment();
+    ///
+    /// let proof = reserves.proof("sam@shire.me").unwrap();
+    /// assert!(proof.verify(&commitment));
+    /// assert!(commitment.is_covered_by(200));
+    /// ```
+    pub fn verify(&self, commitment: &ReservesCommitment) -> bool {
+        if self.index >= commitment.accounts {
+            return false;
+        }
+
+        let account_hash = ReservesTree::account_hash(&self.customer, &self.nonce);
+        let mut current = SumNode::leaf(&account_hash, self.balance);
+        let mut index = self.index;
+
+        for sibling in &self.siblings {
+            let parent = if index.is_multiple_of(2) {
+                SumNode::parent(&current, sibling)
+            } else {
+                SumNode::parent(sibling, &current)
+            };
+
+            current = match parent {
+                Some(parent) => parent,
+                None => return false,
+            };
+            index /= 2;
+        }
+
+        current.hash == commitment.root && current.sum == commitment.total_liabilities
+    }
+}
+
+/// A Merkle sum tree over the liabilities of an exchange, for proof of reserves.
+///
+/// Customer identifiers are blinded with a per-account nonce derived from a
+/// secret key, so the published tree reveals neither who the customers are
+/// nor their individual balances.
+pub struct ReservesTree {
+    liabilities: Vec<Liability>,
+    nonces: Vec<Hash>,
+    levels: Vec<Vec<SumNode>>,
+}
+
+impl ReservesTree {
+    /// Builds the sum tree. Fails if there are no liabilities or their total overflows.
+    pub fn build(liabilities: &[Liability], blinding_key: &[u8]) -> Result<Self, Error> {
+        if liabilities.is_empty() {
+            return Err(Error::EmptyTree);
+        }
+
+        let nonces: Vec<Hash> = liabilities
+            .iter()
+            .map(|liability| Self::nonce(blinding_key, &liability.customer))
+            .collect();
+
+        let leaves: Vec<SumNode> = liabilities
+            .iter()
+            .zip(&nonces)
+            .map(|(liability, nonce)| {
+                SumNode::leaf(
+                    &Self::account_hash(&liability.customer, nonce),
+                    liability.balance,
+                )
+            })
+            .collect();
+
+        let mut levels = vec![leaves];
+
+        while levels.last().unwrap().len() > 1 {
+            let mut level = levels.last().unwrap().clone();
+            if level.len() % 2 == 1 {
+                level.push(SumNode::empty());
+            }
+
+            let parent_level = level
+                .chunks_exact(2)
+                .map(|pair| SumNode::parent(&pair[0], &pair[1]))
+                .collect::<Option<Vec<SumNode>>>()
+                .ok_or_else(|| Error::InvalidInput("total liabilities overflow".to_string()))?;
+
+            levels.push(parent_level);
+        }
+
+        Ok(Self {
+            liabilities: liabilities.to_vec(),
+            nonces,
+            levels,
+        })
+    }
+
+    fn nonce(blinding_key: &[u8], customer: &str) -> Hash {
+        let mut bytes = b"reserves:nonce".to_vec();
+        bytes.extend_from_slice(&MerkleTree::hash(blinding_key));
+        bytes.extend_from_slice(customer.as_bytes());
+        MerkleTree::hash(&bytes)
+    }
+
+    fn account_hash(customer: &str, nonce: &Hash) -> Hash {
+        let mut bytes = nonce.to_vec();
+        bytes.extend_from_slice(customer.as_bytes());
+        MerkleTree::hash(&bytes)
+    }
+
+    pub fn commitment(&self) -> ReservesCommitment {
+        let root = self.levels.last().unwrap()[0];
+
+        ReservesCommitment {
+            root: root.hash,
+            total_liabilities: root.sum,
+            accounts: self.liabilities.len(),
+        }
+    }
+
+    /// Returns the proof package of the given customer.
+    pub fn proof(&self, customer: &str) -> Option<LiabilityProof> {
+        let index = self
+            .liabilities
+            .iter()
+            .position(|liability| liability.customer == customer)?;
+
+        let mut siblings = Vec::with_capacity(self.levels.len() - 1);
+        let mut position = index;
+
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = level
+                .get(position ^ 1)
+                .copied()
+                .unwrap_or_else(SumNode::empty);
+            siblings.push(sibling);
+            position /= 2;
+        }
+
+        Some(LiabilityProof {
+            customer: customer.to_string(),
+            balance: self.liabilities[index].balance,
+            nonce: self.nonces[index],
+            index,
+            siblings,
+        })
+    }
+
+    /// Returns the proof package of every customer, to be distributed privately.
+    pub fn proofs(&self) -> Vec<LiabilityProof> {
+        self.liabilities
+            .iter()
+            .filter_map(|liability| self.proof(&liability.customer))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn liabilities() -> Vec<Liability> {
+        vec![
+            Liability {
+                customer: "bilbo@shire.me".to_string(),
+                balance: 111,
+            },
+            Liability {
+                customer: "frodo@shire.me".to_string(),
+                balance: 33,
+            },
+            Liability {
+                customer: "sam@shire.me".to_string(),
+                balance: 38,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_commitment_sums_all_balances() {
+        let reserves = ReservesTree::build(&liabilities(), b"secret").unwrap();
+
+        let commitment = reserves.commitment();
+
+        assert_eq!(commitment.total_liabilities, 182);
+        assert_eq!(commitment.accounts, 3);
+        assert!(commitment.is_covered_by(182));
+        assert!(!commitment.is_covered_by(181));
+    }
+
+    #[test]
+    fn test_every_proof_verifies() {
+        let reserves = ReservesTree::build(&liabilities(), b"secret").unwrap();
+        let commitment = reserves.commitment();
+
+        let proofs = reserves.proofs();
+
+        assert_eq!(proofs.len(), 3);
+        assert!(proofs.iter().all(|proof| proof.verify(&commitment)));
+    }
+
+    #[test]
+    fn test_tampered_proof_fails() {
+        let reserves = ReservesTree::build(&liabilities(), b"secret").unwrap();
+        let commitment = reserves.commitment();
+
+        let mut understated = reserves.proof("frodo@shire.me").unwrap();
+        understated.balance = 3;
+        assert!(!understated.verify(&commitment));
+
+        let mut hidden = reserves.proof("frodo@shire.me").unwrap();
+        hidden.siblings[0].sum = 0;
+        assert!(!hidden.verify(&commitment));
+    }
+
+    #[test]
+    fn test_blinding_key_changes_the_root() {
+        let first = ReservesTree::build(&liabilities(), b"secret").unwrap();
+        let second = ReservesTree::build(&liabilities(), b"another secret").unwrap();
+
+        assert_ne!(first.commitment().root, second.commitment().root);
+        assert_eq!(
+            first.commitment().total_liabilities,
+            second.commitment().total_liabilities
+        );
+    }
+
+    #[test]
+    fn test_overflowing_liabilities_are_rejected() {
+        let liabilities = vec![
+            Liability {
+                customer: "smaug@erebor.me".to_string(),
+                balance: u64::MAX,
+            },
+            Liability {
+                customer: "thorin@erebor.me".to_string(),
+                balance: 1,
+            },
+        ];
+
+        assert!(ReservesTree::build(&liabilities, b"secret").is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::VecDeque;
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+
+/// The commitment to a closed window: the root of the tree of its items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowRoot {
+    /// The window number, the timestamps of its items divided by the window length.
+    pub window: u64,
+    pub root: Hash,
+    pub count: usize,
+}
+
+impl WindowRoot {
+    /// The leaf of the window in the tree of roots, binding the window number and count to its root.
+    pub fn leaf_hash(&self) -> Hash {
+        let mut bytes = self.window.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&(self.count as u64).to_be_bytes());
+        bytes.extend_from_slice(&self.root);
+        MerkleTree::hash(&bytes)
+    }
+}
+
+/// Commits to a stream of items window by window, with a tree over the window roots.
+///
+/// Items are assigned to a window by their timestamp. When an item of a
+/// later window arrives, the open window is closed and its root emitted, and
+/// its items are dropped: only the roots of the last `retention` windows are
+/// kept, so memory does not grow with the stream.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::RollingTree;
+///
+/// // One minute windows over timestamps in seconds.
+/// let mut rolling = RollingTree::new(60).unwrap();
+///
+/// assert!(rolling.push(0, b"beacon lit at Amon Din").unwrap().is_none());
+/// assert!(rolling.push(42, b"beacon lit at Eilenach").unwrap().is_none());
+///
+/// let closed = rolling.push(75, b"beacon lit at Nardol").unwrap().unwrap();
+/// assert_eq!((closed.window, closed.count), (0, 2));
+/// ```
+pub struct RollingTree {
+    window_length: u64,
+    retention: usize,
+    open: Option<(u64, Vec<Hash>)>,
+    closed: VecDeque<WindowRoot>,
+}
+
+impl RollingTree {
+    /// Creates a rolling tree with windows of `window_length` timestamp units,
+    /// keeping the roots of every closed window.
+    pub fn new(window_length: u64) -> Result<Self, Error> {
+        if window_length == 0 {
+            return Err(Error::InvalidInput(
+                "the windows can't be empty".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            window_length,
+            retention: usize::MAX,
+            open: None,
+            closed: VecDeque::new(),
+        })
+    }
+
+    /// Keeps only the roots of the last `windows` closed windows.
+    pub fn retention(mut self, windows: usize) -> Self {
+        self.retention = windows.max(1);
+        self
+    }
+
+    /// The window of the given timestamp.
+    pub fn window_of(&self, timestamp: u64) -> u64 {
+        timestamp / self.window_length
+    }
+
+    /// Adds an item and returns the root of the window it closed, if any.
+    /// Items of a window that was already closed are rejected.
+    pub fn push(&mut self, timestamp: u64, item: &[u8]) -> Result<Option<WindowRoot>, Error> {
+        let window = self.window_of(timestamp);
+
+        let already_closed = match (&self.open, self.closed.back()) {
+            (Some((open, _)), _) => window < *open,
+            (None, Some(last)) => window <= last.window,
+            (None, None) => false,
+        };
+        if already_closed {
+            return Err(Error::InvalidInput(format!(
+                "the window {} is already closed",
+                window
+            )));
+        }
+
+        let closed = self.advance_to(timestamp);
+
+        self.open
+            .get_or_insert_with(|| (window, Vec::new()))
+            .1
+            .push(MerkleTree::hash(item));
+
+        Ok(closed)
+    }
+
+    /// Closes the open window if `timestamp` is past it, returning its root.
+    /// Lets windows close on time even when no new item arrives.
+    pub fn advance_to(&mut self, timestamp: u64) -> Option<WindowRoot> {
+        let window = self.window_of(timestamp);
+
+        match &self.open {
+            Some((open, _)) if *open < window => self.close(),
+            _ => None,
+        }
+    }
+
+    /// Closes the open window, returning its root.
+    pub fn close(&mut self) -> Option<WindowRoot> {
+        let (window, leaves) = self.open.take()?;
+        let count = leaves.len();
+
+        let root = MerkleTree::from_leaves(leaves)
+            .and_then(|tree| tree.root())
+            .expect("An open window has at least one item.");
+
+        let window_root = WindowRoot {
+            window,
+            root,
+            count,
+        };
+
+        self.closed.push_back(window_root);
+        while self.closed.len() > self.retention {
+            self.closed.pop_front();
+        }
+
+        Some(window_root)
+    }
+
+    /// The window that is still receiving items and its number of items.
+    pub fn open_window(&self) -> Option<(u64, usize)> {
+        self.open
+            .as_ref()
+            .map(|(window, leaves)| (*window, leaves.len()))
+    }
+
+    /// The roots of the retained closed windows, the oldest first.
+    pub fn window_roots(&self) -> impl Iterator<Item = &WindowRoot> {
+        self.closed.iter()
+    }
+
+    /// The tree over the roots of the retained closed windows.
+    pub fn roots_tree(&self) -> Option<MerkleTree> {
+        MerkleTree::from_leaves(self.closed.iter().map(WindowRoot::leaf_hash).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_windows_close_when_later_items_arrive() {
+        let mut rolling = RollingTree::new(10).unwrap();
+
+        let mut closed = Vec::new();
+        for timestamp in [1, 3, 9, 12, 35, 36] {
+            let item = format!("event at {}", timestamp);
+            closed.extend(rolling.push(timestamp, item.as_bytes()).unwrap());
+        }
+
+        assert_eq!(
+            closed
+                .iter()
+                .map(|root| (root.window, root.count))
+                .collect::<Vec<_>>(),
+            vec![(0, 3), (1, 1)]
+        );
+        assert_eq!(
+            closed[0].root,
+            MerkleTree::build(&["event at 1", "event at 3", "event at 9"])
+                .unwrap()
+                .root()
+                .unwrap()
+        );
+        assert_eq!(rolling.open_window(), Some((3, 2)));
+    }
+
+    #[test]
+    fn test_advance_closes_idle_windows() {
+        let mut rolling = RollingTree::new(60).unwrap();
+        rolling.push(5, b"Edoras").unwrap();
+
+        assert!(rolling.advance_to(59).is_none());
+        assert_eq!(rolling.advance_to(60).map(|root| root.window), Some(0));
+        assert!(rolling.open_window().is_none());
+        assert!(rolling.push(30, b"Late to Edoras").is_err());
+        assert!(RollingTree::new(0).is_err());
+    }
+
+    #[test]
+    fn test_retention_bounds_the_roots_tree() {
+        let mut rolling = RollingTree::new(1).unwrap().retention(3);
+        for timestamp in 0..10 {
+            rolling.push(timestamp, b"tick").unwrap();
+        }
+        rolling.close();
+
+        let windows: Vec<u64> = rolling.window_roots().map(|root| root.window).collect();
+        assert_eq!(windows, vec![7, 8, 9]);
+
+        let roots_tree = rolling.roots_tree().unwrap();
+        assert_eq!(roots_tree.leaf_count(), 3);
+        let leaf = rolling.window_roots().next().unwrap().leaf_hash();
+        assert!(roots_tree.contains_hash(&leaf));
+    }
+}
@@ -0,0 +1,296 @@
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// A share drawn by a sampling client, its data and its proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sample {
+    pub share: Vec<u8>,
+    pub proof: Proof,
+}
+
+/// The proofs of leaves drawn at random from a seed, the answer to a data
+/// availability sampling request: a light client checks a few random
+/// leaves instead of downloading all of them.
+///
+/// The client picks the seed, the number of samples and the leaf count it
+/// expects, and checks the bundle against them, so the prover can't choose
+/// which positions are drawn.
+///
+/// Each sample carries the data of its share, not only its leaf, so a
+/// prover withholding the data can't answer. The tree is built over
+/// `leaf_hash`, which commits to the position of each share as well, since
+/// sorted pairs don't authenticate positions.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{MerkleTree, SamplingBundle};
+///
+/// let shares: Vec<String> = (0..64).map(|share| format!("share {}", share)).collect();
+/// let tree = SamplingBundle::share_tree(&shares).unwrap();
+///
+/// let seed = MerkleTree::hash(b"chosen by the light client");
+/// let bundle = tree.sample(&shares, &seed, 8).unwrap();
+///
+/// assert_eq!(bundle.samples.len(), 8);
+/// assert!(bundle.verify(&tree.root().unwrap(), &seed, 8, 64).is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SamplingBundle {
+    pub seed: Hash,
+    pub leaf_count: usize,
+    pub samples: Vec<Sample>,
+}
+
+impl SamplingBundle {
+    /// The leaf of the share at `index`: the hash of the index, as 8 bytes
+    /// big-endian, followed by the share.
+    pub fn leaf_hash(index: usize, share: &[u8]) -> Hash {
+        MerkleTree::hash(&[&(index as u64).to_be_bytes(), share].concat())
+    }
+
+    /// Builds the tree over the leaves of `shares`, which clients sample.
+    pub fn share_tree<T: AsRef<[u8]>>(shares: &[T]) -> Result<MerkleTree, Error> {
+        let leaves = shares
+            .iter()
+            .enumerate()
+            .map(|(index, share)| Self::leaf_hash(index, share.as_ref()))
+            .collect();
+        MerkleTree::from_leaves(leaves).ok_or(Error::EmptyTree)
+    }
+
+    /// The distinct positions drawn from `seed` among `leaf_count` leaves,
+    /// `count` of them or every position if there are fewer.
+    pub fn positions(seed: &Hash, leaf_count: usize, count: usize) -> Vec<usize> {
+        let count = count.min(leaf_count);
+        let mut positions = Vec::with_capacity(count);
+
+        for counter in 0u64.. {
+            if positions.len() == count {
+                break;
+            }
+            let mut bytes = seed.to_vec();
+            bytes.extend_from_slice(&counter.to_be_bytes());
+            let draw = MerkleTree::hash(&bytes);
+            let position =
+                (u64::from_be_bytes(draw[..8].try_into().unwrap()) % leaf_count as u64) as usize;
+            if !positions.contains(&position) {
+                positions.push(position);
+            }
+        }
+
+        positions
+    }
+
+    /// Checks that the samples are the `count` shares drawn from the
+    /// client's `seed` among `leaf_count` leaves, and that each share is in
+    /// the tree of `root` at its drawn position. Every value comes from the
+    /// client, not the bundle.
+    pub fn verify(
+        &self,
+        root: &Hash,
+        seed: &Hash,
+        count: usize,
+        leaf_count: usize,
+    ) -> Result<(), Error> {
+        if self.seed != *seed || self.leaf_count != leaf_count {
+            return Err(Error::InvalidInput(
+                "the bundle answers another sampling request".to_string(),
+            ));
+        }
+
+        let positions = Self::positions(seed, leaf_count, count);
+        if positions.len() != self.samples.len() {
+            return Err(Error::InvalidInput(format!(
+                "expected {} samples, received {}",
+                positions.len(),
+                self.samples.len()
+            )));
+        }
+
+        for (position, sample) in positions.into_iter().zip(&self.samples) {
+            if sample.proof.leaf_index != position || sample.proof.leaf_count != self.leaf_count {
+                return Err(Error::InvalidInput(format!(
+                    "the sample of leaf {} is not the one drawn from the seed",
+                    sample.proof.leaf_index
+                )));
+            }
+            sample
+                .proof
+                .verify(&Self::leaf_hash(position, &sample.share), root)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl MerkleTree {
+    /// Draws `count` distinct shares from `seed` and proves them, in a tree
+    /// built with `SamplingBundle::share_tree` over `shares`.
+    pub fn sample<T: AsRef<[u8]>>(
+        &self,
+        shares: &[T],
+        seed: &Hash,
+        count: usize,
+    ) -> Result<SamplingBundle, Error> {
+        let samples = SamplingBundle::positions(seed, self.leaf_count(), count)
+            .into_iter()
+            .map(|position| {
+                let share = shares
+                    .get(position)
+                    .map(AsRef::as_ref)
+                    .filter(|share| {
+                        SamplingBundle::leaf_hash(position, share) == self.leaves()[position]
+                    })
+                    .ok_or_else(|| {
+                        Error::InvalidInput(format!("the share {} is not in the tree", position))
+                    })?;
+                Ok(Sample {
+                    share: share.to_vec(),
+                    proof: self.proof(position)?,
+                })
+            })
+            .collect::<Result<Vec<Sample>, Error>>()?;
+
+        Ok(SamplingBundle {
+            seed: *seed,
+            leaf_count: self.leaf_count(),
+            samples,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn shares(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|share| format!("page {} of the Red Book", share))
+            .collect()
+    }
+
+    fn tree(count: usize) -> MerkleTree {
+        SamplingBundle::share_tree(&shares(count)).unwrap()
+    }
+
+    #[test]
+    fn test_positions_are_distinct_and_reproducible() {
+        let seed = MerkleTree::hash(b"Westmarch");
+
+        let positions = SamplingBundle::positions(&seed, 100, 16);
+        let mut distinct = positions.clone();
+        distinct.sort();
+        distinct.dedup();
+
+        assert_eq!(distinct.len(), 16);
+        assert_eq!(SamplingBundle::positions(&seed, 100, 16), positions);
+        assert_ne!(
+            SamplingBundle::positions(&MerkleTree::hash(b"Undertowers"), 100, 16),
+            positions
+        );
+        assert_eq!(SamplingBundle::positions(&seed, 5, 16).len(), 5);
+    }
+
+    #[test]
+    fn test_bundles_are_verified_against_the_root() {
+        let tree = tree(37);
+        let seed = MerkleTree::hash(b"Westmarch");
+        let bundle = tree.sample(&shares(37), &seed, 10).unwrap();
+
+        let root = tree.root().unwrap();
+
+        assert_eq!(bundle.verify(&root, &seed, 10, 37), Ok(()));
+        assert!(bundle
+            .verify(&self::tree(38).root().unwrap(), &seed, 10, 37)
+            .is_err());
+        assert!(bundle.verify(&root, &seed, 10, 38).is_err());
+        assert!(bundle.verify(&root, &seed, 11, 37).is_err());
+        let all = tree.sample(&shares(37), &seed, 100).unwrap();
+        assert_eq!(all.samples.len(), 37);
+        assert_eq!(all.verify(&root, &seed, 100, 37), Ok(()));
+    }
+
+    #[test]
+    fn test_chosen_samples_are_rejected() {
+        let tree = tree(37);
+        let mut bundle = tree
+            .sample(&shares(37), &MerkleTree::hash(b"Westmarch"), 4)
+            .unwrap();
+
+        let position = (0..37)
+            .find(|position| {
+                bundle
+                    .samples
+                    .iter()
+                    .all(|s| s.proof.leaf_index != *position)
+            })
+            .unwrap();
+        bundle.samples[2] = Sample {
+            share: shares(37)[position].clone().into_bytes(),
+            proof: tree.proof(position).unwrap(),
+        };
+
+        assert!(bundle
+            .verify(
+                &tree.root().unwrap(),
+                &MerkleTree::hash(b"Westmarch"),
+                4,
+                37
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_ground_seeds_and_empty_bundles_are_rejected() {
+        let tree = tree(37);
+        let root = tree.root().unwrap();
+        let seed = MerkleTree::hash(b"Westmarch");
+
+        let ground = tree
+            .sample(&shares(37), &MerkleTree::hash(b"Undertowers"), 4)
+            .unwrap();
+        assert!(ground.verify(&root, &ground.seed, 4, 37).is_ok());
+        assert!(ground.verify(&root, &seed, 4, 37).is_err());
+
+        let mut empty = tree.sample(&shares(37), &seed, 4).unwrap();
+        empty.samples.clear();
+        assert!(empty.verify(&root, &seed, 4, 37).is_err());
+        assert!(tree
+            .sample(&shares(37), &seed, 0)
+            .unwrap()
+            .verify(&root, &seed, 4, 37)
+            .is_err());
+    }
+
+    #[test]
+    fn test_samples_must_carry_their_share() {
+        let tree = tree(37);
+        let root = tree.root().unwrap();
+        let seed = MerkleTree::hash(b"Westmarch");
+        let bundle = tree.sample(&shares(37), &seed, 4).unwrap();
+
+        // Withheld data, answered with the leaf in place of the share.
+        let mut withheld = bundle.clone();
+        let position = withheld.samples[0].proof.leaf_index;
+        withheld.samples[0].share = tree.leaves()[position].to_vec();
+        assert!(withheld.verify(&root, &seed, 4, 37).is_err());
+
+        // The share and proof of another position, claimed at the drawn one.
+        let mut moved = bundle;
+        let other = (position + 1) % 37;
+        moved.samples[0] = Sample {
+            share: shares(37)[other].clone().into_bytes(),
+            proof: Proof {
+                leaf_index: position,
+                ..tree.proof(other).unwrap()
+            },
+        };
+        assert!(moved.verify(&root, &seed, 4, 37).is_err());
+
+        let mut forged = shares(37);
+        forged[position] = "a page torn out".to_string();
+        assert!(tree.sample(&forged, &seed, 4).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use serde::Serialize;
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+
+/// Encodes a value with bincode using a fixed configuration:
+/// big-endian, fixed width integers and no size limit.
+/// The configuration is part of the commitment, so it must never change.
+pub fn encode_serde<T: Serialize>(item: &T) -> Result<Vec<u8>, Error> {
+    let config = bincode::config::standard()
+        .with_big_endian()
+        .with_fixed_int_encoding();
+
+    bincode::serde::encode_to_vec(item, config).map_err(|error| Error::Encoding(error.to_string()))
+}
+
+impl MerkleTree {
+    /// Create a new MerkleTree from serializable items.
+    /// Each item is encoded with a deterministic bincode configuration before hashing.
+    /// The creation will fail if the items list is empty or an item can't be encoded.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::MerkleTree;
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Ring {
+    ///     bearer: String,
+    ///     count: u8,
+    /// }
+    ///
+    /// let rings = vec![
+    ///     Ring { bearer: "Elves".to_string(), count: 3 },
+    ///     Ring { bearer: "Dwarf-lords".to_string(), count: 7 },
+    /// ];
+    ///
+    /// let merkle_tree = MerkleTree::build_serde(&rings).unwrap();
+    /// ```
+    pub fn build_serde<T: Serialize>(items: &[T]) -> Result<Self, Error> {
+        let leaves = items
+            .iter()
+            .map(|item| encode_serde(item).map(|bytes| Self::hash(&bytes)))
+            .collect::<Result<Vec<Hash>, Error>>()?;
+
+        Self::from_leaves(leaves).ok_or(Error::EmptyTree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Ring {
+        bearer: String,
+        count: u8,
+    }
+
+    #[test]
+    fn test_encoding_is_fixed_width_big_endian() {
+        let ring = Ring {
+            bearer: "Men".to_string(),
+            count: 9,
+        };
+
+        let bytes = encode_serde(&ring).unwrap();
+
+        assert_eq!(bytes, vec![0, 0, 0, 0, 0, 0, 0, 3, b'M', b'e', b'n', 9]);
+    }
+
+    #[test]
+    fn test_build_serde_hashes_encoded_items() {
+        let rings = vec![
+            Ring {
+                bearer: "Elves".to_string(),
+                count: 3,
+            },
+            Ring {
+                bearer: "Dwarf-lords".to_string(),
+                count: 7,
+            },
+        ];
+
+        let tree = MerkleTree::build_serde(&rings).unwrap();
+
+        let encoded: Vec<Vec<u8>> = rings
+            .iter()
+            .map(|ring| encode_serde(ring).unwrap())
+            .collect();
+        let expected = MerkleTree::build(&encoded).unwrap();
+
+        assert_eq!(tree.root(), expected.root());
+        assert!(tree.contains_hash(&MerkleTree::hash(&encoded[1])));
+    }
+
+    #[test]
+    fn test_build_serde_with_no_items_fails() {
+        let result = MerkleTree::build_serde::<Ring>(&[]);
+
+        assert_eq!(result.err(), Some(Error::EmptyTree));
+    }
+}
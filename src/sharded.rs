@@ -0,0 +1,270 @@
+use std::sync::{Mutex, MutexGuard};
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::nested::NestedProof;
+
+/// A tree whose leaves are partitioned into independent shards under a
+/// small top tree over the shard roots.
+///
+/// Every shard has its own lock, so threads inserting into different shards
+/// never wait for each other. The top tree is only built when the root or a
+/// proof is asked for. An empty shard has the root `ShardedTree::EMPTY_SHARD`.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{MerkleTree, ShardedTree};
+///
+/// let tree = ShardedTree::new(4).unwrap();
+///
+/// std::thread::scope(|scope| {
+///     for shire in ["Bywater", "Hobbiton", "Tuckborough", "Buckland"] {
+///         let tree = &tree;
+///         scope.spawn(move || tree.insert(&shire));
+///     }
+/// });
+///
+/// let (shard, index) = tree.position(&MerkleTree::hash(b"Hobbiton")).unwrap();
+/// let proof = tree.proof(shard, index).unwrap();
+///
+/// assert!(proof.verify(&MerkleTree::hash(b"Hobbiton"), &tree.root()).is_ok());
+/// ```
+#[derive(Debug)]
+pub struct ShardedTree {
+    shards: Vec<Mutex<Option<MerkleTree>>>,
+}
+
+impl ShardedTree {
+    /// The root of a shard without leaves.
+    pub const EMPTY_SHARD: Hash = [0; 32];
+
+    pub fn new(shard_count: usize) -> Result<Self, Error> {
+        if shard_count == 0 {
+            return Err(Error::InvalidInput(
+                "a sharded tree needs a shard".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            shards: (0..shard_count).map(|_| Mutex::new(None)).collect(),
+        })
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// The shard a leaf belongs to, derived from its first bytes.
+    pub fn shard_of(&self, leaf: &Hash) -> usize {
+        let prefix = u64::from_be_bytes(leaf[..8].try_into().unwrap());
+        (prefix % self.shards.len() as u64) as usize
+    }
+
+    fn lock(&self, shard: usize) -> Result<MutexGuard<'_, Option<MerkleTree>>, Error> {
+        let shard = self.shards.get(shard).ok_or_else(|| self.missing_shard())?;
+        Ok(shard.lock().expect("The shard lock is not poisoned."))
+    }
+
+    fn missing_shard(&self) -> Error {
+        Error::InvalidInput(format!("the tree has {} shards", self.shards.len()))
+    }
+
+    /// Inserts an item into its shard and returns the shard and its index there.
+    pub fn insert<T: AsRef<[u8]>>(&self, item: &T) -> Result<(usize, usize), Error> {
+        let shard = self.shard_of(&MerkleTree::hash(item.as_ref()));
+        let index = self.insert_into(shard, item)?;
+        Ok((shard, index))
+    }
+
+    /// Inserts an item into a chosen shard and returns its index there.
+    pub fn insert_into<T: AsRef<[u8]>>(&self, shard: usize, item: &T) -> Result<usize, Error> {
+        let mut tree = self.lock(shard)?;
+        match tree.as_mut() {
+            Some(tree) => tree.insert(item)?,
+            None => *tree = MerkleTree::build(&[item]),
+        }
+        Ok(tree.as_ref().unwrap().leaf_count() - 1)
+    }
+
+    /// The shard and index of a leaf, `None` if it is not in the tree.
+    pub fn position(&self, leaf: &Hash) -> Option<(usize, usize)> {
+        let shard = self.shard_of(leaf);
+        let tree = self.lock(shard).ok()?;
+        let index = tree.as_ref()?.leaves().iter().position(|l| l == leaf)?;
+        Some((shard, index))
+    }
+
+    /// The number of leaves in every shard.
+    pub fn leaf_count(&self) -> usize {
+        (0..self.shards.len())
+            .map(|shard| self.shard_len(shard))
+            .sum()
+    }
+
+    fn shard_len(&self, shard: usize) -> usize {
+        self.lock(shard)
+            .unwrap()
+            .as_ref()
+            .map_or(0, MerkleTree::leaf_count)
+    }
+
+    /// The root of a shard.
+    pub fn shard_root(&self, shard: usize) -> Result<Hash, Error> {
+        Ok(Self::root_of(&*self.lock(shard)?))
+    }
+
+    fn top_tree(&self) -> MerkleTree {
+        let roots = (0..self.shards.len())
+            .map(|shard| self.shard_root(shard).unwrap())
+            .collect();
+        MerkleTree::from_leaves(roots).expect("The tree has a shard.")
+    }
+
+    fn root_of(shard: &Option<MerkleTree>) -> Hash {
+        shard
+            .as_ref()
+            .map_or(Self::EMPTY_SHARD, |tree| tree.root().unwrap())
+    }
+
+    /// The root of the top tree over the shard roots.
+    pub fn root(&self) -> Hash {
+        self.top_tree().root().expect("A tree has a root.")
+    }
+
+    /// Proves the leaf at `index` of `shard` up to the shard root, then up
+    /// to the combined root.
+    ///
+    /// Every shard is locked, in order, while proving, so both proofs come
+    /// from the same state of the tree even with concurrent inserts.
+    pub fn proof(&self, shard: usize, index: usize) -> Result<NestedProof, Error> {
+        if shard >= self.shards.len() {
+            return Err(self.missing_shard());
+        }
+        let shards = (0..self.shards.len())
+            .map(|shard| self.lock(shard))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let proof = match shards[shard].as_ref() {
+            Some(tree) => tree.proof(index)?,
+            None => {
+                return Err(Error::IndexOutOfRange {
+                    index,
+                    leaf_count: 0,
+                })
+            }
+        };
+        let roots = shards.iter().map(|shard| Self::root_of(shard)).collect();
+        let top_tree = MerkleTree::from_leaves(roots).expect("The tree has a shard.");
+
+        Ok(NestedProof::new(proof).then(top_tree.proof(shard)?))
+    }
+
+    /// Takes the shards, `None` for the empty ones.
+    pub fn into_shards(self) -> Vec<Option<MerkleTree>> {
+        self.shards
+            .into_iter()
+            .map(|shard| shard.into_inner().expect("The shard lock is not poisoned."))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::thread;
+
+    use super::*;
+
+    fn item(writer: usize, entry: usize) -> String {
+        format!("rider {} of the éored {}", entry, writer)
+    }
+
+    #[test]
+    fn test_concurrent_inserts_are_all_proven() {
+        let tree = ShardedTree::new(5).unwrap();
+
+        thread::scope(|scope| {
+            for writer in 0..4 {
+                let tree = &tree;
+                scope.spawn(move || {
+                    for entry in 0..30 {
+                        tree.insert(&item(writer, entry)).unwrap();
+                    }
+                });
+            }
+        });
+
+        let root = tree.root();
+        assert_eq!(tree.leaf_count(), 120);
+        for writer in 0..4 {
+            for entry in 0..30 {
+                let leaf = MerkleTree::hash(item(writer, entry).as_bytes());
+                let (shard, index) = tree.position(&leaf).unwrap();
+                assert_eq!(shard, tree.shard_of(&leaf));
+                assert_eq!(
+                    tree.proof(shard, index).unwrap().verify(&leaf, &root),
+                    Ok(())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_proofs_are_taken_from_one_state() {
+        let tree = ShardedTree::new(3).unwrap();
+        tree.insert_into(0, &"Theodred").unwrap();
+        tree.insert_into(1, &"Elfhelm").unwrap();
+        tree.insert_into(2, &"Theoden").unwrap();
+        let leaf = MerkleTree::hash(b"Theoden");
+
+        let (roots, proven) = thread::scope(|scope| {
+            let writer = scope.spawn(|| {
+                let mut roots = vec![tree.root()];
+                for entry in 0..200 {
+                    tree.insert_into(2, &item(2, entry)).unwrap();
+                    roots.push(tree.root());
+                }
+                roots
+            });
+            let proven: Vec<Hash> = (0..200)
+                .map(|_| tree.proof(2, 0).unwrap().compute_root(&leaf).unwrap())
+                .collect();
+            (writer.join().unwrap(), proven)
+        });
+
+        // The last shard is its own sibling in the top tree, so a proof
+        // mixing two states would lead to a root the tree never had.
+        assert!(proven.iter().all(|root| roots.contains(root)));
+    }
+
+    #[test]
+    fn test_root_commits_to_every_shard() {
+        let tree = ShardedTree::new(3).unwrap();
+        tree.insert_into(0, &"Eomer").unwrap();
+        let root = tree.root();
+
+        assert_eq!(tree.shard_root(2), Ok(ShardedTree::EMPTY_SHARD));
+        assert!(tree.proof(2, 0).is_err());
+
+        tree.insert_into(2, &"Eowyn").unwrap();
+        assert_ne!(tree.root(), root);
+        assert!(tree
+            .proof(0, 0)
+            .unwrap()
+            .verify(&MerkleTree::hash(b"Eomer"), &root)
+            .is_err());
+
+        let shards = tree.into_shards();
+        assert!(shards[1].is_none());
+        assert_eq!(shards[2].as_ref().unwrap().leaf_count(), 1);
+    }
+
+    #[test]
+    fn test_shards_are_checked() {
+        assert!(ShardedTree::new(0).is_err());
+
+        let tree = ShardedTree::new(2).unwrap();
+        assert!(tree.insert_into(2, &"Gamling").is_err());
+        assert!(tree.shard_root(2).is_err());
+    }
+}
@@ -0,0 +1,12 @@
+/// Signs the messages the crate publishes, such as manifests and checkpoints.
+///
+/// The crate does not pick a signature scheme: implement this for the key
+/// type of the scheme in use, e.g. Ed25519 or an HSM client.
+pub trait Signer {
+    fn sign(&self, message: &[u8]) -> Vec<u8>;
+}
+
+/// Checks signatures made by a `Signer`.
+pub trait SignatureVerifier {
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool;
+}
@@ -0,0 +1,237 @@
+use std::io::{Read, Write};
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::store::NodeStore;
+
+const HEADER: &str = "merkle-snapshot 1";
+
+/// Writes every tree of `store` to `out`, so it can be imported into
+/// another backend or shipped to another machine with `import_snapshot`.
+///
+/// Only the leaves and the root of each tree are written, the other nodes
+/// are rebuilt on import. The snapshot is text lines ending with a footer
+/// holding the SHA-256 of everything before it:
+///
+/// ```text
+/// merkle-snapshot 1
+/// trees <m>
+/// tree <n> <hex root> <name>                   m times, followed by
+/// <hex leaf>                                   n lines
+/// footer <hex SHA-256 of the previous lines>
+/// ```
+///
+/// Trees without leaves are skipped. Fails if a stored root does not
+/// match the stored leaves, or if a name holds a line break.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{export_snapshot, import_snapshot, MemoryNodeStore, MerkleTree, NodeStore};
+///
+/// let tree = MerkleTree::build(&["Narya", "Nenya", "Vilya"]).unwrap();
+/// let mut source = MemoryNodeStore::new();
+/// tree.store(&mut source, "the three").unwrap();
+///
+/// let mut bytes = Vec::new();
+/// export_snapshot(&source, &mut bytes).unwrap();
+///
+/// let mut target = MemoryNodeStore::new();
+/// import_snapshot(bytes.as_slice(), &mut target).unwrap();
+///
+/// assert_eq!(target.trees().unwrap(), vec!["the three".to_string()]);
+/// assert_eq!(MerkleTree::stored_proof(&target, "the three", 2), tree.proof(2));
+/// ```
+pub fn export_snapshot<W: Write>(store: &dyn NodeStore, mut out: W) -> Result<(), Error> {
+    let mut trees = Vec::new();
+    for name in store.trees()? {
+        if name.contains('\n') {
+            return Err(Error::InvalidInput(format!(
+                "the tree name {:?} holds a line break",
+                name
+            )));
+        }
+        if store.leaf_count(&name)? == 0 {
+            continue;
+        }
+
+        let tree = MerkleTree::load(store, &name)?;
+        let root = MerkleTree::stored_root(store, &name)?;
+        if tree.root() != Some(root) {
+            return Err(Error::RootMismatch);
+        }
+        trees.push((name, root, tree));
+    }
+
+    let mut body = format!("{}\ntrees {}\n", HEADER, trees.len());
+    for (name, root, tree) in &trees {
+        body.push_str(&format!(
+            "tree {} {} {}\n",
+            tree.leaf_count(),
+            hex::encode(root),
+            name
+        ));
+        for leaf in tree.leaves() {
+            body.push_str(&format!("{}\n", hex::encode(leaf)));
+        }
+    }
+
+    let footer = format!(
+        "footer {}\n",
+        hex::encode(MerkleTree::hash(body.as_bytes()))
+    );
+    let io_error = |error: std::io::Error| Error::InvalidInput(error.to_string());
+    out.write_all(body.as_bytes()).map_err(io_error)?;
+    out.write_all(footer.as_bytes()).map_err(io_error)?;
+    out.flush().map_err(io_error)
+}
+
+/// Reads a snapshot written by `export_snapshot` into `store`, replacing
+/// the trees stored under the same names, and returns their names.
+///
+/// The footer and the root of every tree are checked before anything is
+/// written, so a corrupt snapshot leaves the store untouched.
+pub fn import_snapshot<R: Read>(
+    mut input: R,
+    store: &mut dyn NodeStore,
+) -> Result<Vec<String>, Error> {
+    let mut text = String::new();
+    input
+        .read_to_string(&mut text)
+        .map_err(|error| Error::InvalidInput(error.to_string()))?;
+    let invalid = |reason: &str| Error::InvalidInput(format!("invalid snapshot: {}", reason));
+
+    let footer_start = text
+        .trim_end_matches('\n')
+        .rfind('\n')
+        .map(|position| position + 1)
+        .ok_or_else(|| invalid("no footer"))?;
+    let (body, footer) = text.split_at(footer_start);
+    let digest = footer
+        .trim_end()
+        .strip_prefix("footer ")
+        .ok_or_else(|| invalid("no footer"))?;
+    if digest != hex::encode(MerkleTree::hash(body.as_bytes())) {
+        return Err(invalid("the footer does not match the content"));
+    }
+
+    let mut lines = body.lines();
+    if lines.next() != Some(HEADER) {
+        return Err(invalid("unknown header"));
+    }
+    let count: usize = lines
+        .next()
+        .and_then(|line| line.strip_prefix("trees "))
+        .and_then(|count| count.parse().ok())
+        .ok_or_else(|| invalid("trees"))?;
+
+    let mut trees = Vec::new();
+    for _ in 0..count {
+        let (leaf_count, root, name) = lines
+            .next()
+            .and_then(parse_tree_line)
+            .ok_or_else(|| invalid("tree"))?;
+        let leaves = (0..leaf_count)
+            .map(|_| lines.next().and_then(parse_hash))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| invalid("leaves"))?;
+
+        let tree = MerkleTree::from_leaves(leaves).ok_or_else(|| invalid("empty tree"))?;
+        if tree.root() != Some(root) {
+            return Err(Error::RootMismatch);
+        }
+        trees.push((name.to_string(), tree));
+    }
+    if lines.next().is_some() {
+        return Err(invalid("trailing lines"));
+    }
+
+    for (name, tree) in &trees {
+        tree.store(store, name)?;
+    }
+    Ok(trees.into_iter().map(|(name, _)| name).collect())
+}
+
+fn parse_tree_line(line: &str) -> Option<(usize, Hash, &str)> {
+    let mut fields = line.strip_prefix("tree ")?.splitn(3, ' ');
+    Some((
+        fields.next()?.parse().ok()?,
+        parse_hash(fields.next()?)?,
+        fields.next()?,
+    ))
+}
+
+fn parse_hash(text: &str) -> Option<Hash> {
+    hex::decode(text).ok()?.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::cache::CachedNodeStore;
+    use crate::store::{MemoryNodeStore, NodeId};
+
+    fn source() -> MemoryNodeStore {
+        let mut store = MemoryNodeStore::new();
+        for (name, count) in [("rivendell", 1), ("lothlórien", 9), ("grey havens", 40)] {
+            let items: Vec<String> = (0..count)
+                .map(|item| format!("{} {}", name, item))
+                .collect();
+            MerkleTree::build(&items)
+                .unwrap()
+                .store(&mut store, name)
+                .unwrap();
+        }
+        store
+    }
+
+    #[test]
+    fn test_snapshots_move_trees_between_stores() {
+        let source = source();
+        let mut bytes = Vec::new();
+        export_snapshot(&source, &mut bytes).unwrap();
+
+        let mut target = CachedNodeStore::new(MemoryNodeStore::new(), 16);
+        let names = import_snapshot(bytes.as_slice(), &mut target).unwrap();
+
+        assert_eq!(names, source.trees().unwrap());
+        for name in &names {
+            assert_eq!(
+                MerkleTree::stored_root(&target, name),
+                MerkleTree::stored_root(&source, name)
+            );
+            for index in 0..source.leaf_count(name).unwrap() {
+                assert_eq!(
+                    MerkleTree::stored_proof(&target, name, index),
+                    MerkleTree::stored_proof(&source, name, index)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_corrupt_snapshots_are_rejected() {
+        let mut bytes = Vec::new();
+        export_snapshot(&source(), &mut bytes).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+
+        let tampered = text.replacen("tree 9", "tree 8", 1);
+        let mut target = MemoryNodeStore::new();
+        assert!(import_snapshot(tampered.as_bytes(), &mut target).is_err());
+        assert!(import_snapshot(&b"merkle-snapshot 1\n"[..], &mut target).is_err());
+        assert!(target.trees().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_corrupt_stores_are_not_exported() {
+        let mut store = source();
+        store
+            .put("lothlórien", &[(NodeId::new(0, 3), [7; 32])])
+            .unwrap();
+
+        assert_eq!(
+            export_snapshot(&store, Vec::new()),
+            Err(Error::RootMismatch)
+        );
+    }
+}
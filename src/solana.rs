@@ -0,0 +1,613 @@
+use crate::error::Error;
+use crate::keccak::keccak256;
+use crate::merkle_tree::Hash;
+
+/// The value of the leaves that were never written.
+pub const EMPTY_NODE: Hash = [0; 32];
+
+/// The root of an empty subtree of the given height.
+pub fn empty_node(level: usize) -> Hash {
+    (0..level).fold(EMPTY_NODE, |node, _| parent(&node, &node))
+}
+
+fn parent(left: &Hash, right: &Hash) -> Hash {
+    let mut bytes = [0; 64];
+    bytes[..32].copy_from_slice(left);
+    bytes[32..].copy_from_slice(right);
+    keccak256(&bytes)
+}
+
+/// Hashes a node with its sibling, in the order given by the bit of the leaf index at that level.
+fn hash_to_parent(node: &Hash, sibling: &Hash, is_left: bool) -> Hash {
+    if is_left {
+        parent(node, sibling)
+    } else {
+        parent(sibling, node)
+    }
+}
+
+fn recompute<const MAX_DEPTH: usize>(leaf: &Hash, proof: &[Hash; MAX_DEPTH], index: u32) -> Hash {
+    proof
+        .iter()
+        .enumerate()
+        .fold(*leaf, |node, (level, sibling)| {
+            hash_to_parent(&node, sibling, (index >> level) & 1 == 0)
+        })
+}
+
+/// One modification of the tree: the new root and the new nodes on the path of the leaf.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChangeLog<const MAX_DEPTH: usize> {
+    pub root: Hash,
+    /// The nodes from the leaf, at `path[0]`, up to the child of the root.
+    pub path: [Hash; MAX_DEPTH],
+    pub index: u32,
+}
+
+impl<const MAX_DEPTH: usize> ChangeLog<MAX_DEPTH> {
+    fn replace_and_recompute_path(
+        &mut self,
+        index: u32,
+        leaf: Hash,
+        proof: &[Hash; MAX_DEPTH],
+    ) -> Hash {
+        self.index = index;
+
+        let mut node = leaf;
+        for (level, sibling) in proof.iter().enumerate() {
+            self.path[level] = node;
+            node = hash_to_parent(&node, sibling, (index >> level) & 1 == 0);
+        }
+
+        self.root = node;
+        node
+    }
+
+    /// Brings a proof computed before this change up to date: the change
+    /// rewrote the sibling where both paths meet, or the leaf itself.
+    fn update_proof_or_leaf(&self, index: u32, proof: &mut [Hash; MAX_DEPTH], leaf: &mut Hash) {
+        if index == self.index {
+            *leaf = self.path[0];
+            return;
+        }
+
+        let padding = 32 - MAX_DEPTH;
+        let critbit = ((index ^ self.index) << padding).leading_zeros() as usize;
+        let level = MAX_DEPTH - 1 - critbit;
+        proof[level] = self.path[level];
+    }
+}
+
+/// The proof of the rightmost leaf, kept to append without being given a proof.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RightmostPath<const MAX_DEPTH: usize> {
+    proof: [Hash; MAX_DEPTH],
+    leaf: Hash,
+    /// The number of leaves appended, the index of the next one.
+    index: u32,
+}
+
+/// A tree compatible with the concurrent merkle trees of SPL Account
+/// Compression, the scheme behind compressed NFTs.
+///
+/// The tree has a fixed depth and only stores its rightmost path and the
+/// last `MAX_BUFFER_SIZE` changes. Proofs computed against any root still in
+/// that changelog buffer are fast forwarded through the later changes, so
+/// several writers can modify the tree concurrently from the same snapshot.
+///
+/// The top `canopy_depth` levels can be cached, so proofs may leave out their
+/// last siblings, which makes them fit in a transaction.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{concurrent_tree_proof, keccak256, ConcurrentMerkleTree};
+///
+/// let leaves: Vec<_> = ["Narya", "Nenya", "Vilya"].iter().map(|ring| keccak256(ring.as_bytes())).collect();
+///
+/// let mut tree = ConcurrentMerkleTree::<14, 64>::new();
+/// for leaf in &leaves {
+///     tree.append(*leaf).unwrap();
+/// }
+///
+/// // Two writers replace leaves with proofs against the same root.
+/// let root = tree.root();
+/// let first_proof = concurrent_tree_proof(&leaves, 14, 0).unwrap();
+/// let second_proof = concurrent_tree_proof(&leaves, 14, 2).unwrap();
+///
+/// tree.set_leaf(&root, &leaves[0], keccak256(b"The One"), &first_proof, 0).unwrap();
+/// tree.set_leaf(&root, &leaves[2], keccak256(b"The Seven"), &second_proof, 2).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConcurrentMerkleTree<const MAX_DEPTH: usize, const MAX_BUFFER_SIZE: usize> {
+    sequence_number: u64,
+    active_index: usize,
+    buffer_size: usize,
+    change_logs: Vec<ChangeLog<MAX_DEPTH>>,
+    rightmost_proof: RightmostPath<MAX_DEPTH>,
+    canopy_depth: usize,
+    /// The cached nodes of the top levels, the deepest first.
+    canopy: Vec<Vec<Hash>>,
+}
+
+impl<const MAX_DEPTH: usize, const MAX_BUFFER_SIZE: usize> Default
+    for ConcurrentMerkleTree<MAX_DEPTH, MAX_BUFFER_SIZE>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const MAX_DEPTH: usize, const MAX_BUFFER_SIZE: usize>
+    ConcurrentMerkleTree<MAX_DEPTH, MAX_BUFFER_SIZE>
+{
+    /// Creates an empty tree without canopy.
+    pub fn new() -> Self {
+        const {
+            assert!(
+                MAX_DEPTH >= 1 && MAX_DEPTH <= 30,
+                "The depth must be between 1 and 30."
+            );
+            assert!(MAX_BUFFER_SIZE >= 1, "The changelog buffer can't be empty.");
+        }
+
+        let mut empty_path = [EMPTY_NODE; MAX_DEPTH];
+        for (level, node) in empty_path.iter_mut().enumerate() {
+            *node = empty_node(level);
+        }
+
+        let mut change_logs = vec![
+            ChangeLog {
+                root: EMPTY_NODE,
+                path: [EMPTY_NODE; MAX_DEPTH],
+                index: 0,
+            };
+            MAX_BUFFER_SIZE
+        ];
+        change_logs[0] = ChangeLog {
+            root: empty_node(MAX_DEPTH),
+            path: empty_path,
+            index: 0,
+        };
+
+        Self {
+            sequence_number: 0,
+            active_index: 0,
+            buffer_size: 1,
+            change_logs,
+            rightmost_proof: RightmostPath {
+                proof: empty_path,
+                leaf: EMPTY_NODE,
+                index: 0,
+            },
+            canopy_depth: 0,
+            canopy: Vec::new(),
+        }
+    }
+
+    /// Creates an empty tree caching its top `canopy_depth` levels below the root.
+    pub fn with_canopy(canopy_depth: usize) -> Result<Self, Error> {
+        if canopy_depth > MAX_DEPTH {
+            return Err(Error::InvalidInput(format!(
+                "the canopy can't be deeper than the tree, {} > {}",
+                canopy_depth, MAX_DEPTH
+            )));
+        }
+
+        let mut tree = Self::new();
+        tree.canopy_depth = canopy_depth;
+        tree.canopy = (MAX_DEPTH - canopy_depth..MAX_DEPTH)
+            .map(|level| vec![empty_node(level); 1 << (MAX_DEPTH - level)])
+            .collect();
+
+        Ok(tree)
+    }
+
+    pub fn root(&self) -> Hash {
+        self.change_logs[self.active_index].root
+    }
+
+    /// The number of modifications applied to the tree.
+    pub fn sequence_number(&self) -> u64 {
+        self.sequence_number
+    }
+
+    /// The number of leaves appended, the index of the next one.
+    pub fn rightmost_index(&self) -> u32 {
+        self.rightmost_proof.index
+    }
+
+    pub fn canopy_depth(&self) -> usize {
+        self.canopy_depth
+    }
+
+    /// The changes still in the buffer, the most recent first.
+    pub fn change_logs(&self) -> impl Iterator<Item = &ChangeLog<MAX_DEPTH>> {
+        (0..self.buffer_size).map(move |age| {
+            &self.change_logs[(self.active_index + MAX_BUFFER_SIZE - age) % MAX_BUFFER_SIZE]
+        })
+    }
+
+    /// Appends a leaf after the rightmost one, without a proof.
+    pub fn append(&mut self, leaf: Hash) -> Result<(), Error> {
+        if leaf == EMPTY_NODE {
+            return Err(Error::InvalidInput(
+                "can't append an empty leaf".to_string(),
+            ));
+        }
+        if self.rightmost_proof.index as u64 >= 1 << MAX_DEPTH {
+            return Err(Error::InvalidInput("the tree is full".to_string()));
+        }
+
+        let index = self.rightmost_proof.index;
+        let mut change_log = ChangeLog {
+            root: EMPTY_NODE,
+            path: [EMPTY_NODE; MAX_DEPTH],
+            index,
+        };
+
+        if index == 0 {
+            change_log.replace_and_recompute_path(0, leaf, &self.rightmost_proof.proof);
+        } else {
+            // Below the level where the new leaf meets the previous one its
+            // siblings are empty, and at that level the sibling is the subtree
+            // holding the previous rightmost leaf.
+            let previous = index - 1;
+            let intersection = index.trailing_zeros() as usize;
+            let mut intersection_node = self.rightmost_proof.leaf;
+            let mut node = leaf;
+
+            for level in 0..MAX_DEPTH {
+                change_log.path[level] = node;
+
+                if level < intersection {
+                    let sibling = empty_node(level);
+                    intersection_node = hash_to_parent(
+                        &intersection_node,
+                        &self.rightmost_proof.proof[level],
+                        (previous >> level) & 1 == 0,
+                    );
+                    node = hash_to_parent(&node, &sibling, true);
+                    self.rightmost_proof.proof[level] = sibling;
+                } else if level == intersection {
+                    node = hash_to_parent(&node, &intersection_node, false);
+                    self.rightmost_proof.proof[level] = intersection_node;
+                } else {
+                    node = hash_to_parent(
+                        &node,
+                        &self.rightmost_proof.proof[level],
+                        (previous >> level) & 1 == 0,
+                    );
+                }
+            }
+
+            change_log.root = node;
+        }
+
+        self.rightmost_proof.leaf = leaf;
+        self.rightmost_proof.index += 1;
+        self.push_change_log(change_log);
+
+        Ok(())
+    }
+
+    /// Checks that `leaf` is at `index`, given a proof against `current_root`,
+    /// a root that may have been changed since but is still in the buffer.
+    pub fn verify_leaf(
+        &self,
+        current_root: &Hash,
+        leaf: &Hash,
+        proof: &[Hash],
+        index: u32,
+    ) -> Result<(), Error> {
+        self.fast_forward(current_root, leaf, proof, index)
+            .map(|_| ())
+    }
+
+    /// Replaces `previous_leaf` at `index` with `new_leaf`, given a proof
+    /// against `current_root`, a root that may have been changed since but
+    /// is still in the buffer. Fails if the leaf was modified in between.
+    pub fn set_leaf(
+        &mut self,
+        current_root: &Hash,
+        previous_leaf: &Hash,
+        new_leaf: Hash,
+        proof: &[Hash],
+        index: u32,
+    ) -> Result<(), Error> {
+        if index >= self.rightmost_proof.index {
+            return Err(Error::IndexOutOfRange {
+                index: index as usize,
+                leaf_count: self.rightmost_proof.index as usize,
+            });
+        }
+
+        let proof = self.fast_forward(current_root, previous_leaf, proof, index)?;
+
+        let mut change_log = ChangeLog {
+            root: EMPTY_NODE,
+            path: [EMPTY_NODE; MAX_DEPTH],
+            index,
+        };
+        change_log.replace_and_recompute_path(index, new_leaf, &proof);
+
+        change_log.update_proof_or_leaf(
+            self.rightmost_proof.index - 1,
+            &mut self.rightmost_proof.proof,
+            &mut self.rightmost_proof.leaf,
+        );
+        self.push_change_log(change_log);
+
+        Ok(())
+    }
+
+    /// Completes the proof from the canopy, updates it through the changes
+    /// made after `root` and checks it against the current root.
+    fn fast_forward(
+        &self,
+        root: &Hash,
+        leaf: &Hash,
+        proof: &[Hash],
+        index: u32,
+    ) -> Result<[Hash; MAX_DEPTH], Error> {
+        if index as u64 >= 1 << MAX_DEPTH {
+            return Err(Error::IndexOutOfRange {
+                index: index as usize,
+                leaf_count: 1 << MAX_DEPTH,
+            });
+        }
+
+        let mut full_proof = self.fill_in_proof(proof, index)?;
+
+        let age = self
+            .change_logs()
+            .position(|change_log| change_log.root == *root)
+            .ok_or_else(|| {
+                Error::InvalidInput("the root is not in the changelog buffer".to_string())
+            })?;
+
+        let mut updated_leaf = *leaf;
+        for change_log in self
+            .change_logs()
+            .take(age)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+        {
+            change_log.update_proof_or_leaf(index, &mut full_proof, &mut updated_leaf);
+        }
+
+        if updated_leaf != *leaf {
+            return Err(Error::InvalidInput(format!(
+                "the leaf {} was modified since the root",
+                index
+            )));
+        }
+        if recompute(leaf, &full_proof, index) != self.root() {
+            return Err(Error::RootMismatch);
+        }
+
+        Ok(full_proof)
+    }
+
+    fn fill_in_proof(&self, proof: &[Hash], index: u32) -> Result<[Hash; MAX_DEPTH], Error> {
+        let required = MAX_DEPTH - self.canopy_depth;
+        if proof.len() < required || proof.len() > MAX_DEPTH {
+            return Err(Error::InvalidInput(format!(
+                "the proof has {} nodes, expected between {} and {}",
+                proof.len(),
+                required,
+                MAX_DEPTH
+            )));
+        }
+
+        let mut full_proof = [EMPTY_NODE; MAX_DEPTH];
+        full_proof[..proof.len()].copy_from_slice(proof);
+
+        for (level, sibling) in full_proof.iter_mut().enumerate().skip(proof.len()) {
+            let position = ((index >> level) ^ 1) as usize;
+            *sibling = self.canopy[level - required][position];
+        }
+
+        Ok(full_proof)
+    }
+
+    fn push_change_log(&mut self, change_log: ChangeLog<MAX_DEPTH>) {
+        let required = MAX_DEPTH - self.canopy_depth;
+        for (offset, nodes) in self.canopy.iter_mut().enumerate() {
+            let level = required + offset;
+            nodes[(change_log.index >> level) as usize] = change_log.path[level];
+        }
+
+        self.active_index = (self.active_index + 1) % MAX_BUFFER_SIZE;
+        self.buffer_size = (self.buffer_size + 1).min(MAX_BUFFER_SIZE);
+        self.change_logs[self.active_index] = change_log;
+        self.sequence_number += 1;
+    }
+}
+
+/// The levels of a tree of the given depth over `leaves`, padded with empty nodes.
+fn concurrent_tree_levels(leaves: &[Hash], depth: usize) -> Result<Vec<Vec<Hash>>, Error> {
+    if depth > 30 || leaves.len() > 1 << depth {
+        return Err(Error::InvalidInput(format!(
+            "{} leaves don't fit in a tree of depth {}",
+            leaves.len(),
+            depth
+        )));
+    }
+
+    let mut levels = vec![leaves.to_vec()];
+    for level in 0..depth {
+        let nodes = levels.last().unwrap();
+        let parents = nodes
+            .chunks(2)
+            .map(|pair| parent(&pair[0], pair.get(1).unwrap_or(&empty_node(level))))
+            .collect();
+        levels.push(parents);
+    }
+
+    Ok(levels)
+}
+
+/// The root of a concurrent merkle tree of the given depth holding `leaves`,
+/// as an indexer rebuilding the tree off-chain computes it.
+pub fn concurrent_tree_root(leaves: &[Hash], depth: usize) -> Result<Hash, Error> {
+    let levels = concurrent_tree_levels(leaves, depth)?;
+    Ok(levels[depth]
+        .first()
+        .copied()
+        .unwrap_or_else(|| empty_node(depth)))
+}
+
+/// The full proof of the leaf at `index` in a concurrent merkle tree of the given depth.
+pub fn concurrent_tree_proof(
+    leaves: &[Hash],
+    depth: usize,
+    index: usize,
+) -> Result<Vec<Hash>, Error> {
+    if index >= leaves.len() {
+        return Err(Error::IndexOutOfRange {
+            index,
+            leaf_count: leaves.len(),
+        });
+    }
+
+    let levels = concurrent_tree_levels(leaves, depth)?;
+
+    Ok((0..depth)
+        .map(|level| {
+            levels[level]
+                .get((index >> level) ^ 1)
+                .copied()
+                .unwrap_or_else(|| empty_node(level))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn leaves(count: usize) -> Vec<Hash> {
+        (0..count)
+            .map(|leaf| keccak256(format!("Compressed Hobbit #{}", leaf).as_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn test_empty_nodes() {
+        assert_eq!(
+            hex::encode(empty_node(1)),
+            "ad3228b676f7d3cd4284a5443f17f1962b36e491b30a40b2405849e597ba5fb5"
+        );
+
+        let tree = ConcurrentMerkleTree::<5, 8>::new();
+        assert_eq!(tree.root(), empty_node(5));
+        assert_eq!(tree.root(), concurrent_tree_root(&[], 5).unwrap());
+    }
+
+    #[test]
+    fn test_appends_match_the_rebuilt_root() {
+        let leaves = leaves(13);
+        let mut tree = ConcurrentMerkleTree::<5, 8>::new();
+
+        for (count, leaf) in leaves.iter().enumerate() {
+            tree.append(*leaf).unwrap();
+            assert_eq!(
+                tree.root(),
+                concurrent_tree_root(&leaves[..count + 1], 5).unwrap()
+            );
+        }
+
+        assert_eq!(tree.rightmost_index(), 13);
+        assert_eq!(tree.sequence_number(), 13);
+    }
+
+    #[test]
+    fn test_concurrent_replacements_against_a_stale_root() {
+        let mut leaves = leaves(10);
+        let mut tree = ConcurrentMerkleTree::<4, 8>::new();
+        for leaf in &leaves {
+            tree.append(*leaf).unwrap();
+        }
+
+        let original = leaves.clone();
+        let snapshot = tree.root();
+        let proofs: Vec<Vec<Hash>> = (0..10)
+            .map(|index| concurrent_tree_proof(&leaves, 4, index).unwrap())
+            .collect();
+
+        for index in [1, 2, 7, 9] {
+            let new_leaf = keccak256(format!("Transferred #{}", index).as_bytes());
+            tree.set_leaf(
+                &snapshot,
+                &leaves[index],
+                new_leaf,
+                &proofs[index],
+                index as u32,
+            )
+            .unwrap();
+            leaves[index] = new_leaf;
+        }
+        tree.append(keccak256(b"Minted late")).unwrap();
+        leaves.push(keccak256(b"Minted late"));
+
+        assert_eq!(tree.root(), concurrent_tree_root(&leaves, 4).unwrap());
+
+        // The same leaf can't be replaced twice from the same snapshot.
+        let error = tree.set_leaf(&snapshot, &original[1], keccak256(b"Again"), &proofs[1], 1);
+        assert!(error.is_err());
+    }
+
+    #[test]
+    fn test_roots_out_of_the_buffer_are_rejected() {
+        let leaves = leaves(6);
+        let mut tree = ConcurrentMerkleTree::<3, 2>::new();
+        tree.append(leaves[0]).unwrap();
+        let old_root = tree.root();
+        for leaf in &leaves[1..] {
+            tree.append(*leaf).unwrap();
+        }
+
+        let proof = concurrent_tree_proof(&leaves[..1], 3, 0).unwrap();
+
+        assert!(tree.verify_leaf(&old_root, &leaves[0], &proof, 0).is_err());
+        assert_eq!(tree.change_logs().count(), 2);
+    }
+
+    #[test]
+    fn test_canopy_shortens_proofs() {
+        let leaves = leaves(20);
+        let mut tree = ConcurrentMerkleTree::<6, 4>::with_canopy(2).unwrap();
+        for leaf in &leaves {
+            tree.append(*leaf).unwrap();
+        }
+
+        let proof = concurrent_tree_proof(&leaves, 6, 5).unwrap();
+        let root = tree.root();
+
+        assert_eq!(tree.verify_leaf(&root, &leaves[5], &proof[..4], 5), Ok(()));
+        assert!(tree.verify_leaf(&root, &leaves[5], &proof[..3], 5).is_err());
+        assert_eq!(
+            tree.verify_leaf(&root, &leaves[6], &proof[..4], 5),
+            Err(Error::RootMismatch)
+        );
+    }
+
+    #[test]
+    fn test_invalid_operations_are_rejected() {
+        let mut tree = ConcurrentMerkleTree::<1, 2>::new();
+
+        assert!(tree.append(EMPTY_NODE).is_err());
+        tree.append(keccak256(b"Bilbo")).unwrap();
+        tree.append(keccak256(b"Frodo")).unwrap();
+        assert!(tree.append(keccak256(b"Sam")).is_err());
+
+        let mut empty = ConcurrentMerkleTree::<3, 2>::new();
+        let root = empty.root();
+        assert!(empty
+            .set_leaf(&root, &EMPTY_NODE, keccak256(b"Bilbo"), &[EMPTY_NODE; 3], 0)
+            .is_err());
+        assert!(ConcurrentMerkleTree::<3, 2>::with_canopy(4).is_err());
+    }
+}
@@ -0,0 +1,469 @@
+use std::collections::HashMap;
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// An opaque identifier of a leaf of a `StableTree`, valid until the leaf
+/// is removed whatever happens to the leaves before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LeafId(u64);
+
+/// Leaves and tombstones are hashed under distinct prefixes, as in RFC 6962,
+/// so no item hashes to the tombstone of another leaf.
+const LEAF_PREFIX: u8 = 0x00;
+const TOMBSTONE_PREFIX: u8 = 0x01;
+
+/// A proof that a leaf was deleted: the tombstone committing to the
+/// deleted leaf is a leaf under a root.
+///
+/// Unlike a removal, which silently drops the leaf and shifts the following
+/// ones, a deletion keeps the position of the leaf for revocation workflows
+/// that must prove what was removed. Pairs are hashed sorted, so the index
+/// of the tombstone is not authenticated by `verify`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletionProof {
+    pub deleted: Hash,
+    /// The proof of inclusion of the tombstone.
+    pub proof: Proof,
+}
+
+impl DeletionProof {
+    /// The leaf that replaces `deleted`, `H(0x01 || deleted)`.
+    pub fn tombstone(deleted: &Hash) -> Hash {
+        MerkleTree::hash(&[&[TOMBSTONE_PREFIX], deleted.as_slice()].concat())
+    }
+
+    /// The index the tree claims for the tombstone.
+    pub fn index(&self) -> usize {
+        self.proof.leaf_index
+    }
+
+    /// Checks that the tombstone of the deleted leaf is under `root`.
+    pub fn verify(&self, root: &Hash) -> Result<(), Error> {
+        self.proof.verify(&Self::tombstone(&self.deleted), root)
+    }
+}
+
+/// A mutable tree whose leaves are referred to by `LeafId` rather than by
+/// index, so references kept outside stay valid when earlier leaves are
+/// inserted or removed.
+///
+/// The leaf of an item is `H(0x00 || item)`, see `StableTree::leaf_hash`.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::StableTree;
+///
+/// let mut tree = StableTree::new();
+/// let isildur = tree.push(&"Isildur").unwrap();
+/// let valandil = tree.push(&"Valandil").unwrap();
+///
+/// tree.insert(0, &"Elendil").unwrap();
+/// tree.remove(isildur).unwrap();
+///
+/// assert_eq!(tree.index_of(valandil), Some(1));
+/// let proof = tree.proof(valandil).unwrap();
+/// assert!(proof.verify(&StableTree::leaf_hash(b"Valandil"), &tree.root().unwrap()).is_ok());
+/// ```
+#[derive(Debug, Default)]
+pub struct StableTree {
+    tree: Option<MerkleTree>,
+    ids: Vec<LeafId>,
+    positions: HashMap<LeafId, usize>,
+    /// The deleted leaves by the identifier of their tombstone.
+    deleted: HashMap<LeafId, Hash>,
+    next_id: u64,
+}
+
+impl StableTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The leaf of `item`, `H(0x00 || item)`.
+    pub fn leaf_hash(item: &[u8]) -> Hash {
+        MerkleTree::hash(&[&[LEAF_PREFIX], item].concat())
+    }
+
+    /// The root, `None` while the tree has no leaves.
+    pub fn root(&self) -> Option<Hash> {
+        self.tree.as_ref().and_then(MerkleTree::root)
+    }
+
+    pub fn tree(&self) -> Option<&MerkleTree> {
+        self.tree.as_ref()
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// The current index of the leaf `id`, `None` once it was removed.
+    pub fn index_of(&self, id: LeafId) -> Option<usize> {
+        self.positions.get(&id).copied()
+    }
+
+    pub fn id_at(&self, index: usize) -> Option<LeafId> {
+        self.ids.get(index).copied()
+    }
+
+    pub fn leaf(&self, id: LeafId) -> Option<Hash> {
+        let index = self.index_of(id)?;
+        self.tree.as_ref()?.node(0, index)
+    }
+
+    fn leaves(&self) -> Vec<Hash> {
+        self.tree
+            .as_ref()
+            .map(|tree| tree.leaves().to_vec())
+            .unwrap_or_default()
+    }
+
+    fn position(&self, id: LeafId) -> Result<usize, Error> {
+        self.index_of(id)
+            .ok_or_else(|| Error::InvalidInput(format!("the leaf {:?} is not in the tree", id)))
+    }
+
+    /// Rebuilds the tree over `leaves`, failing without changes if the tree rejects them.
+    fn rebuild(&mut self, leaves: Vec<Hash>) -> Result<(), Error> {
+        match (&mut self.tree, leaves.is_empty()) {
+            (_, true) => self.tree = None,
+            (Some(tree), false) => tree.replace_leaves(leaves)?,
+            (None, false) => self.tree = MerkleTree::from_leaves(leaves),
+        }
+        Ok(())
+    }
+
+    /// Refreshes the indices of the leaves from `first` on.
+    fn reindex(&mut self, first: usize) {
+        for (index, id) in self.ids.iter().enumerate().skip(first) {
+            self.positions.insert(*id, index);
+        }
+    }
+
+    fn new_id(&mut self) -> LeafId {
+        self.next_id += 1;
+        LeafId(self.next_id)
+    }
+
+    /// Appends an item, returning the identifier of its leaf.
+    pub fn push<T: AsRef<[u8]>>(&mut self, item: &T) -> Result<LeafId, Error> {
+        self.insert(self.leaf_count(), item)
+    }
+
+    /// Inserts an item at `index`, shifting the following leaves, and
+    /// returns the identifier of its leaf.
+    pub fn insert<T: AsRef<[u8]>>(&mut self, index: usize, item: &T) -> Result<LeafId, Error> {
+        if index > self.leaf_count() {
+            return Err(Error::IndexOutOfRange {
+                index,
+                leaf_count: self.leaf_count(),
+            });
+        }
+
+        let leaf = Self::leaf_hash(item.as_ref());
+        match &mut self.tree {
+            Some(tree) if index == tree.leaf_count() => {
+                tree.try_insert_leaf(leaf, item.as_ref().len())?
+            }
+            _ => {
+                let mut leaves = self.leaves();
+                leaves.insert(index, leaf);
+                self.rebuild(leaves)?;
+            }
+        }
+
+        let id = self.new_id();
+        self.ids.insert(index, id);
+        self.reindex(index);
+        Ok(id)
+    }
+
+    /// Replaces the item of the leaf `id`, which keeps its identifier.
+    pub fn update<T: AsRef<[u8]>>(&mut self, id: LeafId, item: &T) -> Result<(), Error> {
+        let index = self.live_position(id)?;
+        let mut leaves = self.leaves();
+        leaves[index] = Self::leaf_hash(item.as_ref());
+        self.rebuild(leaves)
+    }
+
+    /// Removes the leaf `id`, shifting the following leaves, and returns it.
+    pub fn remove(&mut self, id: LeafId) -> Result<Hash, Error> {
+        let index = self.position(id)?;
+        let mut leaves = self.leaves();
+        let leaf = leaves.remove(index);
+        self.rebuild(leaves)?;
+
+        self.ids.remove(index);
+        self.positions.remove(&id);
+        self.deleted.remove(&id);
+        self.reindex(index);
+        Ok(leaf)
+    }
+
+    fn live_position(&self, id: LeafId) -> Result<usize, Error> {
+        if self.is_deleted(id) {
+            return Err(Error::InvalidInput(format!(
+                "the leaf {:?} was deleted",
+                id
+            )));
+        }
+        self.position(id)
+    }
+
+    /// Replaces the leaf `id` with a tombstone committing to it, keeping
+    /// the following leaves in place, and returns the proof of deletion.
+    pub fn delete(&mut self, id: LeafId) -> Result<DeletionProof, Error> {
+        let index = self.live_position(id)?;
+        let mut leaves = self.leaves();
+        let deleted = leaves[index];
+        leaves[index] = DeletionProof::tombstone(&deleted);
+        self.rebuild(leaves)?;
+
+        self.deleted.insert(id, deleted);
+        self.deletion_proof(id)
+    }
+
+    /// Whether the leaf `id` is a tombstone.
+    pub fn is_deleted(&self, id: LeafId) -> bool {
+        self.deleted.contains_key(&id)
+    }
+
+    /// Returns the proof that the leaf `id` was deleted, under the current root.
+    pub fn deletion_proof(&self, id: LeafId) -> Result<DeletionProof, Error> {
+        let deleted = *self
+            .deleted
+            .get(&id)
+            .ok_or_else(|| Error::InvalidInput(format!("the leaf {:?} was not deleted", id)))?;
+
+        Ok(DeletionProof {
+            deleted,
+            proof: self.proof(id)?,
+        })
+    }
+
+    /// Drops every tombstone in a single rebuild and returns the new index
+    /// of each old index, `None` for the tombstones, whose identifiers are
+    /// no longer valid. The other leaves keep their identifiers.
+    pub fn compact(&mut self) -> Result<Vec<Option<usize>>, Error> {
+        let mut mapping = Vec::with_capacity(self.leaf_count());
+        let mut leaves = Vec::with_capacity(self.leaf_count() - self.deleted.len());
+        let mut ids = Vec::with_capacity(leaves.capacity());
+
+        for (id, leaf) in self.ids.iter().zip(self.leaves()) {
+            if self.deleted.contains_key(id) {
+                mapping.push(None);
+            } else {
+                mapping.push(Some(leaves.len()));
+                leaves.push(leaf);
+                ids.push(*id);
+            }
+        }
+        self.rebuild(leaves)?;
+
+        for id in self.deleted.drain().map(|(id, _)| id) {
+            self.positions.remove(&id);
+        }
+        self.ids = ids;
+        self.reindex(0);
+        Ok(mapping)
+    }
+
+    /// Returns the proof of inclusion of the leaf `id` at its current index.
+    pub fn proof(&self, id: LeafId) -> Result<Proof, Error> {
+        let index = self.position(id)?;
+        self.tree.as_ref().ok_or(Error::EmptyTree)?.proof(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_identifiers_survive_structural_changes() {
+        let mut tree = StableTree::new();
+        let kings: Vec<LeafId> = (0..10)
+            .map(|king| tree.push(&format!("king {}", king)).unwrap())
+            .collect();
+
+        tree.insert(0, &"Elros").unwrap();
+        tree.remove(kings[2]).unwrap();
+        tree.remove(kings[0]).unwrap();
+        tree.insert(3, &"Tar-Minyatur").unwrap();
+        tree.update(kings[9], &"Ar-Pharazon").unwrap();
+
+        let expected: Vec<Hash> = ["Elros", "king 1", "king 3", "Tar-Minyatur"]
+            .iter()
+            .map(|item| item.to_string())
+            .chain((4..9).map(|king| format!("king {}", king)))
+            .chain(["Ar-Pharazon".to_string()])
+            .map(|item| StableTree::leaf_hash(item.as_bytes()))
+            .collect();
+        assert_eq!(
+            tree.root(),
+            MerkleTree::from_leaves(expected.clone()).unwrap().root()
+        );
+
+        for id in &kings[3..] {
+            let index = tree.index_of(*id).unwrap();
+            assert_eq!(tree.id_at(index), Some(*id));
+            assert_eq!(tree.leaf(*id), Some(expected[index]));
+            assert!(tree
+                .proof(*id)
+                .unwrap()
+                .verify(&expected[index], &tree.root().unwrap())
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn test_deletions_are_proven_under_later_roots() {
+        let mut tree = StableTree::new();
+        let ids: Vec<LeafId> = ["Gandalf", "Saruman", "Radagast", "Alatar", "Pallando"]
+            .iter()
+            .map(|wizard| tree.push(wizard).unwrap())
+            .collect();
+
+        let proof = tree.delete(ids[1]).unwrap();
+        assert_eq!(proof.deleted, StableTree::leaf_hash(b"Saruman"));
+        assert_eq!(proof.index(), 1);
+        assert_eq!(proof.verify(&tree.root().unwrap()), Ok(()));
+        assert_eq!(
+            tree.leaf(ids[1]),
+            Some(DeletionProof::tombstone(&proof.deleted))
+        );
+        assert_eq!(tree.index_of(ids[4]), Some(4));
+
+        let last = tree.delete(ids[4]).unwrap();
+        assert_eq!(last.verify(&tree.root().unwrap()), Ok(()));
+        assert!(proof.verify(&tree.root().unwrap()).is_err());
+        assert_eq!(
+            tree.deletion_proof(ids[1])
+                .unwrap()
+                .verify(&tree.root().unwrap()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_deleted_leaves_cannot_change() {
+        let mut tree = StableTree::new();
+        let ids = [
+            tree.push(&"Ungoliant").unwrap(),
+            tree.push(&"Shelob").unwrap(),
+        ];
+        tree.delete(ids[0]).unwrap();
+
+        assert!(tree.is_deleted(ids[0]) && !tree.is_deleted(ids[1]));
+        assert!(tree.delete(ids[0]).is_err());
+        assert!(tree.update(ids[0], &"Ungoliant").is_err());
+        assert!(tree.deletion_proof(ids[1]).is_err());
+
+        let forged = DeletionProof {
+            deleted: StableTree::leaf_hash(b"Shelob"),
+            proof: tree.proof(ids[1]).unwrap(),
+        };
+        assert!(forged.verify(&tree.root().unwrap()).is_err());
+
+        tree.remove(ids[0]).unwrap();
+        assert!(!tree.is_deleted(ids[0]));
+    }
+
+    #[test]
+    fn test_items_cannot_forge_tombstones() {
+        let mut tree = StableTree::new();
+        let deleted = StableTree::leaf_hash(b"Sauron");
+        let forgeries = [
+            tree.push(&[&[TOMBSTONE_PREFIX], deleted.as_slice()].concat())
+                .unwrap(),
+            tree.push(&[b"merkle-tree tombstone".as_slice(), &deleted].concat())
+                .unwrap(),
+        ];
+
+        for id in forgeries {
+            let forged = DeletionProof {
+                deleted,
+                proof: tree.proof(id).unwrap(),
+            };
+            assert_eq!(
+                forged.verify(&tree.root().unwrap()),
+                Err(Error::RootMismatch)
+            );
+        }
+    }
+
+    #[test]
+    fn test_compaction_drops_tombstones() {
+        let mut tree = StableTree::new();
+        let ids: Vec<LeafId> = (0..9)
+            .map(|palantir| tree.push(&format!("palantir {}", palantir)).unwrap())
+            .collect();
+        for index in [0, 4, 5, 8] {
+            tree.delete(ids[index]).unwrap();
+        }
+
+        let mapping = tree.compact().unwrap();
+
+        assert_eq!(
+            mapping,
+            [
+                None,
+                Some(0),
+                Some(1),
+                Some(2),
+                None,
+                None,
+                Some(3),
+                Some(4),
+                None
+            ]
+        );
+        let remaining: Vec<String> = [1, 2, 3, 6, 7]
+            .iter()
+            .map(|palantir| format!("palantir {}", palantir))
+            .collect();
+        let leaves = remaining
+            .iter()
+            .map(|item| StableTree::leaf_hash(item.as_bytes()))
+            .collect();
+        assert_eq!(tree.root(), MerkleTree::from_leaves(leaves).unwrap().root());
+        for (old, new) in mapping.iter().enumerate() {
+            assert_eq!(tree.index_of(ids[old]), *new);
+            assert!(!tree.is_deleted(ids[old]));
+        }
+        assert_eq!(
+            tree.compact().unwrap(),
+            [Some(0), Some(1), Some(2), Some(3), Some(4)]
+        );
+
+        for id in &ids {
+            tree.delete(*id).ok();
+        }
+        tree.compact().unwrap();
+        assert!(tree.is_empty() && tree.root().is_none());
+    }
+
+    #[test]
+    fn test_removed_identifiers_are_invalid() {
+        let mut tree = StableTree::new();
+        let anarion = tree.push(&"Anarion").unwrap();
+
+        assert_eq!(tree.remove(anarion), Ok(StableTree::leaf_hash(b"Anarion")));
+        assert!(tree.is_empty() && tree.root().is_none());
+        assert_eq!(tree.index_of(anarion), None);
+        assert!(tree.remove(anarion).is_err());
+        assert!(tree.proof(anarion).is_err());
+        assert!(tree.insert(1, &"Meneldil").is_err());
+
+        let meneldil = tree.push(&"Meneldil").unwrap();
+        assert_ne!(meneldil, anarion);
+        assert_eq!(tree.index_of(meneldil), Some(0));
+    }
+}
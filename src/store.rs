@@ -0,0 +1,395 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// The position of a node in a stored tree, the leaves being level 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId {
+    pub level: usize,
+    pub index: usize,
+}
+
+impl NodeId {
+    pub fn new(level: usize, index: usize) -> Self {
+        Self { level, index }
+    }
+}
+
+/// A storage backend holding the nodes of named trees.
+///
+/// Implementations map the nodes to whatever they store, a key-value
+/// database or an object store. `MemoryNodeStore` keeps them in memory.
+///
+/// Every tree operation reads its nodes with one `get_batch` and writes
+/// them with one `put`, so a backend with batch calls, such as `multi_get`
+/// and write batches in RocksDB or sled, pays one round trip per proof or
+/// commit instead of one per node.
+pub trait NodeStore {
+    /// Returns the node of `tree` at `id`, `None` if the store does not have it.
+    fn get(&self, tree: &str, id: NodeId) -> Result<Option<Hash>, Error>;
+
+    /// Returns the nodes of `tree` at `ids`, in order. Backends with batch
+    /// reads should override it, the default calls `get` once per node.
+    fn get_batch(&self, tree: &str, ids: &[NodeId]) -> Result<Vec<Option<Hash>>, Error> {
+        ids.iter().map(|id| self.get(tree, *id)).collect()
+    }
+
+    /// Writes nodes of `tree`, replacing the nodes at the same positions.
+    fn put(&mut self, tree: &str, nodes: &[(NodeId, Hash)]) -> Result<(), Error>;
+
+    /// Returns the number of leaves of `tree`, 0 if the store does not have it.
+    fn leaf_count(&self, tree: &str) -> Result<usize, Error>;
+
+    /// Records the number of leaves of `tree`.
+    fn set_leaf_count(&mut self, tree: &str, leaf_count: usize) -> Result<(), Error>;
+
+    /// Removes every node of `tree`.
+    fn remove_tree(&mut self, tree: &str) -> Result<(), Error>;
+
+    /// Returns the names of the stored trees, sorted.
+    fn trees(&self) -> Result<Vec<String>, Error>;
+}
+
+/// A `NodeStore` in memory.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryNodeStore {
+    trees: BTreeMap<String, (usize, HashMap<NodeId, Hash>)>,
+}
+
+impl MemoryNodeStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl NodeStore for MemoryNodeStore {
+    fn get(&self, tree: &str, id: NodeId) -> Result<Option<Hash>, Error> {
+        Ok(self
+            .trees
+            .get(tree)
+            .and_then(|(_, nodes)| nodes.get(&id).copied()))
+    }
+
+    fn get_batch(&self, tree: &str, ids: &[NodeId]) -> Result<Vec<Option<Hash>>, Error> {
+        let nodes = self.trees.get(tree).map(|(_, nodes)| nodes);
+        Ok(ids
+            .iter()
+            .map(|id| nodes.and_then(|nodes| nodes.get(id).copied()))
+            .collect())
+    }
+
+    fn put(&mut self, tree: &str, nodes: &[(NodeId, Hash)]) -> Result<(), Error> {
+        let (_, stored) = self.trees.entry(tree.to_string()).or_default();
+        stored.extend(nodes.iter().copied());
+        Ok(())
+    }
+
+    fn leaf_count(&self, tree: &str) -> Result<usize, Error> {
+        Ok(self
+            .trees
+            .get(tree)
+            .map_or(0, |(leaf_count, _)| *leaf_count))
+    }
+
+    fn set_leaf_count(&mut self, tree: &str, leaf_count: usize) -> Result<(), Error> {
+        self.trees.entry(tree.to_string()).or_default().0 = leaf_count;
+        Ok(())
+    }
+
+    fn remove_tree(&mut self, tree: &str) -> Result<(), Error> {
+        self.trees.remove(tree);
+        Ok(())
+    }
+
+    fn trees(&self) -> Result<Vec<String>, Error> {
+        Ok(self.trees.keys().cloned().collect())
+    }
+}
+
+/// Reads a node the store must have.
+pub(crate) fn stored_node<S: NodeStore + ?Sized>(
+    store: &S,
+    tree: &str,
+    id: NodeId,
+) -> Result<Hash, Error> {
+    store.get(tree, id)?.ok_or_else(|| missing_node(tree, id))
+}
+
+/// Reads nodes the store must have, in one batch.
+pub(crate) fn stored_nodes<S: NodeStore + ?Sized>(
+    store: &S,
+    tree: &str,
+    ids: &[NodeId],
+) -> Result<Vec<Hash>, Error> {
+    let nodes = store.get_batch(tree, ids)?;
+    batched_nodes(tree, ids, nodes)
+}
+
+/// Checks that a batch read returned every node of `ids`.
+pub(crate) fn batched_nodes(
+    tree: &str,
+    ids: &[NodeId],
+    nodes: Vec<Option<Hash>>,
+) -> Result<Vec<Hash>, Error> {
+    if nodes.len() != ids.len() {
+        return Err(Error::InvalidInput(format!(
+            "requested {} nodes, received {}",
+            ids.len(),
+            nodes.len()
+        )));
+    }
+
+    ids.iter()
+        .zip(nodes)
+        .map(|(id, node)| node.ok_or_else(|| missing_node(tree, *id)))
+        .collect()
+}
+
+pub(crate) fn missing_node(tree: &str, id: NodeId) -> Error {
+    Error::InvalidInput(format!(
+        "the store has no node {} at level {} of {}",
+        id.index, id.level, tree
+    ))
+}
+
+impl MerkleTree {
+    /// Writes the nodes of the tree whose index is at least `first_leaf`
+    /// shifted to their level, the only nodes that change when leaves are
+    /// appended after `first_leaf`. 0 writes every node.
+    pub(crate) fn store_from<S: NodeStore + ?Sized>(
+        &self,
+        store: &mut S,
+        tree: &str,
+        first_leaf: usize,
+    ) -> Result<(), Error> {
+        store.put(tree, &self.nodes_from(first_leaf))?;
+        store.set_leaf_count(tree, self.leaf_count())
+    }
+
+    /// The nodes whose index is at least `first_leaf` shifted to their level.
+    pub(crate) fn nodes_from(&self, first_leaf: usize) -> Vec<(NodeId, Hash)> {
+        (0..=self.height())
+            .flat_map(|level| {
+                (first_leaf >> level..Self::level_width(self.leaf_count(), level)).map(
+                    move |index| {
+                        let hash = self.node(level, index).expect("The node is in the tree.");
+                        (NodeId::new(level, index), hash)
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// The positions of the siblings on the path of the leaf at `index`.
+    pub(crate) fn sibling_ids(leaf_count: usize, index: usize) -> Vec<NodeId> {
+        (0..Proof::expected_length(leaf_count))
+            .map(|level| {
+                let position = index >> level;
+                let sibling = if position ^ 1 < Self::level_width(leaf_count, level) {
+                    position ^ 1
+                } else {
+                    position
+                };
+                NodeId::new(level, sibling)
+            })
+            .collect()
+    }
+
+    /// Writes every node of the tree to `store` under the name `tree`,
+    /// replacing the tree stored under that name.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::{MemoryNodeStore, MerkleTree};
+    ///
+    /// let tree = MerkleTree::build(&["Celeborn", "Galadriel", "Haldir"]).unwrap();
+    /// let mut store = MemoryNodeStore::new();
+    ///
+    /// tree.store(&mut store, "lórien").unwrap();
+    ///
+    /// let proof = MerkleTree::stored_proof(&store, "lórien", 2).unwrap();
+    /// assert_eq!(proof, tree.proof(2).unwrap());
+    /// assert_eq!(MerkleTree::load(&store, "lórien").unwrap().root(), tree.root());
+    /// ```
+    pub fn store<S: NodeStore + ?Sized>(&self, store: &mut S, tree: &str) -> Result<(), Error> {
+        store.remove_tree(tree)?;
+        self.store_from(store, tree, 0)
+    }
+
+    /// Rebuilds the tree stored under the name `tree` from its leaves,
+    /// read in one batch.
+    pub fn load<S: NodeStore + ?Sized>(store: &S, tree: &str) -> Result<MerkleTree, Error> {
+        let ids: Vec<NodeId> = (0..store.leaf_count(tree)?)
+            .map(|index| NodeId::new(0, index))
+            .collect();
+        let leaves = stored_nodes(store, tree, &ids)?;
+
+        MerkleTree::from_leaves(leaves).ok_or(Error::EmptyTree)
+    }
+
+    /// Returns the root of the tree stored under the name `tree`.
+    pub fn stored_root<S: NodeStore + ?Sized>(store: &S, tree: &str) -> Result<Hash, Error> {
+        let leaf_count = store.leaf_count(tree)?;
+        if leaf_count == 0 {
+            return Err(Error::EmptyTree);
+        }
+        stored_node(
+            store,
+            tree,
+            NodeId::new(Proof::expected_length(leaf_count), 0),
+        )
+    }
+
+    /// Returns the proof of inclusion of the leaf at `index` of the tree
+    /// stored under the name `tree`, reading only the nodes of its path in
+    /// one batch.
+    pub fn stored_proof<S: NodeStore + ?Sized>(
+        store: &S,
+        tree: &str,
+        index: usize,
+    ) -> Result<Proof, Error> {
+        let leaf_count = store.leaf_count(tree)?;
+        if index >= leaf_count {
+            return Err(Error::IndexOutOfRange { index, leaf_count });
+        }
+
+        let siblings = stored_nodes(store, tree, &Self::sibling_ids(leaf_count, index))?;
+
+        Ok(Proof {
+            leaf_index: index,
+            leaf_count,
+            siblings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn tree(count: usize) -> MerkleTree {
+        let items: Vec<String> = (0..count).map(|item| format!("mallorn {}", item)).collect();
+        MerkleTree::build(&items).unwrap()
+    }
+
+    #[test]
+    fn test_stored_trees_serve_the_same_proofs() {
+        let mut store = MemoryNodeStore::new();
+
+        for count in [1, 2, 5, 16, 33] {
+            let tree = tree(count);
+            tree.store(&mut store, "lórien").unwrap();
+
+            assert_eq!(
+                MerkleTree::stored_root(&store, "lórien"),
+                Ok(tree.root().unwrap())
+            );
+            for index in 0..count {
+                assert_eq!(
+                    MerkleTree::stored_proof(&store, "lórien", index),
+                    tree.proof(index)
+                );
+            }
+            assert!(MerkleTree::stored_proof(&store, "lórien", count).is_err());
+        }
+        assert_eq!(store.trees().unwrap(), vec!["lórien".to_string()]);
+    }
+
+    #[test]
+    fn test_appended_nodes_are_stored_incrementally() {
+        let mut store = MemoryNodeStore::new();
+        let mut tree = tree(11);
+        tree.store(&mut store, "lórien").unwrap();
+
+        for item in 11..40 {
+            tree.insert(&format!("mallorn {}", item)).unwrap();
+            tree.store_from(&mut store, "lórien", item).unwrap();
+        }
+
+        assert_eq!(
+            MerkleTree::stored_root(&store, "lórien"),
+            Ok(tree.root().unwrap())
+        );
+        assert_eq!(
+            MerkleTree::load(&store, "lórien").unwrap().root(),
+            tree.root()
+        );
+    }
+
+    #[test]
+    fn test_missing_trees_are_errors() {
+        let store = MemoryNodeStore::new();
+
+        assert_eq!(
+            MerkleTree::stored_root(&store, "fangorn"),
+            Err(Error::EmptyTree)
+        );
+        assert!(MerkleTree::load(&store, "fangorn").is_err());
+    }
+
+    /// Counts the calls made to a store.
+    #[derive(Default)]
+    struct CountingStore {
+        nodes: MemoryNodeStore,
+        reads: std::cell::Cell<usize>,
+        writes: usize,
+    }
+
+    impl NodeStore for CountingStore {
+        fn get(&self, tree: &str, id: NodeId) -> Result<Option<Hash>, Error> {
+            self.reads.set(self.reads.get() + 1);
+            self.nodes.get(tree, id)
+        }
+
+        fn get_batch(&self, tree: &str, ids: &[NodeId]) -> Result<Vec<Option<Hash>>, Error> {
+            self.reads.set(self.reads.get() + 1);
+            self.nodes.get_batch(tree, ids)
+        }
+
+        fn put(&mut self, tree: &str, nodes: &[(NodeId, Hash)]) -> Result<(), Error> {
+            self.writes += 1;
+            self.nodes.put(tree, nodes)
+        }
+
+        fn leaf_count(&self, tree: &str) -> Result<usize, Error> {
+            self.nodes.leaf_count(tree)
+        }
+
+        fn set_leaf_count(&mut self, tree: &str, leaf_count: usize) -> Result<(), Error> {
+            self.nodes.set_leaf_count(tree, leaf_count)
+        }
+
+        fn remove_tree(&mut self, tree: &str) -> Result<(), Error> {
+            self.nodes.remove_tree(tree)
+        }
+
+        fn trees(&self) -> Result<Vec<String>, Error> {
+            self.nodes.trees()
+        }
+    }
+
+    #[test]
+    fn test_operations_make_one_round_trip() {
+        let mut store = CountingStore::default();
+        let tree = tree(100);
+
+        tree.store(&mut store, "lórien").unwrap();
+        assert_eq!(store.writes, 1);
+
+        for index in [0, 63, 99] {
+            store.reads.set(0);
+            assert_eq!(
+                MerkleTree::stored_proof(&store, "lórien", index),
+                tree.proof(index)
+            );
+            assert_eq!(store.reads.get(), 1);
+        }
+
+        store.reads.set(0);
+        MerkleTree::load(&store, "lórien").unwrap();
+        assert_eq!(store.reads.get(), 1);
+    }
+}
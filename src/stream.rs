@@ -0,0 +1,135 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+
+use crate::error::Error;
+use crate::rolling::{RollingTree, WindowRoot};
+
+/// Consumes a stream of `(timestamp, record)` pairs and yields the
+/// commitment of each window as it closes, built on `RollingTree`.
+///
+/// The open window is closed and yielded when the input ends. Records of an
+/// already closed window yield an error and are skipped, the stream goes on.
+///
+/// # Examples
+/// ```
+/// use futures::executor::block_on;
+/// use futures::stream::{self, StreamExt};
+/// use merkle_tree::{RollingTree, WindowedRoots};
+///
+/// let records = stream::iter(vec![
+///     (1, "Osgiliath retaken"),
+///     (7, "Osgiliath lost"),
+///     (12, "Cair Andros holds"),
+/// ]);
+///
+/// let roots: Vec<_> = block_on(WindowedRoots::new(records, RollingTree::new(10).unwrap()).collect());
+///
+/// let counts: Vec<_> = roots.iter().map(|root| root.as_ref().unwrap().count).collect();
+/// assert_eq!(counts, vec![2, 1]);
+/// ```
+pub struct WindowedRoots<S> {
+    records: S,
+    rolling: RollingTree,
+    finished: bool,
+}
+
+impl<S> WindowedRoots<S> {
+    pub fn new(records: S, rolling: RollingTree) -> Self {
+        Self {
+            records,
+            rolling,
+            finished: false,
+        }
+    }
+
+    /// The rolling tree, with the roots of the windows already yielded.
+    pub fn rolling_tree(&self) -> &RollingTree {
+        &self.rolling
+    }
+}
+
+impl<S, T> Stream for WindowedRoots<S>
+where
+    S: Stream<Item = (u64, T)> + Unpin,
+    T: AsRef<[u8]>,
+{
+    type Item = Result<WindowRoot, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if this.finished {
+            return Poll::Ready(None);
+        }
+
+        loop {
+            match Pin::new(&mut this.records).poll_next(cx) {
+                Poll::Ready(Some((timestamp, record))) => {
+                    match this.rolling.push(timestamp, record.as_ref()) {
+                        Ok(None) => continue,
+                        Ok(Some(closed)) => return Poll::Ready(Some(Ok(closed))),
+                        Err(error) => return Poll::Ready(Some(Err(error))),
+                    }
+                }
+                Poll::Ready(None) => {
+                    this.finished = true;
+                    return Poll::Ready(this.rolling.close().map(Ok));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use futures::executor::block_on;
+    use futures::stream::{self, StreamExt};
+
+    #[test]
+    fn test_windows_are_yielded_in_order() {
+        let records =
+            stream::iter((0..25u64).map(|timestamp| (timestamp, timestamp.to_be_bytes())));
+
+        let roots: Vec<WindowRoot> = block_on(
+            WindowedRoots::new(records, RollingTree::new(10).unwrap())
+                .map(Result::unwrap)
+                .collect(),
+        );
+
+        assert_eq!(
+            roots
+                .iter()
+                .map(|root| (root.window, root.count))
+                .collect::<Vec<_>>(),
+            vec![(0, 10), (1, 10), (2, 5)]
+        );
+    }
+
+    #[test]
+    fn test_late_records_yield_errors_and_the_stream_goes_on() {
+        let records = stream::iter(vec![(1, "a"), (15, "b"), (3, "late"), (16, "c")]);
+
+        let roots: Vec<Result<WindowRoot, Error>> =
+            block_on(WindowedRoots::new(records, RollingTree::new(10).unwrap()).collect());
+
+        assert_eq!(roots.len(), 3);
+        assert_eq!(roots[0].as_ref().unwrap().window, 0);
+        assert!(roots[1].is_err());
+        assert_eq!(roots[2].as_ref().unwrap().count, 2);
+    }
+
+    #[test]
+    fn test_empty_stream_yields_nothing() {
+        let records = stream::iter(Vec::<(u64, Vec<u8>)>::new());
+
+        let mut roots = WindowedRoots::new(records, RollingTree::new(10).unwrap());
+
+        assert!(block_on(roots.next()).is_none());
+        assert!(block_on(roots.next()).is_none());
+    }
+}
@@ -0,0 +1,551 @@
+use std::collections::HashMap;
+
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Digest};
+
+use crate::error::Error;
+use crate::merkle_tree::Hash;
+
+const EMPTY_TRIE: u8 = 0;
+const LEAF_PREFIX: u8 = 0b01 << 6;
+const BRANCH_WITHOUT_VALUE_PREFIX: u8 = 0b10 << 6;
+const BRANCH_WITH_VALUE_PREFIX: u8 = 0b11 << 6;
+const HASHED_VALUE_LEAF_PREFIX: u8 = 0b001 << 5;
+const HASHED_VALUE_BRANCH_PREFIX: u8 = 0b0001 << 4;
+
+/// The hash of Substrate tries, BLAKE2b with a 32 bytes output.
+pub fn blake2_256(bytes: &[u8]) -> Hash {
+    Blake2b::<U32>::digest(bytes).into()
+}
+
+/// The value of a Substrate trie node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubstrateValue {
+    Inline(Vec<u8>),
+    /// The hash of a value stored in its own node, in the second version
+    /// of the trie layout.
+    Hashed(Hash),
+}
+
+/// A child of a Substrate branch node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubstrateChild {
+    Hash(Hash),
+    /// Nodes whose encoding is shorter than a hash are embedded in their parent.
+    Inline(Vec<u8>),
+}
+
+impl SubstrateChild {
+    /// The reference to an encoded node from its parent.
+    pub fn of(encoded: &[u8]) -> Self {
+        match encoded.len() < 32 {
+            true => SubstrateChild::Inline(encoded.to_vec()),
+            false => SubstrateChild::Hash(blake2_256(encoded)),
+        }
+    }
+}
+
+/// A node of the base-16 trie of Substrate chains, in the codec of
+/// `sp-trie`. Partial keys are nibbles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubstrateNode {
+    Empty,
+    Leaf {
+        partial: Vec<u8>,
+        value: SubstrateValue,
+    },
+    Branch {
+        partial: Vec<u8>,
+        children: Box<[Option<SubstrateChild>; 16]>,
+        value: Option<SubstrateValue>,
+    },
+}
+
+impl SubstrateNode {
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let mut input = Input(bytes);
+        let header = input.byte()?;
+
+        let node = match header & (0b11 << 6) {
+            LEAF_PREFIX => {
+                let partial = input.partial(header, 2)?;
+                SubstrateNode::Leaf {
+                    partial,
+                    value: SubstrateValue::Inline(input.prefixed()?.to_vec()),
+                }
+            }
+            BRANCH_WITH_VALUE_PREFIX | BRANCH_WITHOUT_VALUE_PREFIX => {
+                let partial = input.partial(header, 2)?;
+                let with_value = header & BRANCH_WITH_VALUE_PREFIX == BRANCH_WITH_VALUE_PREFIX;
+                input.branch(partial, with_value.then_some(false))?
+            }
+            _ if header == EMPTY_TRIE => SubstrateNode::Empty,
+            _ if header & (0b111 << 5) == HASHED_VALUE_LEAF_PREFIX => {
+                let partial = input.partial(header, 3)?;
+                SubstrateNode::Leaf {
+                    partial,
+                    value: SubstrateValue::Hashed(input.hash()?),
+                }
+            }
+            _ if header & (0b1111 << 4) == HASHED_VALUE_BRANCH_PREFIX => {
+                let partial = input.partial(header, 4)?;
+                input.branch(partial, Some(true))?
+            }
+            _ => return Err(malformed_node()),
+        };
+
+        if !input.0.is_empty() {
+            return Err(malformed_node());
+        }
+        Ok(node)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        match self {
+            SubstrateNode::Empty => out.push(EMPTY_TRIE),
+            SubstrateNode::Leaf { partial, value } => {
+                match value {
+                    SubstrateValue::Inline(_) => {
+                        write_header(LEAF_PREFIX, 2, partial.len(), &mut out)
+                    }
+                    SubstrateValue::Hashed(_) => {
+                        write_header(HASHED_VALUE_LEAF_PREFIX, 3, partial.len(), &mut out)
+                    }
+                }
+                write_partial(partial, &mut out);
+                write_value(value, &mut out);
+            }
+            SubstrateNode::Branch {
+                partial,
+                children,
+                value,
+            } => {
+                let (prefix, prefix_bits) = match value {
+                    None => (BRANCH_WITHOUT_VALUE_PREFIX, 2),
+                    Some(SubstrateValue::Inline(_)) => (BRANCH_WITH_VALUE_PREFIX, 2),
+                    Some(SubstrateValue::Hashed(_)) => (HASHED_VALUE_BRANCH_PREFIX, 4),
+                };
+                write_header(prefix, prefix_bits, partial.len(), &mut out);
+                write_partial(partial, &mut out);
+
+                let bitmap = (0..16)
+                    .filter(|&nibble| children[nibble].is_some())
+                    .fold(0u16, |bitmap, nibble| bitmap | 1 << nibble);
+                out.extend_from_slice(&bitmap.to_le_bytes());
+
+                if let Some(value) = value {
+                    write_value(value, &mut out);
+                }
+                for child in children.iter().flatten() {
+                    let bytes = match child {
+                        SubstrateChild::Hash(hash) => hash.as_slice(),
+                        SubstrateChild::Inline(node) => node.as_slice(),
+                    };
+                    write_compact(bytes.len(), &mut out);
+                    out.extend_from_slice(bytes);
+                }
+            }
+        }
+
+        out
+    }
+}
+
+struct Input<'a>(&'a [u8]);
+
+impl<'a> Input<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        if length > self.0.len() {
+            return Err(malformed_node());
+        }
+        let (taken, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn hash(&mut self) -> Result<Hash, Error> {
+        Ok(self.take(32)?.try_into().unwrap())
+    }
+
+    /// Decodes the number of nibbles of a partial key, in the low bits of
+    /// the header continued by the next bytes, then the nibbles.
+    fn partial(&mut self, header: u8, prefix_bits: u32) -> Result<Vec<u8>, Error> {
+        let max = 0xff >> prefix_bits;
+        let mut length = (header & max) as usize;
+        if length == max as usize {
+            length -= 1;
+            loop {
+                let next = self.byte()? as usize;
+                if length > u16::MAX as usize {
+                    return Err(malformed_node());
+                }
+                if next < 255 {
+                    length += next + 1;
+                    break;
+                }
+                length += 255;
+            }
+        }
+
+        let bytes = self.take(length.div_ceil(2))?;
+        let mut nibbles: Vec<u8> = bytes
+            .iter()
+            .flat_map(|byte| [byte >> 4, byte & 0x0f])
+            .collect();
+        if length % 2 == 1 {
+            // An odd key is padded with a zero nibble in front.
+            if nibbles[0] != 0 {
+                return Err(malformed_node());
+            }
+            nibbles.remove(0);
+        }
+        Ok(nibbles)
+    }
+
+    /// Decodes a SCALE compact integer.
+    fn compact(&mut self) -> Result<usize, Error> {
+        let first = self.byte()?;
+        let value = match first & 0b11 {
+            0b00 => (first >> 2) as u64,
+            0b01 => (u16::from_le_bytes([first, self.byte()?]) >> 2) as u64,
+            0b10 => {
+                let mut bytes = [first, 0, 0, 0];
+                bytes[1..].copy_from_slice(self.take(3)?);
+                (u32::from_le_bytes(bytes) >> 2) as u64
+            }
+            _ => {
+                let length = (first >> 2) as usize + 4;
+                if length > 8 {
+                    return Err(malformed_node());
+                }
+                let mut bytes = [0; 8];
+                bytes[..length].copy_from_slice(self.take(length)?);
+                u64::from_le_bytes(bytes)
+            }
+        };
+        usize::try_from(value).map_err(|_| malformed_node())
+    }
+
+    fn prefixed(&mut self) -> Result<&'a [u8], Error> {
+        let length = self.compact()?;
+        self.take(length)
+    }
+
+    /// Decodes the rest of a branch, whose value is hashed, inline or absent.
+    fn branch(
+        &mut self,
+        partial: Vec<u8>,
+        hashed_value: Option<bool>,
+    ) -> Result<SubstrateNode, Error> {
+        let bitmap = u16::from_le_bytes(self.take(2)?.try_into().unwrap());
+        let value = match hashed_value {
+            Some(true) => Some(SubstrateValue::Hashed(self.hash()?)),
+            Some(false) => Some(SubstrateValue::Inline(self.prefixed()?.to_vec())),
+            None => None,
+        };
+
+        let mut children: Box<[Option<SubstrateChild>; 16]> = Box::default();
+        for (nibble, child) in children.iter_mut().enumerate() {
+            if bitmap & 1 << nibble == 0 {
+                continue;
+            }
+            let bytes = self.prefixed()?;
+            *child = Some(match bytes.len() {
+                32 => SubstrateChild::Hash(bytes.try_into().unwrap()),
+                length if length < 32 => SubstrateChild::Inline(bytes.to_vec()),
+                _ => return Err(malformed_node()),
+            });
+        }
+
+        Ok(SubstrateNode::Branch {
+            partial,
+            children,
+            value,
+        })
+    }
+}
+
+fn write_header(prefix: u8, prefix_bits: u32, length: usize, out: &mut Vec<u8>) {
+    let max = (0xff >> prefix_bits) as usize;
+    if length < max {
+        out.push(prefix | length as u8);
+        return;
+    }
+
+    out.push(prefix | max as u8);
+    let mut rest = length - (max - 1);
+    while rest >= 256 {
+        out.push(255);
+        rest -= 255;
+    }
+    out.push((rest - 1) as u8);
+}
+
+fn write_partial(nibbles: &[u8], out: &mut Vec<u8>) {
+    let (first, pairs) = nibbles.split_at(nibbles.len() % 2);
+    out.extend_from_slice(first);
+    out.extend(pairs.chunks(2).map(|pair| pair[0] << 4 | pair[1]));
+}
+
+fn write_value(value: &SubstrateValue, out: &mut Vec<u8>) {
+    match value {
+        SubstrateValue::Inline(bytes) => {
+            write_compact(bytes.len(), out);
+            out.extend_from_slice(bytes);
+        }
+        SubstrateValue::Hashed(hash) => out.extend_from_slice(hash),
+    }
+}
+
+fn write_compact(value: usize, out: &mut Vec<u8>) {
+    match value {
+        0..=0x3f => out.push((value as u8) << 2),
+        0x40..=0x3fff => out.extend_from_slice(&((value as u16) << 2 | 0b01).to_le_bytes()),
+        0x4000..=0x3fff_ffff => out.extend_from_slice(&((value as u32) << 2 | 0b10).to_le_bytes()),
+        _ => {
+            let bytes = (value as u64).to_le_bytes();
+            let length = 8 - bytes.iter().rev().take_while(|byte| **byte == 0).count();
+            out.push(((length - 4) as u8) << 2 | 0b11);
+            out.extend_from_slice(&bytes[..length]);
+        }
+    }
+}
+
+fn malformed_node() -> Error {
+    Error::InvalidInput("malformed trie node".to_string())
+}
+
+/// Reads the values of `keys` from a storage proof of a Substrate chain,
+/// as returned by `state_getReadProof`, against a state root.
+///
+/// The proof is the set of encoded nodes on the paths of the keys, in any
+/// order. Returns the value of every key, `None` for the keys the proof
+/// shows are absent. Fails if a node on a path is missing or malformed.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{blake2_256, read_proof_check, SubstrateNode, SubstrateValue};
+///
+/// let leaf = SubstrateNode::Leaf {
+///     partial: vec![0x6, 0xd, 0x6, 0x1, 0x7, 0x2],
+///     value: SubstrateValue::Inline(b"Bilbo".to_vec()),
+/// }
+/// .encode();
+/// let root = blake2_256(&leaf);
+///
+/// let values = read_proof_check(&root, &[leaf], &[b"mar".as_slice(), b"ent"]).unwrap();
+/// assert_eq!(values, vec![Some(b"Bilbo".to_vec()), None]);
+/// ```
+pub fn read_proof_check(
+    root: &Hash,
+    proof: &[Vec<u8>],
+    keys: &[&[u8]],
+) -> Result<Vec<Option<Vec<u8>>>, Error> {
+    let nodes: HashMap<Hash, &[u8]> = proof
+        .iter()
+        .map(|node| (blake2_256(node), node.as_slice()))
+        .collect();
+    let lookup = |hash: &Hash| {
+        nodes
+            .get(hash)
+            .copied()
+            .ok_or_else(|| Error::InvalidInput("incomplete trie proof".to_string()))
+    };
+    let resolve = |value: SubstrateValue| match value {
+        SubstrateValue::Inline(bytes) => Ok(bytes),
+        SubstrateValue::Hashed(hash) => lookup(&hash).map(<[u8]>::to_vec),
+    };
+
+    keys.iter()
+        .map(|key| {
+            let nibbles: Vec<u8> = key
+                .iter()
+                .flat_map(|byte| [byte >> 4, byte & 0x0f])
+                .collect();
+            let mut rest = nibbles.as_slice();
+            let mut node = SubstrateNode::decode(lookup(root)?)?;
+
+            loop {
+                node = match node {
+                    SubstrateNode::Empty => return Ok(None),
+                    SubstrateNode::Leaf { partial, value } => {
+                        return (partial == rest).then(|| resolve(value)).transpose();
+                    }
+                    SubstrateNode::Branch {
+                        partial,
+                        mut children,
+                        value,
+                    } => {
+                        let Some(after) = rest.strip_prefix(partial.as_slice()) else {
+                            return Ok(None);
+                        };
+                        let Some((&nibble, after)) = after.split_first() else {
+                            return value.map(resolve).transpose();
+                        };
+                        rest = after;
+
+                        match children[nibble as usize].take() {
+                            None => return Ok(None),
+                            Some(SubstrateChild::Hash(hash)) => {
+                                SubstrateNode::decode(lookup(&hash)?)?
+                            }
+                            Some(SubstrateChild::Inline(bytes)) => SubstrateNode::decode(&bytes)?,
+                        }
+                    }
+                };
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::merkle_tree::MerkleTree;
+
+    fn nibbles(bytes: &[u8]) -> Vec<u8> {
+        bytes
+            .iter()
+            .flat_map(|byte| [byte >> 4, byte & 0x0f])
+            .collect()
+    }
+
+    /// A trie of "cat", "do", "doe" and "dog", the value of "dog" in its own node.
+    fn trie() -> (Hash, Vec<Vec<u8>>, Vec<u8>) {
+        let puppy = b"a puppy that grows into a hound of Huan's size".to_vec();
+
+        let cat = SubstrateNode::Leaf {
+            partial: nibbles(b"cat")[2..].to_vec(),
+            value: SubstrateValue::Inline(b"Tevildo".to_vec()),
+        }
+        .encode();
+        let doe = SubstrateNode::Leaf {
+            partial: vec![],
+            value: SubstrateValue::Inline(b"a doe of Ithilien, fleet and shy".to_vec()),
+        }
+        .encode();
+        let dog = SubstrateNode::Leaf {
+            partial: vec![],
+            value: SubstrateValue::Hashed(blake2_256(&puppy)),
+        }
+        .encode();
+
+        let mut children: Box<[Option<SubstrateChild>; 16]> = Box::default();
+        children[5] = Some(SubstrateChild::of(&doe));
+        children[7] = Some(SubstrateChild::of(&dog));
+        let dox = SubstrateNode::Branch {
+            partial: vec![],
+            children,
+            value: None,
+        }
+        .encode();
+
+        let mut children: Box<[Option<SubstrateChild>; 16]> = Box::default();
+        children[6] = Some(SubstrateChild::of(&dox));
+        let d = SubstrateNode::Branch {
+            partial: vec![6, 0xf],
+            children,
+            value: Some(SubstrateValue::Inline(b"a deed".to_vec())),
+        }
+        .encode();
+
+        let mut children: Box<[Option<SubstrateChild>; 16]> = Box::default();
+        children[3] = Some(SubstrateChild::of(&cat));
+        children[4] = Some(SubstrateChild::of(&d));
+        let root = SubstrateNode::Branch {
+            partial: vec![6],
+            children,
+            value: None,
+        }
+        .encode();
+
+        (
+            blake2_256(&root),
+            vec![dog, puppy.clone(), root, doe, dox, d, cat],
+            puppy,
+        )
+    }
+
+    #[test]
+    fn test_values_are_read_from_proofs() {
+        let (root, proof, puppy) = trie();
+
+        let values = read_proof_check(
+            &root,
+            &proof,
+            &[b"cat", b"doe", b"dog", b"do", b"dot", b"cow"],
+        )
+        .unwrap();
+
+        assert_eq!(
+            values,
+            vec![
+                Some(b"Tevildo".to_vec()),
+                Some(b"a doe of Ithilien, fleet and shy".to_vec()),
+                Some(puppy),
+                Some(b"a deed".to_vec()),
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_incomplete_or_forged_proofs_are_rejected() {
+        let (root, mut proof, _) = trie();
+
+        assert!(read_proof_check(&root, &proof[1..], &[b"dog"]).is_err());
+        assert!(read_proof_check(&MerkleTree::hash(b"Sauron"), &proof, &[b"cat"]).is_err());
+
+        proof[0] = SubstrateNode::Leaf {
+            partial: vec![],
+            value: SubstrateValue::Inline(b"a warg".to_vec()),
+        }
+        .encode();
+        assert!(read_proof_check(&root, &proof, &[b"dog"]).is_err());
+    }
+
+    #[test]
+    fn test_nodes_round_trip_through_the_codec() {
+        // The root of an empty trie in every Substrate chain.
+        assert_eq!(
+            hex::encode(blake2_256(&SubstrateNode::Empty.encode())),
+            "03170a2e7597b7b7e3d84c05391d139a62b157e78786d8c082f29dcf4c111314"
+        );
+
+        for length in [0, 1, 2, 15, 62, 63, 64, 317, 318, 600] {
+            let partial: Vec<u8> = (0..length).map(|nibble| (nibble % 16) as u8).collect();
+            let nodes = [
+                SubstrateNode::Leaf {
+                    partial: partial.clone(),
+                    value: SubstrateValue::Inline(vec![7; length * 3]),
+                },
+                SubstrateNode::Leaf {
+                    partial: partial.clone(),
+                    value: SubstrateValue::Hashed([7; 32]),
+                },
+                SubstrateNode::Branch {
+                    partial: partial.clone(),
+                    children: Box::new(std::array::from_fn(|nibble| {
+                        (nibble % 3 == 0)
+                            .then(|| SubstrateChild::Inline(vec![nibble as u8; nibble]))
+                    })),
+                    value: Some(SubstrateValue::Hashed([1; 32])),
+                },
+            ];
+            for node in nodes {
+                assert_eq!(SubstrateNode::decode(&node.encode()), Ok(node));
+            }
+        }
+
+        assert!(SubstrateNode::decode(&[0x01]).is_err());
+        assert!(SubstrateNode::decode(&[0x41, 0x16, 0]).is_err());
+    }
+}
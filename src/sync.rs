@@ -0,0 +1,486 @@
+use std::ops::Range;
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+
+/// A connection to a peer holding another tree, used by `MerkleTree::reconcile`.
+///
+/// Implementations send the requests over whatever channel connects the
+/// peers. On the other side, `MerkleTree::leaf_count` and
+/// `MerkleTree::node_hashes` answer them.
+pub trait Transport {
+    /// Returns the number of leaves of the peer's tree.
+    fn leaf_count(&mut self) -> Result<usize, Error>;
+
+    /// Returns the peer's hashes of the nodes at `indices` of `level`,
+    /// `None` for the nodes its tree does not have.
+    fn node_hashes(&mut self, level: usize, indices: &[usize]) -> Result<Vec<Option<Hash>>, Error>;
+}
+
+/// A tree in the same process can answer the requests directly.
+impl Transport for &MerkleTree {
+    fn leaf_count(&mut self) -> Result<usize, Error> {
+        Ok(MerkleTree::leaf_count(self))
+    }
+
+    fn node_hashes(&mut self, level: usize, indices: &[usize]) -> Result<Vec<Option<Hash>>, Error> {
+        Ok(MerkleTree::node_hashes(self, level, indices))
+    }
+}
+
+impl MerkleTree {
+    /// Returns the hashes of the nodes at `indices` of `level`,
+    /// `None` for the nodes the tree does not have.
+    pub fn node_hashes(&self, level: usize, indices: &[usize]) -> Vec<Option<Hash>> {
+        indices
+            .iter()
+            .map(|&index| self.node(level, index))
+            .collect()
+    }
+
+    /// Finds the indices of the leaves that differ between this tree and the
+    /// peer's, including the leaves only one of them has.
+    ///
+    /// Both trees are compared level by level from the top, and only the
+    /// children of differing nodes are requested at the next level. With `d`
+    /// differences the peers exchange one message per level and `O(d·log n)`
+    /// hashes, instead of the whole leaf lists.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::MerkleTree;
+    ///
+    /// let ours = MerkleTree::build(&["Bag End", "Bree", "Rivendell", "Moria"]).unwrap();
+    /// let theirs = MerkleTree::build(&["Bag End", "Bree", "Lothlórien", "Moria"]).unwrap();
+    ///
+    /// let differences = ours.reconcile(&mut &theirs).unwrap();
+    ///
+    /// assert_eq!(differences, vec![2]);
+    /// ```
+    ///
+    /// The leaves only the peer has are listed one by one, so the peer's
+    /// leaf count is checked against the `Limits` of the tree first. With an
+    /// untrusted peer, set them or use `reconcile_ranges`.
+    pub fn reconcile<T: Transport>(&self, transport: &mut T) -> Result<Vec<usize>, Error> {
+        Ok(self
+            .reconcile_ranges(transport)?
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+
+    /// Like `reconcile`, with the differing leaves merged into ranges.
+    ///
+    /// Only the nodes both trees have are requested, so the work and the
+    /// memory are bounded by the smaller tree, whatever leaf count the
+    /// peer claims: the leaves past its end are a single range.
+    pub fn reconcile_ranges<T: Transport>(
+        &self,
+        transport: &mut T,
+    ) -> Result<Vec<Range<usize>>, Error> {
+        let other_leaf_count = transport.leaf_count()?;
+        self.limits().check_leaf_count(other_leaf_count)?;
+
+        self.differing_ranges(other_leaf_count, |level, indices| {
+            let hashes = transport.node_hashes(level, indices)?;
+            if hashes.len() != indices.len() {
+                return Err(Error::InvalidInput(format!(
+                    "requested {} hashes, received {}",
+                    indices.len(),
+                    hashes.len()
+                )));
+            }
+            Ok(hashes)
+        })
+    }
+
+    /// Returns the indices of the leaves that differ between both trees,
+    /// including the leaves only one of them has.
+    ///
+    /// Only the subtrees whose roots differ are visited, so each difference
+    /// costs `O(log n)` comparisons instead of a scan of every leaf. When the
+    /// sizes differ, the leaves past the end of the smaller tree are always
+    /// included, even if the roots match.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::MerkleTree;
+    ///
+    /// let original = MerkleTree::build(&["Three Rings", "Seven", "Nine"]).unwrap();
+    /// let tampered = MerkleTree::build(&["Three Rings", "Eight", "Nine"]).unwrap();
+    ///
+    /// assert_eq!(original.diff(&tampered), vec![1]);
+    /// ```
+    pub fn diff(&self, other: &MerkleTree) -> Vec<usize> {
+        self.differing_ranges(other.leaf_count(), |level, indices| {
+            Ok(other.node_hashes(level, indices))
+        })
+        .expect("Reading the other tree can't fail.")
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// Returns the lowest index of a leaf that differs between both trees,
+    /// including the leaves only one of them has, `None` if they are equal.
+    ///
+    /// Only unequal subtrees are descended, the left one first, so the
+    /// first difference costs `O(log n)` comparisons.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::MerkleTree;
+    ///
+    /// let ours = MerkleTree::build(&["Eorl", "Brego", "Aldor", "Fréa", "Fréawine"]).unwrap();
+    /// let theirs = MerkleTree::build(&["Eorl", "Brego", "Aldor", "Frumgar", "Fréawine"]).unwrap();
+    ///
+    /// assert_eq!(ours.first_divergence(&theirs), Some(3));
+    /// assert_eq!(ours.first_divergence(&ours), None);
+    /// ```
+    pub fn first_divergence(&self, other: &MerkleTree) -> Option<usize> {
+        let start = self.height().min(other.height());
+        let width = Self::level_width(self.leaf_count(), start)
+            .max(Self::level_width(other.leaf_count(), start));
+
+        let found = (0..width).find_map(|index| self.first_divergence_below(other, start, index));
+
+        // Repeated trailing leaves can make trees of different sizes agree
+        // on every node they both have, the first missing leaf still differs.
+        let shortest = self.leaf_count().min(other.leaf_count());
+        if self.leaf_count() != other.leaf_count() {
+            return Some(found.map_or(shortest, |found| found.min(shortest)));
+        }
+
+        found
+    }
+
+    fn first_divergence_below(
+        &self,
+        other: &MerkleTree,
+        level: usize,
+        index: usize,
+    ) -> Option<usize> {
+        if self.node(level, index) == other.node(level, index) {
+            return None;
+        }
+        if level == 0 {
+            return Some(index);
+        }
+
+        self.first_divergence_below(other, level - 1, 2 * index)
+            .or_else(|| self.first_divergence_below(other, level - 1, 2 * index + 1))
+    }
+
+    /// Descends from the highest level both trees have into the nodes that
+    /// differ, fetching the other tree's nodes one level at a time.
+    ///
+    /// Only the nodes of both trees are compared, the leaves past the end of
+    /// the smaller one are all returned as the last range.
+    pub(crate) fn differing_ranges<F>(
+        &self,
+        other_leaf_count: usize,
+        mut other_nodes: F,
+    ) -> Result<Vec<Range<usize>>, Error>
+    where
+        F: FnMut(usize, &[usize]) -> Result<Vec<Option<Hash>>, Error>,
+    {
+        let own_leaf_count = self.leaf_count();
+        let shortest = own_leaf_count.min(other_leaf_count);
+        // Repeated trailing leaves can make trees of different sizes agree
+        // on every node they both have, the leaves only one has still differ.
+        let tail = shortest..own_leaf_count.max(other_leaf_count);
+
+        let mut differences = Vec::new();
+        if shortest > 0 {
+            let other_height = (0..)
+                .take_while(|&level| Self::level_width(other_leaf_count, level) > 0)
+                .last()
+                .unwrap_or(0);
+            let start = self.height().min(other_height);
+
+            let mut candidates: Vec<usize> = (0..Self::level_width(shortest, start)).collect();
+
+            for level in (0..=start).rev() {
+                if candidates.is_empty() {
+                    break;
+                }
+
+                let theirs = other_nodes(level, &candidates)?;
+                let mut next = Vec::new();
+
+                for (&index, their_hash) in candidates.iter().zip(theirs) {
+                    if self.node(level, index) == their_hash {
+                        continue;
+                    }
+
+                    if level == 0 {
+                        differences.push(index);
+                    } else {
+                        let children_width = Self::level_width(shortest, level - 1);
+                        next.extend(
+                            [2 * index, 2 * index + 1]
+                                .into_iter()
+                                .filter(|&child| child < children_width),
+                        );
+                    }
+                }
+
+                candidates = next;
+            }
+        }
+
+        differences.sort_unstable();
+        differences.dedup();
+        let mut ranges = coalesce(&differences);
+        match ranges.last_mut() {
+            Some(last) if last.end == tail.start => last.end = tail.end,
+            _ if !tail.is_empty() => ranges.push(tail),
+            _ => {}
+        }
+        Ok(ranges)
+    }
+}
+
+/// Merges sorted indices into ranges of consecutive indices.
+pub(crate) fn coalesce(indices: &[usize]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+
+    for &index in indices {
+        match ranges.last_mut() {
+            Some(range) if range.end == index => range.end += 1,
+            _ => ranges.push(index..index + 1),
+        }
+    }
+
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::limits::Limits;
+
+    /// Counts the messages exchanged with the peer.
+    struct CountingTransport<'a> {
+        tree: &'a MerkleTree,
+        messages: usize,
+        hashes: usize,
+    }
+
+    impl Transport for CountingTransport<'_> {
+        fn leaf_count(&mut self) -> Result<usize, Error> {
+            self.messages += 1;
+            Ok(self.tree.leaf_count())
+        }
+
+        fn node_hashes(
+            &mut self,
+            level: usize,
+            indices: &[usize],
+        ) -> Result<Vec<Option<Hash>>, Error> {
+            self.messages += 1;
+            self.hashes += indices.len();
+            Ok(self.tree.node_hashes(level, indices))
+        }
+    }
+
+    fn verses(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|verse| format!("The Road goes ever on and on, verse {}", verse))
+            .collect()
+    }
+
+    #[test]
+    fn test_identical_trees_have_no_differences() {
+        let tree = MerkleTree::build(&verses(13)).unwrap();
+
+        assert!(tree.reconcile(&mut &tree).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reconcile_finds_changed_leaves() {
+        let ours = verses(100);
+        let mut theirs = verses(100);
+        theirs[3] = "Fly, you fools!".to_string();
+        theirs[77] = "You shall not pass!".to_string();
+
+        let our_tree = MerkleTree::build(&ours).unwrap();
+        let their_tree = MerkleTree::build(&theirs).unwrap();
+
+        let mut transport = CountingTransport {
+            tree: &their_tree,
+            messages: 0,
+            hashes: 0,
+        };
+
+        let differences = our_tree.reconcile(&mut transport).unwrap();
+
+        assert_eq!(differences, vec![3, 77]);
+        assert_eq!(transport.messages, 1 + our_tree.height() + 1);
+        assert!(transport.hashes < 2 * 2 * (our_tree.height() + 1));
+    }
+
+    #[test]
+    fn test_reconcile_trees_of_different_sizes() {
+        let ours = MerkleTree::build(&verses(5)).unwrap();
+        let theirs = MerkleTree::build(&verses(6)).unwrap();
+        let small = MerkleTree::build(&verses(2)).unwrap();
+
+        assert_eq!(ours.reconcile(&mut &theirs).unwrap(), vec![5]);
+        assert_eq!(theirs.reconcile(&mut &ours).unwrap(), vec![5]);
+        assert_eq!(small.reconcile(&mut &ours).unwrap(), vec![2, 3, 4]);
+        assert_eq!(ours.reconcile(&mut &small).unwrap(), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_reconcile_trees_with_a_repeated_tail() {
+        let mut repeated = verses(5);
+        repeated.push(repeated[4].clone());
+        let ours = MerkleTree::build(&verses(5)).unwrap();
+        let theirs = MerkleTree::build(&repeated).unwrap();
+        assert_eq!(ours.root(), theirs.root());
+
+        assert_eq!(ours.reconcile(&mut &theirs).unwrap(), vec![5]);
+        assert_eq!(theirs.reconcile(&mut &ours).unwrap(), vec![5]);
+    }
+
+    #[test]
+    fn test_diff_localizes_tampered_leaves() {
+        let original = verses(37);
+        let mut tampered = original.clone();
+        tampered[0] = "LONG LIVE SAURON".to_string();
+        tampered[36] = "LONG LIVE SARUMAN".to_string();
+
+        let original_tree = MerkleTree::build(&original).unwrap();
+        let tampered_tree = MerkleTree::build(&tampered).unwrap();
+
+        assert_eq!(original_tree.diff(&tampered_tree), vec![0, 36]);
+        assert_eq!(tampered_tree.diff(&original_tree), vec![0, 36]);
+        assert!(original_tree.diff(&original_tree).is_empty());
+
+        // Same root, but one tree has the last leaf twice.
+        let mut repeated = verses(5);
+        repeated.push(repeated[4].clone());
+        let repeated_tree = MerkleTree::build(&repeated).unwrap();
+        let five = MerkleTree::build(&verses(5)).unwrap();
+        assert_eq!(five.diff(&repeated_tree), vec![5]);
+        assert_eq!(repeated_tree.diff(&five), vec![5]);
+    }
+
+    #[test]
+    fn test_first_divergence() {
+        let original = verses(37);
+        let mut tampered = original.clone();
+        tampered[20] = "One does not simply walk into Mordor".to_string();
+        tampered[30] = "What about second breakfast?".to_string();
+
+        let original_tree = MerkleTree::build(&original).unwrap();
+        let tampered_tree = MerkleTree::build(&tampered).unwrap();
+
+        assert_eq!(original_tree.first_divergence(&tampered_tree), Some(20));
+        assert_eq!(tampered_tree.first_divergence(&original_tree), Some(20));
+        assert_eq!(original_tree.first_divergence(&original_tree), None);
+
+        let longer = MerkleTree::build(&verses(40)).unwrap();
+        assert_eq!(original_tree.first_divergence(&longer), Some(37));
+        assert_eq!(tampered_tree.first_divergence(&longer), Some(20));
+
+        // Same root, but one tree has the last leaf twice.
+        let mut repeated = verses(5);
+        repeated.push(repeated[4].clone());
+        let repeated_tree = MerkleTree::build(&repeated).unwrap();
+        let five = MerkleTree::build(&verses(5)).unwrap();
+        assert_eq!(five.first_divergence(&repeated_tree), Some(5));
+    }
+
+    #[test]
+    fn test_reconcile_rejects_oversized_peers() {
+        struct HugeTransport;
+
+        impl Transport for HugeTransport {
+            fn leaf_count(&mut self) -> Result<usize, Error> {
+                Ok(usize::MAX / 2)
+            }
+
+            fn node_hashes(&mut self, _: usize, _: &[usize]) -> Result<Vec<Option<Hash>>, Error> {
+                unreachable!("The leaf count is rejected first.")
+            }
+        }
+
+        let limits = Limits {
+            max_leaves: 1 << 20,
+            ..Default::default()
+        };
+        let tree = MerkleTree::builder()
+            .limits(limits)
+            .build(&verses(4))
+            .unwrap();
+
+        assert!(matches!(
+            tree.reconcile(&mut HugeTransport),
+            Err(Error::LimitExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn test_reconcile_ranges_of_oversized_peers() {
+        /// A peer agreeing on every node but claiming a huge tree.
+        struct BoastingTransport<'a>(&'a MerkleTree);
+
+        impl Transport for BoastingTransport<'_> {
+            fn leaf_count(&mut self) -> Result<usize, Error> {
+                Ok(usize::MAX / 2)
+            }
+
+            fn node_hashes(
+                &mut self,
+                level: usize,
+                indices: &[usize],
+            ) -> Result<Vec<Option<Hash>>, Error> {
+                assert!(indices.len() <= 4);
+                Ok(self.0.node_hashes(level, indices))
+            }
+        }
+
+        let tree = MerkleTree::build(&verses(4)).unwrap();
+
+        assert_eq!(
+            tree.reconcile_ranges(&mut BoastingTransport(&tree)),
+            Ok(std::iter::once(4..usize::MAX / 2).collect())
+        );
+    }
+
+    #[test]
+    fn test_reconcile_ranges_merge_the_tail() {
+        let ours = MerkleTree::build(&verses(8)).unwrap();
+        let mut theirs = verses(5);
+        theirs[4] = "Where many paths and errands meet".to_string();
+        let theirs = MerkleTree::build(&theirs).unwrap();
+
+        assert_eq!(
+            ours.reconcile_ranges(&mut &theirs),
+            Ok(std::iter::once(4..8).collect())
+        );
+        assert_eq!(ours.reconcile(&mut &theirs), Ok(vec![4, 5, 6, 7]));
+    }
+
+    #[test]
+    fn test_reconcile_rejects_short_answers() {
+        struct SilentTransport;
+
+        impl Transport for SilentTransport {
+            fn leaf_count(&mut self) -> Result<usize, Error> {
+                Ok(4)
+            }
+
+            fn node_hashes(&mut self, _: usize, _: &[usize]) -> Result<Vec<Option<Hash>>, Error> {
+                Ok(Vec::new())
+            }
+        }
+
+        let tree = MerkleTree::build(&verses(4)).unwrap();
+
+        assert!(tree.reconcile(&mut SilentTransport).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt::Display;
+use std::ops::Range;
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::sync::{coalesce, Transport};
+
+/// A tree over the rows of a database table, to compare a primary with its replicas.
+///
+/// Rows are read from a cursor and turned into bytes by a closure supplied
+/// by the caller, so the helper works with any driver. Both sides must read
+/// the rows in the same order, e.g. sorted by primary key, and encode them the
+/// same way.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::TableDigest;
+///
+/// let primary = vec![(1, "Bilbo"), (2, "Frodo"), (3, "Samwise"), (4, "Meriadoc")];
+/// let replica = vec![(1, "Bilbo"), (2, "Frodo"), (3, "Samwise Gamgee"), (4, "Meriadoc")];
+///
+/// let encode = |row: &(i32, &str)| format!("{}|{}", row.0, row.1).into_bytes();
+///
+/// let primary = TableDigest::from_rows(primary.into_iter().map(Ok::<_, String>), encode).unwrap();
+/// let replica = TableDigest::from_rows(replica.into_iter().map(Ok::<_, String>), encode).unwrap();
+///
+/// assert_eq!(primary.divergent_ranges(&replica), vec![2..3]);
+/// ```
+pub struct TableDigest {
+    tree: Option<MerkleTree>,
+}
+
+impl TableDigest {
+    /// Builds the digest from a cursor over the rows.
+    /// Fails with the first error of the cursor.
+    pub fn from_rows<I, R, E, F>(rows: I, mut encode: F) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = Result<R, E>>,
+        E: Display,
+        F: FnMut(&R) -> Vec<u8>,
+    {
+        let leaves = rows
+            .into_iter()
+            .enumerate()
+            .map(|(index, row)| {
+                row.map(|row| MerkleTree::hash(&encode(&row)))
+                    .map_err(|error| {
+                        Error::InvalidInput(format!("could not read row {}: {}", index, error))
+                    })
+            })
+            .collect::<Result<Vec<Hash>, Error>>()?;
+
+        Ok(Self {
+            tree: MerkleTree::from_leaves(leaves),
+        })
+    }
+
+    /// The root over every row, `None` for an empty table.
+    pub fn root(&self) -> Option<Hash> {
+        self.tree.as_ref().and_then(MerkleTree::root)
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.tree.as_ref().map_or(0, MerkleTree::leaf_count)
+    }
+
+    /// The tree over the rows, `None` for an empty table.
+    /// A replica can serve it to a primary through `Transport`.
+    pub fn tree(&self) -> Option<&MerkleTree> {
+        self.tree.as_ref()
+    }
+
+    /// Whether both tables hold the same rows.
+    pub fn matches(&self, other: &TableDigest) -> bool {
+        self.row_count() == other.row_count() && self.root() == other.root()
+    }
+
+    /// The ranges of row positions that differ between both tables,
+    /// including the rows only one of them has.
+    pub fn divergent_ranges(&self, other: &TableDigest) -> Vec<Range<usize>> {
+        match (&self.tree, &other.tree) {
+            (Some(ours), Some(theirs)) => coalesce(&ours.diff(theirs)),
+            _ => coalesce(&(0..self.row_count().max(other.row_count())).collect::<Vec<_>>()),
+        }
+    }
+
+    /// Like `divergent_ranges`, with the other table behind a transport,
+    /// so only the hashes of the differing subtrees are exchanged.
+    pub fn reconcile_ranges<T: Transport>(
+        &self,
+        transport: &mut T,
+    ) -> Result<Vec<Range<usize>>, Error> {
+        match &self.tree {
+            Some(tree) => tree.reconcile_ranges(transport),
+            // A single range, whatever row count the other side claims.
+            None => Ok(match transport.leaf_count()? {
+                0 => Vec::new(),
+                row_count => std::iter::once(0..row_count).collect(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::convert::Infallible;
+
+    fn digest(rows: &[String]) -> TableDigest {
+        TableDigest::from_rows(rows.iter().map(Ok::<_, Infallible>), |row| {
+            row.as_bytes().to_vec()
+        })
+        .unwrap()
+    }
+
+    fn rows(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|id| format!("{}|ranger of the North|{}", id, id * 7))
+            .collect()
+    }
+
+    #[test]
+    fn test_identical_tables_match() {
+        let primary = digest(&rows(50));
+        let replica = digest(&rows(50));
+
+        assert!(primary.matches(&replica));
+        assert!(primary.divergent_ranges(&replica).is_empty());
+    }
+
+    #[test]
+    fn test_divergent_rows_are_grouped_in_ranges() {
+        let primary_rows = rows(64);
+        let mut replica_rows = rows(64);
+        for row in &mut replica_rows[10..13] {
+            row.push_str("|stale");
+        }
+        replica_rows[40] = "40|orc|0".to_string();
+        replica_rows.truncate(60);
+
+        let primary = digest(&primary_rows);
+        let replica = digest(&replica_rows);
+
+        assert!(!primary.matches(&replica));
+        assert_eq!(
+            primary.divergent_ranges(&replica),
+            vec![10..13, 40..41, 60..64]
+        );
+
+        let mut transport = replica.tree().unwrap();
+        assert_eq!(
+            primary.reconcile_ranges(&mut transport).unwrap(),
+            vec![10..13, 40..41, 60..64]
+        );
+    }
+
+    #[test]
+    fn test_empty_tables() {
+        let empty = digest(&[]);
+
+        assert_eq!(empty.root(), None);
+        assert_eq!(empty.divergent_ranges(&digest(&rows(3))), vec![0..3]);
+        assert!(empty.matches(&digest(&[])));
+    }
+
+    #[test]
+    fn test_cursor_errors_are_reported() {
+        let rows = vec![Ok("Elendil".to_string()), Err("connection reset")];
+
+        let result = TableDigest::from_rows(rows, |row| row.as_bytes().to_vec());
+
+        assert!(matches!(result, Err(Error::InvalidInput(reason)) if reason.contains("row 1")));
+    }
+}
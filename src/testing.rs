@@ -0,0 +1,150 @@
+use proptest::prelude::*;
+
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// The largest tree generated by the strategies of this module.
+const MAX_LEAVES: usize = 200;
+
+/// Generates sets of 1 to `max_leaves` leaves, duplicates included.
+pub fn leaf_sets(max_leaves: usize) -> impl Strategy<Value = Vec<Hash>> {
+    prop::collection::vec(any::<Hash>(), 1..=max_leaves.max(1))
+}
+
+impl Arbitrary for MerkleTree {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        leaf_sets(MAX_LEAVES)
+            .prop_map(|leaves| MerkleTree::from_leaves(leaves).expect("The set has leaves."))
+            .boxed()
+    }
+}
+
+/// A tree with a leaf and its valid proof of inclusion.
+#[derive(Debug, Clone)]
+pub struct ProofCase {
+    pub tree: MerkleTree,
+    pub leaf: Hash,
+    pub proof: Proof,
+}
+
+impl ProofCase {
+    pub fn root(&self) -> Hash {
+        self.tree.root().expect("A tree has a root.")
+    }
+}
+
+impl Arbitrary for ProofCase {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (any::<MerkleTree>(), any::<prop::sample::Index>())
+            .prop_map(|(tree, index)| {
+                let index = index.index(tree.leaf_count());
+                ProofCase {
+                    leaf: tree.node(0, index).expect("The index is in the tree."),
+                    proof: tree.proof(index).expect("The index is in the tree."),
+                    tree,
+                }
+            })
+            .boxed()
+    }
+}
+
+/// How a `MutatedProof` was tampered with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofMutation {
+    /// One bit of a sibling is flipped.
+    FlipSibling { level: usize, bit: usize },
+    /// One bit of the leaf is flipped.
+    FlipLeaf { bit: usize },
+    /// The last sibling is removed.
+    Truncate,
+    /// A hash is appended to the siblings.
+    Extend,
+}
+
+/// A valid proof case tampered with so that it must be rejected.
+#[derive(Debug, Clone)]
+pub struct MutatedProof {
+    pub case: ProofCase,
+    pub mutation: ProofMutation,
+}
+
+impl MutatedProof {
+    /// The tampered leaf and proof, to check against `case.root()`.
+    pub fn mutated(&self) -> (Hash, Proof) {
+        let mut leaf = self.case.leaf;
+        let mut proof = self.case.proof.clone();
+
+        match self.mutation {
+            ProofMutation::FlipSibling { level, bit } => {
+                proof.siblings[level][bit / 8] ^= 1 << (bit % 8);
+            }
+            ProofMutation::FlipLeaf { bit } => leaf[bit / 8] ^= 1 << (bit % 8),
+            ProofMutation::Truncate => {
+                proof.siblings.pop();
+            }
+            ProofMutation::Extend => proof.siblings.push(leaf),
+        }
+
+        (leaf, proof)
+    }
+}
+
+impl Arbitrary for MutatedProof {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            any::<ProofCase>(),
+            any::<prop::sample::Index>(),
+            0..256usize,
+            0..4u8,
+        )
+            .prop_map(|(case, level, bit, kind)| {
+                let levels = case.proof.siblings.len();
+                // A proof without siblings has none to flip or remove.
+                let mutation = match (kind, levels) {
+                    (0, 1..) => ProofMutation::FlipSibling {
+                        level: level.index(levels),
+                        bit,
+                    },
+                    (1, 1..) => ProofMutation::Truncate,
+                    (2, _) => ProofMutation::Extend,
+                    _ => ProofMutation::FlipLeaf { bit },
+                };
+                MutatedProof { case, mutation }
+            })
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn test_valid_proofs_verify(case in any::<ProofCase>()) {
+            prop_assert_eq!(case.proof.verify(&case.leaf, &case.root()), Ok(()));
+            prop_assert_eq!(case.tree.verify_proof(&case.leaf, &case.proof), Ok(()));
+        }
+
+        #[test]
+        fn test_mutated_proofs_are_rejected(mutated in any::<MutatedProof>()) {
+            let (leaf, proof) = mutated.mutated();
+            prop_assert!(proof.verify(&leaf, &mutated.case.root()).is_err());
+        }
+
+        #[test]
+        fn test_leaf_sets_are_bounded(leaves in leaf_sets(5)) {
+            prop_assert!((1..=5).contains(&leaves.len()));
+        }
+    }
+}
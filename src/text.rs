@@ -0,0 +1,272 @@
+use crate::error::Error;
+use crate::merkle_tree::Hash;
+use crate::proof::Proof;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// The text encodings of hashes and proofs used by web APIs and blockchain tooling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    /// Lowercase hex. Decoding accepts either case and an optional 0x prefix.
+    Hex,
+    /// Lowercase hex with a 0x prefix, as in Ethereum tooling. Decoding is the same as `Hex`.
+    PrefixedHex,
+    /// Standard base64 with padding (RFC 4648).
+    Base64,
+    /// Base58 with the Bitcoin alphabet, as in Solana tooling.
+    Base58,
+}
+
+impl TextEncoding {
+    pub fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            TextEncoding::Hex => hex::encode(bytes),
+            TextEncoding::PrefixedHex => format!("0x{}", hex::encode(bytes)),
+            TextEncoding::Base64 => encode_base64(bytes),
+            TextEncoding::Base58 => encode_base58(bytes),
+        }
+    }
+
+    pub fn decode(self, text: &str) -> Result<Vec<u8>, Error> {
+        match self {
+            TextEncoding::Hex | TextEncoding::PrefixedHex => {
+                let digits = text
+                    .strip_prefix("0x")
+                    .or_else(|| text.strip_prefix("0X"))
+                    .unwrap_or(text);
+                hex::decode(digits).map_err(|error| Error::InvalidInput(error.to_string()))
+            }
+            TextEncoding::Base64 => decode_base64(text),
+            TextEncoding::Base58 => decode_base58(text),
+        }
+    }
+
+    /// Decodes a hash or a root, failing if it is not 32 bytes long.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::{MerkleTree, TextEncoding};
+    ///
+    /// let root = MerkleTree::build(&["Erebor", "Dale", "Esgaroth"]).unwrap().root().unwrap();
+    ///
+    /// for encoding in [TextEncoding::PrefixedHex, TextEncoding::Base64, TextEncoding::Base58] {
+    ///     assert_eq!(encoding.decode_hash(&encoding.encode(&root)), Ok(root));
+    /// }
+    /// ```
+    pub fn decode_hash(self, text: &str) -> Result<Hash, Error> {
+        self.decode(text)?.try_into().map_err(|bytes: Vec<u8>| {
+            Error::InvalidInput(format!("a hash has 32 bytes, not {}", bytes.len()))
+        })
+    }
+}
+
+impl Proof {
+    /// Encodes the binary format of `to_bytes` as text.
+    pub fn to_text(&self, encoding: TextEncoding) -> String {
+        encoding.encode(&self.to_bytes())
+    }
+
+    /// Decodes a proof encoded by `to_text`.
+    pub fn from_text(text: &str, encoding: TextEncoding) -> Result<Self, Error> {
+        Self::parse(&encoding.decode(text)?)
+    }
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |group, (position, byte)| {
+                group | (*byte as u32) << (16 - 8 * position)
+            });
+        for position in 0..4 {
+            if position <= chunk.len() {
+                text.push(BASE64_ALPHABET[(group >> (18 - 6 * position) & 0x3f) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+
+    text
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>, Error> {
+    let invalid = || Error::InvalidInput("invalid base64".to_string());
+    if !text.len().is_multiple_of(4) {
+        return Err(invalid());
+    }
+
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+    let groups = text.as_bytes().chunks(4);
+    let last = groups.len().saturating_sub(1);
+
+    for (index, chunk) in groups.enumerate() {
+        let padding = chunk
+            .iter()
+            .rev()
+            .take_while(|digit| **digit == b'=')
+            .count();
+        if padding > 2 || (padding > 0 && index != last) {
+            return Err(invalid());
+        }
+
+        let mut group = 0u32;
+        for digit in &chunk[..4 - padding] {
+            let value = BASE64_ALPHABET
+                .iter()
+                .position(|symbol| symbol == digit)
+                .ok_or_else(invalid)?;
+            group = group << 6 | value as u32;
+        }
+        group <<= 6 * padding;
+
+        let decoded = [(group >> 16) as u8, (group >> 8) as u8, group as u8];
+        // The padded digits must not carry bits, so each text has one decoding.
+        if decoded[3 - padding..].iter().any(|byte| *byte != 0) {
+            return Err(invalid());
+        }
+        bytes.extend_from_slice(&decoded[..3 - padding]);
+    }
+
+    Ok(bytes)
+}
+
+fn encode_base58(bytes: &[u8]) -> String {
+    let zeros = bytes.iter().take_while(|byte| **byte == 0).count();
+
+    // The digits of the number, least significant first.
+    let mut digits: Vec<u8> = Vec::new();
+    for byte in &bytes[zeros..] {
+        let mut carry = *byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    std::iter::repeat_n('1', zeros)
+        .chain(
+            digits
+                .iter()
+                .rev()
+                .map(|digit| BASE58_ALPHABET[*digit as usize] as char),
+        )
+        .collect()
+}
+
+fn decode_base58(text: &str) -> Result<Vec<u8>, Error> {
+    let zeros = text.bytes().take_while(|digit| *digit == b'1').count();
+
+    // The bytes of the number, least significant first.
+    let mut bytes: Vec<u8> = Vec::new();
+    for digit in text.bytes().skip(zeros) {
+        let mut carry = BASE58_ALPHABET
+            .iter()
+            .position(|symbol| *symbol == digit)
+            .ok_or_else(|| Error::InvalidInput("invalid base58".to_string()))?
+            as u32;
+        for byte in bytes.iter_mut() {
+            carry += (*byte as u32) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+
+    bytes.extend(std::iter::repeat_n(0, zeros));
+    bytes.reverse();
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::merkle_tree::MerkleTree;
+
+    #[test]
+    fn test_rfc_4648_vectors() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+
+        for (bytes, text) in vectors {
+            assert_eq!(TextEncoding::Base64.encode(bytes.as_bytes()), text);
+            assert_eq!(
+                TextEncoding::Base64.decode(text),
+                Ok(bytes.as_bytes().to_vec())
+            );
+        }
+        for invalid in ["Zg=", "Zh==", "Z===", "Zg==Zg==", "Zm9v!A=="] {
+            assert!(TextEncoding::Base64.decode(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_base58_vectors() {
+        let vectors: [(&[u8], &str); 5] = [
+            (b"", ""),
+            (b"hello world", "StV1DL6CwTryKyV"),
+            (&[0, 0, 0x28, 0x7f, 0xb4, 0xcd], "11233QC4"),
+            (&[0], "1"),
+            (&[0xff; 4], "7YXq9G"),
+        ];
+
+        for (bytes, text) in vectors {
+            assert_eq!(TextEncoding::Base58.encode(bytes), text);
+            assert_eq!(TextEncoding::Base58.decode(text), Ok(bytes.to_vec()));
+        }
+        assert!(TextEncoding::Base58.decode("0OIl").is_err());
+    }
+
+    #[test]
+    fn test_hex_prefixes() {
+        let hash = MerkleTree::hash(b"Mirkwood");
+        let prefixed = TextEncoding::PrefixedHex.encode(&hash);
+
+        assert_eq!(prefixed, format!("0x{}", hex::encode(hash)));
+        assert_eq!(TextEncoding::Hex.decode_hash(&prefixed), Ok(hash));
+        assert_eq!(
+            TextEncoding::PrefixedHex.decode_hash(&prefixed[2..].to_uppercase()),
+            Ok(hash)
+        );
+        assert!(TextEncoding::Hex.decode_hash("0xabcd").is_err());
+    }
+
+    #[test]
+    fn test_proofs_round_trip_as_text() {
+        let tree = MerkleTree::build(&["Thranduil", "Legolas", "Tauriel", "Galion"]).unwrap();
+        let proof = tree.proof(3).unwrap();
+
+        for encoding in [
+            TextEncoding::Hex,
+            TextEncoding::PrefixedHex,
+            TextEncoding::Base64,
+            TextEncoding::Base58,
+        ] {
+            let text = proof.to_text(encoding);
+            assert_eq!(Proof::from_text(&text, encoding), Ok(proof.clone()));
+        }
+    }
+}
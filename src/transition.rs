@@ -0,0 +1,191 @@
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// A proof that replacing one leaf transforms a root into another.
+///
+/// It is the co-path of the leaf: the siblings do not depend on the leaf,
+/// so the same siblings fold the old leaf into the old root and the new leaf
+/// into the new root. A stateless validator holding only the old root can
+/// check an update, or compute the root after it.
+///
+/// The position of the leaf is not authenticated. Pairs are hashed sorted,
+/// so `leaf_index` only decides at which levels a node without sibling is
+/// paired with itself, and any index whose path pads the same levels
+/// verifies. Applications keying leaves by position commit the index in
+/// the leaf.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::MerkleTree;
+///
+/// let before = MerkleTree::build(&["Theoden", "Eomer", "Grima"]).unwrap();
+/// let after = MerkleTree::build(&["Theoden", "Eomer", "Gamling"]).unwrap();
+///
+/// let proof = before.update_proof(2).unwrap();
+/// let result = proof.verify(
+///     &MerkleTree::hash(b"Grima"),
+///     &MerkleTree::hash(b"Gamling"),
+///     &before.root().unwrap(),
+///     &after.root().unwrap(),
+/// );
+///
+/// assert!(result.is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateProof {
+    pub leaf_index: usize,
+    pub leaf_count: usize,
+    /// The proof of inclusion of the old leaf.
+    pub siblings: Vec<Hash>,
+}
+
+impl UpdateProof {
+    /// Checks the old leaf is under the old root and returns the root after
+    /// replacing it with the new leaf.
+    pub fn new_root(
+        &self,
+        old_leaf: &Hash,
+        old_root: &Hash,
+        new_leaf: &Hash,
+    ) -> Result<Hash, Error> {
+        Proof {
+            leaf_index: self.leaf_index,
+            leaf_count: self.leaf_count,
+            siblings: self.siblings.clone(),
+        }
+        .verify(old_leaf, old_root)?;
+
+        let mut node = *new_leaf;
+        let mut index = self.leaf_index;
+
+        for (level, sibling) in self.siblings.iter().enumerate() {
+            // A node without sibling is paired with itself, which changes with the leaf.
+            let sibling = if index ^ 1 >= MerkleTree::level_width(self.leaf_count, level) {
+                node
+            } else {
+                *sibling
+            };
+            node = MerkleTree::merkle_parent(&[node, sibling]);
+            index /= 2;
+        }
+
+        Ok(node)
+    }
+
+    /// Checks that replacing `old_leaf` by `new_leaf` transforms `old_root` into `new_root`.
+    pub fn verify(
+        &self,
+        old_leaf: &Hash,
+        new_leaf: &Hash,
+        old_root: &Hash,
+        new_root: &Hash,
+    ) -> Result<(), Error> {
+        if self.new_root(old_leaf, old_root, new_leaf)? != *new_root {
+            return Err(Error::RootMismatch);
+        }
+        Ok(())
+    }
+}
+
+impl MerkleTree {
+    /// Returns the proof that replacing the leaf at `index` transforms the
+    /// root, checked against the roots without the leaf count commitment.
+    pub fn update_proof(&self, index: usize) -> Result<UpdateProof, Error> {
+        let proof = self.proof(index)?;
+
+        Ok(UpdateProof {
+            leaf_index: proof.leaf_index,
+            leaf_count: proof.leaf_count,
+            siblings: proof.siblings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn balances(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|account| format!("account {} holds {} mithril", account, account * 3))
+            .collect()
+    }
+
+    fn root(items: &[String]) -> Hash {
+        MerkleTree::build(items).unwrap().root().unwrap()
+    }
+
+    #[test]
+    fn test_every_leaf_update_is_proven() {
+        let before = balances(13);
+        let tree = MerkleTree::build(&before).unwrap();
+
+        for index in 0..before.len() {
+            let mut after = before.clone();
+            after[index] = format!("account {} holds nothing", index);
+
+            let proof = tree.update_proof(index).unwrap();
+            let old_leaf = MerkleTree::hash(before[index].as_bytes());
+            let new_leaf = MerkleTree::hash(after[index].as_bytes());
+
+            assert_eq!(
+                proof.new_root(&old_leaf, &root(&before), &new_leaf),
+                Ok(root(&after)),
+                "leaf {}",
+                index
+            );
+            assert_eq!(
+                proof.verify(&old_leaf, &new_leaf, &root(&before), &root(&after)),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn test_wrong_transitions_are_rejected() {
+        let before = balances(6);
+        let tree = MerkleTree::build(&before).unwrap();
+        let proof = tree.update_proof(1).unwrap();
+
+        let old_leaf = MerkleTree::hash(before[1].as_bytes());
+        let new_leaf = MerkleTree::hash(b"account 1 holds the Arkenstone");
+        let mut other = before.clone();
+        other[2] = "account 2 holds the Arkenstone".to_string();
+
+        assert_eq!(
+            proof.verify(&old_leaf, &new_leaf, &root(&before), &root(&other)),
+            Err(Error::RootMismatch)
+        );
+        assert!(proof
+            .new_root(&new_leaf, &root(&before), &old_leaf)
+            .is_err());
+        assert!(tree.update_proof(6).is_err());
+    }
+
+    #[test]
+    fn test_tampered_indices_only_change_the_padding() {
+        let before = balances(6);
+        let mut after = before.clone();
+        after[1] = "account 1 holds nothing".to_string();
+        let tree = MerkleTree::build(&before).unwrap();
+
+        let old_leaf = MerkleTree::hash(before[1].as_bytes());
+        let new_leaf = MerkleTree::hash(after[1].as_bytes());
+        let mut proof = tree.update_proof(1).unwrap();
+
+        // Leaf 0 pads no level either, so the same co-path claims it.
+        proof.leaf_index = 0;
+        assert_eq!(
+            proof.verify(&old_leaf, &new_leaf, &root(&before), &root(&after)),
+            Ok(())
+        );
+
+        // Leaf 5 has no sibling at level 1, its path is rejected.
+        proof.leaf_index = 5;
+        assert!(proof
+            .verify(&old_leaf, &new_leaf, &root(&before), &root(&after))
+            .is_err());
+    }
+}
@@ -0,0 +1,318 @@
+use std::io::Write;
+
+use crate::audit_log::{Checkpoint, MerkleLogWriter};
+use crate::consistency::ConsistencyProof;
+use crate::error::Error;
+use crate::merkle_tree::Hash;
+use crate::proof::Proof;
+use crate::signature::{SignatureVerifier, Signer};
+
+fn artifact_line(name: &str, digest: &Hash) -> String {
+    format!("{} {}", hex::encode(digest), name)
+}
+
+/// A checkpoint of a transparency log signed by its publisher.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedCheckpoint {
+    pub checkpoint: Checkpoint,
+    pub signature: Vec<u8>,
+}
+
+impl SignedCheckpoint {
+    const DOMAIN: &'static [u8] = b"merkle-tree transparency checkpoint";
+
+    fn message(checkpoint: &Checkpoint) -> Vec<u8> {
+        let mut message = Self::DOMAIN.to_vec();
+        message.extend_from_slice(&(checkpoint.size as u64).to_be_bytes());
+        message.extend_from_slice(&checkpoint.root);
+        message
+    }
+
+    pub fn sign<S: Signer>(checkpoint: Checkpoint, signer: &S) -> Self {
+        Self {
+            signature: signer.sign(&Self::message(&checkpoint)),
+            checkpoint,
+        }
+    }
+
+    pub fn verify_signature<V: SignatureVerifier>(&self, verifier: &V) -> Result<(), Error> {
+        if verifier.verify(&Self::message(&self.checkpoint), &self.signature) {
+            Ok(())
+        } else {
+            Err(Error::InvalidSignature)
+        }
+    }
+
+    /// Checks the signature and that the artifact is in the log at this checkpoint.
+    pub fn verify_artifact<V: SignatureVerifier>(
+        &self,
+        verifier: &V,
+        name: &str,
+        digest: &Hash,
+        proof: &Proof,
+    ) -> Result<(), Error> {
+        self.verify_signature(verifier)?;
+        self.checkpoint
+            .verify_line(&artifact_line(name, digest), proof)
+    }
+}
+
+/// The publisher's side of a binary transparency log.
+///
+/// Publishers append the digest of every artifact they release and publish
+/// signed checkpoints. Consumers check that an artifact they install is in
+/// the log, and with a `TransparencyMonitor` that every checkpoint they see
+/// extends the previous one, so a release can't be hidden from them or
+/// removed later without detection.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{MerkleTree, SignatureVerifier, Signer, TransparencyLog, TransparencyMonitor};
+///
+/// struct Key;
+///
+/// impl Signer for Key {
+///     fn sign(&self, message: &[u8]) -> Vec<u8> {
+///         message.iter().rev().copied().collect()
+///     }
+/// }
+///
+/// impl SignatureVerifier for Key {
+///     fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+///         message.iter().rev().eq(signature.iter())
+///     }
+/// }
+///
+/// let mut log = TransparencyLog::new(Vec::new(), Vec::new());
+/// let mut monitor = TransparencyMonitor::new(Key);
+///
+/// let digest = MerkleTree::hash(b"palantir-1.0.tar.gz");
+/// let index = log.append_artifact("palantir-1.0", &digest).unwrap();
+/// let first = log.publish(&Key).unwrap();
+/// monitor.update(&first, None).unwrap();
+///
+/// log.append_artifact("palantir-1.1", &MerkleTree::hash(b"palantir-1.1.tar.gz")).unwrap();
+/// let second = log.publish(&Key).unwrap();
+/// let consistency = log.prove_consistency(first.checkpoint.size, second.checkpoint.size).unwrap();
+/// monitor.update(&second, Some(&consistency)).unwrap();
+///
+/// let proof = log.prove_artifact(index, second.checkpoint.size).unwrap();
+/// assert!(second.verify_artifact(&Key, "palantir-1.0", &digest, &proof).is_ok());
+/// ```
+pub struct TransparencyLog<L: Write, S: Write> {
+    writer: MerkleLogWriter<L, S>,
+}
+
+impl<L: Write, S: Write> TransparencyLog<L, S> {
+    /// Creates an empty log, writing entries to `log` and checkpoints to `sidecar`.
+    pub fn new(log: L, sidecar: S) -> Self {
+        Self {
+            writer: MerkleLogWriter::new(log, sidecar, 0),
+        }
+    }
+
+    /// Appends the digest of a released artifact and returns its index.
+    pub fn append_artifact(&mut self, name: &str, digest: &Hash) -> Result<usize, Error> {
+        self.writer.append(&artifact_line(name, digest))
+    }
+
+    /// Writes a checkpoint of the current log and signs it.
+    pub fn publish<K: Signer>(&mut self, signer: &K) -> Result<SignedCheckpoint, Error> {
+        Ok(SignedCheckpoint::sign(self.writer.checkpoint()?, signer))
+    }
+
+    /// Proves the artifact at `index` was in the log when it had `size` entries.
+    pub fn prove_artifact(&self, index: usize, size: usize) -> Result<Proof, Error> {
+        self.writer.prove(index, size)
+    }
+
+    /// Proves the log at `new_size` entries extends the log at `old_size` entries.
+    pub fn prove_consistency(
+        &self,
+        old_size: usize,
+        new_size: usize,
+    ) -> Result<ConsistencyProof, Error> {
+        self.writer.prove_consistency(old_size, new_size)
+    }
+
+    pub fn len(&self) -> usize {
+        self.writer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writer.is_empty()
+    }
+
+    /// Returns the log and the sidecar.
+    pub fn into_inner(self) -> (L, S) {
+        self.writer.into_inner()
+    }
+}
+
+/// The consumer's side of a binary transparency log: follows the signed
+/// checkpoints and rejects any that does not extend the last one accepted.
+pub struct TransparencyMonitor<V: SignatureVerifier> {
+    verifier: V,
+    latest: Option<Checkpoint>,
+}
+
+impl<V: SignatureVerifier> TransparencyMonitor<V> {
+    pub fn new(verifier: V) -> Self {
+        Self {
+            verifier,
+            latest: None,
+        }
+    }
+
+    /// The last checkpoint accepted.
+    pub fn latest(&self) -> Option<Checkpoint> {
+        self.latest
+    }
+
+    /// Accepts a newer checkpoint. After the first one, a proof that it
+    /// extends the last accepted checkpoint is required.
+    pub fn update(
+        &mut self,
+        signed: &SignedCheckpoint,
+        consistency: Option<&ConsistencyProof>,
+    ) -> Result<(), Error> {
+        signed.verify_signature(&self.verifier)?;
+        let checkpoint = signed.checkpoint;
+
+        if let Some(latest) = self.latest {
+            let proof = consistency.ok_or_else(|| {
+                Error::InvalidInput("a consistency proof is required".to_string())
+            })?;
+
+            if proof.old_size != latest.size || proof.new_size != checkpoint.size {
+                return Err(Error::InvalidInput(format!(
+                    "the proof is from {} to {} entries, not from {} to {}",
+                    proof.old_size, proof.new_size, latest.size, checkpoint.size
+                )));
+            }
+
+            proof.verify(&latest.root, &checkpoint.root)?;
+        }
+
+        self.latest = Some(checkpoint);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::merkle_tree::MerkleTree;
+
+    struct HmacKey(&'static [u8]);
+
+    impl Signer for HmacKey {
+        fn sign(&self, message: &[u8]) -> Vec<u8> {
+            hmac_sha256::HMAC::mac(message, self.0).to_vec()
+        }
+    }
+
+    impl SignatureVerifier for HmacKey {
+        fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+            hmac_sha256::HMAC::mac(message, self.0) == signature
+        }
+    }
+
+    fn release(log: &mut TransparencyLog<Vec<u8>, Vec<u8>>, versions: std::ops::Range<usize>) {
+        for version in versions {
+            let name = format!("mithril-{}.0", version);
+            log.append_artifact(&name, &MerkleTree::hash(name.as_bytes()))
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_monitor_follows_an_append_only_log() {
+        let key = HmacKey(b"White Council");
+        let mut log = TransparencyLog::new(Vec::new(), Vec::new());
+        let mut monitor = TransparencyMonitor::new(HmacKey(b"White Council"));
+
+        let mut previous: Option<SignedCheckpoint> = None;
+        for batch in 0..5 {
+            release(&mut log, batch * 3..batch * 3 + 3);
+            let signed = log.publish(&key).unwrap();
+
+            let consistency = previous.map(|previous| {
+                log.prove_consistency(previous.checkpoint.size, signed.checkpoint.size)
+                    .unwrap()
+            });
+            monitor.update(&signed, consistency.as_ref()).unwrap();
+            previous = Some(signed);
+        }
+
+        let latest = previous.unwrap();
+        assert_eq!(monitor.latest(), Some(latest.checkpoint));
+
+        let proof = log.prove_artifact(4, latest.checkpoint.size).unwrap();
+        let digest = MerkleTree::hash(b"mithril-4.0");
+        assert_eq!(
+            latest.verify_artifact(&key, "mithril-4.0", &digest, &proof),
+            Ok(())
+        );
+        assert!(latest
+            .verify_artifact(&key, "mithril-5.0", &digest, &proof)
+            .is_err());
+    }
+
+    #[test]
+    fn test_monitor_rejects_forks_and_forged_checkpoints() {
+        let key = HmacKey(b"White Council");
+        let mut log = TransparencyLog::new(Vec::new(), Vec::new());
+        release(&mut log, 0..4);
+        let first = log.publish(&key).unwrap();
+
+        let mut monitor = TransparencyMonitor::new(HmacKey(b"White Council"));
+        monitor.update(&first, None).unwrap();
+
+        // A fork that rewrote a release the monitor already saw.
+        let mut fork = TransparencyLog::new(Vec::new(), Vec::new());
+        release(&mut fork, 1..7);
+        let forked = fork.publish(&key).unwrap();
+        let consistency = fork.prove_consistency(4, 6).unwrap();
+        assert_eq!(
+            monitor.update(&forked, Some(&consistency)),
+            Err(Error::RootMismatch)
+        );
+        assert!(monitor.update(&forked, None).is_err());
+
+        release(&mut log, 4..6);
+        let second = log.publish(&HmacKey(b"Saruman")).unwrap();
+        let consistency = log.prove_consistency(4, 6).unwrap();
+        assert_eq!(
+            monitor.update(&second, Some(&consistency)),
+            Err(Error::InvalidSignature)
+        );
+
+        assert_eq!(monitor.latest(), Some(first.checkpoint));
+    }
+
+    #[test]
+    fn test_monitor_rejects_reordered_releases() {
+        let key = HmacKey(b"White Council");
+        let mut log = TransparencyLog::new(Vec::new(), Vec::new());
+        release(&mut log, 0..2);
+        let first = log.publish(&key).unwrap();
+
+        let mut monitor = TransparencyMonitor::new(HmacKey(b"White Council"));
+        monitor.update(&first, None).unwrap();
+
+        // The same releases swapped, then a new one.
+        let mut reordered = TransparencyLog::new(Vec::new(), Vec::new());
+        release(&mut reordered, 1..2);
+        release(&mut reordered, 0..1);
+        release(&mut reordered, 2..3);
+        let forked = reordered.publish(&key).unwrap();
+        let consistency = reordered.prove_consistency(2, 3).unwrap();
+
+        assert_eq!(
+            monitor.update(&forked, Some(&consistency)),
+            Err(Error::RootMismatch)
+        );
+    }
+}
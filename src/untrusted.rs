@@ -0,0 +1,216 @@
+use crate::error::Error;
+use crate::hashing::{Hash, MerkleHasher, Sha256};
+use crate::limits::Limits;
+use crate::proof::Proof;
+use crate::wire::{WireError, HEADER_LENGTH, MAX_SIBLINGS};
+
+/// A proof decoded from untrusted bytes by `Proof::parse_with_limits`:
+/// its size and shape are checked, but nothing is hashed until `check`.
+///
+/// Services verifying proofs sent over the network split the two phases
+/// so oversized or malformed blobs are rejected before any hashing work.
+///
+/// # Examples
+/// ```
+/// # #[cfg(feature = "tree")] {
+/// use merkle_tree::{Limits, MerkleTree, Proof};
+///
+/// let tree = MerkleTree::build(&["Narya", "Nenya", "Vilya"]).unwrap();
+/// let bytes = tree.proof(1).unwrap().to_bytes();
+///
+/// let limits = Limits {
+///     max_proof_length: 32,
+///     ..Limits::default()
+/// };
+/// let parsed = Proof::parse_with_limits(&bytes, &limits).unwrap();
+///
+/// assert!(parsed.check(&MerkleTree::hash(b"Nenya"), &tree.root().unwrap()).is_ok());
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedProof {
+    proof: Proof,
+    algorithm: u8,
+}
+
+impl ParsedProof {
+    /// The check phase: folds the proof from `leaf` and compares it to `root`.
+    pub fn check(&self, leaf: &Hash, root: &Hash) -> Result<(), Error> {
+        self.check_with::<Sha256>(leaf, root)
+    }
+
+    /// The check phase for a tree built with `H`, which fails if the proof
+    /// was encoded for another hash.
+    pub fn check_with<H: MerkleHasher>(&self, leaf: &Hash, root: &Hash) -> Result<(), Error> {
+        if H::WIRE_ALGORITHM != Some(self.algorithm) {
+            return Err(Error::MalformedProof(WireError::UnknownAlgorithm(
+                self.algorithm,
+            )));
+        }
+        self.proof.verify_with::<H>(leaf, root)
+    }
+
+    /// The algorithm byte the proof was encoded with.
+    pub fn algorithm(&self) -> u8 {
+        self.algorithm
+    }
+
+    pub fn proof(&self) -> &Proof {
+        &self.proof
+    }
+
+    pub fn into_proof(self) -> Proof {
+        self.proof
+    }
+}
+
+impl Proof {
+    /// The parse phase of verifying a proof from an untrusted source.
+    ///
+    /// The size of the input is checked before it is read, against the
+    /// wire length of a proof of `max_proof_length` hashes, reported as the
+    /// `max_proof_bytes` limit. The header is checked against `max_leaves`
+    /// and `max_proof_length` before the siblings are decoded, then the
+    /// proof is decoded as by `parse`, whatever its algorithm, which the
+    /// check phase compares to its hash. Sizes beyond the limits are
+    /// `Error::LimitExceeded`, and encoding errors `Error::MalformedProof`.
+    pub fn parse_with_limits(bytes: &[u8], limits: &Limits) -> Result<ParsedProof, Error> {
+        let max_length = Self::wire_length(limits.max_proof_length.min(MAX_SIBLINGS));
+        if bytes.len() > max_length {
+            return Err(Error::LimitExceeded {
+                limit: "max_proof_bytes",
+                value: bytes.len(),
+                max: max_length,
+            });
+        }
+        if bytes.len() < HEADER_LENGTH {
+            return Err(Error::MalformedProof(WireError::Truncated {
+                expected: HEADER_LENGTH,
+                actual: bytes.len(),
+            }));
+        }
+
+        let leaf_count =
+            u64::from_be_bytes(bytes[2..10].try_into().expect("The header has 8 bytes."));
+        limits.check_leaf_count(usize::try_from(leaf_count).unwrap_or(usize::MAX))?;
+        limits.check_proof_length(bytes[18] as usize)?;
+
+        Ok(ParsedProof {
+            proof: Self::decode(bytes, |_| true)?,
+            algorithm: bytes[1],
+        })
+    }
+}
+
+#[cfg(all(test, feature = "tree"))]
+mod tests {
+
+    use super::*;
+    use crate::merkle_tree::{GenericMerkleTree, MerkleTree};
+
+    struct Tagged;
+
+    impl MerkleHasher for Tagged {
+        const WIRE_ALGORITHM: Option<u8> = Some(200);
+
+        fn hash(bytes: &[u8]) -> Hash {
+            MerkleTree::hash(&[b"tagged:".as_slice(), bytes].concat())
+        }
+    }
+
+    fn bytes(count: usize, index: usize) -> Vec<u8> {
+        let items: Vec<String> = (0..count)
+            .map(|item| format!("seeing-stone {}", item))
+            .collect();
+        MerkleTree::build(&items)
+            .unwrap()
+            .proof(index)
+            .unwrap()
+            .to_bytes()
+    }
+
+    #[test]
+    fn test_limits_are_checked_before_decoding() {
+        let limits = Limits {
+            max_leaves: 100,
+            max_proof_length: 5,
+            ..Limits::default()
+        };
+
+        assert!(Proof::parse_with_limits(&bytes(32, 7), &limits).is_ok());
+        assert_eq!(
+            Proof::parse_with_limits(&bytes(64, 7), &limits),
+            Err(Error::LimitExceeded {
+                limit: "max_proof_bytes",
+                value: Proof::wire_length(6),
+                max: Proof::wire_length(5)
+            })
+        );
+        assert!(matches!(
+            Proof::parse_with_limits(&vec![0; 1 << 20], &limits),
+            Err(Error::LimitExceeded { .. })
+        ));
+
+        // Small proofs whose header claims more siblings or leaves than allowed.
+        let mut long = bytes(32, 7);
+        long[18] = 6;
+        assert!(matches!(
+            Proof::parse_with_limits(&long, &limits),
+            Err(Error::LimitExceeded {
+                limit: "max_proof_length",
+                ..
+            })
+        ));
+
+        let mut large = bytes(32, 7);
+        large[9] = 200;
+        assert_eq!(
+            Proof::parse_with_limits(&large, &limits),
+            Err(Error::LimitExceeded {
+                limit: "max_leaves",
+                value: 200,
+                max: 100
+            })
+        );
+    }
+
+    #[test]
+    fn test_checks_follow_parsing() {
+        let items = ["Amon Sûl", "Orthanc", "Minas Tirith"];
+        let tree = MerkleTree::build(&items).unwrap();
+        let root = tree.root().unwrap();
+        let parsed =
+            Proof::parse_with_limits(&tree.proof(1).unwrap().to_bytes(), &Limits::default())
+                .unwrap();
+
+        assert_eq!(parsed.check(&MerkleTree::hash(b"Orthanc"), &root), Ok(()));
+        assert_eq!(
+            parsed.check(&MerkleTree::hash(b"Osgiliath"), &root),
+            Err(Error::RootMismatch)
+        );
+        assert_eq!(
+            Proof::parse_with_limits(&[1, 1, 0], &Limits::default()),
+            Err(Error::MalformedProof(WireError::Truncated {
+                expected: 19,
+                actual: 3
+            }))
+        );
+    }
+
+    #[test]
+    fn test_checks_use_the_hash_of_the_encoding() {
+        let items = ["Amon Sûl", "Orthanc", "Minas Tirith"];
+        let tree = GenericMerkleTree::<Tagged>::build(&items).unwrap();
+        let root = tree.root().unwrap();
+        let bytes = tree.proof(2).unwrap().to_bytes_with::<Tagged>().unwrap();
+        let parsed = Proof::parse_with_limits(&bytes, &Limits::default()).unwrap();
+        let leaf = Tagged::hash(b"Minas Tirith");
+
+        assert_eq!(parsed.algorithm(), 200);
+        assert_eq!(parsed.check_with::<Tagged>(&leaf, &root), Ok(()));
+        assert_eq!(
+            parsed.check(&leaf, &root),
+            Err(Error::MalformedProof(WireError::UnknownAlgorithm(200)))
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use crate::builder::{AcceptsItems, MerkleTreeBuilder, RawItem};
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// The numbers of items of the vectors of `TestVector::generate`, covering
+/// single leaves, full trees and odd levels at every height.
+pub const VECTOR_SIZES: [usize; 12] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 15, 16, 17];
+
+/// A known-answer vector: items, every level of the tree built over them,
+/// its root and the proof of every leaf, for other implementations to
+/// check their compatibility against.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{MerkleTree, TestVector};
+///
+/// let vectors = TestVector::generate(&MerkleTree::builder()).unwrap();
+///
+/// let three = &vectors[2];
+/// assert_eq!(three.items, vec![b"leaf 0".to_vec(), b"leaf 1".to_vec(), b"leaf 2".to_vec()]);
+/// assert_eq!(three.levels.len(), 3);
+/// assert_eq!(three.proofs.len(), 3);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestVector {
+    pub items: Vec<Vec<u8>>,
+    /// The nodes of every level, from the leaves up to the top of the tree.
+    pub levels: Vec<Vec<Hash>>,
+    /// The root, which differs from the top of the tree with `MutationGuard::CommitLeafCount`.
+    pub root: Hash,
+    pub proofs: Vec<Proof>,
+}
+
+impl TestVector {
+    /// Builds the vector of `items` with the options of `builder`.
+    pub fn new<I: AcceptsItems, T: AsRef<[u8]> + RawItem<I>>(
+        builder: &MerkleTreeBuilder<I>,
+        items: &[T],
+    ) -> Result<Self, Error> {
+        let tree = builder.build(items)?;
+        Self::from_tree(&tree, items)
+    }
+
+    fn from_tree<T: AsRef<[u8]>>(tree: &MerkleTree, items: &[T]) -> Result<Self, Error> {
+        Ok(Self {
+            items: items.iter().map(|item| item.as_ref().to_vec()).collect(),
+            levels: tree.level_roots().map(<[Hash]>::to_vec).collect(),
+            root: tree.root().expect("A tree has a root."),
+            proofs: (0..tree.leaf_count())
+                .map(|index| tree.proof(index))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Builds the vectors of `VECTOR_SIZES` items `leaf 0`, `leaf 1`, …
+    /// with the options of `builder`.
+    pub fn generate<I: AcceptsItems>(builder: &MerkleTreeBuilder<I>) -> Result<Vec<Self>, Error>
+    where
+        String: RawItem<I>,
+    {
+        VECTOR_SIZES
+            .iter()
+            .map(|size| {
+                let items: Vec<String> = (0..*size).map(|item| format!("leaf {}", item)).collect();
+                Self::new(builder, &items)
+            })
+            .collect()
+    }
+
+    /// The vector as a JSON value, with every byte string in hex.
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> serde_json::Value {
+        let hashes = |hashes: &[Hash]| -> Vec<String> { hashes.iter().map(hex::encode).collect() };
+
+        serde_json::json!({
+            "items": self.items.iter().map(hex::encode).collect::<Vec<_>>(),
+            "levels": self.levels.iter().map(|level| hashes(level)).collect::<Vec<_>>(),
+            "root": hex::encode(self.root),
+            "proofs": self
+                .proofs
+                .iter()
+                .map(|proof| serde_json::json!({
+                    "leaf_index": proof.leaf_index,
+                    "leaf_count": proof.leaf_count,
+                    "siblings": hashes(&proof.siblings),
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    /// The vectors of `generate` as a canonical JSON document, with the
+    /// hashing profile of `builder` they were built with.
+    #[cfg(feature = "json")]
+    pub fn generate_json<I: AcceptsItems>(builder: &MerkleTreeBuilder<I>) -> Result<String, Error>
+    where
+        String: RawItem<I>,
+    {
+        let vectors = Self::generate(builder)?;
+        let config = builder.config();
+
+        Ok(crate::jcs::canonicalize_json(&serde_json::json!({
+            "profile": {
+                "leaf": "sha256(item)",
+                "node": "sha256(sorted pair)",
+                "odd_level": "duplicate last node",
+                "mutation_guard": format!("{:?}", config.mutation_guard),
+                "duplicates": format!("{:?}", config.duplicates),
+            },
+            "vectors": vectors.iter().map(Self::to_json).collect::<Vec<_>>(),
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::config::MutationGuard;
+
+    #[test]
+    fn test_vectors_are_consistent() {
+        for builder in [
+            MerkleTree::builder(),
+            MerkleTree::builder().mutation_guard(MutationGuard::CommitLeafCount),
+        ] {
+            let vectors = TestVector::generate(&builder).unwrap();
+            assert_eq!(vectors.len(), VECTOR_SIZES.len());
+
+            for vector in vectors {
+                let top = vector.levels.last().unwrap();
+                assert_eq!(top.len(), 1);
+                assert_eq!(
+                    vector.root,
+                    builder.build(&vector.items).unwrap().root().unwrap()
+                );
+                for (index, (item, proof)) in vector.items.iter().zip(&vector.proofs).enumerate() {
+                    assert_eq!(vector.levels[0][index], MerkleTree::hash(item));
+                    assert_eq!(proof.verify(&MerkleTree::hash(item), &top[0]), Ok(()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_known_answers_do_not_change() {
+        let vectors = TestVector::generate(&MerkleTree::builder()).unwrap();
+
+        assert_eq!(vectors[0].root, MerkleTree::hash(b"leaf 0"));
+        assert_eq!(
+            vectors[2].levels[1],
+            [
+                MerkleTree::merkle_parent(&[
+                    MerkleTree::hash(b"leaf 0"),
+                    MerkleTree::hash(b"leaf 1")
+                ]),
+                MerkleTree::merkle_parent(&[
+                    MerkleTree::hash(b"leaf 2"),
+                    MerkleTree::hash(b"leaf 2")
+                ]),
+            ]
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_vectors_hold_the_profile() {
+        let builder = MerkleTree::builder().mutation_guard(MutationGuard::CommitLeafCount);
+        let document = TestVector::generate_json(&builder).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&document).unwrap();
+
+        assert_eq!(value["profile"]["mutation_guard"], "CommitLeafCount");
+        assert_eq!(value["vectors"][2]["items"][1], hex::encode("leaf 1"));
+        assert_eq!(
+            value["vectors"][3]["root"],
+            hex::encode(TestVector::generate(&builder).unwrap()[3].root)
+        );
+        assert_eq!(
+            value["vectors"][4]["proofs"][4]["siblings"]
+                .as_array()
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+}
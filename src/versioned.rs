@@ -0,0 +1,269 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// A committed version of a `VersionedTree`.
+#[derive(Debug)]
+pub struct TreeVersion {
+    pub version: u64,
+    pub tree: MerkleTree,
+}
+
+impl TreeVersion {
+    pub fn root(&self) -> Hash {
+        self.tree.root().expect("A tree has a root.")
+    }
+}
+
+/// A tree shared between threads where readers never wait for writers.
+///
+/// Readers take the last committed version, a pointer copy under a short
+/// lock, and generate proofs from it. A writer builds the next version on a
+/// copy of the tree and only swaps the pointer once done, so a large batch
+/// of writes does not delay reads. Writers are serialized.
+///
+/// Trees made `with_history` also keep previous versions, so verifiers
+/// pinned to an older checkpoint can be served proofs against its root.
+///
+/// # Examples
+/// ```
+/// use merkle_tree::{MerkleTree, VersionedTree};
+///
+/// let tree = VersionedTree::new(MerkleTree::build(&["Arathorn"]).unwrap());
+/// let before = tree.snapshot();
+///
+/// tree.write(|tree| tree.insert(&"Aragorn")).unwrap();
+///
+/// assert_eq!(before.version, 0);
+/// assert_eq!(before.tree.leaf_count(), 1);
+/// assert_eq!(tree.snapshot().tree.leaf_count(), 2);
+/// ```
+#[derive(Debug)]
+pub struct VersionedTree {
+    /// The retained versions, the last committed one at the back.
+    versions: RwLock<VecDeque<Arc<TreeVersion>>>,
+    /// The number of versions kept before the last committed one.
+    history: usize,
+    writer: Mutex<()>,
+}
+
+impl VersionedTree {
+    pub fn new(tree: MerkleTree) -> Self {
+        Self::with_history(tree, 0)
+    }
+
+    /// Keeps up to `history` versions before the last committed one, each
+    /// a full copy of the tree. `usize::MAX` keeps every version.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::{MerkleTree, VersionedTree};
+    ///
+    /// let tree = VersionedTree::with_history(MerkleTree::build(&["Isildur"]).unwrap(), 8);
+    /// let checkpoint = tree.root();
+    ///
+    /// tree.write(|tree| tree.insert(&"Valandil")).unwrap();
+    ///
+    /// let proof = tree.prove_at_version(0, 0).unwrap();
+    /// assert!(proof.verify(&MerkleTree::hash(b"Isildur"), &checkpoint).is_ok());
+    /// assert_eq!(tree.root_at_version(0), Some(checkpoint));
+    /// ```
+    pub fn with_history(tree: MerkleTree, history: usize) -> Self {
+        Self {
+            versions: RwLock::new(VecDeque::from([Arc::new(TreeVersion { version: 0, tree })])),
+            history,
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// The last committed version, unaffected by later writes.
+    pub fn snapshot(&self) -> Arc<TreeVersion> {
+        Arc::clone(
+            self.versions
+                .read()
+                .expect("The version lock is not poisoned.")
+                .back()
+                .expect("A tree has a committed version."),
+        )
+    }
+
+    /// The committed `version`, `None` if it is not retained or not committed yet.
+    pub fn at_version(&self, version: u64) -> Option<Arc<TreeVersion>> {
+        let versions = self
+            .versions
+            .read()
+            .expect("The version lock is not poisoned.");
+        let oldest = versions.front()?.version;
+        let position = usize::try_from(version.checked_sub(oldest)?).ok()?;
+        versions.get(position).map(Arc::clone)
+    }
+
+    /// The root of the committed `version`, if it is retained.
+    pub fn root_at_version(&self, version: u64) -> Option<Hash> {
+        self.at_version(version).map(|version| version.root())
+    }
+
+    /// Returns the proof of the leaf at `index` in the committed `version`,
+    /// which verifies against the root of that version.
+    pub fn prove_at_version(&self, index: usize, version: u64) -> Result<Proof, Error> {
+        self.retained(version)?.tree.proof(index)
+    }
+
+    /// Checks that `proof` leads from `leaf` to the root of the committed `version`.
+    pub fn verify_at_version(&self, leaf: &Hash, proof: &Proof, version: u64) -> Result<(), Error> {
+        self.retained(version)?.tree.verify_proof(leaf, proof)
+    }
+
+    fn retained(&self, version: u64) -> Result<Arc<TreeVersion>, Error> {
+        self.at_version(version)
+            .ok_or_else(|| Error::InvalidInput(format!("the version {} is not retained", version)))
+    }
+
+    pub fn version(&self) -> u64 {
+        self.snapshot().version
+    }
+
+    pub fn root(&self) -> Hash {
+        self.snapshot().root()
+    }
+
+    /// Returns the proof of the leaf at `index` in the last committed
+    /// version, with that version.
+    pub fn proof(&self, index: usize) -> Result<(u64, Proof), Error> {
+        let snapshot = self.snapshot();
+        Ok((snapshot.version, snapshot.tree.proof(index)?))
+    }
+
+    /// Applies `write` to a copy of the last committed version and commits
+    /// the copy as the next version. If `write` fails, nothing is committed.
+    ///
+    /// Callbacks registered on the tree are not kept, the copy starts without any.
+    /// A panicking `write` commits nothing either and later writes proceed.
+    pub fn write<F, R>(&self, write: F) -> Result<R, Error>
+    where
+        F: FnOnce(&mut MerkleTree) -> Result<R, Error>,
+    {
+        // The writer lock guards no data, a panic while holding it leaves nothing half done.
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let base = self.snapshot();
+        let mut tree = base.tree.clone();
+        let result = write(&mut tree)?;
+
+        let mut versions = self
+            .versions
+            .write()
+            .expect("The version lock is not poisoned.");
+        versions.push_back(Arc::new(TreeVersion {
+            version: base.version + 1,
+            tree,
+        }));
+        while versions.len() - 1 > self.history {
+            versions.pop_front();
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Barrier;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn test_reads_use_committed_versions_during_writes() {
+        let tree = VersionedTree::new(MerkleTree::build(&["entry 0"]).unwrap());
+        let started = Barrier::new(2);
+        let read = Barrier::new(2);
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                tree.write(|tree| {
+                    started.wait();
+                    read.wait();
+                    for entry in 1..2000 {
+                        tree.insert(&format!("entry {}", entry))?;
+                    }
+                    Ok(())
+                })
+                .unwrap();
+            });
+
+            started.wait();
+            // The writer holds its copy, the committed version still serves proofs.
+            let (version, proof) = tree.proof(0).unwrap();
+            let snapshot = tree.snapshot();
+            read.wait();
+
+            assert_eq!(version, 0);
+            assert_eq!(
+                proof.verify(&MerkleTree::hash(b"entry 0"), &snapshot.root()),
+                Ok(())
+            );
+        });
+
+        assert_eq!(tree.version(), 1);
+        assert_eq!(tree.snapshot().tree.leaf_count(), 2000);
+    }
+
+    #[test]
+    fn test_failed_writes_are_not_committed() {
+        let tree = VersionedTree::new(MerkleTree::build(&["entry 0"]).unwrap());
+        let root = tree.root();
+
+        let result = tree.write(|tree| {
+            tree.insert(&"entry 1")?;
+            Err::<(), _>(Error::InvalidInput("aborted".to_string()))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(tree.version(), 0);
+        assert_eq!(tree.root(), root);
+
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            tree.write(|_| -> Result<(), Error> { panic!("the Balrog awoke") })
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(tree.version(), 0);
+        assert!(tree.write(|tree| tree.insert(&"entry 1")).is_ok());
+        assert_eq!(tree.version(), 1);
+    }
+
+    #[test]
+    fn test_proofs_at_retained_versions() {
+        let tree = VersionedTree::with_history(MerkleTree::build(&["entry 0"]).unwrap(), 3);
+        let mut roots = vec![tree.root()];
+        for entry in 1..6 {
+            tree.write(|tree| tree.insert(&format!("entry {}", entry)))
+                .unwrap();
+            roots.push(tree.root());
+        }
+
+        for version in 2..=5 {
+            let root = tree.root_at_version(version).unwrap();
+            assert_eq!(root, roots[version as usize]);
+            for index in 0..=version as usize {
+                let leaf = MerkleTree::hash(format!("entry {}", index).as_bytes());
+                let proof = tree.prove_at_version(index, version).unwrap();
+                assert_eq!(proof.verify(&leaf, &root), Ok(()));
+                assert_eq!(tree.verify_at_version(&leaf, &proof, version), Ok(()));
+            }
+            assert!(tree
+                .prove_at_version(version as usize + 1, version)
+                .is_err());
+        }
+
+        assert!(tree.at_version(1).is_none());
+        assert!(tree.at_version(6).is_none());
+        assert!(tree.prove_at_version(0, 1).is_err());
+        assert!(VersionedTree::new(MerkleTree::build(&["entry 0"]).unwrap())
+            .at_version(0)
+            .is_some());
+    }
+}
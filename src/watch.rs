@@ -0,0 +1,70 @@
+use tokio::sync::watch;
+
+use crate::merkle_tree::{Hash, MerkleTree};
+
+impl MerkleTree {
+    /// Returns a receiver holding the current root, updated every time a
+    /// mutation of the tree commits, so async tasks can await root changes
+    /// while the tree is mutated on another thread.
+    ///
+    /// Each call registers a new channel through `on_root_change`.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::executor::block_on;
+    /// use merkle_tree::MerkleTree;
+    ///
+    /// let mut tree = MerkleTree::build(&["Beren"]).unwrap();
+    /// let mut roots = tree.root_watch();
+    ///
+    /// tree.insert(&"Lúthien").unwrap();
+    ///
+    /// block_on(roots.changed()).unwrap();
+    /// assert_eq!(*roots.borrow_and_update(), tree.root().unwrap());
+    /// ```
+    pub fn root_watch(&mut self) -> watch::Receiver<Hash> {
+        let (sender, receiver) = watch::channel(self.root().expect("A tree has a root."));
+        self.on_root_change(move |root, _| {
+            sender.send_replace(*root);
+        });
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_receivers_see_every_root_transition() {
+        let mut tree = MerkleTree::build(&["Beren"]).unwrap();
+        let mut first = tree.root_watch();
+        let mut second = tree.root_watch();
+
+        assert_eq!(*first.borrow(), tree.root().unwrap());
+        assert!(!first.has_changed().unwrap());
+
+        let handle = std::thread::spawn(move || {
+            tree.insert(&"Lúthien").unwrap();
+            tree.insert(&"Dior").unwrap();
+            tree.root().unwrap()
+        });
+        let root = handle.join().unwrap();
+
+        // The tree and its senders are gone, the last root stays readable.
+        assert!(first.has_changed().is_err());
+        assert_eq!(*first.borrow_and_update(), root);
+        assert_eq!(*second.borrow_and_update(), root);
+    }
+
+    #[test]
+    fn test_dropped_receivers_do_not_block_mutations() {
+        let mut tree = MerkleTree::build(&["Beren"]).unwrap();
+        drop(tree.root_watch());
+
+        tree.insert(&"Lúthien").unwrap();
+
+        assert_eq!(tree.leaf_count(), 2);
+    }
+}
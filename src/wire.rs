@@ -0,0 +1,377 @@
+use std::fmt;
+
+use crate::error::Error;
+use crate::hashing::{Hash, MerkleHasher, Sha256};
+use crate::proof::Proof;
+
+/// A tree of 2^64 leaves has 64 levels above them.
+pub(crate) const MAX_SIBLINGS: usize = 64;
+
+pub(crate) const HEADER_LENGTH: usize = 19;
+
+/// Why bytes are not a proof in the format of `Proof::to_bytes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireError {
+    /// The input ends before the header, or before the siblings it announces.
+    Truncated {
+        expected: usize,
+        actual: usize,
+    },
+    /// The input goes on after the siblings it announces.
+    TrailingBytes {
+        expected: usize,
+        actual: usize,
+    },
+    UnknownVersion(u8),
+    UnknownAlgorithm(u8),
+    /// The leaf count or index does not fit in a `usize` of this platform.
+    TreeTooLarge,
+    /// A tree of the claimed size needs one sibling per level above the leaves.
+    SiblingCount {
+        expected: usize,
+        actual: usize,
+    },
+    /// The direction bits do not match the leaf index.
+    Directions,
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireError::Truncated { expected, actual } => write!(
+                f,
+                "the proof needs {} bytes but ends after {}",
+                expected, actual
+            ),
+            WireError::TrailingBytes { expected, actual } => write!(
+                f,
+                "the proof has {} bytes, {} after its siblings",
+                actual,
+                actual - expected
+            ),
+            WireError::UnknownVersion(version) => {
+                write!(f, "unknown proof format version {}", version)
+            }
+            WireError::UnknownAlgorithm(algorithm) => {
+                write!(f, "unknown proof algorithm {}", algorithm)
+            }
+            WireError::TreeTooLarge => write!(f, "the tree is too large for this platform"),
+            WireError::SiblingCount { expected, actual } => write!(
+                f,
+                "the proof has {} siblings, the tree needs {}",
+                actual, expected
+            ),
+            WireError::Directions => {
+                write!(f, "the direction bits do not match the leaf index")
+            }
+        }
+    }
+}
+
+impl Proof {
+    /// The version of the binary format written by `to_bytes`.
+    pub const WIRE_VERSION: u8 = 1;
+
+    /// The size in bytes of the binary encoding of a proof of `hashes` hashes.
+    pub fn wire_length(hashes: usize) -> usize {
+        HEADER_LENGTH + hashes.div_ceil(8) + hashes * 32
+    }
+
+    /// Encodes the proof of a `MerkleTree` in a stable binary format, to
+    /// persist it or send it to another version of the crate. Proofs of
+    /// trees built with another hash are encoded with `to_bytes_with`.
+    /// Integers are big-endian:
+    ///
+    /// | bytes          | content                                              |
+    /// |----------------|------------------------------------------------------|
+    /// | 1              | format version, 1                                    |
+    /// | 1              | algorithm, the `WIRE_ALGORITHM` of the hash          |
+    /// | 8              | leaf count                                           |
+    /// | 8              | leaf index                                           |
+    /// | 1              | number of siblings `n`                               |
+    /// | `ceil(n / 8)`  | direction bits, bit `i` set if sibling `i` is on the right |
+    /// | `32 * n`       | siblings, from the leaf up                           |
+    ///
+    /// The algorithm is 1 for SHA-256 and 2 for Keccak-256, both over sorted
+    /// pairs. The bits of a byte are read from the least significant one,
+    /// and the unused bits of the last byte are zero.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "tree")] {
+    /// use merkle_tree::{MerkleTree, Proof};
+    ///
+    /// let tree = MerkleTree::build(&["Narya", "Nenya", "Vilya"]).unwrap();
+    /// let bytes = tree.proof(2).unwrap().to_bytes();
+    ///
+    /// let proof = Proof::parse(&bytes).unwrap();
+    /// assert!(proof.verify(&MerkleTree::hash(b"Vilya"), &tree.root().unwrap()).is_ok());
+    /// # }
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encode(Sha256::WIRE_ALGORITHM.expect("SHA-256 has a wire algorithm."))
+    }
+
+    /// Encodes the proof of a tree built with `H`, failing for hashes
+    /// without a `MerkleHasher::WIRE_ALGORITHM`.
+    pub fn to_bytes_with<H: MerkleHasher>(&self) -> Result<Vec<u8>, Error> {
+        H::WIRE_ALGORITHM
+            .map(|algorithm| self.encode(algorithm))
+            .ok_or_else(|| Error::Encoding("the hash has no wire algorithm".to_string()))
+    }
+
+    fn encode(&self, algorithm: u8) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::wire_length(self.siblings.len()));
+        bytes.push(Self::WIRE_VERSION);
+        bytes.push(algorithm);
+        bytes.extend_from_slice(&(self.leaf_count as u64).to_be_bytes());
+        bytes.extend_from_slice(&(self.leaf_index as u64).to_be_bytes());
+        bytes.push(self.siblings.len() as u8);
+        bytes.extend_from_slice(&direction_bits(self.leaf_index, self.siblings.len()));
+        for sibling in &self.siblings {
+            bytes.extend_from_slice(sibling);
+        }
+
+        bytes
+    }
+
+    /// Decodes a proof written by `to_bytes`.
+    ///
+    /// Any input is rejected with an error rather than a panic: unknown
+    /// versions and algorithms, truncated or trailing bytes, an index out of
+    /// the tree, a number of siblings that does not match the tree size, or
+    /// direction bits that do not match the index. Encoding errors are
+    /// `Error::MalformedProof`.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        Self::parse_with::<Sha256>(bytes)
+    }
+
+    /// Decodes a proof written by `to_bytes_with::<H>`, with the checks of
+    /// `parse`.
+    pub fn parse_with<H: MerkleHasher>(bytes: &[u8]) -> Result<Self, Error> {
+        Self::decode(bytes, |algorithm| H::WIRE_ALGORITHM == Some(algorithm))
+    }
+
+    /// Decodes a proof whose algorithm byte passes `accepts`.
+    pub(crate) fn decode(bytes: &[u8], accepts: impl Fn(u8) -> bool) -> Result<Self, Error> {
+        let malformed = |error: WireError| Error::MalformedProof(error);
+
+        if bytes.len() < HEADER_LENGTH {
+            return Err(malformed(WireError::Truncated {
+                expected: HEADER_LENGTH,
+                actual: bytes.len(),
+            }));
+        }
+        if bytes[0] != Self::WIRE_VERSION {
+            return Err(malformed(WireError::UnknownVersion(bytes[0])));
+        }
+        if !accepts(bytes[1]) {
+            return Err(malformed(WireError::UnknownAlgorithm(bytes[1])));
+        }
+
+        let leaf_count = u64::from_be_bytes(bytes[2..10].try_into().unwrap());
+        let leaf_index = u64::from_be_bytes(bytes[10..18].try_into().unwrap());
+        let (Ok(leaf_count), Ok(leaf_index)) =
+            (usize::try_from(leaf_count), usize::try_from(leaf_index))
+        else {
+            return Err(malformed(WireError::TreeTooLarge));
+        };
+        if leaf_index >= leaf_count {
+            return Err(Error::IndexOutOfRange {
+                index: leaf_index,
+                leaf_count,
+            });
+        }
+
+        let count = bytes[18] as usize;
+        let expected = Self::expected_length(leaf_count);
+        if count > MAX_SIBLINGS || count != expected {
+            return Err(malformed(WireError::SiblingCount {
+                expected,
+                actual: count,
+            }));
+        }
+        let length = Self::wire_length(count);
+        if bytes.len() < length {
+            return Err(malformed(WireError::Truncated {
+                expected: length,
+                actual: bytes.len(),
+            }));
+        }
+        if bytes.len() > length {
+            return Err(malformed(WireError::TrailingBytes {
+                expected: length,
+                actual: bytes.len(),
+            }));
+        }
+
+        let directions = &bytes[HEADER_LENGTH..HEADER_LENGTH + count.div_ceil(8)];
+        if directions != direction_bits(leaf_index, count).as_slice() {
+            return Err(malformed(WireError::Directions));
+        }
+
+        let siblings = bytes[HEADER_LENGTH + directions.len()..]
+            .chunks_exact(32)
+            .map(|sibling| Hash::try_from(sibling).unwrap())
+            .collect();
+
+        Ok(Self {
+            leaf_index,
+            leaf_count,
+            siblings,
+        })
+    }
+}
+
+/// Sets bit `level` when the node on the path is a left child, so its sibling is on the right.
+fn direction_bits(leaf_index: usize, count: usize) -> Vec<u8> {
+    let mut bits = vec![0u8; count.div_ceil(8)];
+    for level in 0..count {
+        if (leaf_index >> level) & 1 == 0 {
+            bits[level / 8] |= 1 << (level % 8);
+        }
+    }
+    bits
+}
+
+#[cfg(all(test, feature = "tree"))]
+mod tests {
+
+    use super::*;
+    use crate::merkle_tree::{GenericMerkleTree, MerkleTree};
+
+    /// SHA-256 of tagged bytes, with an algorithm byte of its own.
+    struct Tagged;
+
+    impl MerkleHasher for Tagged {
+        const WIRE_ALGORITHM: Option<u8> = Some(200);
+
+        fn hash(bytes: &[u8]) -> Hash {
+            MerkleTree::hash(&[b"tagged:".as_slice(), bytes].concat())
+        }
+    }
+
+    fn tree(count: usize) -> MerkleTree {
+        let items: Vec<String> = (0..count)
+            .map(|item| format!("palantír {}", item))
+            .collect();
+        MerkleTree::build(&items).unwrap()
+    }
+
+    #[test]
+    fn test_proofs_round_trip() {
+        for count in [1, 2, 3, 9, 100] {
+            let tree = tree(count);
+            for index in 0..count {
+                let proof = tree.proof(index).unwrap();
+                let bytes = proof.to_bytes();
+
+                assert_eq!(bytes.len(), Proof::wire_length(proof.siblings.len()));
+                assert_eq!(Proof::parse(&bytes), Ok(proof));
+            }
+        }
+    }
+
+    #[test]
+    fn test_layout_is_stable() {
+        let proof = tree(3).proof(2).unwrap();
+        let bytes = proof.to_bytes();
+
+        assert_eq!(
+            bytes[..20],
+            [1, 1, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 2, 2, 0b01]
+        );
+        assert_eq!(bytes[20..52], proof.siblings[0]);
+    }
+
+    #[test]
+    fn test_proofs_carry_the_algorithm_of_their_hash() {
+        let items = ["Anor", "Ithil", "Orthanc"];
+        let tagged = GenericMerkleTree::<Tagged>::build(&items).unwrap();
+        let proof = tagged.proof(1).unwrap();
+        let bytes = proof.to_bytes_with::<Tagged>().unwrap();
+
+        assert_eq!(bytes[1], 200);
+        assert_eq!(Proof::parse_with::<Tagged>(&bytes), Ok(proof.clone()));
+        assert_eq!(
+            Proof::parse(&bytes),
+            Err(Error::MalformedProof(WireError::UnknownAlgorithm(200)))
+        );
+
+        let unnamed = GenericMerkleTree::<sha2::Sha512_256>::build(&items).unwrap();
+        assert!(matches!(
+            unnamed
+                .proof(1)
+                .unwrap()
+                .to_bytes_with::<sha2::Sha512_256>(),
+            Err(Error::Encoding(_))
+        ));
+        assert_eq!(
+            tree(3).proof(1).unwrap().to_bytes_with::<Sha256>(),
+            Ok(tree(3).proof(1).unwrap().to_bytes())
+        );
+    }
+
+    #[test]
+    fn test_malformed_proofs_are_rejected() {
+        let bytes = tree(9).proof(5).unwrap().to_bytes();
+
+        let mut version = bytes.clone();
+        version[0] = 2;
+        let mut algorithm = bytes.clone();
+        algorithm[1] = 0;
+        let mut index = bytes.clone();
+        index[17] = 9;
+        let mut directions = bytes.clone();
+        directions[19] ^= 0b1000_0000;
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+
+        let malformed = |error| Err(Error::MalformedProof(error));
+        assert_eq!(
+            Proof::parse(&version),
+            malformed(WireError::UnknownVersion(2))
+        );
+        assert_eq!(
+            Proof::parse(&algorithm),
+            malformed(WireError::UnknownAlgorithm(0))
+        );
+        assert!(Proof::parse(&index).is_err());
+        assert_eq!(Proof::parse(&directions), malformed(WireError::Directions));
+        assert_eq!(
+            Proof::parse(&trailing),
+            malformed(WireError::TrailingBytes {
+                expected: bytes.len(),
+                actual: bytes.len() + 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_arbitrary_input_does_not_panic() {
+        let bytes = tree(40).proof(33).unwrap().to_bytes();
+
+        for length in 0..bytes.len() {
+            assert!(Proof::parse(&bytes[..length]).is_err());
+        }
+
+        // Flip each byte of the proof, and parse pseudo-random inputs of every length.
+        for position in 0..bytes.len() {
+            let mut flipped = bytes.clone();
+            flipped[position] ^= 0xff;
+            let _ = Proof::parse(&flipped);
+        }
+        let mut seed = MerkleTree::hash(b"Orthanc");
+        for length in 0..300 {
+            let noise: Vec<u8> = (0..length)
+                .map(|position| {
+                    if position % 32 == 0 {
+                        seed = MerkleTree::hash(&seed);
+                    }
+                    seed[position % 32]
+                })
+                .collect();
+            let _ = Proof::parse(&noise);
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use crate::error::Error;
+use crate::merkle_tree::{Hash, MerkleTree};
+use crate::proof::Proof;
+
+/// A hash as two field elements, its first and last 16 bytes read as
+/// big-endian integers, since a 256-bit hash does not fit in the scalar
+/// field of the curves SNARKs use.
+pub type HashLimbs = [u128; 2];
+
+fn limbs(hash: &Hash) -> HashLimbs {
+    [
+        u128::from_be_bytes(hash[..16].try_into().unwrap()),
+        u128::from_be_bytes(hash[16..].try_into().unwrap()),
+    ]
+}
+
+/// The private and public inputs of a circuit proving a leaf is under a
+/// root, with the level-by-level layout circuit Merkle gadgets expect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitWitness {
+    pub leaf: HashLimbs,
+    /// The bits of the leaf index, from the leaf up.
+    pub path_indices: Vec<bool>,
+    /// Set at the levels where the sibling is hashed first. Parents hash
+    /// their sorted children, so this is the order the circuit must use,
+    /// not the position of the sibling.
+    pub swap_bits: Vec<bool>,
+    pub siblings: Vec<HashLimbs>,
+    pub root: HashLimbs,
+}
+
+impl CircuitWitness {
+    /// Every input as one field element per entry: the leaf and root limbs,
+    /// then for every level its index bit, swap bit and sibling limbs.
+    pub fn field_elements(&self) -> Vec<u128> {
+        let mut elements = vec![self.leaf[0], self.leaf[1], self.root[0], self.root[1]];
+        for ((index, swap), sibling) in self
+            .path_indices
+            .iter()
+            .zip(&self.swap_bits)
+            .zip(&self.siblings)
+        {
+            elements.extend([*index as u128, *swap as u128, sibling[0], sibling[1]]);
+        }
+        elements
+    }
+
+    /// The inputs as the `input.json` of a Circom circuit, every field
+    /// element a decimal string.
+    pub fn to_circom_json(&self) -> String {
+        let limbs = |limbs: &HashLimbs| format!("[\"{}\", \"{}\"]", limbs[0], limbs[1]);
+        let bits = |bits: &[bool]| {
+            bits.iter()
+                .map(|bit| format!("\"{}\"", *bit as u8))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let siblings = self
+            .siblings
+            .iter()
+            .map(limbs)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!(
+            "{{\"leaf\": {}, \"pathElements\": [{}], \"pathIndices\": [{}], \"swapBits\": [{}], \"root\": {}}}",
+            limbs(&self.leaf),
+            siblings,
+            bits(&self.path_indices),
+            bits(&self.swap_bits),
+            limbs(&self.root)
+        )
+    }
+}
+
+impl Proof {
+    /// Checks the proof leads from `leaf` to `root` and lays it out as the
+    /// witness of a circuit.
+    ///
+    /// # Examples
+    /// ```
+    /// use merkle_tree::MerkleTree;
+    ///
+    /// let tree = MerkleTree::build(&["Beren", "Luthien", "Huan"]).unwrap();
+    /// let proof = tree.proof(1).unwrap();
+    ///
+    /// let leaf = MerkleTree::hash(b"Luthien");
+    /// let witness = proof.circuit_witness(&leaf, &tree.root().unwrap()).unwrap();
+    ///
+    /// assert_eq!(witness.path_indices, vec![true, false]);
+    /// assert_eq!(witness.field_elements().len(), 4 + 2 * 4);
+    /// ```
+    pub fn circuit_witness(&self, leaf: &Hash, root: &Hash) -> Result<CircuitWitness, Error> {
+        self.verify(leaf, root)?;
+
+        let mut node = *leaf;
+        let mut swap_bits = Vec::with_capacity(self.siblings.len());
+        for sibling in &self.siblings {
+            swap_bits.push(sibling < &node);
+            node = MerkleTree::merkle_parent(&[node, *sibling]);
+        }
+
+        Ok(CircuitWitness {
+            leaf: limbs(leaf),
+            path_indices: (0..self.siblings.len())
+                .map(|level| self.leaf_index >> level & 1 == 1)
+                .collect(),
+            swap_bits,
+            siblings: self.siblings.iter().map(limbs).collect(),
+            root: limbs(root),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn join(limbs: &HashLimbs) -> Hash {
+        let mut hash = [0; 32];
+        hash[..16].copy_from_slice(&limbs[0].to_be_bytes());
+        hash[16..].copy_from_slice(&limbs[1].to_be_bytes());
+        hash
+    }
+
+    #[test]
+    fn test_witnesses_fold_like_a_circuit() {
+        let items: Vec<String> = (0..11).map(|item| format!("Silmaril {}", item)).collect();
+        let tree = MerkleTree::build(&items).unwrap();
+
+        for (index, item) in items.iter().enumerate() {
+            let leaf = MerkleTree::hash(item.as_bytes());
+            let witness = tree
+                .proof(index)
+                .unwrap()
+                .circuit_witness(&leaf, &tree.root().unwrap())
+                .unwrap();
+
+            // The folding a circuit does, from field elements only.
+            let mut node = join(&witness.leaf);
+            for (sibling, swap) in witness.siblings.iter().zip(&witness.swap_bits) {
+                let (first, second) = match swap {
+                    true => (join(sibling), node),
+                    false => (node, join(sibling)),
+                };
+                node = MerkleTree::hash([first, second].as_flattened());
+            }
+            assert_eq!(node, join(&witness.root));
+            assert_eq!(Some(join(&witness.root)), tree.root());
+
+            let position = witness
+                .path_indices
+                .iter()
+                .rev()
+                .fold(0, |position, bit| 2 * position + *bit as usize);
+            assert_eq!(position, index);
+        }
+    }
+
+    #[test]
+    fn test_circom_inputs_are_decimal_strings() {
+        let tree = MerkleTree::build(&["Feanor", "Fingolfin"]).unwrap();
+        let proof = tree.proof(0).unwrap();
+        let root = tree.root().unwrap();
+        let witness = proof
+            .circuit_witness(&MerkleTree::hash(b"Feanor"), &root)
+            .unwrap();
+
+        let json = witness.to_circom_json();
+        assert!(json.contains(&format!(
+            "\"leaf\": [\"{}\", \"{}\"]",
+            witness.leaf[0], witness.leaf[1]
+        )));
+        assert!(json.contains("\"pathIndices\": [\"0\"]"));
+        assert_eq!(
+            proof.circuit_witness(&MerkleTree::hash(b"Fingon"), &root),
+            Err(Error::RootMismatch)
+        );
+    }
+}